@@ -179,6 +179,191 @@ CTL_INIT($ctl_runtime_init) {
 #endif
 }
 
+#if defined(CTL_TEST)
+#  include <stdio.h>
+#  include <stdlib.h>
+#  include <string.h>
+#  if defined(__unix__) || defined(__APPLE__)
+#    include <sys/wait.h>
+#    include <unistd.h>
+#    define CTL_TEST_ISOLATED 1
+#  else
+#    define CTL_TEST_ISOLATED 0
+#  endif
+
+typedef struct {
+  const char *name;
+  void (*func)(void);
+} CtlTest;
+
+// Runs each test in a child process where possible, so that a panicking test is reported as a
+// failure instead of aborting the whole run. Without fork (e.g. on Windows), tests run in this
+// process and the first panic ends the run before the summary is printed.
+static int ctl_run_test(const CtlTest *test) {
+#  if CTL_TEST_ISOLATED
+  fflush(NULL);
+  pid_t pid = fork();
+  if (pid == 0) {
+    test->func();
+    exit(0);
+  }
+
+  int status;
+  return pid > 0 && waitpid(pid, &status, 0) == pid && WIFEXITED(status) &&
+         WEXITSTATUS(status) == 0;
+#  else
+  // keep the results so far if this test panics
+  fflush(NULL);
+  test->func();
+  return 1;
+#  endif
+}
+
+static int ctl_run_tests(const CtlTest *tests, size_t count, int argc, char **argv) {
+  size_t passed = 0, failed = 0, filtered = 0;
+  if (!CTL_TEST_ISOLATED) {
+    printf("note: tests run in-process on this target, so a panic aborts the test run\n\n");
+  }
+  for (size_t i = 0; i < count; i++) {
+    int selected = argc < 2;
+    for (int j = 1; j < argc && !selected; j++) {
+      selected = strstr(tests[i].name, argv[j]) != NULL;
+    }
+
+    if (!selected) {
+      filtered++;
+    } else if (ctl_run_test(&tests[i])) {
+      printf("test %s ... ok\n", tests[i].name);
+      passed++;
+    } else {
+      printf("test %s ... FAILED\n", tests[i].name);
+      failed++;
+    }
+  }
+
+  printf("\ntest result: %s. %zu passed; %zu failed; %zu filtered out%s\n",
+         failed ? "FAILED" : "ok", passed, failed, filtered,
+         CTL_TEST_ISOLATED ? "" : " (ran in-process)");
+  return failed != 0;
+}
+#endif
+
+#if defined(__GLIBC__) || defined(__APPLE__)
+#  include <execinfo.h>
+#  include <stdio.h>
+#  include <stdlib.h>
+#  include <string.h>
+
+#  if defined(__linux__) && defined(__GLIBC__)
+// POSIX, but hidden by stdio.h in strict C11 mode
+extern FILE *popen(const char *, const char *);
+extern int pclose(FILE *);
+
+// Finds the path of the executable and the range of addresses it is mapped at, so frame addresses
+// can be turned into the addresses addr2line expects.
+static int ctl_find_exe(char *path, size_t len, uintptr_t *base, uintptr_t *end) {
+  FILE *maps = fopen("/proc/self/maps", "r");
+  if (!maps) {
+    return 0;
+  }
+
+  char line[4096];
+  path[0] = 0;
+  while (fgets(line, sizeof line, maps)) {
+    unsigned long lo, hi;
+    int pos = 0;
+    if (sscanf(line, "%lx-%lx %*s %*s %*s %*s %n", &lo, &hi, &pos) < 2 || !pos) {
+      continue;
+    }
+
+    line[strcspn(line, "\n")] = 0;
+    if (!path[0]) {
+      snprintf(path, len, "%s", line + pos);
+      *base = lo;
+    }
+    if (!strcmp(path, line + pos)) {
+      *end = hi;
+    }
+  }
+  fclose(maps);
+
+  // executables that aren't position independent are linked at their runtime address
+  FILE *exe = fopen(path, "rb");
+  unsigned char header[18];
+  if (exe && fread(header, 1, sizeof header, exe) == sizeof header && header[16] == 2) {
+    *base = 0;
+  }
+  if (exe) {
+    fclose(exe);
+  }
+  return path[0] != 0;
+}
+
+// Symbolizes the frames inside the executable by running addr2line on each of them. This is slow
+// and needs addr2line to be installed, so it is only done when CTL_BACKTRACE=full. When built with
+// --debug-info, the #line directives make these the locations in the CTL source.
+static void ctl_print_backtrace_full(void **frames, int count) {
+  char **symbols = backtrace_symbols(frames, count);
+  char exe[1024];
+  uintptr_t base = 0, end = 0;
+  int found = ctl_find_exe(exe, sizeof exe, &base, &end);
+  for (int i = 0; i < count; i++) {
+    // return addresses point after the call, so look up the byte before them
+    uintptr_t addr = (uintptr_t)frames[i] - 1;
+    int symbolized = 0;
+    if (found && addr >= base && addr < end && !strchr(exe, '\'')) {
+      char cmd[1200];
+      snprintf(cmd, sizeof cmd, "addr2line -f -i -p -e '%s' %#lx 2>/dev/null", exe,
+               (unsigned long)(addr - base));
+      FILE *out = popen(cmd, "r");
+      char loc[1024];
+      // with -i, functions inlined into this frame are printed on their own lines
+      while (out && fgets(loc, sizeof loc, out) && !strstr(loc, "??")) {
+        loc[strcspn(loc, "\n")] = 0;
+        if (symbolized++) {
+          fprintf(stderr, "      %s\n", loc);
+        } else {
+          fprintf(stderr, "  %2d: %s\n", i, loc);
+        }
+      }
+      if (out) {
+        pclose(out);
+      }
+    }
+
+    if (!symbolized) {
+      fprintf(stderr, "  %2d: %s\n", i, symbols ? symbols[i] : "??");
+    }
+  }
+  free(symbols);
+}
+#  endif
+
+// Prints a backtrace of the current thread to stderr if CTL_BACKTRACE is set. Frames are
+// symbolized by backtrace_symbols_fd, which doesn't allocate and only knows about exported
+// symbols. On Linux, CTL_BACKTRACE=full uses addr2line instead to show source locations.
+static void ctl_print_backtrace(void) {
+  const char *mode = getenv("CTL_BACKTRACE");
+  if (!mode) {
+    return;
+  }
+
+  void *frames[64];
+  int count = backtrace(frames, 64);
+  fprintf(stderr, "backtrace:\n");
+  // skip the frame of this function
+#  if defined(__linux__) && defined(__GLIBC__)
+  if (!strcmp(mode, "full")) {
+    ctl_print_backtrace_full(frames + 1, count - 1);
+    return;
+  }
+#  endif
+  backtrace_symbols_fd(frames + 1, count - 1, 2);
+}
+#else
+static void ctl_print_backtrace(void) {}
+#endif
+
 #ifdef __GNUC__
 typedef __uint128_t uint128_t;
 typedef __int128_t int128_t;
@@ -4,7 +4,8 @@ use indexmap::IndexMap;
 use crate::{
     ast::{BinaryOp, UnaryOp},
     comptime_int::ComptimeInt,
-    sym::{ScopeId, ScopeKind, Scopes, VariableId},
+    lexer::{Located, Span},
+    sym::{ScopeId, ScopeKind, Scopes, UserTypeId, VariableId},
     typecheck::MemberFn,
     typeid::{GenericFn, Type, TypeId, Types},
 };
@@ -13,7 +14,7 @@ use super::parsed::RangePattern;
 
 #[derive(Debug, Clone)]
 pub struct Block {
-    pub body: Vec<Stmt>,
+    pub body: Vec<Located<Stmt>>,
     pub scope: ScopeId,
 }
 
@@ -62,7 +63,12 @@ pub enum PatternData {
         inner: TypeId,
     },
     Variable(VariableId),
-    Or(Vec<Pattern>),
+    Or {
+        patterns: Vec<Pattern>,
+        /// The variables visible to the body of the pattern, with the variable bound by each
+        /// alternative that provides their value.
+        bindings: Vec<(VariableId, Vec<VariableId>)>,
+    },
     Void,
     #[default]
     Error,
@@ -114,18 +120,16 @@ pub enum ExprData {
     Binary(BinaryOp, Box<Expr>, Box<Expr>),
     Unary(UnaryOp, Box<Expr>),
     AutoDeref(Box<Expr>, usize),
-    Call(Box<Expr>, IndexMap<String, Expr>),
+    Call(Box<Expr>, IndexMap<String, Expr>, Span),
     CallDyn(GenericFn, IndexMap<String, Expr>),
     CallFnPtr(Box<Expr>, Vec<Expr>),
     DynCoerce(Box<Expr>, ScopeId),
+    DynFnCoerce(Box<Expr>),
     VariantInstance(String, IndexMap<String, Expr>),
     SpanMutCoerce(Box<Expr>),
     Instance(IndexMap<String, Expr>),
     Array(Vec<Expr>),
-    ArrayWithInit {
-        init: Box<Expr>,
-        count: usize,
-    },
+    ArrayWithInit(Box<Expr>),
     Vec(Vec<Expr>),
     VecWithInit {
         init: Box<Expr>,
@@ -146,6 +150,10 @@ pub enum ExprData {
     Fn(GenericFn, ScopeId),
     MemFn(MemberFn, ScopeId),
     Var(VariableId),
+    /// A constant declared by a trait, read through a type that is only known to implement it
+    AssocConst(TypeId, VariableId),
+    /// The value of a const generic parameter
+    ConstParam(UserTypeId),
     Block(Block),
     AffixOperator {
         callee: Box<Expr>,
@@ -176,6 +184,7 @@ pub enum ExprData {
     Subscript {
         callee: Box<Expr>,
         arg: Box<Expr>,
+        span: Span,
     },
     SliceArray {
         callee: Box<Expr>,
@@ -184,9 +193,17 @@ pub enum ExprData {
     As(Box<Expr>, bool),
     Is(Box<Expr>, Pattern),
     Return(Box<Expr>),
+    /// Returns the given value from an async function or generator, resuming at this point the
+    /// next time it is polled. This is `Poll::Pending` for futures, or the next item for generators.
+    Suspend(Box<Expr>),
     Yield(Option<Box<Expr>>, ScopeId),
     Break(Option<Box<Expr>>, ScopeId),
-    Lambda(Vec<Stmt>),
+    Lambda {
+        params: Vec<VariableId>,
+        captures: Vec<VariableId>,
+        moves: bool,
+        body: Block,
+    },
     NeverCoerce(Box<Expr>),
     Continue(ScopeId),
     #[default]
@@ -203,6 +220,7 @@ impl ExprData {
         mfn: MemberFn,
         args: IndexMap<String, Expr>,
         scope: ScopeId,
+        span: Span,
     ) -> Self {
         Self::Call(
             Box::new(Expr::new(
@@ -210,6 +228,7 @@ impl ExprData {
                 Self::MemFn(mfn, scope),
             )),
             args,
+            span,
         )
     }
 
@@ -218,10 +237,12 @@ impl ExprData {
         func: GenericFn,
         args: IndexMap<String, Expr>,
         scope: ScopeId,
+        span: Span,
     ) -> Self {
         Self::Call(
             Expr::new(types.insert(Type::Fn(func.clone())), Self::Fn(func, scope)).into(),
             args,
+            span,
         )
     }
 }
@@ -266,6 +287,7 @@ impl Expr {
                     || matches!(types[expr.ty], Type::MutPtr(_) | Type::RawPtr(_))
             }
             ExprData::Var(id) => scopes.get(*id).mutable,
+            ExprData::AssocConst(_, _) | ExprData::ConstParam(_) => false,
             ExprData::Member { source, .. } => {
                 matches!(types[source.ty], Type::MutPtr(_)) || source.can_addrmut(scopes, types)
             }
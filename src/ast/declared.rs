@@ -15,6 +15,7 @@ pub struct ImplBlock {
     pub span: Span,
     pub scope: ScopeId,
     pub fns: Vec<Fn>,
+    pub consts: Vec<VariableId>,
     pub type_params: Vec<UserTypeId>,
 }
 
@@ -34,8 +35,8 @@ pub enum Stmt {
     Binding {
         id: VariableId,
         value: Expr,
-        constant: bool,
     },
+    Const(VariableId),
     Fn(Fn),
     Struct {
         id: UserTypeId,
@@ -57,6 +58,7 @@ pub enum Stmt {
         impls: Vec<ImplBlock>,
         fns: Vec<Fn>,
     },
+    Alias(UserTypeId),
     Module {
         id: ScopeId,
         body: Vec<Stmt>,
@@ -1,4 +1,7 @@
-use crate::lexer::{Located, Token};
+use crate::{
+    lexer::{Located, Token},
+    lint::{Lint, LintLevel},
+};
 use derive_more::{Deref, Display};
 
 use self::parsed::OperatorFnType;
@@ -34,6 +37,17 @@ impl Attributes {
     pub fn has(&self, name: &str) -> bool {
         self.attrs.iter().any(|attr| attr.name.data == name)
     }
+
+    /// Returns the level set for `lint` by `allow`, `warn` or `deny` attributes, if any.
+    pub fn lint_level(&self, lint: Lint) -> Option<LintLevel> {
+        self.attrs.iter().rev().find_map(|attr| {
+            let level = LintLevel::from_attr(&attr.name.data)?;
+            attr.props
+                .iter()
+                .any(|prop| prop.name.data == lint.name() || prop.name.data == Lint::GROUP_ALL)
+                .then_some(level)
+        })
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Display, Hash)]
@@ -11,6 +11,8 @@ pub enum PathOrigin {
     Super(Span),
     Normal,
     Infer,
+    /// `This::Name`, only valid in type paths
+    This(Span),
 }
 
 impl std::fmt::Display for PathOrigin {
@@ -19,6 +21,7 @@ impl std::fmt::Display for PathOrigin {
             Self::Root => write!(f, "::"),
             Self::Infer => write!(f, ":"),
             Self::Super(_) => write!(f, "super::"),
+            Self::This(_) => write!(f, "{THIS_TYPE}::"),
             _ => Ok(()),
         }
     }
@@ -44,6 +47,19 @@ pub struct Stmt {
     pub attrs: Attributes,
 }
 
+impl Stmt {
+    /// Returns the location of the statement's leading expression or pattern. Items have no
+    /// location of their own and return the default span.
+    pub fn span(&self) -> Span {
+        match &self.data {
+            StmtData::Expr(expr) | StmtData::Defer(expr) => expr.span,
+            StmtData::Guard { cond, .. } => cond.span,
+            StmtData::Let { patt, .. } => patt.span,
+            _ => Span::default(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum StmtData {
     Expr(Expr),
@@ -75,6 +91,8 @@ pub enum StmtData {
         name: Located<String>,
         is_unsafe: bool,
         type_params: TypeParams,
+        assoc_types: TypeParams,
+        consts: Vec<Const>,
         impls: Vec<Path>,
         functions: Vec<Fn>,
     },
@@ -87,6 +105,12 @@ pub enum StmtData {
         functions: Vec<Fn>,
         operators: Vec<OperatorFn>,
     },
+    Alias {
+        public: bool,
+        name: Located<String>,
+        type_params: TypeParams,
+        ty: TypeHint,
+    },
     Binding {
         public: bool,
         constant: bool,
@@ -183,6 +207,14 @@ pub enum ExprData {
         generics: Vec<TypeHint>,
         member: Located<String>,
     },
+    Await(Box<Expr>),
+    Yield(Box<Expr>),
+    Raise(Box<Expr>),
+    Try {
+        body: Vec<Stmt>,
+        catch: Located<Pattern>,
+        handler: Vec<Stmt>,
+    },
     Return(Box<Expr>),
     Tail(Box<Expr>),
     Break(Option<Box<Expr>>, Option<Located<String>>),
@@ -199,10 +231,30 @@ pub enum ExprData {
         body: Box<Expr>,
         moves: bool,
     },
-    StringInterpolation(Vec<Expr>),
+    StringInterpolation(Vec<(Expr, Option<Located<FormatSpec>>)>),
     Error,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatAlign {
+    Left,
+    Right,
+    Center,
+}
+
+/// A format specifier following an interpolated expression, as in `{x:>+08.3}`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FormatSpec {
+    pub fill: Option<char>,
+    pub align: Option<FormatAlign>,
+    pub sign: bool,
+    pub alternate: bool,
+    pub zero: bool,
+    pub width: Option<usize>,
+    pub precision: Option<usize>,
+    pub ty: Option<char>,
+}
+
 pub type PathComponent = (Located<String>, Vec<TypeHint>);
 
 #[derive(Clone, derive_more::Constructor)]
@@ -353,6 +405,14 @@ pub enum TypeHint {
         params: Vec<TypeHint>,
         ret: Box<TypeHint>,
     },
+    DynFn {
+        params: Vec<TypeHint>,
+        ret: Box<TypeHint>,
+    },
+    /// `Name = Type` in the type arguments of a trait
+    AssocType(Located<String>, Box<TypeHint>),
+    /// A literal passed for a const generic parameter
+    Const(Box<Expr>),
     Void,
     This(Span),
     #[default]
@@ -409,6 +469,18 @@ impl std::fmt::Debug for TypeHint {
                 }
                 write!(f, ") {ret:?}")
             }
+            TypeHint::DynFn { params, ret } => {
+                write!(f, "dyn fn (")?;
+                for (i, inner) in params.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{inner:?}")?;
+                }
+                write!(f, ") {ret:?}")
+            }
+            TypeHint::AssocType(name, ty) => write!(f, "{name} = {ty:?}"),
+            TypeHint::Const(_) => write!(f, "<expr>"),
             TypeHint::Void => write!(f, "void"),
             TypeHint::This(_) => write!(f, "{THIS_TYPE}"),
             TypeHint::Error => write!(f, "Error"),
@@ -469,7 +541,9 @@ pub struct Fn {
     pub name: Located<String>,
     pub is_extern: bool,
     pub is_async: bool,
+    pub is_generator: bool,
     pub is_unsafe: bool,
+    pub is_const: bool,
     pub variadic: bool,
     pub assign_subscript: bool,
     pub type_params: TypeParams,
@@ -486,7 +560,9 @@ impl Fn {
             name: Located::new(func.name.span, name),
             is_extern: false,
             is_async: false,
+            is_generator: false,
             is_unsafe: false,
+            is_const: false,
             variadic: false,
             type_params: func.type_params,
             params: func.params,
@@ -535,16 +611,36 @@ pub struct Struct {
     pub name: Located<String>,
     pub type_params: TypeParams,
     pub members: Vec<Member>,
+    pub consts: Vec<Const>,
     pub impls: Vec<ImplBlock>,
     pub functions: Vec<Fn>,
     pub operators: Vec<OperatorFn>,
 }
 
+/// An associated constant. Only constants declared in a trait have no value.
+#[derive(Debug, Clone)]
+pub struct Const {
+    pub public: bool,
+    pub name: Located<String>,
+    pub ty: TypeHint,
+    pub value: Option<Expr>,
+}
+
 #[derive(Debug, Clone)]
 pub struct ImplBlock {
     pub type_params: TypeParams,
     pub path: Path,
+    pub assoc_types: Vec<(Located<String>, TypeHint)>,
+    pub consts: Vec<Const>,
     pub functions: Vec<Fn>,
 }
 
-pub type TypeParams = Vec<(Located<String>, Vec<Path>)>;
+#[derive(Debug, Clone)]
+pub struct TypeParam {
+    pub name: Located<String>,
+    pub impls: Vec<Path>,
+    /// The type of the value of a const generic parameter
+    pub const_ty: Option<TypeHint>,
+}
+
+pub type TypeParams = Vec<TypeParam>;
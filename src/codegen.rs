@@ -9,7 +9,8 @@ use crate::{
     ast::{checked::*, parsed::RangePattern, BinaryOp, UnaryOp},
     comptime_int::ComptimeInt,
     dgraph::{Dependencies, DependencyGraph},
-    error::Diagnostics,
+    error::{Diagnostics, OffsetMode},
+    lexer::Span,
     nearest_pow_of_two,
     project::Project,
     sym::*,
@@ -18,7 +19,7 @@ use crate::{
         BitSizeResult, CInt, FnPtr, GenericFn, GenericTrait, GenericUserType, Integer, Type,
         TypeArgs, TypeId, Types,
     },
-    write_de, writeln_de, CodegenFlags, THIS_PARAM,
+    write_de, writeln_de, CachingSourceProvider, CodegenFlags, SourceProvider, THIS_PARAM,
 };
 
 #[macro_export]
@@ -44,10 +45,20 @@ const ARRAY_DATA_NAME: &str = "data";
 const VOID_INSTANCE: &str = "CTL_VOID";
 const ATTR_NOGEN: &str = "c_opaque";
 const ATTR_LINKNAME: &str = "c_name";
+const ATTR_TRACK_CALLER: &str = "track_caller";
 const NULLPTR: &str = "((void*)0)";
+const FRAME_PTR: &str = "$$frame";
+const FRAME_STATE: &str = "$$state";
+const FRAME_CONTEXT: &str = "$$cx";
+const CALLER_LOCATION: &str = "$$caller";
 
 #[derive(Default)]
-struct TypeGen(DependencyGraph<TypeId>);
+struct TypeGen {
+    graph: DependencyGraph<TypeId>,
+    /// The members of each async function frame, which are only known once the body of its poll
+    /// function has been generated.
+    frames: HashMap<TypeId, IndexMap<String, TypeId>>,
+}
 
 impl TypeGen {
     fn gen_fnptr(
@@ -76,6 +87,37 @@ impl TypeGen {
         write_de!(buffer, ");");
     }
 
+    fn gen_dynfn(
+        flags: &CodegenFlags,
+        decls: &mut Buffer,
+        defs: &mut Buffer,
+        scopes: &Scopes,
+        types: &mut Types,
+        id: TypeId,
+        f: &FnPtr,
+    ) {
+        write_de!(decls, "typedef struct ");
+        decls.emit_mangled_name(scopes, types, id, flags.minify);
+        write_de!(decls, " ");
+        decls.emit_mangled_name(scopes, types, id, flags.minify);
+        write_de!(decls, ";");
+
+        write_de!(defs, "struct ");
+        defs.emit_mangled_name(scopes, types, id, flags.minify);
+        write_de!(defs, "{{void*env;");
+        if f.ret.is_void() {
+            write_de!(defs, "void");
+        } else {
+            defs.emit_type(scopes, types, f.ret, flags.minify);
+        }
+        write_de!(defs, "(*fn)(void*");
+        for &param in f.params.iter() {
+            write_de!(defs, ",");
+            defs.emit_type(scopes, types, param, flags.minify);
+        }
+        write_de!(defs, ");}};");
+    }
+
     fn gen_dynptr(
         flags: &CodegenFlags,
         decls: &mut Buffer,
@@ -226,16 +268,52 @@ impl TypeGen {
         write_de!(defs, "}};");
     }
 
+    fn gen_frame(
+        flags: &CodegenFlags,
+        decls: &mut Buffer,
+        defs: &mut Buffer,
+        scopes: &Scopes,
+        types: &mut Types,
+        ut: &GenericUserType,
+        members: &IndexMap<String, TypeId>,
+    ) {
+        write_de!(decls, "typedef struct ");
+        decls.emit_type_name(scopes, types, ut, flags.minify);
+        write_de!(decls, " ");
+        decls.emit_type_name(scopes, types, ut, flags.minify);
+        write_de!(decls, ";");
+
+        write_de!(defs, "struct ");
+        defs.emit_type_name(scopes, types, ut, flags.minify);
+        write_de!(defs, "{{int {FRAME_STATE};");
+        for (name, &ty) in members {
+            if ty.size_and_align(scopes, types).0 == 0 {
+                write_de!(defs, "CTL_ZST ");
+            }
+
+            defs.emit_type(scopes, types, ty, flags.minify);
+            write_de!(defs, " {name};");
+        }
+        write_de!(defs, "}};");
+    }
+
     fn emit(&self, scopes: &Scopes, types: &mut Types, decls: &mut Buffer, flags: &CodegenFlags) {
         let mut defs = Buffer::default();
-        self.0.visit_all(|&id| match &types[id] {
+        self.graph.visit_all(|&id| match &types[id] {
             Type::Fn(f) => {
                 let f = f.clone().as_fn_ptr(scopes, types);
                 Self::gen_fnptr(scopes, types, &mut defs, flags, &f);
             }
             Type::FnPtr(f) => Self::gen_fnptr(scopes, types, &mut defs, flags, &f.clone()),
+            Type::DynFn(f) => {
+                Self::gen_dynfn(flags, decls, &mut defs, scopes, types, id, &f.clone());
+            }
             Type::User(ut) => {
-                Self::gen_usertype(flags, decls, &mut defs, scopes, types, &ut.clone());
+                if let Some(members) = self.frames.get(&id) {
+                    Self::gen_frame(flags, decls, &mut defs, scopes, types, &ut.clone(), members);
+                } else {
+                    Self::gen_usertype(flags, decls, &mut defs, scopes, types, &ut.clone());
+                }
             }
             Type::DynPtr(tr) | Type::DynMutPtr(tr) => {
                 Self::gen_dynptr(flags, decls, &mut defs, scopes, types, &tr.clone());
@@ -275,7 +353,7 @@ impl TypeGen {
     }
 
     fn add_type(&mut self, scopes: &Scopes, types: &mut Types, ty: TypeId) {
-        if self.0.contains_key(&ty) {
+        if self.graph.contains_key(&ty) {
             return;
         }
 
@@ -297,6 +375,7 @@ impl TypeGen {
                         | Type::Uint(_)
                         | Type::DynMutPtr(_)
                         | Type::DynPtr(_)
+                        | Type::DynFn(_)
                 ) {
                     deps.push(dep);
                 }
@@ -307,7 +386,7 @@ impl TypeGen {
 
         match &types[ty] {
             Type::Int(_) | Type::Uint(_) | Type::DynMutPtr(_) | Type::DynPtr(_) => {}
-            Type::FnPtr(f) => {
+            Type::FnPtr(f) | Type::DynFn(f) => {
                 let ret = f.ret;
                 for param in f.params.clone() {
                     self.add_type(scopes, types, param);
@@ -323,11 +402,16 @@ impl TypeGen {
             }
             &Type::Array(ty, _) => dependency!(ty),
             Type::User(ut) => {
-                self.0.insert(ty, Dependencies::Resolving);
+                self.graph.insert(ty, Dependencies::Resolving);
                 let ut = ut.clone();
                 for m in scopes.get(ut.id).members.values() {
                     dependency!(m.ty.with_templates(types, &ut.ty_args));
                 }
+                if let Some(members) = self.frames.get(&ty) {
+                    for ty in members.values().copied().collect::<Vec<_>>() {
+                        dependency!(ty);
+                    }
+                }
 
                 match &scopes.get(ut.id).kind {
                     UserTypeKind::Union(union) => {
@@ -348,10 +432,38 @@ impl TypeGen {
             _ => return,
         }
 
-        self.0.insert(ty, Dependencies::Resolved(deps));
+        self.graph.insert(ty, Dependencies::Resolved(deps));
+    }
+
+    fn add_frame(
+        &mut self,
+        scopes: &Scopes,
+        types: &mut Types,
+        ty: TypeId,
+        members: IndexMap<String, TypeId>,
+    ) {
+        self.frames.insert(ty, members);
+        // the frame type may have been added before its members were known
+        self.graph.remove(&ty);
+        self.add_type(scopes, types, ty);
     }
 }
 
+#[derive(PartialEq, Eq, Clone)]
+struct LambdaState {
+    scope: ScopeId,
+    captures: HashMap<VariableId, String>,
+    moves: bool,
+}
+
+/// The state of an async function's poll function, whose locals live in the frame struct so they
+/// survive across suspension points.
+#[derive(PartialEq, Eq, Clone, Default)]
+struct FrameState {
+    members: IndexMap<String, TypeId>,
+    resume_points: usize,
+}
+
 #[derive(Eq, Clone)]
 struct State {
     func: GenericFn,
@@ -359,6 +471,8 @@ struct State {
     caller: ScopeId,
     emitted_names: HashMap<String, VariableId>,
     renames: HashMap<VariableId, String>,
+    lambda: Option<LambdaState>,
+    frame: Option<FrameState>,
 }
 
 impl State {
@@ -369,6 +483,8 @@ impl State {
             tmpvar: 0,
             emitted_names: Default::default(),
             renames: Default::default(),
+            lambda: None,
+            frame: None,
         }
     }
 
@@ -386,10 +502,29 @@ impl State {
     }
 
     pub fn tmpvar(&mut self) -> String {
+        let v = self.tmplabel();
+        if self.frame.is_some() {
+            format!("{FRAME_PTR}->{v}")
+        } else {
+            v
+        }
+    }
+
+    pub fn tmplabel(&mut self) -> String {
         let v = format!("${}", self.tmpvar);
         self.tmpvar += 1;
         v
     }
+
+    /// Returns the name used to access the local `name`, which is a frame member in async
+    /// functions.
+    pub fn local(&self, name: String) -> String {
+        if self.frame.is_some() {
+            format!("{FRAME_PTR}->{name}")
+        } else {
+            name
+        }
+    }
 }
 
 impl std::hash::Hash for State {
@@ -443,6 +578,10 @@ impl Buffer {
                 self.emit_type_name(scopes, types, &tr.clone(), min);
             }
             Type::FnPtr(f) => self.emit_fnptr_name(scopes, types, &f.clone(), min),
+            Type::DynFn(f) => {
+                self.emit(if min { "d" } else { "dyn" });
+                self.emit_fnptr_name(scopes, types, &f.clone(), min);
+            }
             Type::Fn(f) => {
                 let fptr = f.clone().as_fn_ptr(scopes, types);
                 self.emit_fnptr_name(scopes, types, &fptr, min)
@@ -452,6 +591,18 @@ impl Buffer {
                 self.emit_type_name_ex(scopes, types, &ut, min, true);
             }
             &Type::Array(ty, len) => self.emit_array_struct_name(scopes, types, ty, len, min),
+            Type::Const(value) => {
+                let sign = if *value < ComptimeInt::from(0) {
+                    "n"
+                } else {
+                    ""
+                };
+                let abs = value.clone().abs();
+                write_de!(self, "{}{sign}{abs}", if min { "k" } else { "const_" });
+            }
+            Type::GenericArray(_, _) => {
+                panic!("ICE: GenericArray in emit_generic_mangled_name")
+            }
             Type::Unknown => {
                 write_de!(self, "__Unknown");
                 eprintln!("ICE: TypeId::Unknown in emit_generic_mangled_name")
@@ -460,6 +611,17 @@ impl Buffer {
         }
     }
 
+    fn emit_lambda_fn_name(
+        &mut self,
+        scopes: &Scopes,
+        types: &mut Types,
+        ut: &GenericUserType,
+        min: bool,
+    ) {
+        self.emit_type_name(scopes, types, ut, min);
+        write_de!(self, "$call");
+    }
+
     fn emit_fnptr_name(&mut self, scopes: &Scopes, types: &mut Types, f: &FnPtr, min: bool) {
         write_de!(self, "fn");
         self.emit_mangled_name(scopes, types, f.ret, min);
@@ -504,7 +666,7 @@ impl Buffer {
                     write_de!(self, "*");
                 }
             }
-            Type::FnPtr(_) => self.emit_mangled_name(scopes, types, id, min),
+            Type::FnPtr(_) | Type::DynFn(_) => self.emit_mangled_name(scopes, types, id, min),
             Type::Fn(_) => self.emit_mangled_name(scopes, types, id, min),
             Type::User(ut) => {
                 if scopes.get(ut.id).kind.is_template() {
@@ -520,6 +682,8 @@ impl Buffer {
                 }
             }
             &Type::Array(_, _) => self.emit_mangled_name(scopes, types, id, min),
+            Type::GenericArray(_, _) => panic!("ICE: GenericArray in emit_type"),
+            Type::Const(_) => panic!("ICE: Const in emit_type"),
             Type::DynPtr(tr) | Type::DynMutPtr(tr) => {
                 self.emit_type_name(scopes, types, &tr.clone(), min)
             }
@@ -720,14 +884,13 @@ macro_rules! tmpbuf_emit {
 }
 
 macro_rules! enter_block {
-    ($self: expr, $scope: expr, $ty: expr, |$tmp: ident| $body: expr) => {{
+    ($self: expr, $state: expr, $scope: expr, $ty: expr, |$tmp: ident| $body: expr) => {{
         let ty = $ty;
         let scope = $scope;
         let old = std::mem::replace(&mut $self.cur_block, scope);
-        let $tmp = scope_var_or_label(scope);
+        let $tmp = $state.local(scope_var_or_label(scope));
         hoist!($self, {
-            $self.emit_type(ty);
-            write_de!($self.buffer, " {};", $tmp);
+            $self.emit_local_decl($state, ty, &$tmp);
             $body;
         });
 
@@ -742,10 +905,9 @@ macro_rules! enter_loop {
         let scope = $scope;
         let old_block = std::mem::replace(&mut $self.cur_block, scope);
         let old_loop = std::mem::replace(&mut $self.cur_loop, scope);
-        let $tmp = scope_var_or_label($self.cur_loop);
+        let $tmp = $state.local(scope_var_or_label($self.cur_loop));
         hoist!($self, {
-            $self.emit_type(ty);
-            write_de!($self.buffer, " {};", $tmp);
+            $self.emit_local_decl($state, ty, &$tmp);
             $body;
         });
 
@@ -776,6 +938,56 @@ macro_rules! usebuf {
     }};
 }
 
+/// Surrounds the index of a span in `Codegen::spans` in the generated code. The markers are
+/// replaced by `SpanMap::extract` once the code is complete, since buffers are rearranged too often
+/// to track output positions while emitting.
+const SPAN_MARKER_START: char = '\x01';
+const SPAN_MARKER_END: char = '\x02';
+
+/// Maps lines of the generated C code to the CTL source that produced them.
+#[derive(Default)]
+pub struct SpanMap(Vec<(u32, Option<Span>)>);
+
+impl SpanMap {
+    /// Removes the span markers from `code`. Unless `minify` is set, each marker is replaced with a
+    /// line break so the C compiler reports a distinct line for every statement, as compilers stop
+    /// tracking columns on very long lines.
+    fn extract(code: &str, spans: &[Span], minify: bool) -> (String, SpanMap) {
+        let mut result = String::with_capacity(code.len());
+        let mut map = SpanMap::default();
+        let mut line = 1;
+        let mut rest = code;
+        while let Some(start) = rest.find(SPAN_MARKER_START) {
+            let (text, marker) = rest.split_at(start);
+            result.push_str(text);
+            line += text.bytes().filter(|&ch| ch == b'\n').count() as u32;
+            if !minify && !result.ends_with('\n') {
+                result.push('\n');
+                line += 1;
+            }
+
+            let end = marker.find(SPAN_MARKER_END).unwrap();
+            let span = marker[1..end].parse::<usize>().ok().map(|i| spans[i]);
+            map.0.push((line, span));
+            rest = &marker[end + 1..];
+        }
+        result.push_str(rest);
+        (result, map)
+    }
+
+    /// Returns the span of the CTL code that generated `line` (1-based) of the C code, if any.
+    pub fn find(&self, line: u32) -> Option<Span> {
+        let idx = self.0.partition_point(|&(l, _)| l <= line);
+        idx.checked_sub(1).and_then(|idx| self.0[idx].1)
+    }
+}
+
+impl FromIterator<(u32, Option<Span>)> for SpanMap {
+    fn from_iter<T: IntoIterator<Item = (u32, Option<Span>)>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
 #[derive(PartialEq, Eq, Hash)]
 struct Vtable {
     tr: GenericTrait,
@@ -797,10 +1009,16 @@ pub struct Codegen {
     emitted_vtables: HashSet<Vtable>,
     defers: Vec<(ScopeId, Vec<Expr>)>,
     tg: TypeGen,
+    lambdas: Buffer,
+    lambda_count: usize,
+    emitted_lambdas: HashSet<TypeId>,
+    sources: CachingSourceProvider,
+    spans: Vec<Span>,
+    cur_span: Option<Span>,
 }
 
 impl Codegen {
-    pub fn build(proj: Project, flags: CodegenFlags) -> (String, Diagnostics) {
+    pub fn build(proj: Project, flags: CodegenFlags) -> (String, SpanMap, Diagnostics) {
         let exports = proj
             .scopes
             .functions()
@@ -808,7 +1026,7 @@ impl Codegen {
             .map(|(id, _)| {
                 State::in_body_scope(GenericFn::from_id(&proj.scopes, id), &proj.scopes)
             });
-        let (funcs, main) = if flags.lib {
+        let (funcs, main) = if flags.lib || flags.test {
             (exports.collect(), None)
         } else {
             let main = State::in_body_scope(
@@ -834,8 +1052,18 @@ impl Codegen {
             emitted_vtables: Default::default(),
             defers: Default::default(),
             tg: Default::default(),
+            lambdas: Default::default(),
+            lambda_count: 0,
+            emitted_lambdas: Default::default(),
+            sources: Default::default(),
+            spans: Default::default(),
+            cur_span: None,
+        };
+        let main = if this.flags.test {
+            Some(this.gen_test_main())
+        } else {
+            main.map(|mut main| this.gen_c_main(&mut main))
         };
-        let main = main.map(|mut main| this.gen_c_main(&mut main));
         let mut static_defs = Buffer::default();
         let mut static_init = Buffer::default();
         let mut prototypes = Buffer::default();
@@ -885,6 +1113,9 @@ impl Codegen {
         if this.flags.no_bit_int {
             writeln_de!(this.buffer, "#define CTL_NOBITINT");
         }
+        if this.flags.test {
+            writeln_de!(this.buffer, "#define CTL_TEST");
+        }
         this.buffer.emit(include_str!("../ctl/ctl.h"));
         this.tg.emit(
             &this.proj.scopes,
@@ -895,6 +1126,7 @@ impl Codegen {
         this.buffer.emit(prototypes.finish());
         this.buffer.emit(this.vtables.finish());
         this.buffer.emit(static_defs.finish());
+        this.buffer.emit(std::mem::take(&mut this.lambdas).finish());
         this.buffer.emit(functions.finish());
         write_de!(this.buffer, "static void $ctl_static_init(void){{");
         this.buffer.emit(static_init.finish());
@@ -903,7 +1135,8 @@ impl Codegen {
             this.buffer.emit(main);
         }
 
-        (this.buffer.finish(), this.proj.diag)
+        let (code, spans) = SpanMap::extract(&this.buffer.finish(), &this.spans, this.flags.minify);
+        (code, spans, this.proj.diag)
     }
 
     fn emit_vtable(&mut self, vtable: Vtable) {
@@ -989,6 +1222,9 @@ impl Codegen {
                         &func,
                         self.flags.minify,
                     );
+                    if self.tracks_caller(func.id) {
+                        write_de!(self.buffer, "$untracked");
+                    }
                     write_de!(self.buffer, ",");
                     self.funcs.insert(State::new(func, vtable.scope));
                 }
@@ -1001,6 +1237,10 @@ impl Codegen {
     }
 
     fn gen_c_main(&mut self, main: &mut State) -> String {
+        if self.flags.debug_info {
+            // attribute the C entry point to the CTL one instead of whatever was emitted last
+            self.emit_line_directive(self.proj.scopes.get(main.func.id).name.span);
+        }
         write_de!(self.buffer, "int main(int argc, char **argv){{");
         let returns = !self.proj.scopes.get(main.func.id).ret.is_void();
         if let Some(id) = self
@@ -1056,17 +1296,66 @@ impl Codegen {
         std::mem::take(&mut self.buffer).finish()
     }
 
+    fn gen_test_main(&mut self) -> String {
+        let tests = self.proj.tests.clone();
+        if tests.is_empty() {
+            write_de!(
+                self.buffer,
+                "int main(int argc, char **argv){{return ctl_run_tests(NULL,0,argc,argv);}}"
+            );
+            return std::mem::take(&mut self.buffer).finish();
+        }
+
+        write_de!(self.buffer, "static const CtlTest $ctl_tests[]={{");
+        for &id in tests.iter() {
+            let state =
+                State::in_body_scope(GenericFn::from_id(&self.proj.scopes, id), &self.proj.scopes);
+            let mut path: Vec<_> = self
+                .proj
+                .scopes
+                .walk(state.caller)
+                .flat_map(|(_, scope)| scope.kind.name(&self.proj.scopes))
+                .map(|name| name.data.as_str())
+                .collect();
+            path.reverse();
+            let name = path.join("::");
+            write_de!(self.buffer, "{{\"{name}\",");
+            self.buffer.emit_fn_name(
+                &self.proj.scopes,
+                &mut self.proj.types,
+                &state.func,
+                self.flags.minify,
+            );
+            if self.tracks_caller(id) {
+                write_de!(self.buffer, "$untracked");
+            }
+            write_de!(self.buffer, "}},");
+            self.funcs.insert(state);
+        }
+        write_de!(
+            self.buffer,
+            "}};int main(int argc, char **argv){{return ctl_run_tests($ctl_tests,{},argc,argv);}}",
+            tests.len()
+        );
+        std::mem::take(&mut self.buffer).finish()
+    }
+
     fn emit_fn(&mut self, state: &mut State, prototypes: &mut Buffer) {
         // TODO: emit an error if a function has the c_macro attribute and a body
         let func = self.proj.scopes.get(state.func.id);
         if func.attrs.has(ATTR_NOGEN) {
             return;
+        } else if func.is_async || func.is_generator {
+            return self.emit_frame_fn(state, prototypes);
         }
 
         usebuf!(self, prototypes, {
             self.emit_prototype(state, true);
             write_de!(self.buffer, ";");
         });
+        if self.tracks_caller(state.func.id) {
+            self.emit_untracked_fn(state, prototypes);
+        }
         let func = self.proj.scopes.get(state.func.id);
         if let Some(body) = func.body.clone() {
             let void_return = func
@@ -1074,6 +1363,11 @@ impl Codegen {
                 .with_templates(&mut self.proj.types, &state.func.ty_args)
                 .is_void();
             let params = func.params.clone();
+            let span = func.name.span;
+            if self.flags.debug_info {
+                self.emit_line_directive(span);
+            }
+            let prev_span = self.set_span(Some(span));
             let unused = self.emit_prototype(state, false);
             write_de!(self.buffer, "{{");
             for id in unused {
@@ -1107,7 +1401,208 @@ impl Codegen {
                     write_de!(self.buffer, ";}}");
                 }
             });
+            self.set_span(prev_span);
+        }
+    }
+
+    /// Emits a shim for a function that tracks its caller, for uses that don't call it directly
+    /// (function pointers, vtables). It reports the location of the function itself.
+    fn emit_untracked_fn(&mut self, state: &mut State, prototypes: &mut Buffer) {
+        let func = self.proj.scopes.get(state.func.id);
+        let span = func.name.span;
+        let ret = func
+            .ret
+            .with_templates(&mut self.proj.types, &state.func.ty_args);
+        let params: Vec<_> = func.params.iter().map(|param| param.ty).collect();
+
+        let mut proto = Buffer::default();
+        usebuf!(self, &mut proto, {
+            write_de!(self.buffer, "static ");
+            if ret == TypeId::NEVER {
+                write_de!(self.buffer, "CTL_NORETURN ");
+            }
+            if ret.is_void() {
+                write_de!(self.buffer, "void ");
+            } else {
+                self.emit_type(ret);
+                write_de!(self.buffer, " ");
+            }
+            self.buffer.emit_fn_name(
+                &self.proj.scopes,
+                &mut self.proj.types,
+                &state.func,
+                self.flags.minify,
+            );
+            write_de!(self.buffer, "$untracked(");
+            for (i, ty) in params.iter().enumerate() {
+                if i > 0 {
+                    write_de!(self.buffer, ",");
+                }
+                let ty = ty.with_templates(&mut self.proj.types, &state.func.ty_args);
+                self.emit_type(ty);
+                write_de!(self.buffer, " $p{i}");
+            }
+            if params.is_empty() {
+                write_de!(self.buffer, "void");
+            }
+            write_de!(self.buffer, ")");
+        });
+        let proto = proto.finish();
+        write_de!(prototypes, "{proto};");
+
+        let loc = self.emit_location(span);
+        write_de!(self.buffer, "{proto}{{");
+        if !ret.is_void() {
+            write_de!(self.buffer, "return ");
+        }
+        self.buffer.emit_fn_name(
+            &self.proj.scopes,
+            &mut self.proj.types,
+            &state.func,
+            self.flags.minify,
+        );
+        write_de!(self.buffer, "(");
+        for i in 0..params.len() {
+            write_de!(self.buffer, "$p{i},");
+        }
+        write_de!(self.buffer, "{loc});}}");
+    }
+
+    /// Emits an async or generator function as a constructor, which stores the arguments in a new
+    /// frame, and a poll function, which runs the body until it completes or suspends. Each
+    /// suspension point is a case of a switch over the frame's state, so the next poll resumes from
+    /// where it left off.
+    fn emit_frame_fn(&mut self, state: &mut State, prototypes: &mut Buffer) {
+        let func = self.proj.scopes.get(state.func.id);
+        let Some(body) = func.body.clone() else {
+            return;
+        };
+        let params = func.params.clone();
+        let frame_ty = func
+            .ret
+            .with_templates(&mut self.proj.types, &state.func.ty_args);
+        let poll_ty = body
+            .ty
+            .with_templates(&mut self.proj.types, &state.func.ty_args);
+        let frame_ut = self.proj.types[frame_ty].as_user().unwrap().clone();
+        let context = self
+            .proj
+            .scopes
+            .get(frame_ut.id)
+            .kind
+            .as_frame()
+            .and_then(|frame| frame.context);
+        let context_ty = context.map(|id| self.proj.scopes.get(id).ty);
+
+        usebuf!(self, prototypes, {
+            self.emit_prototype(state, true);
+            write_de!(self.buffer, ";static ");
+            self.emit_type(poll_ty);
+            write_de!(self.buffer, " ");
+            self.emit_frame_fn_name(&frame_ut);
+            write_de!(self.buffer, "(");
+            self.emit_type(frame_ty);
+            write_de!(self.buffer, "*");
+            if let Some(ty) = context_ty {
+                write_de!(self.buffer, ",");
+                self.emit_type(ty);
+            }
+            write_de!(self.buffer, ");");
+        });
+
+        self.emit_prototype(state, false);
+        write_de!(self.buffer, "{{return(");
+        self.emit_type(frame_ty);
+        write_de!(self.buffer, "){{.{FRAME_STATE}=0");
+        let mut members = IndexMap::new();
+        for param in params.iter() {
+            let ty = param
+                .ty
+                .with_templates(&mut self.proj.types, &state.func.ty_args);
+            let name = if let Some(&PatternData::Variable(id)) =
+                param.patt.as_checked().map(|patt| &patt.data)
+            {
+                let start = self.buffer.0.len();
+                self.emit_var_name(id, state);
+                self.buffer.0.split_off(start)
+            } else {
+                param.label.clone()
+            };
+            write_de!(self.buffer, ",.{name}={name}");
+            members.insert(name, ty);
+        }
+        write_de!(self.buffer, "}};}}");
+
+        let mut poll_state = state.clone();
+        poll_state.frame = Some(FrameState {
+            members,
+            resume_points: 0,
+        });
+        write_de!(self.buffer, "static ");
+        self.emit_type(poll_ty);
+        write_de!(self.buffer, " ");
+        self.emit_frame_fn_name(&frame_ut);
+        write_de!(self.buffer, "(");
+        self.emit_type(frame_ty);
+        write_de!(self.buffer, "*{FRAME_PTR}");
+        if let Some(ty) = context_ty {
+            write_de!(self.buffer, ",");
+            self.emit_type(ty);
+            write_de!(self.buffer, " {FRAME_CONTEXT}");
+        }
+        write_de!(self.buffer, "){{");
+        if let Some(id) = context {
+            // the context can change between polls, so store the current one in the frame
+            self.emit_var_decl(id, &mut poll_state);
+            write_de!(self.buffer, "={FRAME_CONTEXT};");
+        }
+        write_de!(self.buffer, "switch({FRAME_PTR}->{FRAME_STATE}){{case 0:;");
+        for param in params.iter() {
+            let Some(patt) = param
+                .patt
+                .as_checked()
+                .filter(|patt| !matches!(patt.data, PatternData::Variable(_)))
+            else {
+                continue;
+            };
+
+            let ty = param
+                .ty
+                .with_templates(&mut self.proj.types, &state.func.ty_args);
+            let src = format!("{FRAME_PTR}->{}", param.label);
+            self.emit_pattern_bindings(&mut poll_state, &patt.data, &src, ty);
         }
+
+        hoist_point!(self, {
+            write_de!(self.buffer, "{FRAME_PTR}->{FRAME_STATE}=-1;return ");
+            self.emit_expr_inline(body, &mut poll_state);
+            write_de!(self.buffer, ";");
+        });
+        if self.proj.scopes.get(frame_ut.id).kind.is_generator() {
+            // a finished generator keeps returning None
+            write_de!(self.buffer, "default:return ");
+            self.emit_expr_inline(Expr::option_null(poll_ty), &mut poll_state);
+            write_de!(self.buffer, ";}}}}");
+        } else {
+            write_de!(self.buffer, "default:CTL_UNREACHABLE();}}}}");
+        }
+
+        let members = poll_state.frame.take().unwrap().members;
+        self.tg
+            .add_frame(&self.proj.scopes, &mut self.proj.types, frame_ty, members);
+    }
+
+    fn emit_frame_fn_name(&mut self, frame: &GenericUserType) {
+        let kind = &self.proj.scopes.get(frame.id).kind;
+        let suffix = if kind.is_async_fn() { "$poll" } else { "$next" };
+        let func = kind.as_frame().unwrap().func;
+        self.buffer.emit_fn_name(
+            &self.proj.scopes,
+            &mut self.proj.types,
+            &GenericFn::new(func, frame.ty_args.clone()),
+            self.flags.minify,
+        );
+        write_de!(self.buffer, "{suffix}");
     }
 
     fn emit_expr_stmt(&mut self, expr: Expr, state: &mut State) {
@@ -1209,13 +1704,17 @@ impl Codegen {
                 write_de!(self.buffer, "}}");
                 self.emit_vtable(vtable);
             }
-            ExprData::Call(callee, args) => {
+            ExprData::Call(callee, args, span) => {
+                if let Some(inst) = self.builtin_fn_impl(&callee, state) {
+                    return self.emit_builtin_fn_call(state, expr.ty, inst, args);
+                }
+
                 let func = self.proj.types[callee.ty].as_fn().unwrap();
                 if let Some(name) = self.proj.scopes.intrinsic_name(func.id) {
                     let mut func = func.clone();
                     func.fill_templates(&mut self.proj.types, &state.func.ty_args);
                     let name = name.to_string();
-                    return self.emit_intrinsic(&name, expr.ty, &func, args, state);
+                    return self.emit_intrinsic(&name, expr.ty, &func, args, state, span);
                 } else if let Some(id) = self.proj.scopes.get(func.id).constructor {
                     if self.proj.scopes.get(id).kind.is_union() {
                         return self.emit_variant_instance(
@@ -1233,9 +1732,21 @@ impl Codegen {
                     write_de!(self.buffer, "VOID(");
                 }
                 let id = func.id;
-                self.emit_expr(*callee, state);
+                let resolved = match callee.data {
+                    ExprData::Fn(func, scope) => Some(self.emit_fn_ref(state, func, scope, true)),
+                    ExprData::MemFn(mfn, scope) => {
+                        Some(self.emit_member_fn(state, mfn, scope, true))
+                    }
+                    _ => {
+                        self.emit_expr(*callee, state);
+                        None
+                    }
+                };
                 write_de!(self.buffer, "(");
-                self.finish_emit_fn_args(state, id, args);
+                let caller = resolved
+                    .filter(|&id| self.tracks_caller(id))
+                    .map(|_| self.caller_location(state, span));
+                self.finish_emit_fn_args(state, id, args, caller);
                 if expr.ty.is_void() {
                     write_de!(self.buffer, ")");
                 }
@@ -1258,25 +1769,49 @@ impl Codegen {
                     write_de!(self.buffer, ")");
                 } else {
                     write_de!(self.buffer, ",");
-                    self.finish_emit_fn_args(state, func.id, args);
+                    self.finish_emit_fn_args(state, func.id, args, None);
                 }
                 if expr.ty.is_void() {
                     write_de!(self.buffer, ")");
                 }
             }
-            ExprData::CallFnPtr(inner, args) => {
+            ExprData::CallFnPtr(mut inner, args) => {
                 if expr.ty.is_void() {
                     write_de!(self.buffer, "VOID(");
                 }
-                write_de!(self.buffer, "(");
-                self.emit_expr(*inner, state);
-                write_de!(self.buffer, ")(");
-                for (i, arg) in args.into_iter().enumerate() {
-                    if i > 0 {
+                inner.ty = inner
+                    .ty
+                    .with_templates(&mut self.proj.types, &state.func.ty_args);
+                if self.proj.types[inner.ty].is_dyn_fn() {
+                    let tmp = hoist!(self, self.emit_tmpvar(*inner, state));
+                    write_de!(self.buffer, "{tmp}.fn({tmp}.env");
+                    for arg in args {
+                        write_de!(self.buffer, ",");
+                        self.emit_expr(arg, state);
+                    }
+                } else if let Some(ut) = self.proj.types[inner.ty].as_user().cloned() {
+                    self.emit_lambda_fn_name(&ut);
+                    write_de!(self.buffer, "(");
+                    let ty = self.proj.types.insert(Type::MutPtr(inner.ty));
+                    self.emit_expr(
+                        Expr::new(ty, ExprData::Unary(UnaryOp::AddrMut, inner)),
+                        state,
+                    );
+                    for arg in args {
                         write_de!(self.buffer, ",");
+                        self.emit_expr(arg, state);
                     }
+                } else {
+                    write_de!(self.buffer, "(");
+                    self.emit_expr(*inner, state);
+                    write_de!(self.buffer, ")(");
+                    for (i, arg) in args.into_iter().enumerate() {
+                        if i > 0 {
+                            write_de!(self.buffer, ",");
+                        }
 
-                    self.emit_expr(arg, state);
+                        self.emit_expr(arg, state);
+                    }
                 }
                 write_de!(self.buffer, ")");
                 if expr.ty.is_void() {
@@ -1293,7 +1828,8 @@ impl Codegen {
                 }
                 write_de!(self.buffer, "}}}}");
             }
-            ExprData::ArrayWithInit { init, count } => {
+            ExprData::ArrayWithInit(init) => {
+                let count = *self.proj.types[expr.ty].as_array().unwrap().1;
                 // number chosen arbitrarily
                 if count <= 32 {
                     write_de!(self.buffer, "(");
@@ -1306,10 +1842,10 @@ impl Codegen {
                     write_de!(self.buffer, "}}}}");
                 } else {
                     tmpbuf_emit!(self, state, |tmp| {
-                        self.emit_type(expr.ty);
+                        self.emit_local_decl(state, expr.ty, &tmp);
                         write_de!(
                             self.buffer,
-                            " {tmp}; for(usize i=0;i<{count};i++){{{tmp}.{ARRAY_DATA_NAME}[i]="
+                            "for(usize i=0;i<{count};i++){{{tmp}.{ARRAY_DATA_NAME}[i]="
                         );
                         self.emit_expr_inline(*init, state);
                         write_de!(self.buffer, ";}}");
@@ -1324,7 +1860,7 @@ impl Codegen {
 
                 tmpbuf_emit!(self, state, |tmp| {
                     let len = exprs.len();
-                    self.emit_with_capacity(expr.ty, &tmp, &ut, len);
+                    self.emit_with_capacity(state, expr.ty, &tmp, &ut, len);
                     for (i, expr) in exprs.into_iter().enumerate() {
                         write_de!(self.buffer, "{tmp}.$ptr[{i}]=");
                         self.emit_expr_inline(expr, state);
@@ -1337,7 +1873,7 @@ impl Codegen {
                 tmpbuf_emit!(self, state, |tmp| {
                     let ut = self.proj.types[expr.ty].as_user().unwrap().clone();
                     let len = self.emit_tmpvar(*count, state);
-                    self.emit_with_capacity(expr.ty, &tmp, &ut, &len);
+                    self.emit_with_capacity(state, expr.ty, &tmp, &ut, &len);
                     write_de!(self.buffer, "for(usize i=0;i<{len};i++){{");
                     hoist_point!(self, {
                         write_de!(self.buffer, "((");
@@ -1355,7 +1891,7 @@ impl Codegen {
                 }
 
                 tmpbuf_emit!(self, state, |tmp| {
-                    self.emit_with_capacity(expr.ty, &tmp, &ut, exprs.len());
+                    self.emit_with_capacity(state, expr.ty, &tmp, &ut, exprs.len());
                     let insert = State::with_inst(
                         GenericFn::from_id(
                             &self.proj.scopes,
@@ -1405,7 +1941,7 @@ impl Codegen {
                         scope,
                     );
 
-                    self.emit_with_capacity(expr.ty, &tmp, &ut, exprs.len());
+                    self.emit_with_capacity(state, expr.ty, &tmp, &ut, exprs.len());
                     for (key, val) in exprs {
                         self.buffer.emit_fn_name(
                             &self.proj.scopes,
@@ -1423,6 +1959,16 @@ impl Codegen {
                 });
             }
             ExprData::Int(value) => self.emit_literal(value, expr.ty),
+            ExprData::ConstParam(id) => {
+                let value = match state.func.ty_args.get(&id).map(|&ty| &self.proj.types[ty]) {
+                    Some(Type::Const(value)) => value.clone(),
+                    _ => panic!(
+                        "ICE: const generic parameter '{}' has no value",
+                        self.proj.scopes.get(id).name
+                    ),
+                };
+                self.emit_literal(value, expr.ty)
+            }
             ExprData::Float(mut value) => {
                 self.emit_cast(expr.ty);
                 value.retain(|c| c != '_');
@@ -1438,23 +1984,34 @@ impl Codegen {
                 write_de!(self.buffer, "\"");
             }
             ExprData::Void => self.buffer.emit(VOID_INSTANCE),
-            ExprData::Fn(mut func, scope) => {
-                func.fill_templates(&mut self.proj.types, &state.func.ty_args);
-                let state = State::new(func, scope);
-                self.buffer.emit_fn_name(
-                    &self.proj.scopes,
-                    &mut self.proj.types,
-                    &state.func,
-                    self.flags.minify,
-                );
-                self.funcs.insert(state);
+            ExprData::Fn(func, scope) => {
+                self.emit_fn_ref(state, func, scope, false);
+            }
+            ExprData::MemFn(mfn, scope) => {
+                self.emit_member_fn(state, mfn, scope, false);
             }
-            ExprData::MemFn(mfn, scope) => self.emit_member_fn(state, mfn, scope),
             ExprData::Var(id) => {
                 if self.proj.scopes.get(id).is_static {
                     self.statics.insert(id);
                 }
-                self.emit_var_name(id, state);
+                self.emit_capture(id, false, state);
+            }
+            ExprData::AssocConst(ty, id) => {
+                let ty = ty.with_templates(&mut self.proj.types, &state.func.ty_args);
+                let name = self.proj.scopes.get(id).name.data.clone();
+                let caller = state.caller;
+                let Some(id) = TypeChecker::with_project(&mut self.proj, |tc| {
+                    tc.find_assoc_const(ty, &name, caller)
+                }) else {
+                    panic!(
+                        "searching from scope: '{}', cannot find constant '{name}' for type '{}'",
+                        self.proj.scopes.full_name(caller, ""),
+                        ty.name(&self.proj.scopes, &mut self.proj.types)
+                    )
+                };
+
+                self.statics.insert(id);
+                self.emit_capture(id, false, state);
             }
             ExprData::Instance(members) => self.emit_instance(state, expr.ty, members),
             ExprData::VariantInstance(name, members) => {
@@ -1466,8 +2023,8 @@ impl Codegen {
                 {
                     let tmp = tmpbuf!(self, state, |tmp| {
                         let ptr = self.proj.types.insert(Type::Ptr(source.ty));
-                        self.emit_type(ptr);
-                        writeln_de!(self.buffer, " {tmp}=&");
+                        self.emit_local(state, ptr, &tmp);
+                        writeln_de!(self.buffer, "=&");
                         self.emit_expr_inline(*source, state);
                         writeln_de!(self.buffer, ";");
                         tmp
@@ -1482,14 +2039,15 @@ impl Codegen {
                     );
                 }
             }
-            ExprData::Block(block) => enter_block!(self, block.scope, expr.ty, |name| {
+            ExprData::Block(block) => enter_block!(self, state, block.scope, expr.ty, |name| {
                 let yields = block.is_yielding(&self.proj.scopes);
+                let label = scope_var_or_label(block.scope);
                 write_nm!(self, "{{");
                 self.emit_block(block, state);
                 if !yields {
                     write_de!(self.buffer, "{name}={VOID_INSTANCE};");
                 } else {
-                    write_de!(self.buffer, "{name}:;");
+                    write_de!(self.buffer, "{label}:;");
                 }
                 write_nm!(self, "}}");
             }),
@@ -1502,7 +2060,7 @@ impl Codegen {
                     .proj
                     .scopes
                     .create_scope(ScopeId::ROOT, ScopeKind::None, false);
-                enter_block!(self, dummy, expr.ty, |name| {
+                enter_block!(self, state, dummy, expr.ty, |name| {
                     write_de!(self.buffer, "if(");
                     self.emit_expr_inline(*cond, state);
                     write_de!(self.buffer, "){{");
@@ -1562,12 +2120,49 @@ impl Codegen {
                     });
                     write_de!(self.buffer, "{}:;}}", loop_cont_label(self.cur_loop));
                     if self.proj.scopes[scope].kind.as_loop().unwrap().breaks != LoopBreak::None {
-                        write_de!(self.buffer, "{name}:;");
+                        write_de!(self.buffer, "{}:;", scope_var_or_label(scope));
                     }
                 });
             }
-            ExprData::Subscript { callee, arg } => {
-                // TODO: bounds check
+            ExprData::Subscript {
+                mut callee,
+                mut arg,
+                span,
+            } => {
+                callee.ty = callee
+                    .ty
+                    .with_templates(&mut self.proj.types, &state.func.ty_args);
+                let index = if let Some((_, &len)) = callee
+                    .ty
+                    .strip_references_r(&self.proj.types)
+                    .as_array()
+                    .filter(|_| !self.flags.no_bounds_check)
+                {
+                    arg.ty = arg
+                        .ty
+                        .with_templates(&mut self.proj.types, &state.func.ty_args);
+                    let signed = arg
+                        .ty
+                        .as_integral(&self.proj.types, true)
+                        .is_some_and(|int| int.signed);
+                    let index = hoist!(self, self.emit_tmpvar(*arg, state));
+                    let loc = self.emit_location(span);
+                    hoist!(self, {
+                        write_de!(self.buffer, "if(CTL_UNLIKELY(");
+                        if signed {
+                            write_de!(self.buffer, "{index}<0||");
+                        }
+                        write_de!(self.buffer, "{index}>={len})){{");
+                        self.emit_panic(loc, |this| {
+                            this.emit_string_literal("array index out of bounds")
+                        });
+                        write_de!(self.buffer, ";}}");
+                    });
+                    Ok(index)
+                } else {
+                    Err(arg)
+                };
+
                 if self.proj.types[callee.ty].is_array() {
                     match callee.data {
                         ExprData::Unary(UnaryOp::Deref, expr) => {
@@ -1590,11 +2185,19 @@ impl Codegen {
                     write_de!(self.buffer, ")");
                 }
 
-                write_de!(self.buffer, "[");
-                self.emit_expr(*arg, state);
-                write_de!(self.buffer, "]");
+                match index {
+                    Ok(index) => write_de!(self.buffer, "[{index}]"),
+                    Err(arg) => {
+                        write_de!(self.buffer, "[");
+                        self.emit_expr(*arg, state);
+                        write_de!(self.buffer, "]");
+                    }
+                }
             }
-            ExprData::SliceArray { callee, arg } => {
+            ExprData::SliceArray { mut callee, arg } => {
+                callee.ty = callee
+                    .ty
+                    .with_templates(&mut self.proj.types, &state.func.ty_args);
                 let indirection = Self::indirection(&self.proj.types, callee.ty);
                 let src = tmpbuf!(self, state, |tmp| {
                     let len = *callee
@@ -1603,8 +2206,8 @@ impl Codegen {
                         .as_array()
                         .unwrap()
                         .1;
-                    self.emit_type(expr.ty);
-                    write_de!(self.buffer, " {tmp}={{.$ptr=");
+                    self.emit_local(state, expr.ty, &tmp);
+                    write_de!(self.buffer, "={{.$ptr=");
                     if indirection != 0 {
                         self.buffer.emit("*".repeat(indirection - 1));
                         self.emit_expr_inline(*callee, state);
@@ -1645,16 +2248,37 @@ impl Codegen {
                     .with_templates(&mut self.proj.types, &state.func.ty_args);
                 let void = expr.ty.is_void();
                 let tmp = self.emit_tmpvar(*expr, state);
-                let str = if void {
+                let str = if state.frame.is_some() {
+                    format!("{FRAME_PTR}->{FRAME_STATE}=-1;return {tmp}")
+                } else if void {
                     write_de!(self.buffer, "(void){tmp};");
                     "return".into()
                 } else {
                     format!("return {tmp}")
                 };
-                self.leave_scope(state, &str, self.proj.scopes.get(state.func.id).body_scope);
+                let scope = state
+                    .lambda
+                    .as_ref()
+                    .map(|lambda| lambda.scope)
+                    .unwrap_or(self.proj.scopes.get(state.func.id).body_scope);
+                self.leave_scope(state, &str, scope);
             }),
+            ExprData::Suspend(expr) => {
+                let frame = state
+                    .frame
+                    .as_mut()
+                    .expect("ICE: suspend outside of async function");
+                frame.resume_points += 1;
+                let point = frame.resume_points;
+                hoist!(self, {
+                    write_de!(self.buffer, "{FRAME_PTR}->{FRAME_STATE}={point};return ");
+                    self.emit_expr_inline(*expr, state);
+                    write_de!(self.buffer, ";case {point}:;");
+                });
+                self.buffer.emit(VOID_INSTANCE);
+            }
             ExprData::Yield(expr, scope) => never_expr!(self, {
-                write_de!(self.buffer, "{}=", scope_var_or_label(scope));
+                write_de!(self.buffer, "{}=", state.local(scope_var_or_label(scope)));
                 if let Some(expr) = expr {
                     self.emit_expr_inline(*expr, state);
                 } else {
@@ -1667,7 +2291,7 @@ impl Codegen {
                 // }
             }),
             ExprData::Break(expr, scope) => never_expr!(self, {
-                write_de!(self.buffer, "{}=", scope_var_or_label(scope));
+                write_de!(self.buffer, "{}=", state.local(scope_var_or_label(scope)));
                 if let Some(expr) = expr {
                     self.emit_expr_inline(*expr, state);
                 } else {
@@ -1695,7 +2319,7 @@ impl Codegen {
                     .proj
                     .scopes
                     .create_scope(ScopeId::ROOT, ScopeKind::None, false);
-                enter_block!(self, dummy, expr.ty, |name| {
+                enter_block!(self, state, dummy, expr.ty, |name| {
                     scrutinee.ty = scrutinee
                         .ty
                         .with_templates(&mut self.proj.types, &state.func.ty_args);
@@ -1745,7 +2369,37 @@ impl Codegen {
                 hoist!(self, self.buffer.emit(bindings.finish()));
                 write_de!(self.buffer, "({})", conditions.finish());
             }
-            ExprData::Lambda(_) => todo!(),
+            ExprData::Lambda {
+                params,
+                captures,
+                moves,
+                body,
+            } => self.emit_lambda(state, expr.ty, params, captures, moves, body),
+            ExprData::DynFnCoerce(mut inner) => {
+                inner.ty = inner
+                    .ty
+                    .with_templates(&mut self.proj.types, &state.func.ty_args);
+                let ty = inner.ty;
+                let ut = self.proj.types[ty].as_user().cloned().unwrap();
+                let has_env = !self.proj.scopes.get(ut.id).members.is_empty();
+                let tmp = hoist!(self, self.emit_tmpvar(*inner, state));
+                let env = if has_env {
+                    tmpbuf!(self, state, |env| {
+                        let ptr = self.proj.types.insert(Type::RawPtr(ty));
+                        self.emit_local(state, ptr, &env);
+                        write_de!(self.buffer, "=CTL_MALLOC(sizeof({tmp}),_Alignof(");
+                        self.emit_type(ty);
+                        write_de!(self.buffer, "));*{env}={tmp};");
+                        env
+                    })
+                } else {
+                    "NULL".into()
+                };
+                self.emit_cast(expr.ty);
+                write_de!(self.buffer, "{{.env={env},.fn=");
+                self.emit_lambda_fn_name(&ut);
+                write_de!(self.buffer, "}}");
+            }
             ExprData::NeverCoerce(inner) => {
                 if matches!(expr.ty, TypeId::VOID | TypeId::CVOID) {
                     self.emit_expr_inline(*inner, state);
@@ -1781,7 +2435,7 @@ impl Codegen {
                             expr.ty = expr
                                 .ty
                                 .with_templates(&mut self.proj.types, &state.func.ty_args);
-                            self.emit_member_fn(state, mfn, scope);
+                            self.emit_member_fn(state, mfn, scope, false);
                             write_de!(self.buffer, "(");
                             self.emit_expr_inline(expr, state);
                             write_de!(self.buffer, ",&{formatter});");
@@ -1838,6 +2492,7 @@ impl Codegen {
                                 mfn,
                                 [(param, (*callee).clone())].into(),
                                 scope,
+                                Span::default(),
                             ),
                         );
                         self.emit_expr_stmt(expr, state);
@@ -1847,8 +2502,8 @@ impl Codegen {
                     write_de!(self.buffer, ")");
                 } else {
                     tmpbuf_emit!(self, state, |tmp| {
-                        self.emit_type(expr.ty);
-                        write_de!(self.buffer, " {tmp}={deref}");
+                        self.emit_local(state, expr.ty, &tmp);
+                        write_de!(self.buffer, "={deref}");
                         self.emit_expr_inline((*callee).clone(), state);
                         write_de!(self.buffer, ";");
 
@@ -1863,6 +2518,7 @@ impl Codegen {
                                 mfn,
                                 [(param, *callee)].into(),
                                 scope,
+                                Span::default(),
                             ),
                         );
                         self.emit_expr_stmt(expr, state);
@@ -1873,53 +2529,289 @@ impl Codegen {
         }
     }
 
-    #[inline(always)]
-    fn emit_expr_inline(&mut self, mut expr: Expr, state: &mut State) {
-        expr.ty = expr
-            .ty
-            .with_templates(&mut self.proj.types, &state.func.ty_args);
-        self.emit_expr_inner(expr, state);
-    }
+    #[inline(always)]
+    fn emit_expr_inline(&mut self, mut expr: Expr, state: &mut State) {
+        expr.ty = expr
+            .ty
+            .with_templates(&mut self.proj.types, &state.func.ty_args);
+        self.emit_expr_inner(expr, state);
+    }
+
+    /// Emits the name of `func`, or of its untracked shim if it tracks its caller and is not being
+    /// called directly. Returns the ID of the referenced function.
+    fn emit_fn_ref(
+        &mut self,
+        state: &mut State,
+        mut func: GenericFn,
+        scope: ScopeId,
+        call: bool,
+    ) -> FunctionId {
+        func.fill_templates(&mut self.proj.types, &state.func.ty_args);
+        let state = State::new(func, scope);
+        self.buffer.emit_fn_name(
+            &self.proj.scopes,
+            &mut self.proj.types,
+            &state.func,
+            self.flags.minify,
+        );
+        let id = state.func.id;
+        if !call && self.tracks_caller(id) {
+            write_de!(self.buffer, "$untracked");
+        }
+        self.funcs.insert(state);
+        id
+    }
+
+    fn emit_member_fn(
+        &mut self,
+        state: &mut State,
+        mut mfn: MemberFn,
+        scope: ScopeId,
+        call: bool,
+    ) -> FunctionId {
+        if let MemberFnType::Trait(mut tr) = mfn.typ {
+            let inst = mfn
+                .inst
+                .with_templates(&mut self.proj.types, &state.func.ty_args);
+            tr.fill_templates(&mut self.proj.types, &state.func.ty_args);
+            mfn.func = self.find_implementation(
+                inst,
+                &tr,
+                &self.proj.scopes.get(mfn.func.id).name.data.clone(),
+                state.caller,
+                |tc, id| {
+                    TypeArgs::in_order(
+                        tc.scopes(),
+                        id,
+                        mfn.func.ty_args.0.into_iter().map(|kv| kv.1),
+                    )
+                },
+            );
+        }
+
+        self.emit_fn_ref(state, mfn.func, scope, call)
+    }
+
+    fn builtin_fn_impl(&mut self, callee: &Expr, state: &State) -> Option<TypeId> {
+        let ExprData::MemFn(mfn, _) = &callee.data else {
+            return None;
+        };
+        let tr = mfn.typ.as_trait()?;
+        let inst = mfn
+            .inst
+            .with_templates(&mut self.proj.types, &state.func.ty_args);
+        if let Some(frame_tr) = self.proj.scopes.frame_trait(&mut self.proj.types, inst) {
+            // only the required method is generated, provided methods are regular trait functions
+            if frame_tr.id == tr.id && self.proj.scopes.get(mfn.func.id).body.is_none() {
+                return Some(inst);
+            }
+        }
+
+        if !FN_TRAITS
+            .iter()
+            .any(|name| self.proj.scopes.lang_traits.get(*name) == Some(&tr.id))
+        {
+            return None;
+        }
+
+        self.proj
+            .scopes
+            .fn_signature(&mut self.proj.types, inst)
+            .is_some()
+            .then_some(inst)
+    }
+
+    fn emit_builtin_fn_call(
+        &mut self,
+        state: &mut State,
+        ret: TypeId,
+        inst: TypeId,
+        mut args: IndexMap<String, Expr>,
+    ) {
+        if let Type::User(ut) = &self.proj.types[inst] {
+            if self.proj.scopes.get(ut.id).kind.as_frame().is_some() {
+                let ut = ut.clone();
+                self.emit_frame_fn_name(&ut);
+                write_de!(self.buffer, "(");
+                self.emit_expr(args.shift_remove(THIS_PARAM).unwrap(), state);
+                for arg in args.into_values() {
+                    write_de!(self.buffer, ",");
+                    self.emit_expr(arg, state);
+                }
+                write_de!(self.buffer, ")");
+                return;
+            }
+        }
+
+        let f = self
+            .proj
+            .scopes
+            .fn_signature(&mut self.proj.types, inst)
+            .unwrap();
+        let mut recv = args.shift_remove(THIS_PARAM).unwrap();
+        recv.ty = recv
+            .ty
+            .with_templates(&mut self.proj.types, &state.func.ty_args);
+        let by_value = !matches!(self.proj.types[recv.ty], Type::Ptr(_) | Type::MutPtr(_));
+        let recv = hoist!(self, self.emit_tmpvar(recv, state));
+        let recv = if by_value { recv } else { format!("(*{recv})") };
+        let (_, mut fn_args) = args.shift_remove_index(0).unwrap();
+        fn_args.ty = fn_args
+            .ty
+            .with_templates(&mut self.proj.types, &state.func.ty_args);
+        let tuple = self.proj.types[fn_args.ty].as_user().map(|ut| ut.id);
+        let fn_args = hoist!(self, self.emit_tmpvar(fn_args, state));
+
+        if ret.is_void() {
+            write_de!(self.buffer, "VOID(");
+        }
+        match &self.proj.types[inst] {
+            Type::DynFn(_) => write_de!(self.buffer, "{recv}.fn({recv}.env"),
+            Type::User(ut) => {
+                let ut = ut.clone();
+                self.emit_lambda_fn_name(&ut);
+                write_de!(self.buffer, "(&{recv}");
+            }
+            _ => write_de!(self.buffer, "{recv}("),
+        }
+        let has_env = !self.proj.types[inst].is_fn_ptr();
+        for i in 0..f.params.len() {
+            if i > 0 || has_env {
+                write_de!(self.buffer, ",");
+            }
+            let member = member_name(&self.proj.scopes, tuple, &i.to_string());
+            write_de!(self.buffer, "{fn_args}.{member}");
+        }
+        write_de!(self.buffer, ")");
+        if ret.is_void() {
+            write_de!(self.buffer, ")");
+        }
+    }
+
+    fn emit_lambda(
+        &mut self,
+        state: &mut State,
+        ty: TypeId,
+        params: Vec<VariableId>,
+        captures: Vec<VariableId>,
+        moves: bool,
+        body: Block,
+    ) {
+        let ty = ty.with_templates(&mut self.proj.types, &state.func.ty_args);
+        let ret = self
+            .proj
+            .scopes
+            .fn_signature(&mut self.proj.types, ty)
+            .unwrap()
+            .ret;
+        let lambda = self.proj.types[ty].as_user().cloned();
+        let name = if let Some(ut) = &lambda {
+            let mut name = Buffer::default();
+            name.emit_lambda_fn_name(
+                &self.proj.scopes,
+                &mut self.proj.types,
+                ut,
+                self.flags.minify,
+            );
+            name.finish()
+        } else {
+            self.lambda_count += 1;
+            format!("$lambda{}", self.lambda_count - 1)
+        };
+
+        let fields: HashMap<_, _> = captures
+            .iter()
+            .map(|&id| {
+                let name = &self.proj.scopes.get(id).name.data;
+                (
+                    id,
+                    member_name(&self.proj.scopes, lambda.as_ref().map(|ut| ut.id), name),
+                )
+            })
+            .collect();
+        // defer bodies are emitted once per exit, but the function only needs to exist once
+        if lambda.is_none() || self.emitted_lambdas.insert(ty) {
+            let mut lstate = State::new(state.func.clone(), body.scope);
+            lstate.lambda = Some(LambdaState {
+                scope: body.scope,
+                captures: fields.clone(),
+                moves,
+            });
+
+            let old_buffer = std::mem::take(&mut self.buffer);
+            let old_temporaries = std::mem::take(&mut self.temporaries);
+            let old_defers = std::mem::take(&mut self.defers);
+            let old_never = std::mem::take(&mut self.emitted_never_in_this_block);
+            write_de!(self.buffer, "static ");
+            if ret == TypeId::NEVER {
+                write_de!(self.buffer, "CTL_NORETURN ");
+            }
+            if ret.is_void() {
+                write_de!(self.buffer, "void ");
+            } else {
+                self.emit_type(ret);
+                write_de!(self.buffer, " ");
+            }
+            write_de!(self.buffer, "{name}(");
+            if lambda.is_some() {
+                write_de!(self.buffer, "void*$$self");
+            }
+            for (i, &id) in params.iter().enumerate() {
+                if i > 0 || lambda.is_some() {
+                    write_de!(self.buffer, ",");
+                }
+                self.emit_var_decl(id, &mut lstate);
+            }
+            if params.is_empty() && lambda.is_none() {
+                write_de!(self.buffer, "void");
+            }
+            write_de!(self.buffer, "){{");
+            if !captures.is_empty() {
+                self.emit_type(ty);
+                write_de!(self.buffer, "*$$env=$$self;");
+            } else if lambda.is_some() {
+                write_de!(self.buffer, "(void)$$self;");
+            }
+            for &id in params.iter() {
+                if self.proj.scopes.get(id).unused {
+                    write_de!(self.buffer, "(void)");
+                    self.emit_var_name(id, &mut lstate);
+                    write_de!(self.buffer, ";");
+                }
+            }
+            self.emit_block(body, &mut lstate);
+            write_de!(self.buffer, "}}");
+
+            let func = std::mem::replace(&mut self.buffer, old_buffer);
+            self.lambdas.emit(func.finish());
+            self.temporaries = old_temporaries;
+            self.defers = old_defers;
+            self.emitted_never_in_this_block = old_never;
+        }
 
-    fn emit_member_fn(&mut self, state: &mut State, mut mfn: MemberFn, scope: ScopeId) {
-        if let MemberFnType::Trait(mut tr) = mfn.typ {
-            let inst = mfn
-                .inst
-                .with_templates(&mut self.proj.types, &state.func.ty_args);
-            tr.fill_templates(&mut self.proj.types, &state.func.ty_args);
-            mfn.func = self.find_implementation(
-                inst,
-                &tr,
-                &self.proj.scopes.get(mfn.func.id).name.data.clone(),
-                state.caller,
-                |tc, id| {
-                    TypeArgs::in_order(
-                        tc.scopes(),
-                        id,
-                        mfn.func.ty_args.0.into_iter().map(|kv| kv.1),
-                    )
-                },
-            );
+        if lambda.is_none() {
+            return self.buffer.emit(name);
         }
 
-        mfn.func
-            .fill_templates(&mut self.proj.types, &state.func.ty_args);
-        let state = State::new(mfn.func, scope);
-        self.buffer.emit_fn_name(
-            &self.proj.scopes,
-            &mut self.proj.types,
-            &state.func,
-            self.flags.minify,
-        );
-        self.funcs.insert(state);
+        self.emit_cast(ty);
+        write_de!(self.buffer, "{{");
+        if captures.is_empty() {
+            write_de!(self.buffer, "CTL_DUMMY_INIT");
+        }
+        for id in captures {
+            write_de!(self.buffer, ".{}=", fields[&id]);
+            self.emit_capture(id, !moves, state);
+            write_de!(self.buffer, ",");
+        }
+        write_de!(self.buffer, "}}");
     }
 
     fn emit_instance(&mut self, state: &mut State, ty: TypeId, members: IndexMap<String, Expr>) {
         let ut_id = self.proj.types[ty].as_user().unwrap().id;
         if self.proj.scopes.get(ut_id).kind.is_packed_struct() {
             return tmpbuf_emit!(self, state, |tmp| {
-                self.emit_type(ty);
-                write_de!(self.buffer, " {tmp} = {{}};");
+                self.emit_local(state, ty, &tmp);
+                write_de!(self.buffer, "={{}};");
                 for (name, value) in members {
                     let ty = value.ty;
                     let expr = hoist!(self, self.emit_tmpvar(value, state));
@@ -2076,8 +2968,8 @@ impl Codegen {
             }
             BinaryOp::NoneCoalesce => {
                 tmpbuf_emit!(self, state, |tmp| {
-                    self.emit_type(ret);
-                    write_de!(self.buffer, " {tmp};");
+                    self.emit_local_decl(state, ret, &tmp);
+                    write_de!(self.buffer, "");
 
                     lhs.ty = lhs
                         .ty
@@ -2144,10 +3036,10 @@ impl Codegen {
             BinaryOp::LogicalAnd | BinaryOp::LogicalOr => {
                 tmpbuf_emit!(self, state, |tmp| {
                     let lor = matches!(op, BinaryOp::LogicalOr);
-                    let end_label = state.tmpvar();
+                    let end_label = state.tmplabel();
 
-                    self.emit_type(TypeId::BOOL);
-                    write_de!(self.buffer, " {tmp}=");
+                    self.emit_local(state, TypeId::BOOL, &tmp);
+                    write_de!(self.buffer, "=");
                     self.emit_expr_inline(lhs, state);
                     write_de!(
                         self.buffer,
@@ -2230,8 +3122,8 @@ impl Codegen {
                 if let &Type::Array(inner, len) = &self.proj.types[ret] {
                     let (sz, _) = inner.size_and_align(&self.proj.scopes, &mut self.proj.types);
                     tmpbuf_emit!(self, state, |tmp| {
-                        self.emit_type(ret);
-                        write_de!(self.buffer, " {tmp};CTL_MEMCPY(&{tmp},");
+                        self.emit_local_decl(state, ret, &tmp);
+                        write_de!(self.buffer, "CTL_MEMCPY(&{tmp},");
                         self.emit_expr(lhs, state);
                         write_de!(self.buffer, ",{len}*{sz});");
                     });
@@ -2269,55 +3161,7 @@ impl Codegen {
                     write_de!(self.buffer, ".{ARRAY_DATA_NAME}");
                 }
             }
-            UnaryOp::Try => {
-                tmpbuf_emit!(self, state, |tmp| {
-                    lhs.ty = lhs
-                        .ty
-                        .with_templates(&mut self.proj.types, &state.func.ty_args);
-
-                    self.emit_type(ret);
-                    write_de!(self.buffer, " {tmp};");
-
-                    let inner_ty = lhs.ty;
-                    let inner_tmp = self.emit_tmpvar(lhs, state);
-                    self.emit_pattern_if_stmt(
-                        state,
-                        &PatternData::Variant {
-                            pattern: None,
-                            variant: "None".into(),
-                            inner: ret,
-                            borrows: false,
-                        },
-                        &inner_tmp,
-                        inner_ty,
-                    );
-                    hoist_point!(self, {
-                        let mut buffer = Buffer::default();
-                        usebuf!(self, &mut buffer, {
-                            write_de!(self.buffer, "return ");
-                            let mut ret_type = self.proj.scopes.get(state.func.id).ret;
-                            ret_type =
-                                ret_type.with_templates(&mut self.proj.types, &state.func.ty_args);
-                            self.emit_expr_inner(Expr::option_null(ret_type), state);
-                        });
-                        self.leave_scope(
-                            state,
-                            &buffer.finish(),
-                            self.proj.scopes.get(state.func.id).body_scope,
-                        );
-                    });
-                    write_de!(self.buffer, "}}{tmp}=");
-                    if inner_ty
-                        .can_omit_tag(&self.proj.scopes, &self.proj.types)
-                        .is_some()
-                    {
-                        write_de!(self.buffer, "{inner_tmp};");
-                    } else {
-                        write_de!(self.buffer, "{inner_tmp}.$Some.$0;");
-                    }
-                });
-            }
-            UnaryOp::Unwrap => panic!("ICE: UnaryOp::Unwrap in gen_expr"),
+            UnaryOp::Unwrap | UnaryOp::Try => panic!("ICE: UnaryOp::{op:?} in gen_expr"),
         }
     }
 
@@ -2326,6 +3170,7 @@ impl Codegen {
         state: &mut State,
         original_id: FunctionId,
         args: IndexMap<String, Expr>,
+        caller: Option<String>,
     ) {
         let mut args: IndexMap<_, _> = args
             .into_iter()
@@ -2358,18 +3203,28 @@ impl Codegen {
             .flat_map(|param| args.shift_remove(&param.label))
             .for_each(|arg| arg!(arg));
         args.into_iter().for_each(|(_, arg)| arg!(arg));
+        if let Some(caller) = caller {
+            if count > 0 {
+                write_de!(self.buffer, ",");
+            }
+            self.buffer.emit(caller);
+        }
         write_de!(self.buffer, ")");
     }
 
     fn emit_loop_break(&mut self, state: &mut State, ty: TypeId, optional: bool) {
         if optional {
-            write_de!(self.buffer, "{}=", scope_var_or_label(self.cur_loop));
+            write_de!(
+                self.buffer,
+                "{}=",
+                state.local(scope_var_or_label(self.cur_loop))
+            );
             self.emit_expr_inline(Expr::option_null(ty), state);
         } else {
             write_de!(
                 self.buffer,
                 "{}={VOID_INSTANCE}",
-                scope_var_or_label(self.cur_loop)
+                state.local(scope_var_or_label(self.cur_loop))
             );
         }
         write_de!(self.buffer, ";break;");
@@ -2428,17 +3283,18 @@ impl Codegen {
 
     fn emit_with_capacity(
         &mut self,
+        state: &mut State,
         ty: TypeId,
         tmp: &str,
         ut: &GenericUserType,
         len: impl std::fmt::Display,
     ) {
-        self.emit_type(ty);
-        write_de!(self.buffer, " {tmp}=");
+        self.emit_local(state, ty, tmp);
+        write_de!(self.buffer, "=");
         // FIXME: this should technically use the scope that is creating the literal, but since
         // none of the constructors for literals use trait functions in any way, it doesn't matter
         // right now
-        let state = State::in_body_scope(
+        let new_state = State::in_body_scope(
             GenericFn::new(
                 self.proj
                     .scopes
@@ -2453,11 +3309,11 @@ impl Codegen {
         self.buffer.emit_fn_name(
             &self.proj.scopes,
             &mut self.proj.types,
-            &state.func,
+            &new_state.func,
             self.flags.minify,
         );
         write_de!(self.buffer, "({len});");
-        self.funcs.insert(state);
+        self.funcs.insert(new_state);
     }
 
     fn emit_intrinsic(
@@ -2467,6 +3323,7 @@ impl Codegen {
         func: &GenericFn,
         mut args: IndexMap<String, Expr>,
         state: &mut State,
+        span: Span,
     ) {
         match name {
             "numeric_abs" => {
@@ -2488,62 +3345,52 @@ impl Codegen {
             "min_value" => {
                 self.emit_literal(ret.as_integral(&self.proj.types, true).unwrap().min(), ret)
             }
-            "size_of" => {
-                write_de!(
-                    self.buffer,
-                    "(usize){}",
-                    func.first_type_arg()
-                        .unwrap()
-                        .size_and_align(&self.proj.scopes, &mut self.proj.types)
-                        .0
-                );
-            }
-            "align_of" => {
-                write_de!(
-                    self.buffer,
-                    "(usize){}",
-                    func.first_type_arg()
-                        .unwrap()
-                        .size_and_align(&self.proj.scopes, &mut self.proj.types)
-                        .1
-                );
+            "size_of" | "align_of" => {
+                let ty = func.first_type_arg().unwrap();
+                if ty.contains_frame(&self.proj.scopes, &mut self.proj.types) {
+                    // frame members are only known once the poll function has been generated
+                    let op = if name == "size_of" {
+                        "sizeof"
+                    } else {
+                        "_Alignof"
+                    };
+                    write_de!(self.buffer, "(usize){op}(");
+                    self.emit_type(ty);
+                    write_de!(self.buffer, ")");
+                } else {
+                    let (size, align) = ty.size_and_align(&self.proj.scopes, &mut self.proj.types);
+                    let value = if name == "size_of" { size } else { align };
+                    write_de!(self.buffer, "(usize){value}");
+                }
             }
             "panic" => {
-                let panic = State::in_body_scope(
-                    GenericFn::from_id(
-                        &self.proj.scopes,
-                        self.proj
-                            .scopes
-                            .lang_fns
-                            .get("panic_handler")
-                            .cloned()
-                            .expect("a panic handler should exist"),
-                    ),
-                    &self.proj.scopes,
-                );
-
-                write_de!(self.buffer, "VOID(");
-                self.buffer.emit_fn_name(
-                    &self.proj.scopes,
-                    &mut self.proj.types,
-                    &panic.func,
-                    self.flags.minify,
-                );
-                write_de!(self.buffer, "(");
-                for (i, (_, expr)) in args.into_iter().enumerate() {
-                    if i > 0 {
-                        write_de!(self.buffer, ",");
+                let loc = self.caller_location(state, span);
+                self.emit_panic(loc, |this| {
+                    for (i, (_, expr)) in args.into_iter().enumerate() {
+                        if i > 0 {
+                            write_de!(this.buffer, ",");
+                        }
+                        this.emit_expr(expr, state);
                     }
-                    self.emit_expr(expr, state);
-                }
-                write_de!(self.buffer, "))");
-
-                self.funcs.insert(panic);
+                })
+            }
+            "caller_location" => {
+                let loc = self.caller_location(state, span);
+                self.buffer.emit(loc);
+            }
+            "bounds_checks" => {
+                write_de!(self.buffer, "{}", u8::from(!self.flags.no_bounds_check));
             }
             "unreachable_unchecked" => {
                 hoist!(self, write_de!(self.buffer, "CTL_UNREACHABLE();"));
                 self.buffer.emit(VOID_INSTANCE);
             }
+            // evaluated by the type checker for each instantiation
+            "static_assert" => self.buffer.emit(VOID_INSTANCE),
+            "compile_error" => {
+                hoist!(self, write_de!(self.buffer, "CTL_UNREACHABLE();"));
+                self.buffer.emit(VOID_INSTANCE);
+            }
             "binary_op" => {
                 let mut args = args.into_iter();
                 let arg0 = args
@@ -2654,8 +3501,8 @@ impl Codegen {
 
     fn emit_tmpvar_ident(&mut self, expr: Expr, state: &mut State) {
         tmpbuf_emit!(self, state, |tmp| {
-            self.emit_type(expr.ty);
-            write_de!(self.buffer, " {tmp}=");
+            self.emit_local(state, expr.ty, &tmp);
+            write_de!(self.buffer, "=");
             self.emit_expr_inner(expr, state);
             write_de!(self.buffer, ";");
         });
@@ -2663,8 +3510,8 @@ impl Codegen {
 
     fn emit_tmpvar(&mut self, expr: Expr, state: &mut State) -> String {
         let tmp = state.tmpvar();
-        self.emit_type(expr.ty);
-        write_de!(self.buffer, " {tmp}=");
+        self.emit_local(state, expr.ty, &tmp);
+        write_de!(self.buffer, "=");
         self.emit_expr_inner(expr, state);
         write_de!(self.buffer, ";");
         tmp
@@ -2944,26 +3791,80 @@ impl Codegen {
                 });
             }
             PatternData::Void => {}
-            PatternData::Or(patterns) => {
+            PatternData::Or {
+                patterns,
+                bindings: vars,
+            } => {
+                for (id, alts) in vars {
+                    let unused = self.proj.scopes.get(*id).unused;
+                    for &alt in alts {
+                        self.proj.scopes.get_mut(alt).unused = unused;
+                    }
+                }
+
                 let mut conds = JoiningBuilder::new("||", "1");
-                let mut binds = Buffer::default();
+                let mut alts = vec![];
                 for pattern in patterns {
                     let mut tmp = JoiningBuilder::new("&&", "1");
+                    let mut alt_bindings = Buffer::default();
                     self.emit_pattern_inner(
                         state,
                         &pattern.data,
                         src,
                         ty,
                         borrow,
-                        &mut binds,
+                        &mut alt_bindings,
                         &mut tmp,
                     );
-                    conds.next_str(format!("({})", tmp.finish()));
+                    let cond = format!("({})", tmp.finish());
+                    conds.next_str(cond.clone());
+                    alts.push((cond, alt_bindings));
                 }
 
-                if !binds.0.is_empty() {
-                    todo!("codegen for or pattern with variable bindings");
-                }
+                let vars: Vec<_> = vars
+                    .iter()
+                    .filter(|(id, _)| !self.proj.scopes.get(*id).unused)
+                    .collect();
+                usebuf!(self, bindings, {
+                    // the variables are assigned in the branch of the alternative that matched,
+                    // so they can't be declared const
+                    for (id, _) in vars.iter() {
+                        if state.frame.is_some() {
+                            // only adds the variable to the frame
+                            usebuf!(self, &mut Buffer::default(), {
+                                self.emit_var_decl(*id, state);
+                            });
+                        } else {
+                            let ty = self
+                                .proj
+                                .scopes
+                                .get(*id)
+                                .ty
+                                .with_templates(&mut self.proj.types, &state.func.ty_args);
+                            self.emit_type(ty);
+                            write_de!(self.buffer, " ");
+                            self.emit_var_name(*id, state);
+                            write_de!(self.buffer, ";");
+                        }
+                    }
+
+                    // only bind the variables of the matching alternative, the others may refer to
+                    // inactive union members
+                    for (i, (cond, alt_bindings)) in alts.into_iter().enumerate() {
+                        if i > 0 {
+                            write_de!(self.buffer, "else ");
+                        }
+                        write_de!(self.buffer, "if{cond}{{");
+                        self.buffer.emit(alt_bindings.finish());
+                        for (id, alts) in vars.iter() {
+                            self.emit_var_name(*id, state);
+                            write_de!(self.buffer, "=");
+                            self.emit_var_name(alts[i], state);
+                            write_de!(self.buffer, ";");
+                        }
+                        write_de!(self.buffer, "}}");
+                    }
+                });
 
                 conditions.next_str(format!("({})", conds.finish()));
             }
@@ -3020,7 +3921,15 @@ impl Codegen {
         hoist_point!(self, {
             self.defers.push((block.scope, vec![]));
             for stmt in block.body.into_iter() {
-                self.emit_stmt(stmt, state);
+                if matches!(stmt.data, Stmt::None) {
+                    continue;
+                }
+                if self.flags.debug_info {
+                    self.emit_line_directive(stmt.span);
+                }
+                let prev_span = self.set_span(Some(stmt.span));
+                self.emit_stmt(stmt.data, state);
+                self.set_span(prev_span);
                 if self.emitted_never_in_this_block {
                     break;
                 }
@@ -3052,6 +3961,15 @@ impl Codegen {
         );
     }
 
+    fn emit_lambda_fn_name(&mut self, ut: &GenericUserType) {
+        self.buffer.emit_lambda_fn_name(
+            &self.proj.scopes,
+            &mut self.proj.types,
+            ut,
+            self.flags.minify,
+        );
+    }
+
     fn emit_cast(&mut self, id: TypeId) {
         write_de!(self.buffer, "(");
         self.emit_type(id);
@@ -3130,9 +4048,25 @@ impl Codegen {
             }
         }
 
+        let tracked = self.tracks_caller(state.func.id);
+        if tracked {
+            if !params.is_empty() {
+                write_de!(self.buffer, ",");
+            }
+            let ty = self.location_type();
+            self.emit_type(ty);
+            if !is_prototype {
+                write_de!(self.buffer, " {CALLER_LOCATION}");
+            }
+        }
+
         if variadic {
-            write_de!(self.buffer, "{}...)", [",", ""][params.is_empty() as usize]);
-        } else if params.is_empty() {
+            write_de!(
+                self.buffer,
+                "{}...)",
+                [",", ""][(params.is_empty() && !tracked) as usize]
+            );
+        } else if params.is_empty() && !tracked {
             write_de!(self.buffer, "void)");
         } else {
             write_de!(self.buffer, ")");
@@ -3146,6 +4080,13 @@ impl Codegen {
     }
 
     fn emit_var_name(&mut self, id: VariableId, state: &mut State) {
+        if state.frame.is_some() && !self.proj.scopes.get(id).is_static {
+            write_de!(self.buffer, "{FRAME_PTR}->");
+        }
+        self.emit_var_ident(id, state);
+    }
+
+    fn emit_var_ident(&mut self, id: VariableId, state: &mut State) {
         use std::collections::hash_map::*;
 
         if self.flags.minify {
@@ -3191,6 +4132,25 @@ impl Codegen {
         }
     }
 
+    fn emit_capture(&mut self, id: VariableId, by_ref: bool, state: &mut State) {
+        let Some((field, moved)) = state
+            .lambda
+            .as_ref()
+            .and_then(|lambda| Some((lambda.captures.get(&id)?, lambda.moves)))
+        else {
+            if by_ref {
+                write_de!(self.buffer, "&");
+            }
+            return self.emit_var_name(id, state);
+        };
+
+        match (moved, by_ref) {
+            (false, false) => write_de!(self.buffer, "(*$$env->{field})"),
+            (true, true) => write_de!(self.buffer, "(&$$env->{field})"),
+            _ => write_de!(self.buffer, "$$env->{field}"),
+        }
+    }
+
     fn emit_var_decl(&mut self, id: VariableId, state: &mut State) -> TypeId {
         let var = self.proj.scopes.get(id);
         let ty = var
@@ -3198,6 +4158,13 @@ impl Codegen {
             .with_templates(&mut self.proj.types, &state.func.ty_args);
         if var.is_static {
             write_de!(self.buffer, "static ");
+        } else if state.frame.is_some() {
+            write_de!(self.buffer, "{FRAME_PTR}->");
+            let start = self.buffer.0.len();
+            self.emit_var_ident(id, state);
+            let name = self.buffer.0[start..].to_string();
+            Self::add_frame_member(state, ty, &name);
+            return ty;
         }
 
         let emit_const = !var.mutable && !var.is_static;
@@ -3210,6 +4177,36 @@ impl Codegen {
         ty
     }
 
+    /// Emits the declaration of the local `name` up to its initializer. In async functions, the
+    /// local is instead recorded as a member of the frame.
+    fn emit_local(&mut self, state: &mut State, ty: TypeId, name: &str) {
+        if !Self::add_frame_member(state, ty, name) {
+            self.emit_type(ty);
+            write_de!(self.buffer, " ");
+        }
+        self.buffer.emit(name);
+    }
+
+    fn emit_local_decl(&mut self, state: &mut State, ty: TypeId, name: &str) {
+        if !Self::add_frame_member(state, ty, name) {
+            self.emit_type(ty);
+            write_de!(self.buffer, " {name};");
+        }
+    }
+
+    fn add_frame_member(state: &mut State, ty: TypeId, name: &str) -> bool {
+        let Some(frame) = &mut state.frame else {
+            return false;
+        };
+
+        let member = name
+            .strip_prefix(FRAME_PTR)
+            .and_then(|name| name.strip_prefix("->"))
+            .unwrap_or(name);
+        frame.members.insert(member.into(), ty);
+        true
+    }
+
     fn emit_bitfield_assign(
         &mut self,
         source: Expr,
@@ -3228,16 +4225,16 @@ impl Codegen {
             .unwrap();
         let src = tmpbuf!(self, state, |tmp| {
             let ptr = self.proj.types.insert(Type::MutPtr(source.ty));
-            self.emit_type(ptr);
-            writeln_de!(self.buffer, " {tmp}=&");
+            self.emit_local(state, ptr, &tmp);
+            writeln_de!(self.buffer, "=&");
             self.emit_expr_inline(source, state);
             writeln_de!(self.buffer, ";");
             format!("(*{tmp})")
         });
 
         let expr = tmpbuf!(self, state, |tmp| {
-            self.emit_type(ty);
-            writeln_de!(self.buffer, " {tmp}=");
+            self.emit_local(state, ty, &tmp);
+            writeln_de!(self.buffer, "=");
             if op != BinaryOp::Assign {
                 writeln_de!(self.buffer, "(");
                 self.emit_bitfield_read(&src, id, member, ty);
@@ -3441,6 +4438,144 @@ impl Codegen {
         }
     }
 
+    /// Emits a call to the `panic_handler` lang function, with the message written by `msg` and
+    /// the caller location expression `loc`.
+    fn emit_panic(&mut self, loc: String, msg: impl FnOnce(&mut Self)) {
+        let panic = State::in_body_scope(
+            GenericFn::from_id(
+                &self.proj.scopes,
+                self.proj
+                    .scopes
+                    .lang_fns
+                    .get("panic_handler")
+                    .cloned()
+                    .expect("a panic handler should exist"),
+            ),
+            &self.proj.scopes,
+        );
+
+        write_de!(self.buffer, "VOID(");
+        self.buffer.emit_fn_name(
+            &self.proj.scopes,
+            &mut self.proj.types,
+            &panic.func,
+            self.flags.minify,
+        );
+        write_de!(self.buffer, "(");
+        msg(self);
+        write_de!(self.buffer, ",{loc}))");
+
+        self.funcs.insert(panic);
+    }
+
+    /// Functions marked `@(track_caller)` receive the location they were called from as a hidden
+    /// trailing parameter. Extern functions can't, and async functions and generators don't run
+    /// their body at the call site, so the attribute is ignored for them.
+    fn tracks_caller(&self, id: FunctionId) -> bool {
+        let f = self.proj.scopes.get(id);
+        f.attrs.has(ATTR_TRACK_CALLER)
+            && f.body.is_some()
+            && !f.is_extern
+            && !f.is_async
+            && !f.is_generator
+    }
+
+    fn location_type(&mut self) -> TypeId {
+        let id = self
+            .proj
+            .scopes
+            .lang_types
+            .get("location")
+            .copied()
+            .expect("a location type should exist");
+        self.proj
+            .types
+            .insert(Type::User(GenericUserType::new(id, Default::default())))
+    }
+
+    /// Returns an expression for the location to report for a call at `span`. Inside a function
+    /// that tracks its caller, this is the location it was called from.
+    fn caller_location(&mut self, state: &State, span: Span) -> String {
+        if self.tracks_caller(state.func.id) && state.lambda.is_none() {
+            CALLER_LOCATION.into()
+        } else {
+            self.emit_location(span)
+        }
+    }
+
+    /// Returns a `core::location::Location` literal for the start of `span`.
+    fn emit_location(&mut self, span: Span) -> String {
+        let (path, line, column) = self.span_location(span);
+        let ty = self.location_type();
+        let ut_id = self.proj.types[ty].as_user().map(|ut| ut.id);
+        let mut buffer = Buffer::default();
+        usebuf!(self, &mut buffer, {
+            self.emit_cast(ty);
+            write_de!(
+                self.buffer,
+                "{{.{}=",
+                member_name(&self.proj.scopes, ut_id, "file")
+            );
+            self.emit_string_literal(&path);
+            write_de!(
+                self.buffer,
+                ",.{}={line},.{}={column}}}",
+                member_name(&self.proj.scopes, ut_id, "line"),
+                member_name(&self.proj.scopes, ut_id, "column"),
+            );
+        });
+        buffer.finish()
+    }
+
+    /// Returns the path, line and column of the start of `span`, with the path relative to the
+    /// working directory when possible.
+    fn span_location(&mut self, span: Span) -> (String, u32, u32) {
+        let path = self.proj.diag.file_path(span.file);
+        let range = self
+            .sources
+            .get_source(path, |data| {
+                Diagnostics::get_span_range(data, span, OffsetMode::Utf32)
+            })
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        let cwd = std::env::current_dir().ok();
+        let path = cwd
+            .as_ref()
+            .and_then(|cwd| path.strip_prefix(cwd).ok())
+            .unwrap_or(path);
+        (
+            path.display().to_string(),
+            range.start.line + 1,
+            range.start.character + 1,
+        )
+    }
+
+    /// Marks the following C code as generated from `span` for the span map, returning the span
+    /// that was previously current so it can be restored afterwards.
+    fn set_span(&mut self, span: Option<Span>) -> Option<Span> {
+        self.buffer.0.push(SPAN_MARKER_START);
+        if let Some(span) = span {
+            write_de!(self.buffer, "{}", self.spans.len());
+            self.spans.push(span);
+        }
+        self.buffer.0.push(SPAN_MARKER_END);
+        std::mem::replace(&mut self.cur_span, span)
+    }
+
+    /// Emits a `#line` directive mapping the following C code to the start of `span`.
+    fn emit_line_directive(&mut self, span: Span) {
+        let (path, line, _) = self.span_location(span);
+        write_de!(self.buffer, "\n#line {line} \"");
+        for ch in path.chars() {
+            if matches!(ch, '\\' | '"') {
+                write_de!(self.buffer, "\\");
+            }
+            write_de!(self.buffer, "{ch}");
+        }
+        write_de!(self.buffer, "\"\n");
+    }
+
     fn emit_string_literal(&mut self, value: &str) {
         write_de!(self.buffer, "STRLIT(\"");
         for byte in value.as_bytes() {
@@ -3579,3 +4714,61 @@ fn bit_mask(bits: u32) -> u64 {
         u64::MAX
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{SpanMap, SPAN_MARKER_END, SPAN_MARKER_START};
+    use crate::{error::FileId, lexer::Span};
+
+    fn span(pos: u32) -> Span {
+        Span {
+            pos,
+            len: 1,
+            file: FileId::default(),
+        }
+    }
+
+    fn marker(idx: &str) -> String {
+        format!("{SPAN_MARKER_START}{idx}{SPAN_MARKER_END}")
+    }
+
+    #[test]
+    pub fn span_map_extract() {
+        let spans = [span(10), span(20)];
+        let code = format!("a;{}b;\nc;{}d;{}e;", marker("0"), marker("1"), marker(""));
+        let (code, map) = SpanMap::extract(&code, &spans, false);
+        assert_eq!(code, "a;\nb;\nc;\nd;\ne;");
+        assert_eq!(map.find(1), None);
+        assert_eq!(map.find(2), Some(spans[0]));
+        assert_eq!(map.find(3), Some(spans[0]));
+        assert_eq!(map.find(4), Some(spans[1]));
+        assert_eq!(map.find(5), None);
+        assert_eq!(map.find(100), None);
+    }
+
+    #[test]
+    pub fn span_map_extract_after_newline() {
+        let spans = [span(10)];
+        let code = format!("a;\n#line 2 \"x.ctl\"\n{}b;", marker("0"));
+        let (code, map) = SpanMap::extract(&code, &spans, false);
+        assert_eq!(code, "a;\n#line 2 \"x.ctl\"\nb;");
+        assert_eq!(map.find(2), None);
+        assert_eq!(map.find(3), Some(spans[0]));
+    }
+
+    #[test]
+    pub fn span_map_extract_minified() {
+        let spans = [span(10), span(20)];
+        let code = format!("a;{}b;\nc;{}d;", marker("0"), marker("1"));
+        let (code, map) = SpanMap::extract(&code, &spans, true);
+        assert_eq!(code, "a;b;\nc;d;");
+        assert_eq!(map.find(1), Some(spans[0]));
+        assert_eq!(map.find(2), Some(spans[1]));
+    }
+
+    #[test]
+    pub fn span_map_find_empty() {
+        assert_eq!(SpanMap::default().find(1), None);
+        assert_eq!(SpanMap::default().find(0), None);
+    }
+}
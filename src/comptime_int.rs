@@ -510,7 +510,13 @@ impl ComptimeInt {
                     SMALL_BITS - v.leading_zeros()
                 }
             }
-            ComptimeInt::Big(v) => v.bits() as u32,
+            ComptimeInt::Big(v) => {
+                if self.is_negative() {
+                    (-v - 1u32).bits() as u32 + 1
+                } else {
+                    v.bits() as u32
+                }
+            }
         }
     }
 
@@ -532,7 +538,11 @@ impl ComptimeInt {
     }
 
     pub fn fits_into(&self, bits: u32, signed: bool) -> bool {
-        self.bits() <= bits && (!signed || !self.is_negative())
+        if signed {
+            self.bits() + <u32 as From<bool>>::from(!self.is_negative()) <= bits
+        } else {
+            !self.is_negative() && self.bits() <= bits
+        }
     }
 }
 
@@ -540,7 +550,7 @@ impl ComptimeInt {
 mod tests {
     use crate::typeid::Integer;
 
-    // use super::*;
+    use super::ComptimeInt;
 
     #[test]
     pub fn bigint_limits() {
@@ -562,4 +572,27 @@ mod tests {
             assert_eq!(imax.bits(), (bits).saturating_sub(1), "(IMAX = {imax})");
         }
     }
+
+    #[test]
+    pub fn fits_into_limits() {
+        for bits in 1..256 {
+            let mut int = Integer {
+                bits,
+                signed: false,
+                char: false,
+            };
+            let umax = int.max();
+            int.signed = true;
+            let imin = int.min();
+            let imax = int.max();
+
+            assert!(umax.fits_into(bits, false), "(UMAX = {umax})");
+            assert!(!(umax.clone() + &ComptimeInt::from(1)).fits_into(bits, false));
+            assert!(!ComptimeInt::from(-1).fits_into(bits, false));
+            assert!(imin.fits_into(bits, true), "(IMIN = {imin})");
+            assert!(!(imin - 1).fits_into(bits, true));
+            assert!(imax.fits_into(bits, true), "(IMAX = {imax})");
+            assert!(!(imax + &ComptimeInt::from(1)).fits_into(bits, true));
+        }
+    }
 }
@@ -1,9 +1,14 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 use tower_lsp::lsp_types::{Position, Range};
 
 use crate::{
+    comptime_int::ComptimeInt,
     lexer::{Located, Span, Token},
+    lint::{Lint, LintLevel},
     sym::Scopes,
     typeid::{TypeId, Types},
 };
@@ -15,7 +20,7 @@ pub enum OffsetMode {
     Utf32,
 }
 
-#[derive(Default, Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Default, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub struct FileId(u32);
 
 impl std::fmt::Display for FileId {
@@ -30,6 +35,9 @@ pub struct Diagnostics {
     warnings: Vec<Error>,
     paths: Vec<PathBuf>,
     errors_disabled: bool,
+    lints: Vec<(Lint, Option<LintLevel>, Error)>,
+    lint_levels: HashMap<Lint, LintLevel>,
+    warnings_level: Option<LintLevel>,
 }
 
 impl Diagnostics {
@@ -39,8 +47,55 @@ impl Diagnostics {
         }
     }
 
-    pub fn warn(&mut self, err: Error) {
-        self.warnings.push(err);
+    /// Reports a lint. `level` is the level set by attributes on the enclosing items, if any. The
+    /// lint is turned into a warning or error by `apply_lint_levels`.
+    pub fn lint(&mut self, lint: Lint, level: Option<LintLevel>, err: Error) {
+        self.lints.push((lint, level, err));
+    }
+
+    /// Sets the level of the lint `name`, or of every lint if `name` is `warnings`. Returns false if
+    /// there is no such lint.
+    pub fn set_lint_level(&mut self, name: &str, level: LintLevel) -> bool {
+        if name == Lint::GROUP_ALL {
+            self.warnings_level = Some(level);
+        } else if let Some(lint) = Lint::from_name(name) {
+            self.lint_levels.insert(lint, level);
+        } else {
+            return false;
+        }
+        true
+    }
+
+    /// Turns the reported lints into warnings or errors. A level set by an attribute takes
+    /// precedence over one set for the lint by name, which takes precedence over the level set for
+    /// `warnings`.
+    pub fn apply_lint_levels(&mut self) {
+        for (lint, level, err) in std::mem::take(&mut self.lints) {
+            let explicit = level.or_else(|| self.lint_levels.get(&lint).copied());
+            let level = explicit
+                .or(self
+                    .warnings_level
+                    .filter(|_| lint.default_level() == LintLevel::Warn))
+                .unwrap_or(lint.default_level());
+            let err = Error {
+                lint: Some(lint.name()),
+                ..err
+            };
+            match level {
+                LintLevel::Allow => {}
+                LintLevel::Deny => {
+                    self.errors
+                        .push(err.with_note(format!("the '{}' lint is set to deny", lint.name())));
+                }
+                LintLevel::Warn if explicit.is_some() => {
+                    self.warnings
+                        .push(err.with_note(format!("the '{}' lint is set to warn", lint.name())));
+                }
+                LintLevel::Warn => self
+                    .warnings
+                    .push(err.with_note(format!("'@(warn({}))' is on by default", lint.name()))),
+            }
+        }
     }
 
     pub fn add_file(&mut self, path: PathBuf) -> FileId {
@@ -132,6 +187,13 @@ impl Diagnostics {
 pub struct Error {
     pub message: String,
     pub span: Span,
+    /// The stable code of the diagnostic, which `ctl explain` describes in more detail
+    pub code: Option<&'static str>,
+    /// The name of the lint that reported this diagnostic, if any
+    pub lint: Option<&'static str>,
+    /// Secondary spans with a short message describing how they relate to the error
+    pub labels: Vec<(Span, String)>,
+    pub notes: Vec<String>,
 }
 
 impl Error {
@@ -139,14 +201,34 @@ impl Error {
         Self {
             message: message.into(),
             span: span.into(),
+            code: None,
+            lint: None,
+            labels: Vec::new(),
+            notes: Vec::new(),
         }
     }
 
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, label: impl Into<String>) -> Self {
+        self.labels.push((span, label.into()));
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
     pub fn invalid_operator(op: impl std::fmt::Display, ty: &str, span: Span) -> Self {
         Self::new(
             format!("operator '{op}' is invalid for a value of type '{ty}'"),
             span,
         )
+        .with_code("E0001")
     }
 
     pub fn shared_member(name: &str, span: Span) -> Self {
@@ -154,10 +236,11 @@ impl Error {
             format!("cannot declare variant member with same name as shared member '{name}'"),
             span,
         )
+        .with_code("E0002")
     }
 
     pub fn unterminated_str(span: Span) -> Self {
-        Self::new("unterminated string literal", span)
+        Self::new("unterminated string literal", span).with_code("E0003")
     }
 
     pub fn non_ascii_char(span: Span) -> Self {
@@ -165,10 +248,11 @@ impl Error {
             "invalid char escape (must be within the range 0..=0x7f)",
             span,
         )
+        .with_code("E0004")
     }
 
     pub fn not_valid_here(token: &Located<Token>) -> Self {
-        Self::new(format!("'{}' is not valid here", token.data), token.span)
+        Self::new(format!("'{}' is not valid here", token.data), token.span).with_code("E0005")
     }
 
     pub fn type_mismatch(
@@ -186,6 +270,7 @@ impl Error {
             ),
             span,
         )
+        .with_code("E0006")
     }
 
     pub fn type_mismatch_s(expected: &str, received: &str, span: Span) -> Self {
@@ -193,10 +278,11 @@ impl Error {
             format!("type mismatch: expected type '{expected}', found '{received}'"),
             span,
         )
+        .with_code("E0006")
     }
 
     pub fn private(item: &str, span: Span) -> Self {
-        Self::new(format!("'{item}' is private"), span)
+        Self::new(format!("'{item}' is private"), span).with_code("E0007")
     }
 
     pub fn private_member(ty: &str, member: &str, span: Span) -> Self {
@@ -204,22 +290,23 @@ impl Error {
             format!("cannot access private member '{member}' of type '{ty}'"),
             span,
         )
+        .with_code("E0008")
     }
 
     pub fn no_member(ty: &str, member: &str, span: Span) -> Self {
-        Self::new(format!("no member '{member}' found on type '{ty}'"), span)
+        Self::new(format!("no member '{member}' found on type '{ty}'"), span).with_code("E0009")
     }
 
     pub fn no_method(ty: &str, method: &str, span: Span) -> Self {
-        Self::new(format!("no method '{method}' found on type '{ty}'"), span)
+        Self::new(format!("no method '{method}' found on type '{ty}'"), span).with_code("E0010")
     }
 
     pub fn no_symbol(symbol: &str, span: Span) -> Self {
-        Self::new(format!("no symbol '{symbol}' found in this module"), span)
+        Self::new(format!("no symbol '{symbol}' found in this module"), span).with_code("E0011")
     }
 
     pub fn no_lang_item(name: &str, span: Span) -> Self {
-        Self::new(format!("missing language item: '{name}'"), span)
+        Self::new(format!("missing language item: '{name}'"), span).with_code("E0012")
     }
 
     pub fn doesnt_implement(ty: &str, trait_name: &str, span: Span) -> Self {
@@ -227,30 +314,33 @@ impl Error {
             format!("type '{ty}' does not implement '{trait_name}'"),
             span,
         )
+        .with_code("E0013")
     }
 
     pub fn wildcard_import(span: Span) -> Self {
-        Self::new("wildcard import is only valid with modules", span)
+        Self::new("wildcard import is only valid with modules", span).with_code("E0014")
     }
 
     pub fn is_unsafe(span: Span) -> Self {
-        Self::new("this operation is unsafe", span)
+        Self::new("this operation is unsafe", span).with_code("E0015")
     }
 
-    pub fn redefinition(name: &str, span: Span) -> Self {
-        Self::redefinition_k("name", name, span)
+    pub fn redefinition(name: &str, span: Span, prev: Span) -> Self {
+        Self::redefinition_k("name", name, span, prev)
     }
 
-    pub fn redefinition_k(kind: &str, name: &str, span: Span) -> Self {
+    pub fn redefinition_k(kind: &str, name: &str, span: Span, prev: Span) -> Self {
         Self::new(format!("redefinition of {kind} '{name}'"), span)
+            .with_label(prev, "previously defined here")
+            .with_code("E0016")
     }
 
     pub fn must_be_irrefutable(ty: &str, span: Span) -> Self {
-        Self::new(format!("{ty} must be irrefuable"), span)
+        Self::new(format!("{ty} must be irrefuable"), span).with_code("E0017")
     }
 
     pub fn expected_found(expected: &str, received: &str, span: Span) -> Self {
-        Self::new(format!("expected {expected}, found {received}"), span)
+        Self::new(format!("expected {expected}, found {received}"), span).with_code("E0018")
     }
 
     pub fn match_statement(why: &str, span: Span) -> Self {
@@ -258,14 +348,15 @@ impl Error {
             format!("match statement does not cover all cases {why}"),
             span,
         )
+        .with_code("E0019")
     }
 
     pub fn cyclic(a: &str, b: &str, span: Span) -> Self {
-        Self::new(format!("cyclic dependency between {a} and {b}"), span)
+        Self::new(format!("cyclic dependency between {a} and {b}"), span).with_code("E0020")
     }
 
     pub fn bad_destructure(ty: &str, span: Span) -> Self {
-        Self::new(format!("cannot destructure value of type '{ty}'"), span)
+        Self::new(format!("cannot destructure value of type '{ty}'"), span).with_code("E0021")
     }
 
     pub fn subscript_addr(span: Span) -> Self {
@@ -273,10 +364,11 @@ impl Error {
             "taking address of subscript that returns a value creates a temporary",
             span,
         )
+        .with_code("E0022")
     }
 
     pub fn bitfield_addr(span: Span) -> Self {
-        Self::new("taking address of bitfield creates a temporary", span)
+        Self::new("taking address of bitfield creates a temporary", span).with_code("E0023")
     }
 
     pub fn recursive_type(member: &str, span: Span, variant: bool) -> Self {
@@ -287,14 +379,43 @@ impl Error {
             ),
             span,
         )
+        .with_code("E0024")
     }
 
     pub fn no_consteval(span: Span) -> Self {
-        Self::new("expression is not compile time evaluatable", span)
+        Self::new("expression is not compile time evaluatable", span).with_code("E0025")
     }
 
     pub fn consteval_overflow(span: Span) -> Self {
-        Self::new("expression overflows during constant evaluation", span)
+        Self::new("expression overflows during constant evaluation", span).with_code("E0026")
+    }
+
+    pub fn consteval_div_zero(span: Span) -> Self {
+        Self::new("division by zero during constant evaluation", span).with_code("E0029")
+    }
+
+    pub fn consteval_limit(limit: &str, span: Span) -> Self {
+        Self::new(format!("constant evaluation exceeded the {limit}"), span).with_code("E0030")
+    }
+
+    pub fn consteval_bounds(index: &ComptimeInt, len: usize, span: Span) -> Self {
+        Self::new(
+            format!("index {index} is out of bounds for array of length {len} during constant evaluation"),
+            span,
+        )
+        .with_code("E0031")
+    }
+
+    pub fn consteval_panic(msg: &str, span: Span) -> Self {
+        Self::new(format!("panicked during constant evaluation: {msg}"), span).with_code("E0032")
+    }
+
+    pub fn static_assert(msg: Option<&str>, span: Span) -> Self {
+        match msg {
+            Some(msg) => Self::new(format!("static assertion failed: {msg}"), span),
+            None => Self::new("static assertion failed", span),
+        }
+        .with_code("E0033")
     }
 
     pub fn bitfield_member(name: &str, span: Span) -> Self {
@@ -302,5 +423,158 @@ impl Error {
             format!("member '{name}' of packed struct must have integer or enum union type (union with all empty variants)"),
             span,
         )
+        .with_code("E0027")
+    }
+
+    pub fn backend(message: &str, span: Span) -> Self {
+        Self::new(format!("C compiler: {message}"), span)
+            .with_code("E0028")
+            .with_note("this error was reported by the C compiler for the code generated here")
+    }
+}
+
+/// Returns the candidate that is closest to `name`, if any of them are similar enough to be a
+/// plausible misspelling of it. Empty names left behind by parse errors and the `$` names of
+/// compiler generated items are never suggested.
+pub fn closest_match<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let max = name.chars().count().max(3) / 3;
+    candidates
+        .into_iter()
+        .filter(|&c| c != name && !c.is_empty() && !c.starts_with('$'))
+        .filter_map(|c| {
+            let dist = edit_distance(name, c);
+            (dist <= max || c.eq_ignore_ascii_case(name)).then_some((dist, c))
+        })
+        .min()
+        .map(|(_, c)| c)
+}
+
+/// The number of insertions, deletions, substitutions and transpositions of adjacent characters
+/// needed to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<_> = a.chars().collect();
+    let b: Vec<_> = b.chars().collect();
+    let mut prev2 = vec![0; b.len() + 1];
+    let mut prev: Vec<_> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for i in 0..a.len() {
+        cur[0] = i + 1;
+        for j in 0..b.len() {
+            cur[j + 1] = (prev[j] + (a[i] != b[j]) as usize)
+                .min(prev[j + 1] + 1)
+                .min(cur[j] + 1);
+            if i > 0 && j > 0 && a[i] == b[j - 1] && a[i - 1] == b[j] {
+                cur[j + 1] = cur[j + 1].min(prev2[j - 1] + 1);
+            }
+        }
+        std::mem::swap(&mut prev2, &mut prev);
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{closest_match, Diagnostics, Error};
+    use crate::{
+        lexer::Span,
+        lint::{Lint, LintLevel},
+    };
+
+    fn apply(
+        warnings: Option<LintLevel>,
+        named: Option<LintLevel>,
+        attr: Option<LintLevel>,
+    ) -> Diagnostics {
+        let mut diag = Diagnostics::default();
+        if let Some(level) = warnings {
+            assert!(diag.set_lint_level(Lint::GROUP_ALL, level));
+        }
+        if let Some(level) = named {
+            assert!(diag.set_lint_level(Lint::UnusedVariables.name(), level));
+        }
+        diag.lint(
+            Lint::UnusedVariables,
+            attr,
+            Error::new("unused variable: 'x'", Span::default()),
+        );
+        diag.apply_lint_levels();
+        diag
+    }
+
+    #[test]
+    pub fn closest_match_skips_placeholders() {
+        assert_eq!(closest_match("y", ["", "$y", "z"]), Some("z"));
+        assert_eq!(closest_match("y", ["", "$ok0"]), None);
+    }
+
+    #[test]
+    pub fn lint_default_level() {
+        let diag = apply(None, None, None);
+        assert!(diag.errors().is_empty());
+        let [warning] = diag.warnings() else {
+            panic!("expected one warning");
+        };
+        assert_eq!(warning.code, None);
+        assert_eq!(warning.lint, Some("unused_variables"));
+        assert_eq!(
+            warning.notes,
+            ["'@(warn(unused_variables))' is on by default"]
+        );
+    }
+
+    #[test]
+    pub fn lint_warnings_level() {
+        let diag = apply(Some(LintLevel::Deny), None, None);
+        assert!(diag.warnings().is_empty());
+        let [error] = diag.errors() else {
+            panic!("expected one error");
+        };
+        assert_eq!(error.lint, Some("unused_variables"));
+        assert_eq!(error.notes, ["the 'unused_variables' lint is set to deny"]);
+
+        let diag = apply(Some(LintLevel::Allow), None, None);
+        assert!(diag.warnings().is_empty() && diag.errors().is_empty());
+    }
+
+    #[test]
+    pub fn lint_named_level_beats_warnings() {
+        let diag = apply(Some(LintLevel::Deny), Some(LintLevel::Allow), None);
+        assert!(diag.warnings().is_empty() && diag.errors().is_empty());
+
+        let diag = apply(Some(LintLevel::Allow), Some(LintLevel::Warn), None);
+        assert!(diag.errors().is_empty());
+        assert_eq!(diag.warnings().len(), 1);
+    }
+
+    #[test]
+    pub fn lint_attribute_beats_command_line() {
+        let diag = apply(Some(LintLevel::Deny), None, Some(LintLevel::Warn));
+        assert!(diag.errors().is_empty());
+        let [warning] = diag.warnings() else {
+            panic!("expected one warning");
+        };
+        assert_eq!(
+            warning.notes,
+            ["the 'unused_variables' lint is set to warn"]
+        );
+
+        let diag = apply(
+            Some(LintLevel::Deny),
+            Some(LintLevel::Deny),
+            Some(LintLevel::Allow),
+        );
+        assert!(diag.warnings().is_empty() && diag.errors().is_empty());
+
+        let diag = apply(
+            Some(LintLevel::Allow),
+            Some(LintLevel::Allow),
+            Some(LintLevel::Deny),
+        );
+        assert!(diag.warnings().is_empty());
+        assert_eq!(diag.errors().len(), 1);
     }
 }
@@ -0,0 +1,447 @@
+/// Long form explanations for the error codes attached by the named `Error` constructors, shown by
+/// `ctl explain`. Codes are never reused once assigned.
+const EXPLANATIONS: &[(&str, &str)] = &[
+    (
+        "E0001",
+        r#"An operator was used with a value whose type doesn't support it.
+
+Example:
+
+    let x = 5;
+    let y = x ?? 1; // error: operator '??' is invalid for a value of type 'int'
+
+Built in operators like `??` and unary `*` only apply to specific kinds of types, while the
+overloadable ones require the type to implement the corresponding trait in `core::ops`."#,
+    ),
+    (
+        "E0002",
+        r#"A variant of a union declared a member with the same name as one of the union's shared
+members.
+
+Example:
+
+    union Shape {
+        shared name: str,
+
+        Circle { name: str, radius: f64 }, // error
+    }
+
+Shared members are available on every variant, so rename one of the two members."#,
+    ),
+    (
+        "E0003",
+        r#"A string literal was not closed before the end of the file.
+
+Example:
+
+    let s = "hello;
+
+Add the closing `"`."#,
+    ),
+    (
+        "E0004",
+        r#"A byte character escape was outside of the ASCII range.
+
+Example:
+
+    let c = b'\u{e9}'; // error
+
+Byte characters must be in the range 0..=0x7f. Use a byte string for multi-byte sequences."#,
+    ),
+    (
+        "E0005",
+        r#"A token appeared somewhere the grammar doesn't allow it, such as a modifier on a
+declaration that doesn't accept it.
+
+Example:
+
+    pub fn main() {
+        pub let x = 5; // error: 'pub' is not valid here
+    }
+
+Remove the token or move it to a position where it is allowed."#,
+    ),
+    (
+        "E0006",
+        r#"An expression had a different type than the one required by its context.
+
+Example:
+
+    let x: int = "hello"; // error: expected type 'int', found 'str'
+
+Change the expression or the annotation so that the types agree. Numbers of different types must
+be converted explicitly with `as`."#,
+    ),
+    (
+        "E0007",
+        r#"An item that isn't `pub` was used from outside of the module that declares it.
+
+Example:
+
+    mod inner {
+        fn helper() {}
+    }
+
+    fn main() {
+        inner::helper(); // error: 'helper' is private
+    }
+
+Mark the item `pub` if it is meant to be used by other modules."#,
+    ),
+    (
+        "E0008",
+        r#"A member that isn't `pub` was accessed from outside of the module that declares its type.
+
+Example:
+
+    mod inner {
+        pub struct Point { x: int, pub y: int }
+    }
+
+    fn get(p: inner::Point): int {
+        p.x // error: cannot access private member 'x' of type 'Point'
+    }
+
+Mark the member `pub` or provide a public method that returns it."#,
+    ),
+    (
+        "E0009",
+        r#"A member access named a member that doesn't exist on the type.
+
+Example:
+
+    struct Point { x: int, y: int }
+
+    fn get(p: Point): int {
+        p.z // error: no member 'z' found on type 'Point'
+    }
+
+Check the spelling of the member, and that the value has the type you expect."#,
+    ),
+    (
+        "E0010",
+        r#"A method call named a method that isn't available on the type.
+
+Example:
+
+    let x = 5;
+    x.frobnicate(); // error: no method 'frobnicate' found on type 'int'
+
+Methods come from the type's own `impl` blocks, the traits it implements and extensions that are
+in scope. Make sure the trait or extension providing the method is imported."#,
+    ),
+    (
+        "E0011",
+        r#"A path referred to a name that isn't declared or imported in the current scope.
+
+Example:
+
+    fn main() {
+        undefined_fn(); // error: no symbol 'undefined_fn' found in this module
+    }
+
+Check the spelling, or add a `use` statement for the item."#,
+    ),
+    (
+        "E0012",
+        r#"The compiler needed an item that the core or std library marks as a language item with
+`@(lang(...))`, but no such item was found.
+
+This usually happens when compiling with `--no-core` or `--no-std`, or with a modified standard
+library. Provide an item with the missing `@(lang(...))` attribute."#,
+    ),
+    (
+        "E0013",
+        r#"A type was used where a trait implementation is required, but the type doesn't implement
+the trait.
+
+Example:
+
+    struct Point { x: int, y: int }
+
+    fn main() {
+        let p = Point(x: 1, y: 2);
+        println("{p}"); // error: type 'Point' does not implement 'Format'
+    }
+
+Implement the trait for the type, or convert the value to one that implements it."#,
+    ),
+    (
+        "E0014",
+        r#"A wildcard `use` statement was applied to something that isn't a module.
+
+Example:
+
+    struct Point { x: int, y: int }
+
+    use Point::*; // error
+
+Wildcard imports bring every public item of a module into scope. Import the items individually."#,
+    ),
+    (
+        "E0015",
+        r#"An unsafe operation was performed outside of an `unsafe` block or function, such as
+dereferencing a raw pointer or calling an unsafe function.
+
+Example:
+
+    fn read(ptr: *raw int): int {
+        *ptr // error: this operation is unsafe
+    }
+
+Wrap the operation in `unsafe { ... }` after making sure it is sound."#,
+    ),
+    (
+        "E0016",
+        r#"Two items with the same name were declared in the same scope.
+
+Example:
+
+    fn foo() {}
+    fn foo() {} // error: redefinition of name 'foo'
+
+Rename or remove one of the declarations. The error points at both of them."#,
+    ),
+    (
+        "E0017",
+        r#"A pattern that can fail to match was used where every value must match, such as in a `let`
+binding or a function parameter.
+
+Example:
+
+    let x: ?int = null;
+    let ?y = x; // error: let binding pattern must be irrefutable
+
+Use `if x is ?y { ... }`, `guard x is ?y else { ... }` or `match` to handle the other cases."#,
+    ),
+    (
+        "E0018",
+        r#"The compiler expected one kind of item or expression and found another.
+
+Example:
+
+    struct Point { x: int, y: int }
+
+    struct Line {
+        impl Point {} // error: expected trait, found type 'Point'
+    }
+
+Check that the path refers to the kind of item that is required here."#,
+    ),
+    (
+        "E0019",
+        r#"A `match` expression doesn't handle every possible value of the scrutinee.
+
+Example:
+
+    fn name(x: ?int): str {
+        match x {
+            ?0 => "zero",
+        } // error: match statement does not cover all cases
+    }
+
+Add the missing patterns, or a catch-all `_` arm."#,
+    ),
+    (
+        "E0020",
+        r#"Two items depend on each other in a way the compiler cannot resolve, such as two statics
+whose initializers refer to each other.
+
+Break the cycle by removing one of the dependencies."#,
+    ),
+    (
+        "E0021",
+        r#"A destructuring pattern was used on a type that cannot be destructured that way.
+
+Example:
+
+    let x = 5;
+    let {a, b} = x; // error: cannot destructure value of type 'int'
+
+Make sure the pattern matches the shape of the type."#,
+    ),
+    (
+        "E0022",
+        r#"The address of a subscript expression was taken, but the subscript operator returns a value
+instead of a pointer. The address refers to a temporary copy, so writes through it are lost.
+
+Implement the subscript operator returning a pointer, or copy the value into a variable first."#,
+    ),
+    (
+        "E0023",
+        r#"The address of a member of a packed struct was taken. Packed members don't have an
+address of their own, so the result would refer to a temporary copy.
+
+Copy the member into a variable first."#,
+    ),
+    (
+        "E0024",
+        r#"A struct or union contains itself by value, so it would have infinite size.
+
+Example:
+
+    struct Node {
+        value: int,
+        next: ?Node, // error: member 'next' gives this struct infinite size
+    }
+
+Store the recursive value behind a pointer, for example `?*Node` or `Vec<Node>`."#,
+    ),
+    (
+        "E0025",
+        r#"An expression was used where a compile time constant is required, but the compiler cannot
+evaluate it at compile time.
+
+Example:
+
+    fn count(): uint { 5 }
+
+    let arr: [int; count()] = [1, 2, 3, 4, 5]; // error
+
+Use a literal or a constant expression instead."#,
+    ),
+    (
+        "E0026",
+        r#"A constant expression overflowed the range of its type while it was evaluated at compile
+time, for example when an array length is computed as `0 - 1`.
+
+Make sure the constant fits in its type."#,
+    ),
+    (
+        "E0027",
+        r#"A member of a packed struct had a type that cannot be stored as a bitfield. Packed struct
+members must be integers, bools or unions whose variants are all empty.
+
+Example:
+
+    packed struct Flags {
+        name: str, // error
+    }
+
+Move the member out of the packed struct."#,
+    ),
+    (
+        "E0028",
+        r#"The C compiler rejected the code generated for this part of the program. The error is
+reported at the CTL statement that produced the offending C code.
+
+This can happen when an `extern` function declaration doesn't match its C definition, or when
+flags passed to the C compiler with `--ccargs` reject the generated code. Otherwise, it is a bug in
+the CTL compiler. Use `ctl print` to inspect the generated code."#,
+    ),
+    (
+        "E0029",
+        r#"A constant expression divided by zero while it was evaluated at compile time.
+
+Example:
+
+    const ZERO: uint = 0;
+    let arr: [int; 10 / ZERO] = []; // error
+
+Make sure the divisor of a constant expression is never zero."#,
+    ),
+    (
+        "E0030",
+        r#"A const function ran for too long or recursed too deeply while it was evaluated at
+compile time. The compile-time interpreter stops after a fixed number of steps so that an
+infinite loop cannot hang the compiler.
+
+Example:
+
+    const fn spin(): uint {
+        mut i = 0u;
+        while true { i++; }
+        i
+    }
+
+    const X: uint = spin(); // error
+
+Make sure every loop and recursion in a const function terminates."#,
+    ),
+    (
+        "E0031",
+        r#"An array was indexed out of bounds while it was evaluated at compile time.
+
+Example:
+
+    const fn get(arr: [uint; 3], i: uint): uint { arr[i] }
+
+    const X: uint = get([1, 2, 3], 3); // error
+
+Make sure every index used by a const function is smaller than the length of the array."#,
+    ),
+    (
+        "E0032",
+        r#"A const function panicked while it was evaluated at compile time, either by calling
+`panic` or by unwrapping a null value.
+
+Example:
+
+    const fn first(x: ?uint): uint { x! }
+
+    const X: uint = first(null); // error
+
+Make sure the arguments passed to const functions don't lead to a panic."#,
+    ),
+    (
+        "E0033",
+        r#"The condition of a `static_assert` evaluated to false. Static assertions are checked
+while the program is compiled, or when a generic function that contains one is instantiated.
+
+Example:
+
+    struct Header {
+        magic: u32,
+        len: u32,
+    }
+
+    fn main() {
+        static_assert(size_of::<Header>() == 16, "Header must be 16 bytes"); // error
+    }
+
+Either fix the code the assertion is guarding, or update the assertion if the assumption it
+checks has changed."#,
+    ),
+];
+
+pub fn explain(code: &str) -> Option<&'static str> {
+    EXPLANATIONS
+        .iter()
+        .find(|(c, _)| c.eq_ignore_ascii_case(code))
+        .map(|(_, text)| *text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{explain, EXPLANATIONS};
+
+    #[test]
+    pub fn known_code() {
+        let text = explain("E0006").unwrap();
+        assert!(text.starts_with("An expression had a different type"));
+        assert_eq!(explain("e0006"), Some(text));
+    }
+
+    #[test]
+    pub fn unknown_code() {
+        assert_eq!(explain("E9999"), None);
+        assert_eq!(explain("unused_variables"), None);
+        assert_eq!(explain(""), None);
+    }
+
+    #[test]
+    pub fn every_code_is_explained() {
+        let source = include_str!("error.rs");
+        for (i, _) in source.match_indices("\"E0") {
+            let code = &source[i + 1..i + 6];
+            assert!(explain(code).is_some(), "{code} has no explanation");
+        }
+
+        for (i, (code, _)) in EXPLANATIONS.iter().enumerate() {
+            assert!(
+                EXPLANATIONS[..i].iter().all(|(c, _)| c != code),
+                "{code} is explained twice"
+            );
+        }
+    }
+}
@@ -5,6 +5,7 @@ use unicode_xid::UnicodeXID;
 
 use crate::{
     error::{Diagnostics, Error, FileId},
+    lint::Lint,
     THIS_PARAM, THIS_TYPE,
 };
 
@@ -236,7 +237,7 @@ impl Token<'_> {
     }
 }
 
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct Span {
     pub pos: u32,
     pub len: u32,
@@ -401,16 +402,8 @@ impl<'a> Lexer<'a> {
                     let mut count = 1;
                     while count > 0 {
                         match self.advance() {
-                            Some('*') => {
-                                if self.advance_if('/') {
-                                    count -= 1;
-                                }
-                            }
-                            Some('/') => {
-                                if self.advance_if('*') {
-                                    count += 1;
-                                }
-                            }
+                            Some('*') if self.advance_if('/') => count -= 1,
+                            Some('/') if self.advance_if('*') => count += 1,
                             None => {
                                 diag.error(Error::new("unterminated block comment", self.here(0)));
                                 break;
@@ -535,6 +528,21 @@ impl<'a> Lexer<'a> {
         )
     }
 
+    /// Reads the raw text of a format specifier in an interpolated string, stopping before the
+    /// closing `}`.
+    pub fn format_spec(&mut self) -> Located<&'a str> {
+        let start = self.pos;
+        let spec = self.advance_while(|ch| ch != '}' && ch != '"');
+        Located::new(
+            Span {
+                pos: start as u32,
+                file: self.file,
+                len: spec.len() as u32,
+            },
+            spec,
+        )
+    }
+
     pub fn next_skip_comments(&mut self, diag: &mut Diagnostics) -> Located<Token<'a>> {
         loop {
             let t = self.next(diag);
@@ -773,14 +781,18 @@ impl<'a> Lexer<'a> {
         } else {
             let value = &self.src[start..self.pos];
             if warn_leading_zero && value.len() > 1 {
-                diag.warn(Error::new(
-                    "leading zero in decimal literal (use 0o to create an octal literal)",
-                    Span {
-                        pos: start as u32,
-                        len: value.len() as u32,
-                        file: self.file,
-                    },
-                ));
+                diag.lint(
+                    Lint::LeadingZero,
+                    None,
+                    Error::new(
+                        "leading zero in decimal literal (use 0o to create an octal literal)",
+                        Span {
+                            pos: start as u32,
+                            len: value.len() as u32,
+                            file: self.file,
+                        },
+                    ),
+                );
             }
 
             Token::Int {
@@ -3,7 +3,9 @@ mod codegen;
 mod comptime_int;
 mod dgraph;
 mod error;
+mod explain;
 mod lexer;
+mod lint;
 mod lsp;
 mod parser;
 mod pretty;
@@ -13,13 +15,19 @@ mod sym;
 mod typecheck;
 mod typeid;
 
-use std::path::{Path, PathBuf};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 use anyhow::{Context, Result};
 use ast::parsed::{Stmt, StmtData};
 use codegen::Codegen;
+pub use codegen::SpanMap;
 pub use error::*;
+pub use explain::explain;
 pub use lexer::*;
+pub use lint::{Lint, LintLevel};
 use project::Project;
 pub use source::*;
 use typecheck::LspInput;
@@ -46,6 +54,7 @@ pub struct ProjectConfig {
     pub root: Option<String>,
     pub name: Option<String>,
     pub build: Option<String>,
+    pub lints: Option<HashMap<String, LintLevel>>,
 }
 
 pub struct Compiler<S: CompileState> {
@@ -94,6 +103,11 @@ impl<T: SourceProvider> Compiler<Source<T>> {
                         // TODO: prevent duplicate names, naming module core/std, etc.
                         name = Self::safe_name(&rename);
                     }
+                    for (lint, level) in config.lints.into_iter().flatten() {
+                        if !diag.set_lint_level(&lint, level) {
+                            anyhow::bail!("unknown lint '{lint}' in {}", path.display());
+                        }
+                    }
                 }
                 Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
                 Err(err) => return Err(err.into()),
@@ -173,6 +187,15 @@ impl Compiler<Parsed> {
         &self.state.1
     }
 
+    /// Sets the level of a lint for the whole compilation, overriding any level set by a
+    /// `ctl.toml`. Attributes in the source still take precedence.
+    pub fn set_lint_level(&mut self, name: &str, level: LintLevel) -> Result<()> {
+        if !self.state.1.set_lint_level(name, level) {
+            anyhow::bail!("unknown lint '{name}'");
+        }
+        Ok(())
+    }
+
     pub fn typecheck(self, lsp: LspInput) -> Compiler<Checked> {
         Compiler {
             state: Checked(TypeChecker::check(self.state.0, self.state.1, lsp)),
@@ -181,8 +204,14 @@ impl Compiler<Parsed> {
 }
 
 impl Compiler<Checked> {
-    pub fn build(mut self, flags: CodegenFlags) -> (Option<String>, Diagnostics) {
-        if !flags.lib && self.state.0.main.is_none() {
+    pub fn diagnostics(&self) -> &Diagnostics {
+        &self.state.0.diag
+    }
+
+    /// Generates the C code for the project, along with a map from positions in the C code back to
+    /// the CTL source that generated them.
+    pub fn build(mut self, flags: CodegenFlags) -> (Option<(String, SpanMap)>, Diagnostics) {
+        if !flags.lib && !flags.test && self.state.0.main.is_none() {
             self.state
                 .0
                 .diag
@@ -192,8 +221,8 @@ impl Compiler<Checked> {
             return (None, self.state.0.diag);
         }
 
-        let (code, diag) = Codegen::build(self.state.0, flags);
-        (Some(code), diag)
+        let (code, spans, diag) = Codegen::build(self.state.0, flags);
+        (Some((code, spans)), diag)
     }
 
     pub fn project(self) -> Project {
@@ -214,6 +243,9 @@ pub struct CodegenFlags {
     pub no_bit_int: bool,
     pub lib: bool,
     pub minify: bool,
+    pub no_bounds_check: bool,
+    pub debug_info: bool,
+    pub test: bool,
 }
 
 pub fn project_from_file(
@@ -0,0 +1,81 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl LintLevel {
+    pub fn from_attr(name: &str) -> Option<Self> {
+        match name {
+            "allow" => Some(LintLevel::Allow),
+            "warn" => Some(LintLevel::Warn),
+            "deny" => Some(LintLevel::Deny),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! lints {
+    ($($(#[$meta:meta])* $variant:ident => $name:literal, $level:ident;)*) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum Lint {
+            $($(#[$meta])* $variant,)*
+        }
+
+        impl Lint {
+            pub const ALL: &'static [Lint] = &[$(Lint::$variant,)*];
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Lint::$variant => $name,)*
+                }
+            }
+
+            pub fn default_level(self) -> LintLevel {
+                match self {
+                    $(Lint::$variant => LintLevel::$level,)*
+                }
+            }
+        }
+    };
+}
+
+lints! {
+    /// A decimal integer literal with a leading zero, which would be octal in C
+    LeadingZero => "leading_zero", Warn;
+    /// `mut` on a binding in a destructuring pattern that is already mutable
+    RedundantMut => "redundant_mut", Warn;
+    /// A variable that is never read
+    UnusedVariables => "unused_variables", Warn;
+    /// A `use` statement whose imported name is never referred to
+    UnusedImports => "unused_imports", Warn;
+    /// A private function that is never called
+    DeadCode => "dead_code", Warn;
+    /// A statement that follows an expression of type `never`
+    UnreachableCode => "unreachable_code", Warn;
+    /// Taking the address of a subscript that returns a value
+    SubscriptAddr => "subscript_addr", Warn;
+    /// Taking the address of a member of a packed struct
+    BitfieldAddr => "bitfield_addr", Warn;
+    /// Calling a mutating method on a member of a packed struct
+    BitfieldReceiver => "bitfield_receiver", Warn;
+    /// `&mut` on a function, which only produces an immutable function pointer
+    MutFnPtr => "mut_fn_ptr", Warn;
+    /// A fallible cast (`as!`) that can never fail
+    InfallibleCast => "infallible_cast", Warn;
+    /// An `unsafe` expression inside of an already unsafe context
+    RedundantUnsafe => "redundant_unsafe", Warn;
+    /// An `allow`, `warn` or `deny` attribute naming a lint that doesn't exist
+    UnknownLints => "unknown_lints", Warn;
+}
+
+impl Lint {
+    /// The name that refers to every lint at once.
+    pub const GROUP_ALL: &'static str = "warnings";
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Self::ALL.iter().copied().find(|lint| lint.name() == name)
+    }
+}
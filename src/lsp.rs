@@ -404,7 +404,7 @@ impl LanguageServer for LspBackend {
             LspItem::Property(src_ty, id, name) => {
                 let ut = scopes.get(*id);
                 let mem = ut.members.get(name);
-                let public = ["", "pub "][mem.map_or(false, |m| m.public) as usize];
+                let public = ["", "pub "][mem.is_some_and(|m| m.public) as usize];
                 let ty = mem.map_or(TypeId::UNKNOWN, |m| m.ty);
                 if matches!(ut.kind, UserTypeKind::Tuple | UserTypeKind::AnonStruct) {
                     let real = src_ty
@@ -541,26 +541,51 @@ impl LspBackend {
                     .map(|err| (DiagnosticSeverity::WARNING, err)),
             )
         {
-            let path = diag.file_path(err.span.file);
-            let range = if let Some(doc) = Url::from_file_path(path)
-                .ok()
-                .and_then(|uri| self.documents.get(&uri))
-            {
-                Diagnostics::get_span_range(&doc.text, err.span, OffsetMode::Utf16)
-            } else if let Ok(Some(range)) = cache.get_source(path, |data| {
-                Diagnostics::get_span_range(data, err.span, OffsetMode::Utf16)
-            }) {
-                range
-            } else {
+            let mut location = |span: Span| {
+                let path = diag.file_path(span.file);
+                let range = if let Some(doc) = Url::from_file_path(path)
+                    .ok()
+                    .and_then(|uri| self.documents.get(&uri))
+                {
+                    Diagnostics::get_span_range(&doc.text, span, OffsetMode::Utf16)
+                } else if let Ok(Some(range)) = cache.get_source(path, |data| {
+                    Diagnostics::get_span_range(data, span, OffsetMode::Utf16)
+                }) {
+                    range
+                } else {
+                    return None;
+                };
+                Some(Location::new(Url::from_file_path(path).ok()?, range))
+            };
+
+            let Some(Location { uri, range }) = location(err.span) else {
                 continue;
             };
+            let related = err
+                .labels
+                .iter()
+                .flat_map(|(span, message)| {
+                    Some(DiagnosticRelatedInformation {
+                        location: location(*span)?,
+                        message: message.clone(),
+                    })
+                })
+                .collect::<Vec<_>>();
+            let mut message = err.message.clone();
+            for note in err.notes.iter() {
+                message = format!("{message}\nnote: {note}");
+            }
 
-            let entry = all.entry(Url::from_file_path(path).unwrap()).or_default();
-            entry.push(Diagnostic {
+            all.entry(uri).or_default().push(Diagnostic {
                 range,
                 severity: Some(severity),
+                code: err
+                    .code
+                    .or(err.lint)
+                    .map(|code| NumberOrString::String(code.into())),
                 source: Some("ctlsp".into()),
-                message: err.message.clone(),
+                message,
+                related_information: (!related.is_empty()).then_some(related),
                 ..Diagnostic::default()
             });
         }
@@ -702,14 +727,22 @@ fn visualize_type_params(
     scopes: &Scopes,
     types: &mut Types,
 ) {
+    let params: Vec<_> = params
+        .iter()
+        .filter(|&&id| !scopes.get(id).implicit)
+        .collect();
     if !params.is_empty() {
         *res += "<";
-        for (i, id) in params.iter().enumerate() {
+        for (i, id) in params.into_iter().enumerate() {
             if i > 0 {
                 res.push_str(", ");
             }
 
-            *res += &visualize_type(*id, scopes, types);
+            if let Some(ty) = scopes.get(*id).const_ty {
+                *res += &format!("const {}: {}", scopes.get(*id).name, ty.name(scopes, types));
+            } else {
+                *res += &visualize_type(*id, scopes, types);
+            }
         }
         *res += ">";
     }
@@ -754,6 +787,10 @@ fn visualize_func(id: FunctionId, small: bool, scopes: &Scopes, types: &mut Type
             res += "extern ";
         }
 
+        if func.is_const {
+            res += "const ";
+        }
+
         if func.is_unsafe {
             write_de!(res, "unsafe fn {}", func.name.data)
         } else {
@@ -953,8 +990,16 @@ fn visualize_type(id: UserTypeId, scopes: &Scopes, types: &mut Types) -> String
             visualize_type_params(&mut res, &ut.type_params, scopes, types);
             write_de!(res, " for {}", ty.name(scopes, types));
         }
+        UserTypeKind::Alias(ty) => {
+            write_de!(res, "type {}", ut.name.data);
+            visualize_type_params(&mut res, &ut.type_params, scopes, types);
+            write_de!(res, " = {}", ty.name(scopes, types));
+        }
         UserTypeKind::AnonStruct => {}
         UserTypeKind::Tuple => {}
+        UserTypeKind::Lambda(_) => {}
+        UserTypeKind::AsyncFn(_) => {}
+        UserTypeKind::Generator(_) => {}
     }
 
     res
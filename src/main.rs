@@ -1,17 +1,17 @@
 use anyhow::{Context, Result};
-use clap::{Args, Parser, Subcommand, ValueHint};
+use clap::{Args, Parser, Subcommand, ValueEnum, ValueHint};
 use ctl::{
-    project_from_file, CachingSourceProvider, CodegenFlags, Compiler, Diagnostics, Error, FileId,
-    LspBackend, OffsetMode, SourceProvider,
+    explain, project_from_file, CachingSourceProvider, CodegenFlags, Compiler, Diagnostics, Error,
+    LintLevel, LspBackend, OffsetMode, SourceProvider, Span, SpanMap,
 };
 use std::{
     ffi::OsString,
     fs::File,
-    io::Write,
+    io::{IsTerminal, Write},
     path::{Path, PathBuf},
     process::{Command, Stdio},
 };
-use tower_lsp::{lsp_types::Range, LspService, Server};
+use tower_lsp::{LspService, Server};
 
 #[derive(Parser)]
 struct Arguments {
@@ -45,6 +45,18 @@ struct Arguments {
     #[arg(global = true)]
     no_bit_int: bool,
 
+    /// Compile without bounds checks for array and span subscripts. Out of bounds accesses become
+    /// undefined behavior instead of a panic.
+    #[clap(action, long)]
+    #[arg(global = true)]
+    no_bounds_check: bool,
+
+    /// Emit #line directives that map the generated C code back to the CTL source, and build
+    /// with debug info so C debuggers can step through it.
+    #[clap(action, long)]
+    #[arg(global = true)]
+    debug_info: bool,
+
     /// Compile as a library
     #[clap(action, short, long)]
     #[arg(global = true)]
@@ -54,6 +66,21 @@ struct Arguments {
     #[clap(action, short, long)]
     #[arg(global = true)]
     quiet: bool,
+
+    /// Silence the named lint. `warnings` refers to every lint.
+    #[clap(short = 'A', long = "allow", value_name = "LINT")]
+    #[arg(global = true)]
+    allow: Vec<String>,
+
+    /// Report the named lint as a warning. `warnings` refers to every lint.
+    #[clap(short = 'W', long = "warn", value_name = "LINT")]
+    #[arg(global = true)]
+    warn: Vec<String>,
+
+    /// Report the named lint as an error. `-D warnings` turns every warning into an error.
+    #[clap(short = 'D', long = "deny", value_name = "LINT")]
+    #[arg(global = true)]
+    deny: Vec<String>,
 }
 
 #[derive(Args)]
@@ -81,6 +108,12 @@ struct BuildOrRun {
     libs: Vec<String>,
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum MessageFormat {
+    Human,
+    Json,
+}
+
 #[derive(Subcommand)]
 enum SubCommand {
     #[clap(alias = "p")]
@@ -118,17 +151,104 @@ enum SubCommand {
         #[arg(trailing_var_arg = true, value_hint = ValueHint::CommandWithArguments)]
         targs: Vec<OsString>,
     },
+    #[clap(alias = "c")]
+    Check {
+        /// The path to the file or project folder
+        input: PathBuf,
+
+        /// The format of the reported diagnostics. The json format prints one object per line.
+        #[clap(long, value_enum, default_value = "human")]
+        message_format: MessageFormat,
+    },
+    /// Build and run the functions marked `@(test)`. On Unix-like targets each test runs in its
+    /// own process, so a panic only fails that test. Elsewhere, tests run in-process and the first
+    /// panic aborts the run.
+    #[clap(alias = "t")]
+    Test {
+        #[clap(flatten)]
+        build: BuildOrRun,
+
+        /// Only run tests whose name contains one of these strings
+        filters: Vec<String>,
+    },
     #[clap(alias = "l")]
     Lsp,
+    /// Print a detailed explanation of an error code
+    Explain {
+        /// The error code, for example E0006
+        code: String,
+    },
+}
+
+/// A diagnostic printed by the C compiler for the generated code, which is read from stdin.
+struct CcDiagnostic<'a> {
+    error: bool,
+    span: Option<Span>,
+    message: &'a str,
+    raw: &'a str,
 }
 
-fn compile_results(src: &str, leak: bool, output: &Path, build: BuildOrRun) -> Result<()> {
+/// Parses lines of the form `<stdin>:line[:column]: severity: message`, as printed by clang and
+/// gcc. Errors inside of macros defined by `ctl.h` are attributed to the place the macro was
+/// expanded.
+fn parse_cc_diagnostics<'a>(stderr: &'a str, spans: &SpanMap) -> Vec<CcDiagnostic<'a>> {
+    let mut result: Vec<CcDiagnostic> = vec![];
+    for raw in stderr.lines() {
+        let Some(rest) = raw.strip_prefix("<stdin>:") else {
+            continue;
+        };
+        let Some((line, rest)) = rest.split_once(':') else {
+            continue;
+        };
+        let Ok(line) = line.parse() else {
+            continue;
+        };
+        let rest = match rest.split_once(':') {
+            Some((col, rest)) if col.parse::<u32>().is_ok() => rest,
+            _ => rest,
+        };
+        let Some((severity, message)) = rest.split_once(':') else {
+            continue;
+        };
+        let (severity, message) = (severity.trim(), message.trim());
+        if severity == "note" && message.starts_with("in expansion of macro") {
+            if let Some(last) = result.last_mut().filter(|last| last.span.is_none()) {
+                last.span = spans.find(line);
+            }
+            continue;
+        }
+
+        let error = match severity {
+            "error" | "fatal error" => true,
+            "warning" => false,
+            _ => continue,
+        };
+        result.push(CcDiagnostic {
+            error,
+            span: spans.find(line),
+            message,
+            raw,
+        });
+    }
+    result
+}
+
+fn compile_results(
+    src: &str,
+    spans: &SpanMap,
+    diag: &Diagnostics,
+    leak: bool,
+    debug_info: bool,
+    output: &Path,
+    build: BuildOrRun,
+) -> Result<()> {
     let warnings = ["-Wall", "-Wextra"];
     let mut cc = Command::new(build.cc)
         .args(["-fwrapv", "-std=c11", "-x", "c", "-", "-o"])
         .arg(output)
         .args(if !leak { &["-lgc"][..] } else { &[] })
         .args(if build.optimized { &["-O2"][..] } else { &[] })
+        .args(if debug_info { &["-g"][..] } else { &[] })
         .args(if build.verbose { &warnings[..] } else { &[] })
         .args(build.ccargs.unwrap_or_default().split(' '))
         .args(build.libs.iter().map(|lib| format!("-l{lib}")))
@@ -138,22 +258,39 @@ fn compile_results(src: &str, leak: bool, output: &Path, build: BuildOrRun) -> R
         } else {
             Stdio::null()
         })
-        .stderr(if build.verbose {
-            Stdio::inherit()
-        } else {
-            Stdio::null()
-        })
+        .stderr(Stdio::piped())
         .spawn()
         .context("Couldn't invoke the compiler")?;
     cc.stdin
-        .as_mut()
+        .take()
         .context("The C compiler closed stdin")?
         .write_all(src.as_bytes())?;
-    let status = cc.wait()?;
-    if !status.success() {
+    let result = cc.wait_with_output()?;
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    let cc_diags = parse_cc_diagnostics(&stderr, spans);
+    let mapped = cc_diags.iter().any(|d| d.error && d.span.is_some());
+    if build.verbose || (!mapped && !result.status.success()) {
+        eprint!("{stderr}");
+    }
+
+    let mut printer = DiagnosticPrinter::new();
+    for cc_diag in cc_diags {
+        match cc_diag.span {
+            Some(span) if cc_diag.error => {
+                printer.display(diag, ERROR, &Error::backend(cc_diag.message, span))
+            }
+            Some(span) if build.verbose => {
+                printer.display(diag, WARNING, &Error::backend(cc_diag.message, span))
+            }
+            None if cc_diag.error && mapped && !build.verbose => eprintln!("{}", cc_diag.raw),
+            _ => {}
+        }
+    }
+
+    if !result.status.success() {
         anyhow::bail!(
             "The C compiler returned non-zero exit code {:?}",
-            status.code().unwrap_or_default()
+            result.status.code().unwrap_or_default()
         );
     }
 
@@ -188,64 +325,282 @@ fn print_results(src: &[u8], pretty: bool, output: &mut impl Write) -> Result<()
     Ok(())
 }
 
-fn display_diagnostics(diag: &Diagnostics) {
-    fn format<S: SourceProvider>(
-        provider: &mut S,
-        diag: &Diagnostics,
-        id: FileId,
-        errors: &[Error],
-        mut format: impl FnMut(&str, Range),
-    ) {
-        for err in errors.iter().filter(|err| err.span.file == id) {
-            // TODO: do something with the errors
-            _ = provider.get_source(diag.file_path(err.span.file), |data| {
-                format(
-                    &err.message,
-                    Diagnostics::get_span_range(data, err.span, OffsetMode::Utf32),
-                );
-            });
+struct Snippet<'a> {
+    path: &'a Path,
+    line: u32,
+    column: u32,
+    text: String,
+    underline: String,
+}
+
+impl<'a> Snippet<'a> {
+    fn new(provider: &mut CachingSourceProvider, path: &'a Path, span: Span) -> Option<Self> {
+        provider
+            .get_source(path, |data| {
+                let pos = (span.pos as usize).min(data.len());
+                let line_start = data[..pos].rfind('\n').map_or(0, |i| i + 1);
+                let line_end = data[pos..].find('\n').map_or(data.len(), |i| pos + i);
+                let end = (pos + span.len as usize).clamp(pos, line_end);
+                let range = Diagnostics::get_span_range(data, span, OffsetMode::Utf32);
+                // keep tabs in the padding so the underline lines up with the source
+                let padding =
+                    data[line_start..pos]
+                        .chars()
+                        .map(|ch| if ch == '\t' { '\t' } else { ' ' });
+                Snippet {
+                    path,
+                    line: range.start.line + 1,
+                    column: range.start.character + 1,
+                    text: data[line_start..line_end].trim_end().into(),
+                    underline: padding.collect::<String>()
+                        + &"^".repeat(data[pos..end].chars().count().max(1)),
+                }
+            })
+            .ok()
+            .flatten()
+    }
+}
+
+struct Style {
+    color: bool,
+}
+
+impl Style {
+    fn paint(&self, code: &str, text: impl std::fmt::Display) -> String {
+        if self.color {
+            format!("\x1b[{code}m{text}\x1b[0m")
+        } else {
+            text.to_string()
         }
     }
+}
 
-    let cwd = std::env::current_dir().ok();
-    let mut provider = CachingSourceProvider::new();
-    for (id, path) in diag.paths() {
-        let path = cwd
-            .as_ref()
-            .and_then(|cwd| path.strip_prefix(cwd).ok())
-            .unwrap_or(path);
-        format(&mut provider, diag, id, diag.errors(), |msg, range| {
-            eprintln!(
-                "error: {}:{}:{}: {msg}",
-                path.display(),
-                range.start.line + 1,
-                range.start.character + 1
-            );
-        });
+fn display_error(
+    provider: &mut CachingSourceProvider,
+    diag: &Diagnostics,
+    cwd: Option<&Path>,
+    style: &Style,
+    (severity, color): (&str, &str),
+    err: &Error,
+) {
+    let display_path = |path: &Path| {
+        cwd.and_then(|cwd| path.strip_prefix(cwd).ok())
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    };
+
+    let primary = Snippet::new(provider, diag.file_path(err.span.file), err.span);
+    let labels: Vec<_> = err
+        .labels
+        .iter()
+        .flat_map(|(span, label)| {
+            Snippet::new(provider, diag.file_path(span.file), *span).map(|s| (s, label))
+        })
+        .collect();
+    let width = primary
+        .iter()
+        .chain(labels.iter().map(|(s, _)| s))
+        .map(|s| s.line.to_string().len())
+        .max()
+        .unwrap_or(0);
+    let gutter = |line: &str| style.paint("1;34", format!("{line:>width$} |"));
+
+    let severity = match err.code.or(err.lint) {
+        Some(code) => format!("{severity}[{code}]"),
+        None => severity.into(),
+    };
+    eprintln!(
+        "{}{}",
+        style.paint(color, severity),
+        style.paint("1", format!(": {}", err.message))
+    );
+    if let Some(snippet) = &primary {
+        eprintln!(
+            "{}{} {}:{}:{}",
+            " ".repeat(width),
+            style.paint("1;34", "-->"),
+            display_path(snippet.path),
+            snippet.line,
+            snippet.column,
+        );
+        eprintln!("{}", gutter(""));
+        eprintln!("{} {}", gutter(&snippet.line.to_string()), snippet.text);
+        eprintln!("{} {}", gutter(""), style.paint(color, &snippet.underline));
     }
 
-    for (id, path) in diag.paths() {
-        let path = cwd
-            .as_ref()
-            .and_then(|cwd| path.strip_prefix(cwd).ok())
-            .unwrap_or(path);
-        format(&mut provider, diag, id, diag.warnings(), |msg, range| {
+    for (snippet, label) in labels {
+        if primary.as_ref().map(|p| p.path) != Some(snippet.path) {
             eprintln!(
-                "warning: {}:{}:{}: {msg}",
-                path.display(),
-                range.start.line + 1,
-                range.start.character + 1
+                "{}{} {}:{}:{}",
+                " ".repeat(width),
+                style.paint("1;34", ":::"),
+                display_path(snippet.path),
+                snippet.line,
+                snippet.column,
             );
-        });
+        }
+        eprintln!("{}", gutter(""));
+        eprintln!("{} {}", gutter(&snippet.line.to_string()), snippet.text);
+        eprintln!(
+            "{} {}",
+            gutter(""),
+            style.paint(
+                "1;34",
+                format!("{} {label}", snippet.underline.replace('^', "-"))
+            )
+        );
+    }
+
+    for note in err.notes.iter() {
+        eprintln!(
+            "{} {} {note}",
+            " ".repeat(width + 1),
+            style.paint("1;34", "= note:")
+        );
+    }
+    eprintln!();
+}
+
+const ERROR: (&str, &str) = ("error", "1;31");
+const WARNING: (&str, &str) = ("warning", "1;33");
+
+struct DiagnosticPrinter {
+    provider: CachingSourceProvider,
+    cwd: Option<PathBuf>,
+    style: Style,
+}
+
+impl DiagnosticPrinter {
+    fn new() -> Self {
+        Self {
+            provider: CachingSourceProvider::new(),
+            cwd: std::env::current_dir().ok(),
+            style: Style {
+                color: std::io::stderr().is_terminal(),
+            },
+        }
+    }
+
+    fn display(&mut self, diag: &Diagnostics, severity: (&str, &str), err: &Error) {
+        display_error(
+            &mut self.provider,
+            diag,
+            self.cwd.as_deref(),
+            &self.style,
+            severity,
+            err,
+        );
+    }
+}
+
+fn display_diagnostics(diag: &Diagnostics) {
+    let mut printer = DiagnosticPrinter::new();
+    for (severity, errors) in [(ERROR, diag.errors()), (WARNING, diag.warnings())] {
+        for (id, _) in diag.paths() {
+            for err in errors.iter().filter(|err| err.span.file == id) {
+                printer.display(diag, severity, err);
+            }
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct JsonPosition {
+    line: u32,
+    column: u32,
+}
+
+#[derive(serde::Serialize)]
+struct JsonLocation {
+    file: String,
+    /// Byte offsets of the start and end of the span
+    span: (u32, u32),
+    /// 1-based, with columns counted in characters
+    start: JsonPosition,
+    end: JsonPosition,
+}
+
+impl JsonLocation {
+    fn new(provider: &mut CachingSourceProvider, diag: &Diagnostics, span: Span) -> Self {
+        let path = diag.file_path(span.file);
+        let range = provider
+            .get_source(path, |data| {
+                Diagnostics::get_span_range(data, span, OffsetMode::Utf32)
+            })
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        Self {
+            file: path.display().to_string(),
+            span: (span.pos, span.pos + span.len),
+            start: JsonPosition {
+                line: range.start.line + 1,
+                column: range.start.character + 1,
+            },
+            end: JsonPosition {
+                line: range.end.line + 1,
+                column: range.end.character + 1,
+            },
+        }
     }
 }
 
+#[derive(serde::Serialize)]
+struct JsonLabel<'a> {
+    message: &'a str,
+    #[serde(flatten)]
+    location: JsonLocation,
+}
+
+#[derive(serde::Serialize)]
+struct JsonDiagnostic<'a> {
+    severity: &'static str,
+    code: Option<&'static str>,
+    lint: Option<&'static str>,
+    message: &'a str,
+    #[serde(flatten)]
+    location: JsonLocation,
+    labels: Vec<JsonLabel<'a>>,
+    notes: &'a [String],
+}
+
+fn display_diagnostics_json(diag: &Diagnostics, out: &mut impl Write) -> Result<()> {
+    let mut provider = CachingSourceProvider::new();
+    for (severity, errors) in [("error", diag.errors()), ("warning", diag.warnings())] {
+        for err in errors {
+            let diagnostic = JsonDiagnostic {
+                severity,
+                code: err.code,
+                lint: err.lint,
+                message: &err.message,
+                location: JsonLocation::new(&mut provider, diag, err.span),
+                labels: err
+                    .labels
+                    .iter()
+                    .map(|(span, message)| JsonLabel {
+                        message,
+                        location: JsonLocation::new(&mut provider, diag, *span),
+                    })
+                    .collect(),
+                notes: &err.notes,
+            };
+            serde_json::to_writer(&mut *out, &diagnostic)?;
+            writeln!(out)?;
+        }
+    }
+
+    Ok(())
+}
+
 fn main() -> Result<()> {
     let args = Arguments::parse();
     let input = match &args.command {
         SubCommand::Print { input, .. } => input,
         SubCommand::Build { build, .. } => &build.input,
         SubCommand::Run { build, .. } => &build.input,
+        SubCommand::Test { build, .. } => &build.input,
+        SubCommand::Check { input, .. } => input,
         SubCommand::Lsp => {
             tokio::runtime::Builder::new_multi_thread()
                 .enable_all()
@@ -258,27 +613,56 @@ fn main() -> Result<()> {
                 });
             return Ok(());
         }
+        SubCommand::Explain { code } => {
+            let Some(text) = explain(code) else {
+                anyhow::bail!("'{code}' is not a valid error code");
+            };
+            println!("{text}");
+            return Ok(());
+        }
     };
-    let result = Compiler::new()
-        .parse(project_from_file(input, vec![], args.no_core, args.no_std))?
+    let mut parsed =
+        Compiler::new().parse(project_from_file(input, vec![], args.no_core, args.no_std))?;
+    for (names, level) in [
+        (&args.allow, LintLevel::Allow),
+        (&args.warn, LintLevel::Warn),
+        (&args.deny, LintLevel::Deny),
+    ] {
+        for name in names {
+            parsed.set_lint_level(name, level)?;
+        }
+    }
+    let checked = parsed
         .inspect(|ast| {
             if args.dump_ast {
                 ast.dump()
             }
         })
-        .typecheck(Default::default())
-        .build(CodegenFlags {
-            leak: args.leak,
-            no_bit_int: args.no_bit_int,
-            lib: args.shared,
-            minify: matches!(args.command, SubCommand::Print { minify: true, .. }),
-        });
-    let result = match result {
-        (Some(code), diag) => {
+        .typecheck(Default::default());
+    if let SubCommand::Check { message_format, .. } = args.command {
+        let diag = checked.diagnostics();
+        match message_format {
+            MessageFormat::Human => display_diagnostics(diag),
+            MessageFormat::Json => display_diagnostics_json(diag, &mut std::io::stdout().lock())?,
+        }
+        std::process::exit(diag.has_errors() as i32);
+    }
+
+    let result = checked.build(CodegenFlags {
+        leak: args.leak,
+        no_bit_int: args.no_bit_int,
+        lib: args.shared,
+        minify: matches!(args.command, SubCommand::Print { minify: true, .. }),
+        no_bounds_check: args.no_bounds_check,
+        debug_info: args.debug_info,
+        test: matches!(args.command, SubCommand::Test { .. }),
+    });
+    let (result, spans, diag) = match result {
+        (Some((code, spans)), diag) => {
             if !args.quiet {
                 display_diagnostics(&diag);
             }
-            code
+            (code, spans, diag)
         }
         (None, diag) => {
             eprintln!("Compilation failed: ");
@@ -296,12 +680,28 @@ fn main() -> Result<()> {
             }
         }
         SubCommand::Build { build, output } => {
-            compile_results(&result, args.leak, &output, build)?;
+            compile_results(
+                &result,
+                &spans,
+                &diag,
+                args.leak,
+                args.debug_info,
+                &output,
+                build,
+            )?;
         }
         SubCommand::Run { build, targs } => {
             // TODO: safe?
             let output = Path::new("./a.out");
-            compile_results(&result, args.leak, output, build)?;
+            compile_results(
+                &result,
+                &spans,
+                &diag,
+                args.leak,
+                args.debug_info,
+                output,
+                build,
+            )?;
             #[cfg(unix)]
             {
                 use std::os::unix::process::CommandExt;
@@ -318,8 +718,167 @@ fn main() -> Result<()> {
                 std::process::exit(status.code().unwrap_or_default());
             }
         }
-        SubCommand::Lsp => {}
+        SubCommand::Test { build, filters } => {
+            let output = Path::new("./a.out");
+            compile_results(
+                &result,
+                &spans,
+                &diag,
+                args.leak,
+                args.debug_info,
+                output,
+                build,
+            )?;
+            let status = Command::new(output)
+                .args(filters)
+                .spawn()
+                .context("Couldn't invoke the generated program")?
+                .wait()?;
+            std::process::exit(status.code().unwrap_or(1));
+        }
+        SubCommand::Check { .. } | SubCommand::Lsp | SubCommand::Explain { .. } => {}
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{display_diagnostics_json, parse_cc_diagnostics};
+    use ctl::{project_from_file, Compiler, FileId, Span, SpanMap};
+    use serde_json::{json, Value};
+    use std::io::Write;
+
+    fn span(pos: u32) -> Span {
+        Span {
+            pos,
+            len: 1,
+            file: FileId::default(),
+        }
+    }
+
+    fn spans() -> SpanMap {
+        [(2, Some(span(10))), (5, Some(span(20))), (8, None)]
+            .into_iter()
+            .collect()
+    }
+
+    #[test]
+    pub fn clang_diagnostics() {
+        let stderr = "\
+<stdin>:3:10: error: use of undeclared identifier 'x'
+    3 | int y = x;
+      |         ^
+<stdin>:6:5: warning: unused variable 'z' [-Wunused-variable]
+2 warnings and 1 error generated.
+";
+        let diags = parse_cc_diagnostics(stderr, &spans());
+        assert_eq!(diags.len(), 2);
+        assert!(diags[0].error);
+        assert_eq!(diags[0].span, Some(span(10)));
+        assert_eq!(diags[0].message, "use of undeclared identifier 'x'");
+        assert_eq!(diags[0].raw, stderr.lines().next().unwrap());
+        assert!(!diags[1].error);
+        assert_eq!(diags[1].span, Some(span(20)));
+        assert_eq!(diags[1].message, "unused variable 'z' [-Wunused-variable]");
+    }
+
+    #[test]
+    pub fn gcc_diagnostics() {
+        let stderr = "\
+<stdin>: In function 'p_main':
+<stdin>:4:5: error: 'x' undeclared (first use in this function)
+<stdin>:4:5: note: each undeclared identifier is reported only once
+<stdin>:9:1: fatal error: unterminated comment
+compilation terminated.
+";
+        let diags = parse_cc_diagnostics(stderr, &spans());
+        assert_eq!(diags.len(), 2);
+        assert!(diags[0].error);
+        assert_eq!(diags[0].span, Some(span(10)));
+        assert_eq!(
+            diags[0].message,
+            "'x' undeclared (first use in this function)"
+        );
+        assert!(diags[1].error);
+        assert_eq!(diags[1].span, None);
+        assert_eq!(diags[1].message, "unterminated comment");
+    }
+
+    #[test]
+    pub fn diagnostics_without_column() {
+        let stderr = "<stdin>:6: error: expected ';'\n<stdin>:1: warning: header\n";
+        let diags = parse_cc_diagnostics(stderr, &spans());
+        assert_eq!(diags.len(), 2);
+        assert!(diags[0].error);
+        assert_eq!(diags[0].span, Some(span(20)));
+        assert_eq!(diags[0].message, "expected ';'");
+        assert!(!diags[1].error);
+        assert_eq!(diags[1].span, None);
+    }
+
+    #[test]
+    pub fn macro_expansion_note() {
+        let stderr = "\
+<stdin>:1:20: error: invalid operands to binary +
+<stdin>:6:1: note: in expansion of macro 'CTL_ADD'
+<stdin>:3:2: error: expected expression
+<stdin>:7:1: note: in expansion of macro 'CTL_SUB'
+";
+        let diags = parse_cc_diagnostics(stderr, &spans());
+        assert_eq!(diags.len(), 2);
+        // the first error is inside the macro definition, so it takes the location it was
+        // expanded at
+        assert_eq!(diags[0].span, Some(span(20)));
+        // the second already has a location of its own
+        assert_eq!(diags[1].span, Some(span(10)));
+    }
+
+    #[test]
+    pub fn json_diagnostics() {
+        let mut file = tempfile::Builder::new().suffix(".ctl").tempfile().unwrap();
+        write!(file, "fn main() {{\n    let x = 5;\n    missing();\n}}\n").unwrap();
+        let checked = Compiler::new()
+            .parse(project_from_file(file.path(), vec![], true, true))
+            .unwrap()
+            .typecheck(Default::default());
+
+        let mut out = Vec::new();
+        display_diagnostics_json(checked.diagnostics(), &mut out).unwrap();
+        let lines: Vec<Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let path = file.path().display().to_string();
+        assert_eq!(
+            lines,
+            [
+                json!({
+                    "severity": "error",
+                    "code": "E0011",
+                    "lint": null,
+                    "message": "no symbol 'missing' found in this module",
+                    "file": path,
+                    "span": [31, 38],
+                    "start": { "line": 3, "column": 5 },
+                    "end": { "line": 3, "column": 12 },
+                    "labels": [],
+                    "notes": [],
+                }),
+                json!({
+                    "severity": "warning",
+                    "code": null,
+                    "lint": "unused_variables",
+                    "message": "unused variable: 'x'",
+                    "file": path,
+                    "span": [20, 21],
+                    "start": { "line": 2, "column": 9 },
+                    "end": { "line": 2, "column": 10 },
+                    "labels": [],
+                    "notes": ["'@(warn(unused_variables))' is on by default"],
+                }),
+            ]
+        );
+    }
+}
@@ -4,6 +4,7 @@ use crate::{
     ast::{parsed::*, Attribute, Attributes, UnaryOp},
     error::{Diagnostics, Error, FileId},
     lexer::{Lexer, Located, Precedence, Span, Token},
+    lint::Lint,
     THIS_PARAM, THIS_TYPE,
 };
 
@@ -12,6 +13,7 @@ struct FnConfig {
     is_extern: bool,
     is_public: bool,
     is_unsafe: bool,
+    is_const: bool,
     require_body: bool,
 }
 
@@ -26,6 +28,8 @@ pub struct Parser<'a, 'b> {
     lexer: Lexer<'a>,
     peek: Option<Located<Token<'a>>>,
     needs_sync: bool,
+    /// Set when a `yield` expression is parsed, making the enclosing function a generator
+    yields: bool,
     diag: &'b mut Diagnostics,
 }
 
@@ -36,6 +40,7 @@ impl<'a, 'b> Parser<'a, 'b> {
             lexer: Lexer::new(src, file),
             peek: None,
             needs_sync: false,
+            yields: false,
         }
     }
 
@@ -76,6 +81,7 @@ impl<'a, 'b> Parser<'a, 'b> {
                 is_public: public.is_some(),
                 is_extern: is_extern.is_some(),
                 is_unsafe: is_unsafe.is_some(),
+                is_const: false,
                 require_body: is_extern.is_none(),
             },
             attrs,
@@ -274,8 +280,63 @@ impl<'a, 'b> Parser<'a, 'b> {
                     components.push(ident);
                 }
             }
+            Token::Type => {
+                self.next();
+                if let Some(token) = is_unsafe {
+                    self.error_no_sync(Error::not_valid_here(&token));
+                }
+
+                if let Some(token) = is_extern {
+                    self.error_no_sync(Error::not_valid_here(&token));
+                }
+
+                let name = self.expect_ident("expected name");
+                let type_params = self.type_params();
+                self.expect(Token::Assign);
+                let ty = self.type_hint();
+                self.expect(Token::Semicolon);
+                Ok(Stmt {
+                    data: StmtData::Alias {
+                        public: public.is_some(),
+                        name,
+                        type_params,
+                        ty,
+                    },
+                    attrs,
+                })
+            }
             Token::Static | Token::Const => {
                 let token = self.next();
+                if token.data == Token::Const
+                    && (self.matches(Token::Fn) || self.matches(Token::Unsafe))
+                {
+                    if let Some(token) = is_extern {
+                        self.error_no_sync(Error::not_valid_here(&token));
+                    }
+
+                    let config = FnConfig {
+                        is_public: public.is_some(),
+                        is_extern: false,
+                        is_unsafe: is_unsafe.is_some() || self.next_if(Token::Unsafe).is_some(),
+                        is_const: true,
+                        require_body: true,
+                    };
+                    return Ok(Stmt {
+                        attrs: Default::default(),
+                        data: match self.expect_fn(config, attrs) {
+                            Ok(Left(func)) => StmtData::Fn(func),
+                            Ok(Right(func)) => {
+                                self.error(Error::new(
+                                    "operator functions can only be defined in types and extensions",
+                                    func.name.span,
+                                ));
+                                StmtData::Fn(Fn::from_operator_fn(func.name.data.to_string(), func))
+                            }
+                            Err(()) => StmtData::Error,
+                        },
+                    });
+                }
+
                 if let Some(token) = is_unsafe {
                     self.error_no_sync(Error::not_valid_here(&token));
                 }
@@ -435,10 +496,21 @@ impl<'a, 'b> Parser<'a, 'b> {
                 span,
                 ExprData::Path(Located::new(span, THIS_PARAM.to_owned()).into()),
             ),
-            Token::ThisType => Expr::new(
-                span,
-                ExprData::Path(Located::new(span, THIS_TYPE.to_owned()).into()),
-            ),
+            Token::ThisType => {
+                if !self.matches(Token::ScopeRes) {
+                    return Expr::new(
+                        span,
+                        ExprData::Path(Located::new(span, THIS_TYPE.to_owned()).into()),
+                    );
+                }
+
+                let this_span = span;
+                let data = self.path_components(None, &mut span);
+                Expr::new(
+                    span,
+                    ExprData::Path(Path::new(PathOrigin::This(this_span), data)),
+                )
+            }
             Token::Ident(ident) => {
                 let data = self.path_components(Some(Located::new(span, ident.into())), &mut span);
                 Expr::new(span, ExprData::Path(Path::new(PathOrigin::Normal, data)))
@@ -471,8 +543,8 @@ impl<'a, 'b> Parser<'a, 'b> {
             }
             Token::StringPart(value) => {
                 let mut parts = vec![
-                    Expr::new(span, ExprData::String(value.into())),
-                    self.expression(),
+                    (Expr::new(span, ExprData::String(value.into())), None),
+                    self.interpolated_expr(),
                 ];
                 while let Some(part) = self.next_if_map(|t| {
                     t.data
@@ -482,13 +554,12 @@ impl<'a, 'b> Parser<'a, 'b> {
                     if self.matches_pred(|t| matches!(t, Token::StringPart(_) | Token::String(_))) {
                         let span = self.peek().span;
                         self.error(Error::new("expected expression", span));
-                        parts.push(part.map(ExprData::String));
+                        parts.push((part.map(ExprData::String), None));
                     } else {
-                        let expr = self.expression();
-                        span.extend_to(expr.span);
-                        parts.push(part.map(ExprData::String));
+                        let expr = self.interpolated_expr();
+                        span.extend_to(expr.0.span);
+                        parts.push((part.map(ExprData::String), None));
                         parts.push(expr);
-                        // TODO: ':' format options
                     }
                 }
 
@@ -498,7 +569,7 @@ impl<'a, 'b> Parser<'a, 'b> {
                         data: Token::String(data),
                     } => {
                         span.extend_to(inner);
-                        parts.push(Expr::new(inner, ExprData::String(data.into())));
+                        parts.push((Expr::new(inner, ExprData::String(data.into())), None));
                     }
                     token => self.error(Error::new("expected end of string", token.span)),
                 }
@@ -525,7 +596,7 @@ impl<'a, 'b> Parser<'a, 'b> {
                 if matches!(op, UnaryOp::Neg) {
                     if let ExprData::Integer(patt) = &mut expr.data {
                         expr.span = span.extended_to(expr.span);
-                        patt.negative = true;
+                        patt.negative = !patt.negative;
                         return expr;
                     }
                 }
@@ -588,6 +659,7 @@ impl<'a, 'b> Parser<'a, 'b> {
             Token::Loop => self.loop_expr(span, None),
             Token::For => self.for_expr(span, None),
             Token::Match => self.match_expr(span),
+            Token::Try => self.try_expr(span),
             Token::LBrace => {
                 if let Some(rbrace) = self.next_if(Token::RBrace) {
                     Expr::new(span.extended_to(rbrace.span), ExprData::Array(Vec::new()))
@@ -675,6 +747,15 @@ impl<'a, 'b> Parser<'a, 'b> {
 
                 Expr::new(span, ExprData::Return(expr))
             }
+            Token::Raise => {
+                let expr = self.expression();
+                Expr::new(span.extended_to(expr.span), ExprData::Raise(expr.into()))
+            }
+            Token::Yield => {
+                self.yields = true;
+                let expr = self.expression();
+                Expr::new(span.extended_to(expr.span), ExprData::Yield(expr.into()))
+            }
             Token::Break => {
                 let label = self
                     .next_if(Token::At)
@@ -843,6 +924,13 @@ impl<'a, 'b> Parser<'a, 'b> {
                     );
                 }
 
+                if let Some(token) = self.next_if(Token::Await) {
+                    return Expr::new(
+                        left.span.extended_to(token.span),
+                        ExprData::Await(left.into()),
+                    );
+                }
+
                 let member = self.expect_ident("expected member name");
                 let generics = if self.next_if(Token::ScopeRes).is_some() {
                     self.expect(Token::LAngle);
@@ -947,6 +1035,13 @@ impl<'a, 'b> Parser<'a, 'b> {
                 }
                 (false, self.match_expr(token.span))
             }
+            Token::Try => {
+                let token = self.next();
+                if let Some(label) = label {
+                    self.label_error(label)
+                }
+                (false, self.try_expr(token.span))
+            }
             Token::LCurly => {
                 let token = self.next();
                 (false, self.block_expr(token.span, label.map(|l| l.data)))
@@ -1075,6 +1170,24 @@ impl<'a, 'b> Parser<'a, 'b> {
         )
     }
 
+    fn try_expr(&mut self, token: Span) -> Expr {
+        let body = self.block().data;
+        self.expect(Token::Catch);
+        let catch = self.pattern_ex(false, EvalContext::IfWhile);
+        let Located {
+            span,
+            data: handler,
+        } = self.block();
+        Expr::new(
+            token.extended_to(span),
+            ExprData::Try {
+                body,
+                catch,
+                handler,
+            },
+        )
+    }
+
     fn block_expr(&mut self, token: Span, label: Option<String>) -> Expr {
         let mut stmts = Vec::new();
         let span = self.next_until(Token::RCurly, token, |this| {
@@ -1102,12 +1215,15 @@ impl<'a, 'b> Parser<'a, 'b> {
         };
 
         let ret = self.next_if(Token::Colon).map(|_| self.type_hint());
+        // a yield inside of a lambda doesn't make the enclosing function a generator
+        let yields = std::mem::take(&mut self.yields);
         let body = if ret.is_none() {
             self.expression()
         } else {
             let token = self.expect(Token::LCurly);
             self.block_expr(token.span, None)
         };
+        self.yields = yields;
 
         Expr::new(
             head.span.extended_to(body.span),
@@ -1130,7 +1246,7 @@ impl<'a, 'b> Parser<'a, 'b> {
         let mut data = first.map(|s| vec![(s, Vec::new())]).unwrap_or_default();
         while self.next_if(Token::ScopeRes).is_some() {
             if self.next_if(Token::LAngle).is_some() {
-                let params = self.rangle_csv_one(*outspan, Self::type_hint);
+                let params = self.rangle_csv_one(*outspan, Self::type_arg);
                 data.last_mut().unwrap().1 = params.data;
                 *outspan = params.span;
             } else {
@@ -1160,7 +1276,7 @@ impl<'a, 'b> Parser<'a, 'b> {
         loop {
             let ident = self.expect_ident("expected type name");
             if self.next_if(Token::LAngle).is_some() {
-                let params = self.rangle_csv_one(ident.span, Self::type_hint);
+                let params = self.rangle_csv_one(ident.span, Self::type_arg);
                 data.push((ident, params.data));
             } else {
                 data.push((ident, Vec::new()));
@@ -1308,10 +1424,11 @@ impl<'a, 'b> Parser<'a, 'b> {
         self.csv_one(Token::RCurly, span, |this| {
             let mutable = this.next_if(Token::Mut);
             if let Some(token) = mutable.clone().filter(|_| mut_var) {
-                this.diag.warn(Error::new(
-                    format!("redundant '{}'", Token::Mut),
-                    token.span,
-                ));
+                this.diag.lint(
+                    Lint::RedundantMut,
+                    None,
+                    Error::new(format!("redundant '{}'", Token::Mut), token.span),
+                );
             }
             let mutable = mutable.is_some();
             let name = this.expect_ident("expected name");
@@ -1418,7 +1535,7 @@ impl<'a, 'b> Parser<'a, 'b> {
             let mut span = patt.span;
             let mut patterns = vec![patt];
             while self.next_if(Token::BitOr).is_some() {
-                let patt = self.pattern_ex(mut_var, ctx);
+                let patt = self.pattern_impl(mut_var, ctx);
                 span.extend_to(patt.span);
                 patterns.push(patt);
             }
@@ -1443,7 +1560,10 @@ impl<'a, 'b> Parser<'a, 'b> {
 
     fn attribute(&mut self) -> Attribute {
         Attribute {
-            name: self.expect_ident("expected name"),
+            name: match self.next_if(Token::Const) {
+                Some(token) => Located::new(token.span, "const".into()),
+                None => self.expect_ident("expected name"),
+            },
             props: self
                 .next_if(Token::LParen)
                 .map(|tk| self.csv_one(Token::RParen, tk.span, Self::attribute).data)
@@ -1466,7 +1586,21 @@ impl<'a, 'b> Parser<'a, 'b> {
         self.next_if(Token::LAngle)
             .map(|tk| {
                 self.rangle_csv_one(tk.span, |this| {
-                    (this.expect_ident("expected type name"), this.trait_impls())
+                    if this.next_if(Token::Const).is_some() {
+                        let name = this.expect_ident("expected name");
+                        this.expect(Token::Colon);
+                        TypeParam {
+                            name,
+                            impls: Vec::new(),
+                            const_ty: Some(this.type_hint()),
+                        }
+                    } else {
+                        TypeParam {
+                            name: this.expect_ident("expected type name"),
+                            impls: this.trait_impls(),
+                            const_ty: None,
+                        }
+                    }
                 })
                 .data
             })
@@ -1486,6 +1620,37 @@ impl<'a, 'b> Parser<'a, 'b> {
         impls
     }
 
+    fn fn_type_hint(&mut self) -> (Vec<TypeHint>, TypeHint) {
+        let left = self.expect(Token::LParen);
+        let params = self
+            .csv(Vec::new(), Token::RParen, left.span, Self::type_hint)
+            .data;
+        let ret = if self.next_if(Token::FatArrow).is_some() {
+            self.type_hint()
+        } else {
+            TypeHint::Void
+        };
+        (params, ret)
+    }
+
+    fn type_arg(&mut self) -> TypeHint {
+        if matches!(self.peek().data, Token::Int { .. }) {
+            return TypeHint::Const(self.prefix(EvalContext::Normal).into());
+        }
+
+        let ty = self.type_hint();
+        let TypeHint::Regular(path) = &ty else {
+            return ty;
+        };
+        match path.as_identifier() {
+            Some(name) if self.next_if(Token::Assign).is_some() => {
+                let name = Located::new(path.span(), name.into());
+                TypeHint::AssocType(name, self.type_hint().into())
+            }
+            _ => ty,
+        }
+    }
+
     fn type_hint(&mut self) -> TypeHint {
         match self.peek().data {
             Token::Asterisk => {
@@ -1555,25 +1720,34 @@ impl<'a, 'b> Parser<'a, 'b> {
                 self.next();
                 TypeHint::Void
             }
-            Token::ThisType => TypeHint::This(self.next().span),
+            Token::ThisType => {
+                let span = self.next().span;
+                if self.next_if(Token::ScopeRes).is_none() {
+                    return TypeHint::This(span);
+                }
+
+                let mut path = self.type_path();
+                path.origin = PathOrigin::This(span);
+                TypeHint::Regular(path)
+            }
             Token::Fn => {
                 self.next();
-                let left = self.expect(Token::LParen);
-                let params = self
-                    .csv(Vec::new(), Token::RParen, left.span, Self::type_hint)
-                    .data;
-                let ret = if self.next_if(Token::FatArrow).is_some() {
-                    self.type_hint()
-                } else {
-                    TypeHint::Void
-                };
-
+                let (params, ret) = self.fn_type_hint();
                 TypeHint::Fn {
                     is_extern: false,
                     params,
                     ret: ret.into(),
                 }
             }
+            Token::Dyn => {
+                self.next();
+                self.expect(Token::Fn);
+                let (params, ret) = self.fn_type_hint();
+                TypeHint::DynFn {
+                    params,
+                    ret: ret.into(),
+                }
+            }
             Token::Struct => {
                 let span = self.next().span;
                 self.expect(Token::LCurly);
@@ -1614,10 +1788,17 @@ impl<'a, 'b> Parser<'a, 'b> {
         let mut functions = Vec::new();
         let mut operators = Vec::new();
         let mut members = Vec::new();
+        let mut consts = Vec::new();
         let mut impls = Vec::new();
         self.next_until(Token::RCurly, span, |this| {
             let attrs = this.attributes();
             let public = this.next_if(Token::Pub);
+            let is_const = this.next_if(Token::Const).is_some();
+            if is_const && !this.matches(Token::Fn) && !this.matches(Token::Unsafe) {
+                consts.push(this.assoc_const(public.is_some(), true));
+                return;
+            }
+
             if let Some(token) = public.as_ref().filter(|_| union) {
                 this.error_no_sync(Error::not_valid_here(token));
             }
@@ -1626,6 +1807,7 @@ impl<'a, 'b> Parser<'a, 'b> {
                 is_public: public.is_some(),
                 is_extern: false,
                 is_unsafe: this.next_if(Token::Unsafe).is_some(),
+                is_const,
                 require_body: true,
             };
             if config.is_unsafe {
@@ -1669,6 +1851,7 @@ impl<'a, 'b> Parser<'a, 'b> {
             name,
             type_params,
             members,
+            consts,
             impls,
             functions,
             operators,
@@ -1682,19 +1865,28 @@ impl<'a, 'b> Parser<'a, 'b> {
         let mut functions = Vec::new();
         let mut operators = Vec::new();
         let mut members = Vec::new();
+        let mut consts = Vec::new();
         let mut impls = Vec::new();
         let mut variants = Vec::new();
 
         self.expect(Token::LCurly);
         self.next_until(Token::RCurly, span, |this| {
             let attrs = this.attributes();
+            let is_public = this.next_if(Token::Pub).is_some();
+            let is_const = this.next_if(Token::Const).is_some();
+            if is_const && !this.matches(Token::Fn) && !this.matches(Token::Unsafe) {
+                consts.push(this.assoc_const(is_public, true));
+                return;
+            }
+
             let config = FnConfig {
                 is_extern: false,
-                is_public: this.next_if(Token::Pub).is_some(),
+                is_public,
                 is_unsafe: this.next_if(Token::Unsafe).is_some(),
+                is_const,
                 require_body: true,
             };
-            if config.is_public || config.is_unsafe {
+            if config.is_public || config.is_unsafe || config.is_const {
                 match this.expect_fn(config, attrs) {
                     Ok(Left(func)) => functions.push(func),
                     Ok(Right(func)) => operators.push(func),
@@ -1776,6 +1968,7 @@ impl<'a, 'b> Parser<'a, 'b> {
                 name,
                 type_params,
                 members,
+                consts,
                 functions,
                 impls,
                 operators,
@@ -1790,13 +1983,31 @@ impl<'a, 'b> Parser<'a, 'b> {
         self.expect(Token::LCurly);
 
         let mut functions = Vec::new();
+        let mut assoc_types = Vec::new();
+        let mut consts = Vec::new();
         self.next_until(Token::RCurly, span, |this| {
+            if this.next_if(Token::Type).is_some() {
+                assoc_types.push(TypeParam {
+                    name: this.expect_ident("expected name"),
+                    impls: this.trait_impls(),
+                    const_ty: None,
+                });
+                this.expect(Token::Semicolon);
+                return;
+            }
+
+            if this.next_if(Token::Const).is_some() {
+                consts.push(this.assoc_const(true, false));
+                return;
+            }
+
             let is_unsafe = this.next_if(Token::Unsafe).is_some();
             let config = FnConfig {
                 require_body: false,
                 is_extern: false,
                 is_public: true,
                 is_unsafe,
+                is_const: false,
             };
             match this.expect_fn(config, Default::default()) {
                 Ok(Left(func)) => functions.push(func),
@@ -1816,6 +2027,8 @@ impl<'a, 'b> Parser<'a, 'b> {
             is_unsafe,
             name,
             type_params,
+            assoc_types,
+            consts,
             impls,
             functions,
         }
@@ -1841,6 +2054,7 @@ impl<'a, 'b> Parser<'a, 'b> {
                     require_body: true,
                     is_extern: false,
                     is_public: this.next_if(Token::Pub).is_some(),
+                    is_const: this.next_if(Token::Const).is_some(),
                     is_unsafe: this.next_if(Token::Unsafe).is_some(),
                 };
                 match this.expect_fn(config, attrs) {
@@ -1868,18 +2082,35 @@ impl<'a, 'b> Parser<'a, 'b> {
         self.expect(Token::LCurly);
 
         let mut functions = Vec::new();
+        let mut assoc_types = Vec::new();
+        let mut consts = Vec::new();
         self.next_until(Token::RCurly, span, |this| {
+            if this.next_if(Token::Type).is_some() {
+                let name = this.expect_ident("expected name");
+                this.expect(Token::Assign);
+                assoc_types.push((name, this.type_hint()));
+                this.expect(Token::Semicolon);
+                return;
+            }
+
             let attrs = this.attributes();
             if let Some(token) = this.next_if(Token::Pub) {
                 this.error_no_sync(Error::not_valid_here(&token));
             }
 
+            let is_const = this.next_if(Token::Const).is_some();
+            if is_const && !this.matches(Token::Fn) && !this.matches(Token::Unsafe) {
+                consts.push(this.assoc_const(true, true));
+                return;
+            }
+
             let is_unsafe = this.next_if(Token::Unsafe).is_some();
             let config = FnConfig {
                 is_public: true,
                 is_extern: false,
                 require_body: true,
                 is_unsafe,
+                is_const,
             };
             match this.expect_fn(config, attrs) {
                 Ok(Left(func)) => functions.push(func),
@@ -1896,16 +2127,36 @@ impl<'a, 'b> Parser<'a, 'b> {
         ImplBlock {
             type_params,
             path,
+            assoc_types,
+            consts,
             functions,
         }
     }
 
+    fn assoc_const(&mut self, public: bool, has_value: bool) -> Const {
+        let name = self.expect_ident("expected name");
+        self.expect(Token::Colon);
+        let ty = self.type_hint();
+        let value = has_value.then(|| {
+            self.expect(Token::Assign);
+            self.expression()
+        });
+        self.expect(Token::Semicolon);
+        Const {
+            public,
+            name,
+            ty,
+            value,
+        }
+    }
+
     fn try_function(
         &mut self,
         FnConfig {
             is_public,
             is_unsafe,
             is_extern,
+            is_const,
             require_body,
         }: FnConfig,
         attrs: Attributes,
@@ -2013,6 +2264,7 @@ impl<'a, 'b> Parser<'a, 'b> {
             TypeHint::Void
         };
 
+        let yields = std::mem::take(&mut self.yields);
         let body = if let Some(semi) = self.next_if(Token::Semicolon) {
             if require_body {
                 self.error(Error::new("expected '{'", semi.span));
@@ -2022,6 +2274,7 @@ impl<'a, 'b> Parser<'a, 'b> {
             let lcurly = self.expect(Token::LCurly);
             Some(self.block_expr(lcurly.span, None))
         };
+        let is_generator = std::mem::replace(&mut self.yields, yields);
 
         match name.data {
             Left(ident) => Ok(Left(Fn {
@@ -2029,7 +2282,9 @@ impl<'a, 'b> Parser<'a, 'b> {
                 public: is_public,
                 is_extern,
                 is_async,
+                is_generator,
                 is_unsafe,
+                is_const,
                 variadic,
                 type_params,
                 params,
@@ -2042,6 +2297,12 @@ impl<'a, 'b> Parser<'a, 'b> {
                 if is_async {
                     self.error(Error::not_valid_here(&head_token));
                 }
+                if is_generator {
+                    self.error(Error::new(
+                        "operator functions cannot be generators",
+                        name.span,
+                    ));
+                }
                 Ok(Right(OperatorFn {
                     name: Located::new(name.span, op),
                     type_params,
@@ -2210,6 +2471,88 @@ impl<'a, 'b> Parser<'a, 'b> {
         self.needs_sync = false;
     }
 
+    fn interpolated_expr(&mut self) -> (Expr, Option<Located<FormatSpec>>) {
+        let expr = self.expression();
+        if self.next_if(Token::Colon).is_none() {
+            return (expr, None);
+        }
+
+        let raw = self.lexer.format_spec();
+        let Some(spec) = Self::parse_format_spec(raw.data) else {
+            self.error_no_sync(Error::new(
+                format!("invalid format specifier '{}'", raw.data),
+                raw.span,
+            ));
+            return (expr, None);
+        };
+        (expr, Some(Located::new(raw.span, spec)))
+    }
+
+    fn parse_format_spec(mut spec: &str) -> Option<FormatSpec> {
+        fn align(ch: char) -> Option<FormatAlign> {
+            match ch {
+                '<' => Some(FormatAlign::Left),
+                '>' => Some(FormatAlign::Right),
+                '^' => Some(FormatAlign::Center),
+                _ => None,
+            }
+        }
+
+        fn number(spec: &mut &str) -> Option<usize> {
+            let len = spec
+                .find(|ch: char| !ch.is_ascii_digit())
+                .unwrap_or(spec.len());
+            let (digits, rest) = spec.split_at(len);
+            *spec = rest;
+            digits.parse().ok()
+        }
+
+        let mut result = FormatSpec::default();
+        let mut chars = spec.chars();
+        match (chars.next(), chars.next().and_then(align)) {
+            (Some(fill), Some(a)) => {
+                result.fill = Some(fill);
+                result.align = Some(a);
+                spec = chars.as_str();
+            }
+            (Some(ch), _) if align(ch).is_some() => {
+                result.align = align(ch);
+                spec = &spec[1..];
+            }
+            _ => {}
+        }
+
+        if let Some(rest) = spec.strip_prefix('+') {
+            result.sign = true;
+            spec = rest;
+        }
+        if let Some(rest) = spec.strip_prefix('#') {
+            result.alternate = true;
+            spec = rest;
+        }
+        if let Some(rest) = spec.strip_prefix('0') {
+            result.zero = true;
+            spec = rest;
+        }
+        if spec.starts_with(|ch: char| ch.is_ascii_digit()) {
+            result.width = Some(number(&mut spec)?);
+        }
+        if let Some(rest) = spec.strip_prefix('.') {
+            spec = rest;
+            result.precision = Some(number(&mut spec)?);
+        }
+        if let Some(ty) = spec
+            .chars()
+            .next()
+            .filter(|ch| matches!(ch, 'x' | 'X' | 'o' | 'b'))
+        {
+            result.ty = Some(ty);
+            spec = &spec[1..];
+        }
+
+        spec.is_empty().then_some(result)
+    }
+
     fn error(&mut self, err: Error) {
         if !self.needs_sync {
             self.diag.error(err);
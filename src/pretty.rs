@@ -1,6 +1,6 @@
 use crate::ast::parsed::{
-    Expr, ExprData, Fn, ImplBlock, IntPattern, OperatorFn, Stmt, StmtData, Struct, UsePath,
-    UsePathTail,
+    Const, Expr, ExprData, Fn, ImplBlock, IntPattern, OperatorFn, Stmt, StmtData, Struct,
+    TypeParam, UsePath, UsePathTail,
 };
 
 const INDENT: &str = "  ";
@@ -70,6 +70,8 @@ pub fn print_stmt(stmt: &Stmt, indent: usize) {
             public,
             name,
             type_params,
+            assoc_types,
+            consts,
             impls,
             functions,
             is_unsafe,
@@ -84,11 +86,20 @@ pub fn print_stmt(stmt: &Stmt, indent: usize) {
             let plus_1 = INDENT.repeat(indent + 1);
             if !type_params.is_empty() {
                 eprintln!("{tabs}Type Params:");
-                for (name, path) in type_params {
-                    eprintln!("{plus_1}{name}: {path:?}");
+                for param in type_params {
+                    print_type_param(&plus_1, param);
                 }
             }
 
+            if !assoc_types.is_empty() {
+                eprintln!("{tabs}Associated Types:");
+                for param in assoc_types {
+                    print_type_param(&plus_1, param);
+                }
+            }
+
+            print_consts(indent, consts);
+
             if !impls.is_empty() {
                 eprintln!("{tabs}Impls: ");
                 for i in impls {
@@ -117,8 +128,8 @@ pub fn print_stmt(stmt: &Stmt, indent: usize) {
             let plus_1 = INDENT.repeat(indent + 1);
             if !type_params.is_empty() {
                 eprintln!("{tabs}Type Params:");
-                for (name, path) in type_params {
-                    eprintln!("{plus_1}{name}: {path:?}");
+                for param in type_params {
+                    print_type_param(&plus_1, param);
                 }
             }
 
@@ -132,6 +143,24 @@ pub fn print_stmt(stmt: &Stmt, indent: usize) {
                 print_op_fn(f, indent + 1);
             }
         }
+        StmtData::Alias {
+            public,
+            name,
+            type_params,
+            ty,
+        } => {
+            eprint!("{tabs}Alias[{name}, for={ty:?}]");
+            print_bool!(public);
+            eprintln!();
+
+            let plus_1 = INDENT.repeat(indent + 1);
+            if !type_params.is_empty() {
+                eprintln!("{tabs}Type Params:");
+                for param in type_params {
+                    print_type_param(&plus_1, param);
+                }
+            }
+        }
         StmtData::Binding {
             name,
             ty,
@@ -355,6 +384,30 @@ pub fn print_expr(expr: &Expr, indent: usize) {
             }
             print_expr(source, indent + 1);
         }
+        ExprData::Await(expr) => {
+            eprintln!("{tabs}Await");
+            print_expr(expr, indent + 1);
+        }
+        ExprData::Yield(expr) => {
+            eprintln!("{tabs}Yield");
+            print_expr(expr, indent + 1);
+        }
+        ExprData::Raise(expr) => {
+            eprintln!("{tabs}Raise");
+            print_expr(expr, indent + 1);
+        }
+        ExprData::Try {
+            body,
+            catch,
+            handler,
+        } => {
+            eprintln!("{tabs}Try");
+            let tabs = INDENT.repeat(indent + 1);
+            eprintln!("{tabs}Body: ");
+            print_stmts(body, indent + 2);
+            eprintln!("{tabs}Catch[{catch:?}]: ");
+            print_stmts(handler, indent + 2);
+        }
         ExprData::Return(expr) => {
             eprintln!("{tabs}Return");
             print_expr(expr, indent + 1);
@@ -438,7 +491,7 @@ pub fn print_expr(expr: &Expr, indent: usize) {
         }
         ExprData::StringInterpolation(parts) => {
             eprintln!("{tabs}StringInterpolation");
-            for part in parts {
+            for (part, _) in parts {
                 print_expr(part, indent + 1);
             }
         }
@@ -456,7 +509,9 @@ fn print_fn(
         name,
         is_extern,
         is_async,
+        is_generator,
         is_unsafe,
+        is_const,
         type_params,
         variadic,
         params,
@@ -471,7 +526,9 @@ fn print_fn(
     let tabs = INDENT.repeat(indent);
     eprint!("{tabs}Fn[{name}]");
     print_bool!(is_async);
+    print_bool!(is_generator);
     print_bool!(is_unsafe);
+    print_bool!(is_const);
     print_bool!(is_extern);
     print_bool!(variadic);
     print_bool!(public);
@@ -481,8 +538,8 @@ fn print_fn(
     let plus_2 = INDENT.repeat(indent + 2);
     if !type_params.is_empty() {
         eprintln!("{plus_1}Type Params:");
-        for (name, path) in type_params {
-            eprintln!("{plus_2}{name}: {path:?}");
+        for param in type_params {
+            print_type_param(&plus_2, param);
         }
     }
     if !params.is_empty() {
@@ -517,8 +574,8 @@ fn print_op_fn(
     let plus_2 = INDENT.repeat(indent + 2);
     if !type_params.is_empty() {
         eprintln!("{plus_1}Type Params:");
-        for (name, path) in type_params {
-            eprintln!("{plus_2}{name}: {path:?}");
+        for param in type_params {
+            print_type_param(&plus_2, param);
         }
     }
     if !params.is_empty() {
@@ -541,6 +598,7 @@ fn print_struct(
         name,
         type_params,
         members,
+        consts,
         impls,
         functions,
         public,
@@ -558,8 +616,8 @@ fn print_struct(
     let plus_1 = INDENT.repeat(indent + 1);
     if !type_params.is_empty() {
         eprintln!("{tabs}Type Params:");
-        for (name, impls) in type_params {
-            eprintln!("{plus_1}{name}: {impls:?}");
+        for param in type_params {
+            print_type_param(&plus_1, param);
         }
     }
 
@@ -570,6 +628,8 @@ fn print_struct(
         }
     }
 
+    print_consts(indent, consts);
+
     eprintln!("{tabs}Functions:");
     for f in functions {
         print_fn(f, indent + 1);
@@ -579,6 +639,36 @@ fn print_struct(
     }
 }
 
+fn print_type_param(tabs: &str, param: &TypeParam) {
+    if let Some(ty) = &param.const_ty {
+        eprintln!("{tabs}const {}: {ty:?}", param.name);
+    } else {
+        eprintln!("{tabs}{}: {:?}", param.name, param.impls);
+    }
+}
+
+fn print_consts(indent: usize, consts: &[Const]) {
+    let tabs = INDENT.repeat(indent);
+    if !consts.is_empty() {
+        eprintln!("{tabs}Consts:");
+        let plus_1 = INDENT.repeat(indent + 1);
+        for Const {
+            public,
+            name,
+            ty,
+            value,
+        } in consts
+        {
+            eprint!("{plus_1}Const[{name}]");
+            print_bool!(public);
+            eprintln!(": {ty:?}");
+            if let Some(value) = value {
+                print_expr(value, indent + 2);
+            }
+        }
+    }
+}
+
 fn print_impls(indent: usize, impls: &[ImplBlock]) {
     let tabs = INDENT.repeat(indent);
     let plus_1 = INDENT.repeat(indent + 1);
@@ -587,12 +677,16 @@ fn print_impls(indent: usize, impls: &[ImplBlock]) {
         for imp in impls {
             if !imp.type_params.is_empty() {
                 eprintln!("{tabs}Type Params:");
-                for (name, impls) in imp.type_params.iter() {
-                    eprintln!("{plus_1}{name}: {impls:?}");
+                for param in imp.type_params.iter() {
+                    print_type_param(&plus_1, param);
                 }
             }
 
             eprintln!("{plus_1}{:?}", imp.path);
+            for (name, ty) in imp.assoc_types.iter() {
+                eprintln!("{plus_1}type {name} = {ty:?}");
+            }
+            print_consts(indent + 1, &imp.consts);
             for f in imp.functions.iter() {
                 print_fn(f, indent + 2)
             }
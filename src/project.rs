@@ -28,6 +28,7 @@ pub struct Project {
     pub completions: Option<Completions>,
     pub tokens: Vec<SpanSemanticToken>,
     pub main: Option<FunctionId>,
+    pub tests: Vec<FunctionId>,
     pub deps: DependencyGraph<TypeId>,
     pub static_deps: DependencyGraph<VariableId>,
 }
@@ -11,9 +11,13 @@ use crate::{
     },
     comptime_int::ComptimeInt,
     lexer::{Located, Span},
-    typeid::{GenericTrait, GenericUserType, Type, TypeId, Types},
+    lint::{Lint, LintLevel},
+    typeid::{FnPtr, GenericTrait, GenericUserType, Type, TypeId, Types},
 };
 
+/// Lang names of the traits implemented by the compiler for callable types
+pub const FN_TRAITS: [&str; 3] = ["fn_call", "fn_call_mut", "fn_call_once"];
+
 macro_rules! id {
     ($name: ident => $output: ident,
      $vec: ident,
@@ -48,7 +52,7 @@ macro_rules! id {
                     .insert(name, Vis { id: kind, public });
                 InsertionResult {
                     id,
-                    existed: prev.is_some(),
+                    prev: prev.map(|prev| prev.id.into()),
                     item: kind.into(),
                 }
             }
@@ -79,11 +83,29 @@ pub enum InsertedItem {
     ValueLike(ValueItem),
 }
 
+impl InsertedItem {
+    pub fn span(&self, scopes: &Scopes) -> Span {
+        match *self {
+            InsertedItem::TypeLike(TypeItem::Type(id)) => scopes.get(id).name.span,
+            InsertedItem::TypeLike(TypeItem::Module(id)) => scopes[id]
+                .kind
+                .name(scopes)
+                .map(|name| name.span)
+                .unwrap_or_default(),
+            InsertedItem::ValueLike(ValueItem::Fn(id)) => scopes.get(id).name.span,
+            InsertedItem::ValueLike(ValueItem::Var(id)) => scopes.get(id).name.span,
+            InsertedItem::ValueLike(
+                ValueItem::StructConstructor(id, _) | ValueItem::UnionConstructor(id),
+            ) => scopes.get(id).name.span,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, From)]
 pub struct InsertionResult<T> {
     pub id: T,
     pub item: InsertedItem,
-    pub existed: bool,
+    pub prev: Option<InsertedItem>,
 }
 
 id!(FunctionId => Function, fns, vns);
@@ -114,13 +136,25 @@ pub struct BlockScopeKind {
     pub yields: bool,
     pub label: Option<String>,
     pub branches: bool,
+    /// The body of a `try` block. Errors raised inside are yielded from this block, and `target`
+    /// is the type of the error.
+    pub catches: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct LambdaScopeKind {
+    pub target: Option<TypeId>,
+    pub returns: bool,
+    pub captures: Vec<VariableId>,
+    pub moves: bool,
+    pub mutates: bool,
 }
 
 #[derive(Default, Debug, Clone, EnumAsInner)]
 pub enum ScopeKind {
     Block(BlockScopeKind),
     Loop(LoopScopeKind),
-    Lambda(Option<TypeId>, bool),
+    Lambda(LambdaScopeKind),
     Function(FunctionId),
     UserType(UserTypeId),
     Impl(usize),
@@ -192,6 +226,7 @@ pub struct Variable {
     pub name: Located<String>,
     pub ty: TypeId,
     pub is_static: bool,
+    pub is_const: bool,
     pub mutable: bool,
     pub value: Option<CheckedExpr>,
     pub unused: bool,
@@ -205,7 +240,10 @@ pub struct Function {
     pub name: Located<String>,
     pub is_extern: bool,
     pub is_async: bool,
+    pub is_generator: bool,
     pub is_unsafe: bool,
+    /// Can this function be run by the compile-time interpreter
+    pub is_const: bool,
     pub variadic: bool,
     /// Is this a trait function with a body
     pub has_body: bool,
@@ -264,6 +302,26 @@ pub struct PackedStruct {
     pub align: usize,
 }
 
+#[derive(Debug, Clone)]
+pub struct Lambda {
+    pub params: Vec<TypeId>,
+    pub ret: TypeId,
+    /// Does the body modify any state captured by value
+    pub mutates: bool,
+}
+
+/// The state of a suspended `async fn` or generator function.
+#[derive(Debug, Clone)]
+pub struct FnFrame {
+    pub func: FunctionId,
+    /// The declared return type of the function. This is the value a future produces when it
+    /// completes, or the type of each value yielded by a generator.
+    pub output: TypeId,
+    /// The hidden variable holding the `Context` an `async fn` is being polled with, which is
+    /// passed on to the futures it awaits.
+    pub context: Option<VariableId>,
+}
+
 #[derive(Debug, EnumAsInner)]
 pub enum UserTypeKind {
     Struct,
@@ -275,6 +333,28 @@ pub enum UserTypeKind {
     Tuple,
     Trait(UserTypeId, bool),
     Extension(TypeId),
+    /// A type alias. Its type parameters are substituted into the aliased type wherever it is
+    /// named, so it never appears in a `Type::User`.
+    Alias(TypeId),
+    Lambda(Lambda),
+    AsyncFn(FnFrame),
+    Generator(FnFrame),
+}
+
+impl UserTypeKind {
+    pub fn as_frame(&self) -> Option<&FnFrame> {
+        match self {
+            UserTypeKind::AsyncFn(f) | UserTypeKind::Generator(f) => Some(f),
+            _ => None,
+        }
+    }
+
+    pub fn as_frame_mut(&mut self) -> Option<&mut FnFrame> {
+        match self {
+            UserTypeKind::AsyncFn(f) | UserTypeKind::Generator(f) => Some(f),
+            _ => None,
+        }
+    }
 }
 
 pub struct ImplBlockData {
@@ -301,6 +381,11 @@ pub struct UserType {
     pub members: IndexMap<String, CheckedMember>,
     pub members_resolved: bool,
     pub recursive: bool,
+    /// A type parameter that isn't passed positionally: an associated type of a trait, or one
+    /// standing in for the associated type of another type parameter's bound
+    pub implicit: bool,
+    /// For a const generic parameter, the type of its value
+    pub const_ty: Option<TypeId>,
 }
 
 impl UserType {
@@ -335,6 +420,8 @@ impl UserType {
             subscripts: Vec::new(),
             members_resolved: true,
             recursive: false,
+            implicit: false,
+            const_ty: None,
         }
     }
 }
@@ -403,11 +490,15 @@ pub enum ValueItem {
 #[derive(Default)]
 pub struct Scope {
     pub public: bool,
+    /// The attributes of the module this scope belongs to, if it is a module scope
+    pub attrs: Attributes,
     pub kind: ScopeKind,
     pub parent: Option<ScopeId>,
     pub tns: HashMap<String, Vis<TypeItem>>,
     pub vns: HashMap<String, Vis<ValueItem>>,
     pub use_stmts: Vec<UsePath>,
+    /// Names imported by private `use` statements that haven't been referred to yet
+    pub unused_imports: HashMap<String, Span>,
 }
 
 impl Scope {
@@ -464,7 +555,7 @@ impl Scopes {
         id
     }
 
-    pub fn walk(&self, id: ScopeId) -> ScopeIter {
+    pub fn walk(&self, id: ScopeId) -> ScopeIter<'_> {
         ScopeIter {
             scopes: self,
             next: Some(id),
@@ -489,6 +580,16 @@ impl Scopes {
             .find_map(|(_, scope)| scope.kind.as_function().copied())
     }
 
+    /// Returns the level set for `lint` by attributes on the items and modules enclosing `id`.
+    pub fn lint_level(&self, id: ScopeId, lint: Lint) -> Option<LintLevel> {
+        self.walk(id).find_map(|(_, scope)| match scope.kind {
+            ScopeKind::Function(f) => self.get(f).attrs.lint_level(lint),
+            ScopeKind::UserType(ut) => self.get(ut).attrs.lint_level(lint),
+            ScopeKind::Module(_) => scope.attrs.lint_level(lint),
+            _ => None,
+        })
+    }
+
     pub fn module_of(&self, id: ScopeId) -> Option<ScopeId> {
         self.walk(id)
             .find(|(_, current)| current.kind.is_module())
@@ -502,6 +603,13 @@ impl Scopes {
             .map(|(i, var)| (VariableId(i), var))
     }
 
+    pub fn scopes(&self) -> impl Iterator<Item = (ScopeId, &Scope)> {
+        self.scopes
+            .iter()
+            .enumerate()
+            .map(|(i, scope)| (ScopeId(i), scope))
+    }
+
     pub fn functions(&self) -> impl Iterator<Item = (FunctionId, &Scoped<Function>)> {
         self.fns
             .iter()
@@ -547,6 +655,8 @@ impl Scopes {
                             subscripts: Vec::new(),
                             members_resolved: true,
                             recursive: false,
+                            implicit: false,
+                            const_ty: None,
                         },
                         false,
                         ScopeId::ROOT,
@@ -580,6 +690,8 @@ impl Scopes {
                     subscripts: Vec::new(),
                     members_resolved: true,
                     recursive: false,
+                    implicit: false,
+                    const_ty: None,
                 },
                 false,
                 ScopeId::ROOT,
@@ -620,6 +732,8 @@ impl Scopes {
                             subscripts: Vec::new(),
                             members_resolved: true,
                             recursive: false,
+                            implicit: false,
+                            const_ty: None,
                         },
                         false,
                         ScopeId::ROOT,
@@ -653,6 +767,8 @@ impl Scopes {
                     subscripts: Vec::new(),
                     members_resolved: true,
                     recursive: false,
+                    implicit: false,
+                    const_ty: None,
                 },
                 false,
                 ScopeId::ROOT,
@@ -705,12 +821,93 @@ impl Scopes {
         result
     }
 
-    pub fn has_builtin_impl(&self, types: &Types, id: TypeId, bound: &GenericTrait) -> bool {
+    /// Returns the signature of a function pointer, `dyn fn`, or lambda type.
+    pub fn fn_signature(&self, types: &mut Types, id: TypeId) -> Option<FnPtr> {
+        match &types[id] {
+            Type::FnPtr(f) | Type::DynFn(f) => Some(f.clone()),
+            Type::User(ut) => {
+                let lambda = self.get(ut.id).kind.as_lambda()?;
+                let ty_args = ut.ty_args.clone();
+                Some(FnPtr {
+                    params: lambda
+                        .params
+                        .iter()
+                        .map(|p| p.with_templates(types, &ty_args))
+                        .collect(),
+                    ret: lambda.ret.with_templates(types, &ty_args),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the type produced by the future returned from an `async fn`.
+    pub fn future_output(&self, types: &mut Types, id: TypeId) -> Option<TypeId> {
+        let ut = types[id].as_user()?;
+        let output = self.get(ut.id).kind.as_async_fn()?.output;
+        let ty_args = ut.ty_args.clone();
+        Some(output.with_templates(types, &ty_args))
+    }
+
+    /// Returns the type of the values yielded by the iterator returned from a generator function.
+    pub fn generator_item(&self, types: &mut Types, id: TypeId) -> Option<TypeId> {
+        let ut = types[id].as_user()?;
+        let output = self.get(ut.id).kind.as_generator()?.output;
+        let ty_args = ut.ty_args.clone();
+        Some(output.with_templates(types, &ty_args))
+    }
+
+    /// Returns the trait implemented by the compiler for the frame of an `async fn` (`Future`) or
+    /// generator (`Iterator`).
+    pub fn frame_trait(&self, types: &mut Types, id: TypeId) -> Option<GenericTrait> {
+        let (name, output) = if let Some(output) = self.future_output(types, id) {
+            ("future", output)
+        } else {
+            ("iter", self.generator_item(types, id)?)
+        };
+        let tr_id = *self.lang_traits.get(name)?;
+        Some(GenericTrait::from_type_args(self, tr_id, [output]))
+    }
+
+    pub fn has_builtin_impl(&self, types: &mut Types, id: TypeId, bound: &GenericTrait) -> bool {
+        if let Some(tr) = self.frame_trait(types, id) {
+            if tr.id == bound.id {
+                return &tr == bound;
+            }
+        }
+
         let ty = &types[id];
         if ty.is_numeric() && Some(&bound.id) == self.lang_traits.get("numeric") {
             return true;
         }
 
+        if let Some(&name) = FN_TRAITS
+            .iter()
+            .find(|&&name| self.lang_traits.get(name) == Some(&bound.id))
+        {
+            let mutates = ty
+                .as_user()
+                .and_then(|ut| self.get(ut.id).kind.as_lambda())
+                .is_some_and(|lambda| lambda.mutates);
+            if mutates && name == "fn_call" {
+                return false;
+            }
+
+            let Some(f) = self.fn_signature(types, id) else {
+                return false;
+            };
+            let mut ty_args = bound.ty_args.values();
+            let args_match = match ty_args.next().map(|&args| &types[args]) {
+                Some(Type::Void) => f.params.is_empty(),
+                Some(Type::User(ut)) => {
+                    self.get(ut.id).kind.is_tuple() && ut.ty_args.values().eq(f.params.iter())
+                }
+                _ => false,
+            };
+            return args_match && ty_args.next() == Some(&f.ret);
+        }
+
+        let ty = &types[id];
         if let Some(int) = ty.as_integral(false) {
             if Some(&bound.id) == self.lang_traits.get("integral") {
                 return true;
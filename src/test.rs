@@ -9,10 +9,27 @@ use std::{
 use tempfile::NamedTempFile;
 use wait_timeout::ChildExt;
 
-fn test_diagnostics(diag: Diagnostics, expected: &[&str]) -> datatest_stable::Result<()> {
-    let mut errors: Vec<_> = diag.errors().iter().map(|e| e.message.clone()).collect();
+fn test_diagnostics(
+    diag: Diagnostics,
+    expected: &[&str],
+    notes: &[&str],
+) -> datatest_stable::Result<()> {
+    for note in notes {
+        if !diag
+            .errors()
+            .iter()
+            .any(|err| err.notes.iter().any(|n| n.contains(note)))
+        {
+            Err(format!("missing note: '{note}'"))?;
+        }
+    }
+
+    let mut errors: Vec<_> = diag.errors().iter().collect();
     for line in expected {
-        if let Some(pos) = errors.iter().position(|err| err.contains(line)) {
+        // lines are either an error code, a lint name or a part of the message
+        if let Some(pos) = errors.iter().position(|err| {
+            err.code == Some(line) || err.lint == Some(line) || err.message.contains(line)
+        }) {
             errors.swap_remove(pos);
         } else {
             Err(format!("missing error output: '{line}'"))?;
@@ -20,6 +37,7 @@ fn test_diagnostics(diag: Diagnostics, expected: &[&str]) -> datatest_stable::Re
     }
 
     if !errors.is_empty() {
+        let errors: Vec<_> = errors.iter().map(|err| err.message.as_str()).collect();
         Err(format!("unexpected errors: '{}'", errors.join("\n")))?;
     }
 
@@ -32,6 +50,10 @@ fn compile_test(path: &Path) -> datatest_stable::Result<()> {
     let mut lexer = Lexer::new(&file, FileId::default());
     let mut errors = vec![];
     let mut expected = vec![];
+    let mut notes = vec![];
+    let mut panics = vec![];
+    let mut backtrace = vec![];
+    let mut test_args = None;
     while let Token::LineComment(data) = lexer.next(&mut diag).data {
         let data = data.trim();
         let output = data.trim_start_matches("Output:");
@@ -43,9 +65,33 @@ fn compile_test(path: &Path) -> datatest_stable::Result<()> {
         if output != data {
             errors.push(output.trim());
         }
+
+        let output = data.trim_start_matches("Note:");
+        if output != data {
+            notes.push(output.trim());
+        }
+
+        let output = data.trim_start_matches("Panic:");
+        if output != data {
+            panics.push(output.trim());
+        }
+
+        let output = data.trim_start_matches("Backtrace:");
+        if output != data {
+            backtrace.push(output.trim());
+        }
+
+        let output = data.trim_start_matches("Test:");
+        if output != data {
+            test_args = Some(output.split_whitespace().collect::<Vec<_>>());
+        }
+    }
+
+    if !backtrace.is_empty() && panics.is_empty() {
+        return Err("backtrace requirements without a panic!".into());
     }
 
-    if expected.is_empty() && errors.is_empty() {
+    if expected.is_empty() && errors.is_empty() && panics.is_empty() && test_args.is_none() {
         return Err("no requirements specified!".into());
     }
 
@@ -53,10 +99,14 @@ fn compile_test(path: &Path) -> datatest_stable::Result<()> {
     let (code, diag) = Compiler::new()
         .parse(proj)?
         .typecheck(Default::default())
-        .build(CodegenFlags::default());
-    test_diagnostics(diag, &errors)?;
-    let Some(code) = code else {
-        if !expected.is_empty() {
+        .build(CodegenFlags {
+            debug_info: !backtrace.is_empty(),
+            test: test_args.is_some(),
+            ..Default::default()
+        });
+    test_diagnostics(diag, &errors, &notes)?;
+    let Some((code, _)) = code else {
+        if !expected.is_empty() || !panics.is_empty() {
             return Err(format!("expected '{}', but build failed", expected.join("\n")).into());
         }
         return Ok(());
@@ -68,6 +118,7 @@ fn compile_test(path: &Path) -> datatest_stable::Result<()> {
             .arg("-o")
             .arg(&tmpfile)
             .args(["-std=c11", "-lgc", "-x", "c", "-"])
+            .args(backtrace.first().map(|_| "-g"))
             .stdin(Stdio::piped())
             .stdout(Stdio::null())
             .stderr(Stdio::piped())
@@ -91,13 +142,38 @@ fn compile_test(path: &Path) -> datatest_stable::Result<()> {
                 ))?;
             }
         }
-        let mut child = Command::new(&tmpfile).stdout(Stdio::piped()).spawn()?;
+        let mut child = Command::new(&tmpfile)
+            .args(test_args.iter().flatten())
+            .envs(backtrace.first().map(|_| ("CTL_BACKTRACE", "full")))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
         let mut stdout = child.stdout.take().unwrap();
+        let mut stderr = child.stderr.take().unwrap();
         let status = child
             .wait_timeout(Duration::from_secs(5))?
             .ok_or("Test took too long!")?;
-        if !status.success() {
-            Err(format!("binary returned exit code {:?}", status.code()))?;
+
+        let mut data = Vec::new();
+        stderr.read_to_end(&mut data)?;
+        let stderr = String::from_utf8_lossy(&data);
+        if panics.is_empty() && !status.success() {
+            Err(format!(
+                "binary returned exit code {:?}:\n{stderr}",
+                status.code()
+            ))?;
+        } else if !panics.is_empty() && status.success() {
+            Err("expected the binary to panic, but it exited successfully")?;
+        }
+
+        // frames only have source locations when addr2line can symbolize them
+        if !has_addr2line() {
+            backtrace.clear();
+        }
+        for panic in panics.into_iter().chain(backtrace) {
+            if !stderr.contains(panic) {
+                Err(format!("missing panic output: '{panic}', got '{stderr}'"))?;
+            }
         }
 
         let mut data = Vec::new();
@@ -115,4 +191,13 @@ fn compile_test(path: &Path) -> datatest_stable::Result<()> {
     Ok(())
 }
 
+fn has_addr2line() -> bool {
+    Command::new("addr2line")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
 datatest_stable::harness!(compile_test, "tests", r".*/**/*.ctl");
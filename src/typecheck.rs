@@ -18,17 +18,21 @@ use crate::{
     },
     comptime_int::ComptimeInt,
     dgraph::Dependencies,
-    error::{Diagnostics, Error},
+    error::{closest_match, Diagnostics, Error},
     lexer::{Located, Span},
+    lint::{Lint, LintLevel},
     project::{Project, SpanSemanticToken},
     sym::*,
     typeid::{
         BitSizeResult, CInt, FnPtr, GenericExtension, GenericFn, GenericTrait, GenericUserType,
-        Type, TypeArgs, TypeId, Types, WithTypeArgs,
+        Integer, Type, TypeArgs, TypeId, Types, WithTypeArgs,
     },
     THIS_PARAM, THIS_TYPE,
 };
 
+mod instantiate;
+mod interp;
+
 macro_rules! resolve_type {
     ($self: expr, $ty: expr) => {{
         let id = match $self.proj.types[$ty] {
@@ -153,6 +157,12 @@ pub enum ResolvedValue {
     Fn(GenericFn),
     MemberFn(MemberFn),
     Var(VariableId),
+    AssocConst {
+        ty: TypeId,
+        id: VariableId,
+        value_ty: TypeId,
+    },
+    ConstParam(UserTypeId),
     NotFound(Error),
     #[default]
     Error,
@@ -320,6 +330,11 @@ pub struct TypeChecker {
     listening_expr: usize,
     current_expr: usize,
     current_static: Option<(VariableId, Vec<VariableId>)>,
+    used_fns: HashSet<FunctionId>,
+    unread_vars: HashSet<VariableId>,
+    resolving_aliases: Vec<UserTypeId>,
+    unchecked_consts: IndexMap<VariableId, PExpr>,
+    unchecked_fns: IndexMap<FunctionId, PExpr>,
 }
 
 impl TypeChecker {
@@ -333,6 +348,11 @@ impl TypeChecker {
             listening_expr: 1,
             current_expr: 1,
             current_static: None,
+            used_fns: HashSet::new(),
+            unread_vars: HashSet::new(),
+            resolving_aliases: Vec::new(),
+            unchecked_consts: IndexMap::new(),
+            unchecked_fns: IndexMap::new(),
         };
 
         let mut autouse = vec![];
@@ -366,29 +386,55 @@ impl TypeChecker {
             this.check_stmt(stmt);
         }
 
+        while let Some(&id) = this.unchecked_consts.keys().next() {
+            this.resolve_const(id);
+        }
+
         this.proj.main = this.proj.scopes[this.proj.scope]
             .vns
             .get("main")
             .and_then(|id| id.as_fn())
             .copied();
-        for (_, var) in
-            this.proj.scopes.vars().filter(|(_, v)| {
-                v.unused && !v.name.data.starts_with('_') && v.name.data != THIS_PARAM
+        this.proj.tests = this
+            .proj
+            .scopes
+            .functions()
+            .filter(|(_, f)| {
+                f.attrs.has("test")
+                    && this
+                        .proj
+                        .scopes
+                        .walk(f.body_scope)
+                        .any(|(id, _)| id == this.proj.scope)
             })
-        {
-            if this
-                .proj
-                .scopes
-                .walk(var.scope)
-                .any(|(id, _)| id == this.proj.scope)
-            {
-                this.proj.diag.warn(Error::new(
-                    format!("unused variable: '{}'", var.name.data),
-                    var.name.span,
-                ));
+            .map(|(id, _)| id)
+            .collect();
+        let unused: Vec<_> = this
+            .proj
+            .scopes
+            .vars()
+            .filter(|(_, v)| !v.name.data.starts_with('_') && v.name.data != THIS_PARAM)
+            .filter(|(id, v)| v.unused || this.unread_vars.contains(id))
+            .filter(|(_, v)| this.in_project(v.scope))
+            .map(|(id, v)| {
+                (
+                    v.scope,
+                    v.name.clone(),
+                    !v.unused && this.unread_vars.contains(&id),
+                )
+            })
+            .collect();
+        for (scope, name, assigned) in unused {
+            let mut err = Error::new(format!("unused variable: '{}'", name.data), name.span);
+            if assigned {
+                err = err.with_note("the variable is assigned to, but never read");
             }
+            this.lint_in(scope, Lint::UnusedVariables, err);
         }
+        this.check_instantiations();
+        this.check_dead_code();
 
+        this.proj.diag.apply_lint_levels();
         this.proj
     }
 
@@ -402,6 +448,11 @@ impl TypeChecker {
             listening_expr: 1,
             current_expr: 1,
             current_static: None,
+            used_fns: HashSet::new(),
+            unread_vars: HashSet::new(),
+            resolving_aliases: Vec::new(),
+            unchecked_consts: IndexMap::new(),
+            unchecked_fns: IndexMap::new(),
         };
         let res = f(&mut tc);
         std::mem::swap(proj, &mut tc.proj);
@@ -413,6 +464,113 @@ impl TypeChecker {
         T::default()
     }
 
+    fn in_project(&self, scope: ScopeId) -> bool {
+        self.proj
+            .scopes
+            .walk(scope)
+            .any(|(id, _)| id == self.proj.scope)
+    }
+
+    fn check_dead_code(&mut self) {
+        let mut imports: Vec<_> = self
+            .proj
+            .scopes
+            .scopes()
+            .filter(|(id, _)| self.in_project(*id))
+            .flat_map(|(id, scope)| {
+                scope
+                    .unused_imports
+                    .iter()
+                    .map(move |(name, &span)| (id, name.clone(), span))
+            })
+            .collect();
+        imports.sort_by_key(|(_, _, span)| (span.file, span.pos));
+        for (scope, name, span) in imports {
+            self.lint_in(
+                scope,
+                Lint::UnusedImports,
+                Error::new(format!("unused import: '{name}'"), span),
+            );
+        }
+
+        let unused: Vec<_> = self
+            .proj
+            .scopes
+            .functions()
+            .filter(|&(id, f)| {
+                !f.public
+                    && !f.is_extern
+                    && f.constructor.is_none()
+                    && !f.name.data.starts_with('_')
+                    && !f.attrs.has("test")
+                    && Some(id) != self.proj.main
+                    && !self.used_fns.contains(&id)
+                    && match self.proj.scopes[f.scope].kind {
+                        ScopeKind::Module(_) | ScopeKind::Block(_) => true,
+                        // trait methods and those in impl blocks can be called through the trait
+                        ScopeKind::UserType(ut) => !self.proj.scopes.get(ut).kind.is_trait(),
+                        _ => false,
+                    }
+                    && self.in_project(f.scope)
+            })
+            .map(|(_, f)| {
+                let method = self.proj.scopes[f.scope].kind.is_user_type();
+                (f.body_scope, f.name.clone(), method)
+            })
+            .collect();
+        for (scope, name, method) in unused {
+            let what = if method { "method" } else { "function" };
+            self.lint_in(
+                scope,
+                Lint::DeadCode,
+                Error::new(format!("{what} '{}' is never used", name.data), name.span),
+            );
+        }
+    }
+
+    /// Records a use of `id` for the dead code lint, unless it comes from inside the function's own
+    /// body.
+    fn mark_fn_used(&mut self, id: FunctionId) {
+        let body = self.proj.scopes.get(id).body_scope;
+        if !self
+            .proj
+            .scopes
+            .walk(self.current)
+            .any(|(id, _)| id == body)
+        {
+            self.used_fns.insert(id);
+        }
+    }
+
+    fn lint(&mut self, lint: Lint, error: Error) {
+        self.lint_in(self.current, lint, error)
+    }
+
+    fn lint_in(&mut self, scope: ScopeId, lint: Lint, error: Error) {
+        let level = self.proj.scopes.lint_level(scope, lint);
+        self.proj.diag.lint(lint, level, error);
+    }
+
+    fn check_lint_attrs(&mut self, attrs: &Attributes) {
+        for attr in attrs.iter() {
+            if LintLevel::from_attr(&attr.name.data).is_none() {
+                continue;
+            }
+
+            for prop in attr.props.iter() {
+                if prop.name.data != Lint::GROUP_ALL && Lint::from_name(&prop.name.data).is_none() {
+                    self.lint(
+                        Lint::UnknownLints,
+                        Error::new(
+                            format!("unknown lint: '{}'", prop.name.data),
+                            prop.name.span,
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
     fn current_function(&self) -> Option<FunctionId> {
         self.proj.scopes.function_of(self.current)
     }
@@ -432,9 +590,10 @@ impl TypeChecker {
             },
         );
 
-        if res.existed && no_redef {
+        if let Some(prev) = res.prev.filter(|_| no_redef) {
             let name = res.id.name(&self.proj.scopes);
-            self.error(Error::redefinition(&name.data, name.span))
+            let err = Error::redefinition(&name.data, name.span, prev.span(&self.proj.scopes));
+            self.error(err)
         }
         res.id
     }
@@ -474,6 +633,13 @@ impl TypeChecker {
             return;
         }
 
+        let items = self.member_items(ty, method);
+        self.proj.completions = Some(Completions { items, method });
+    }
+
+    /// Returns the members and functions accessible on a value of type `ty`. If `method` is true,
+    /// only functions that take `this` are included, along with the member variables.
+    fn member_items(&mut self, ty: TypeId, method: bool) -> Vec<LspItem> {
         let mut completions = vec![];
         let mut added = HashSet::new();
         let mut add_methods = |scopes: &Scopes,
@@ -570,10 +736,7 @@ impl TypeChecker {
             );
         }
 
-        self.proj.completions = Some(Completions {
-            items: completions,
-            method,
-        });
+        completions
     }
 
     fn check_cursor_completions(&mut self, span: Span, ty: bool) {
@@ -716,9 +879,11 @@ impl TypeChecker {
                     public: pub_constructor,
                     name: base.name.clone(),
                     is_async: false,
+                    is_generator: false,
                     is_extern: false,
                     variadic: false,
                     is_unsafe: false,
+                    is_const: false,
                     type_params: vec![],
                     params: base
                         .members
@@ -749,15 +914,17 @@ impl TypeChecker {
                         member.name.span,
                     ),
                 );
-                if prev.is_some() {
+                if let Some(prev) = prev {
                     this.error(Error::redefinition_k(
                         "member variable",
                         &member.name.data,
                         member.name.span,
+                        prev.span,
                     ))
                 }
             }
 
+            this.declare_consts(base.consts, true);
             let (impls, blocks, subscripts) = this.declare_impl_blocks(base.impls, base.operators);
             let mut fns = this.declare_fns(base.functions);
             let kind = if packed {
@@ -788,10 +955,11 @@ impl TypeChecker {
             name.data,
             Vis::new(ValueItem::StructConstructor(id, init.id), pub_constructor),
         );
-        if prev.is_some() {
+        if let Some(prev) = prev {
             self.error(Error::redefinition(
                 &self.proj.scopes.get(id).name.data,
                 name.span,
+                InsertedItem::from(prev.id).span(&self.proj.scopes),
             ))
         }
 
@@ -819,21 +987,19 @@ impl TypeChecker {
             let mut params = Vec::with_capacity(base.members.len());
             let mut fns = Vec::with_capacity(base.members.len());
             for member in base.members {
-                if members
-                    .insert(
-                        member.name.data.clone(),
-                        CheckedMember::new(
-                            member.public,
-                            this.declare_type_hint(member.ty.clone()),
-                            member.name.span,
-                        ),
-                    )
-                    .is_some()
-                {
+                if let Some(prev) = members.insert(
+                    member.name.data.clone(),
+                    CheckedMember::new(
+                        member.public,
+                        this.declare_type_hint(member.ty.clone()),
+                        member.name.span,
+                    ),
+                ) {
                     this.error(Error::redefinition_k(
                         "member",
                         &member.name.data,
                         member.name.span,
+                        prev.span,
                     ))
                 }
 
@@ -845,6 +1011,7 @@ impl TypeChecker {
                 });
             }
 
+            this.declare_consts(base.consts, true);
             let (impls, blocks, subscripts) = this.declare_impl_blocks(base.impls, base.operators);
             let ret = Self::typehint_for_struct(&base.name, &base.type_params);
             let mut enum_union = true;
@@ -946,8 +1113,10 @@ impl TypeChecker {
                     name: Located::new(variant.name.span, variant.name.data),
                     is_extern: false,
                     is_async: false,
+                    is_generator: false,
                     variadic: false,
                     is_unsafe: false,
+                    is_const: false,
                     type_params: vec![],
                     params,
                     ret: ret.clone(),
@@ -1006,15 +1175,17 @@ impl TypeChecker {
                         member.name.span,
                     ),
                 );
-                if prev.is_some() {
+                if let Some(prev) = prev {
                     this.error(Error::redefinition_k(
                         "member variable",
                         &member.name.data,
                         member.name.span,
+                        prev.span,
                     ))
                 }
             }
 
+            this.declare_consts(base.consts, true);
             let (impls, blocks, subscripts) = this.declare_impl_blocks(base.impls, base.operators);
             let mut fns = this.declare_fns(base.functions);
             let ut = this.ut_from_stuff(
@@ -1039,10 +1210,11 @@ impl TypeChecker {
             name.data,
             Vis::new(ValueItem::UnionConstructor(id), base.public),
         );
-        if prev.is_some() {
+        if let Some(prev) = prev {
             self.error(Error::redefinition(
                 &self.proj.scopes.get(id).name.data,
                 name.span,
+                InsertedItem::from(prev.id).span(&self.proj.scopes),
             ))
         }
         self.proj.scopes[scope].kind = ScopeKind::UserType(id);
@@ -1051,6 +1223,7 @@ impl TypeChecker {
     }
 
     fn declare_stmt(&mut self, autouse: &mut Vec<ScopeId>, stmt: PStmt) -> DStmt {
+        self.check_lint_attrs(&stmt.attrs);
         match stmt.data {
             PStmtData::Module {
                 public,
@@ -1070,12 +1243,13 @@ impl TypeChecker {
                 let parent = self.current;
                 self.enter(ScopeKind::Module(name.clone()), |this| {
                     this.check_hover(name.span, this.current.into());
-                    if this.proj.scopes[parent]
+                    this.proj.scopes[this.current].attrs = stmt.attrs.clone();
+                    if let Some(prev) = this.proj.scopes[parent]
                         .tns
                         .insert(name.data.clone(), Vis::new(this.current.into(), public))
-                        .is_some()
                     {
-                        this.error(Error::redefinition(&name.data, name.span))
+                        let prev = InsertedItem::from(prev.id).span(&this.proj.scopes);
+                        this.error(Error::redefinition(&name.data, name.span, prev))
                     }
 
                     if stmt.attrs.iter().any(|attr| attr.name.data == "autouse") {
@@ -1120,6 +1294,8 @@ impl TypeChecker {
                 public,
                 name,
                 type_params,
+                assoc_types,
+                consts,
                 impls,
                 functions,
                 sealed,
@@ -1144,7 +1320,7 @@ impl TypeChecker {
                         false,
                     );
                     let fns = this.declare_fns(functions);
-                    let tr = this.ut_from_stuff(
+                    let mut tr = this.ut_from_stuff(
                         stmt.attrs,
                         name,
                         public,
@@ -1156,6 +1332,11 @@ impl TypeChecker {
                         &[],
                         &[],
                     );
+                    for id in this.declare_type_params(assoc_types) {
+                        this.proj.scopes.get_mut(id).implicit = true;
+                        tr.type_params.push(id);
+                    }
+                    this.declare_consts(consts, false);
                     (tr, fns, this_id)
                 });
 
@@ -1212,6 +1393,29 @@ impl TypeChecker {
                     fns,
                 }
             }
+            PStmtData::Alias {
+                public,
+                name,
+                type_params,
+                ty,
+            } => {
+                let alias = self.enter(ScopeKind::None, |this| {
+                    let ty = this.declare_type_hint(ty);
+                    this.ut_from_stuff(
+                        stmt.attrs,
+                        name,
+                        public,
+                        Default::default(),
+                        UserTypeKind::Alias(ty),
+                        type_params,
+                        &[],
+                        Vec::new(),
+                        &[],
+                        &[],
+                    )
+                });
+                DStmt::Alias(self.insert_user_type(alias, public))
+            }
             PStmtData::Fn(f) => DStmt::Fn(self.declare_fn(f)),
             PStmtData::Binding {
                 public,
@@ -1227,23 +1431,26 @@ impl TypeChecker {
                     unused = false;
                 }
 
-                DStmt::Binding {
-                    id: self.insert::<VariableId>(
-                        Variable {
-                            public,
-                            name,
-                            ty,
-                            unused,
-                            is_static: true,
-                            mutable: false,
-                            value: None,
-                            has_hint: true,
-                        },
+                let id = self.insert::<VariableId>(
+                    Variable {
                         public,
-                        true,
-                    ),
-                    value,
-                    constant,
+                        name,
+                        ty,
+                        unused,
+                        is_static: true,
+                        is_const: constant,
+                        mutable: false,
+                        value: None,
+                        has_hint: true,
+                    },
+                    public,
+                    true,
+                );
+                if constant {
+                    self.unchecked_consts.insert(id, value);
+                    DStmt::Const(id)
+                } else {
+                    DStmt::Binding { id, value }
                 }
             }
             PStmtData::Use(stmt) => {
@@ -1262,6 +1469,7 @@ impl TypeChecker {
 
     fn declare_fn(&mut self, f: Fn) -> DFn {
         let span = f.name.span;
+        self.check_lint_attrs(&f.attrs);
         if f.variadic && (!f.is_extern || f.body.is_some()) {
             self.error(Error::new(
                 "only imported extern functions may be variadic",
@@ -1276,6 +1484,23 @@ impl TypeChecker {
             ))
         }
 
+        if f.is_async && f.is_extern {
+            self.error(Error::new("extern functions cannot be 'async'", span))
+        } else if f.is_async && f.body.is_none() {
+            self.error(Error::new("async functions must have a body", span))
+        } else if f.is_generator && f.is_async {
+            self.error(Error::new("async functions cannot be generators", span))
+        } else if f.is_generator && f.is_extern {
+            self.error(Error::new("extern functions cannot be generators", span))
+        }
+
+        let is_const = f.is_const || f.attrs.has("const");
+        if is_const && f.is_async {
+            self.error(Error::new("const functions cannot be 'async'", span))
+        } else if is_const && f.is_generator {
+            self.error(Error::new("const functions cannot be generators", span))
+        }
+
         let id = self.insert::<FunctionId>(
             Function {
                 public: f.public,
@@ -1283,7 +1508,9 @@ impl TypeChecker {
                 name: f.name,
                 is_extern: f.is_extern,
                 is_async: f.is_async,
+                is_generator: f.is_generator && !f.is_async && !f.is_extern,
                 is_unsafe: f.is_unsafe,
+                is_const: is_const && !f.is_async && !f.is_generator,
                 variadic: f.variadic,
                 assign_subscript: f.assign_subscript,
                 has_body: f.body.is_some(),
@@ -1360,9 +1587,23 @@ impl TypeChecker {
                         .map(|expr| DefaultExpr::Unchecked(this.current, expr)),
                 })
                 .collect();
-            this.proj.scopes.get_mut(id).ret = this.declare_type_hint(f.ret);
+            let ret = this.declare_type_hint(f.ret);
+            let func = this.proj.scopes.get(id);
+            this.proj.scopes.get_mut(id).ret = if func.is_async {
+                this.insert_frame_type(id, ret, UserTypeKind::AsyncFn)
+            } else if func.is_generator {
+                this.insert_frame_type(id, ret, UserTypeKind::Generator)
+            } else {
+                ret
+            };
 
-            DFn { id, body: f.body }
+            match f.body {
+                Some(body) if this.proj.scopes.get(id).is_const => {
+                    this.unchecked_fns.insert(id, body);
+                    DFn { id, body: None }
+                }
+                body => DFn { id, body },
+            }
         })
     }
 
@@ -1452,6 +1693,7 @@ impl TypeChecker {
             span: f.name.span,
             scope: this.current,
             fns: vec![this.declare_fn(f)],
+            consts: Vec::new(),
         });
         self.proj.scopes[block.scope].kind = ScopeKind::Impl(impls.len());
         impls.push(TraitImpl::Unchecked {
@@ -1467,22 +1709,21 @@ impl TypeChecker {
 
     fn declare_type_params(&mut self, vec: TypeParams) -> Vec<UserTypeId> {
         vec.into_iter()
-            .map(|(name, impls)| {
-                self.insert(
-                    UserType::template(
-                        name,
-                        self.current,
-                        impls
-                            .into_iter()
-                            .map(|path| TraitImpl::Unchecked {
-                                scope: self.current,
-                                data: TraitImplData::Path(path),
-                            })
-                            .collect(),
-                    ),
-                    false,
-                    false,
-                )
+            .map(|param| {
+                let mut template = UserType::template(
+                    param.name,
+                    self.current,
+                    param
+                        .impls
+                        .into_iter()
+                        .map(|path| TraitImpl::Unchecked {
+                            scope: self.current,
+                            data: TraitImplData::Path(path),
+                        })
+                        .collect(),
+                );
+                template.const_ty = param.const_ty.map(|ty| self.declare_type_hint(ty));
+                self.insert(template, false, false)
             })
             .collect()
     }
@@ -1496,16 +1737,29 @@ impl TypeChecker {
         let mut declared_blocks = Vec::new();
         let mut subscripts = Vec::new();
         for ImplBlock {
-            path,
+            mut path,
             functions,
             type_params,
+            assoc_types,
+            consts,
         } in blocks
         {
+            if let Some((_, args)) = path.components.last_mut() {
+                args.extend(
+                    assoc_types
+                        .into_iter()
+                        .map(|(name, ty)| TypeHint::AssocType(name, ty.into())),
+                );
+            }
+
+            // the constants are declared in the body of the type so they can be named through it
+            let consts = self.declare_consts(consts, false);
             let block = self.enter(ScopeKind::None, |this| DImplBlock {
                 type_params: this.declare_type_params(type_params),
                 span: path.final_component_span(),
                 scope: this.current,
                 fns: functions.into_iter().map(|f| this.declare_fn(f)).collect(),
+                consts,
             });
             self.proj.scopes[block.scope].kind = ScopeKind::Impl(impls.len());
             impls.push(TraitImpl::Unchecked {
@@ -1522,21 +1776,53 @@ impl TypeChecker {
         (impls, declared_blocks, subscripts)
     }
 
+    fn declare_consts(&mut self, consts: Vec<Const>, warn_unused: bool) -> Vec<VariableId> {
+        consts
+            .into_iter()
+            .map(
+                |Const {
+                     public,
+                     name,
+                     ty,
+                     value,
+                 }| {
+                    let ty = self.declare_type_hint(ty);
+                    let id = self.insert::<VariableId>(
+                        Variable {
+                            public,
+                            name,
+                            ty,
+                            unused: warn_unused,
+                            is_static: true,
+                            is_const: true,
+                            mutable: false,
+                            value: None,
+                            has_hint: true,
+                        },
+                        public,
+                        true,
+                    );
+                    if let Some(value) = value {
+                        self.unchecked_consts.insert(id, value);
+                    }
+                    id
+                },
+            )
+            .collect()
+    }
+
     fn declare_type_hint(&mut self, hint: TypeHint) -> TypeId {
         self.proj.types.add_unresolved(hint, self.current)
     }
 
-    fn typehint_for_struct(
-        name: &Located<String>,
-        type_params: &[(Located<String>, Vec<Path>)],
-    ) -> TypeHint {
+    fn typehint_for_struct(name: &Located<String>, type_params: &[TypeParam]) -> TypeHint {
         TypeHint::Regular(Path::new(
             PathOrigin::Normal,
             vec![(
                 name.clone(),
                 type_params
                     .iter()
-                    .map(|(n, _)| TypeHint::Regular(Path::from(n.clone())))
+                    .map(|param| TypeHint::Regular(Path::from(param.name.clone())))
                     .collect(),
             )],
         ))
@@ -1579,6 +1865,8 @@ impl TypeChecker {
             subscripts: subscripts.iter().map(|s| s.id).collect(),
             members_resolved: false,
             recursive: false,
+            implicit: false,
+            const_ty: None,
         }
     }
 }
@@ -1695,6 +1983,10 @@ impl TypeChecker {
                     }
                 });
             }
+            DStmt::Alias(id) => {
+                let span = self.proj.scopes.get(id).name.span;
+                self.resolve_alias(id, &TypeArgs::default(), span);
+            }
             DStmt::Expr(expr) => return CStmt::Expr(self.check_expr(expr, None)),
             DStmt::Let { ty, value, patt } => {
                 let span = patt.span;
@@ -1770,32 +2062,72 @@ impl TypeChecker {
                 return CStmt::Guard { cond, body };
             }
             DStmt::Fn(f) => self.check_fn(f),
-            DStmt::Binding {
-                id,
-                value,
-                constant,
-            } => {
-                // FIXME: detect cycles like static X: usize = X;
-                // FIXME: non-const statics should be disallowed
-                let ty = resolve_type!(self, self.proj.scopes.get_mut(id).ty);
-
-                self.proj.static_deps.insert(id, Dependencies::Resolving);
-                let prev = self.current_static.replace((id, Vec::new()));
-                let value = self.enter(ScopeKind::Static(id), |this| this.type_check(value, ty));
-                let (_, deps) = std::mem::replace(&mut self.current_static, prev).unwrap();
-                self.proj
-                    .static_deps
-                    .insert(id, Dependencies::Resolved(deps));
-
-                let var = self.proj.scopes.get_mut(id);
-                var.value = Some(value);
-            }
+            DStmt::Binding { id, value } => self.check_static(id, value),
+            DStmt::Const(id) => self.resolve_const(id),
             DStmt::None => {}
         }
 
         CStmt::None
     }
 
+    fn check_static(&mut self, id: VariableId, value: PExpr) {
+        // FIXME: detect cycles like static X: usize = X;
+        // FIXME: non-const statics should be disallowed
+        let ty = resolve_type!(self, self.proj.scopes.get_mut(id).ty);
+
+        self.proj.static_deps.insert(id, Dependencies::Resolving);
+        let prev = self.current_static.replace((id, Vec::new()));
+        let span = value.span;
+        let mut value = self.enter(ScopeKind::Static(id), |this| this.type_check(value, ty));
+        if self.is_const_fn_call(&value) {
+            if let Some(result) = self
+                .interpret(&value, span)
+                .and_then(|result| self.materialize(result, value.ty, span))
+            {
+                value = result;
+            }
+        }
+        let (_, deps) = std::mem::replace(&mut self.current_static, prev).unwrap();
+        self.proj
+            .static_deps
+            .insert(id, Dependencies::Resolved(deps));
+
+        let var = self.proj.scopes.get_mut(id);
+        var.value = Some(value);
+    }
+
+    fn is_const_fn_call(&self, expr: &CExpr) -> bool {
+        let CExprData::Call(callee, _, _) = &expr.data else {
+            return false;
+        };
+        let id = match &callee.data {
+            CExprData::Fn(func, _) => func.id,
+            CExprData::MemFn(mfn, _) if matches!(mfn.typ, MemberFnType::Normal) => mfn.func.id,
+            _ => return false,
+        };
+        let func = self.proj.scopes.get(id);
+        func.is_const && func.constructor.is_none()
+    }
+
+    /// Checks the value of the constant `id` if that hasn't happened yet. Constants can be needed
+    /// before their declaration is reached, for example in the length of an array type.
+    fn resolve_const(&mut self, id: VariableId) {
+        if let Some(value) = self.unchecked_consts.shift_remove(&id) {
+            let scope = self.proj.scopes.get(id).scope;
+            self.enter_id_and_resolve(scope, |this| this.check_static(id, value));
+        }
+    }
+
+    /// Checks the body of the const function `id` if that hasn't happened yet, so it can be run
+    /// by the interpreter before its declaration is reached.
+    fn resolve_const_fn(&mut self, id: FunctionId) {
+        if self.unchecked_fns.contains_key(&id) {
+            let prev = self.current_static.take();
+            self.check_fn(DFn { id, body: None });
+            self.current_static = prev;
+        }
+    }
+
     fn signatures_match(
         scopes: &Scopes,
         types: &mut Types,
@@ -1904,6 +2236,19 @@ impl TypeChecker {
             ))
         }
 
+        for (&id, &ty) in tr.ty_args.iter() {
+            if ty == TypeId::UNKNOWN && self.proj.scopes.get(id).implicit {
+                self.error(Error::new(
+                    format!(
+                        "missing associated type '{}' in implementation of trait '{}'",
+                        self.proj.scopes.get(id).name.data,
+                        self.proj.scopes.get(tr.id).name.data,
+                    ),
+                    block.span,
+                ))
+            }
+        }
+
         for mut dep in self
             .proj
             .scopes
@@ -1934,6 +2279,8 @@ impl TypeChecker {
             }
         }
 
+        self.check_impl_consts(this, tr, block.consts, block.span);
+
         let mut required = self.proj.scopes.get(tr.id).fns.clone();
         for f in block.fns {
             let Located {
@@ -1989,7 +2336,34 @@ impl TypeChecker {
         }
     }
 
+    fn check_test_fn(&mut self, id: FunctionId) {
+        let func = self.proj.scopes.get(id);
+        let message = if func.is_extern || func.is_async || func.is_generator {
+            "test functions cannot be extern, async, or generators"
+        } else if !func.type_params.is_empty() {
+            "test functions cannot be generic"
+        } else if !func.params.is_empty() {
+            "test functions cannot have parameters"
+        } else if !func.ret.is_void() {
+            "test functions must return void"
+        } else {
+            return;
+        };
+        self.error(Error::new(message, func.name.span))
+    }
+
     fn check_fn(&mut self, DFn { id, body, .. }: DFn) {
+        let func = self.proj.scopes.get(id);
+        let body = if func.is_const && func.has_body {
+            // const functions may have been checked early by resolve_const_fn
+            let Some(body) = self.unchecked_fns.shift_remove(&id) else {
+                return;
+            };
+            Some(body)
+        } else {
+            body
+        };
+
         // TODO: disallow private type in public interface
         self.enter_id_and_resolve(self.proj.scopes.get(id).body_scope, |this| {
             this.resolve_proto(id);
@@ -2037,13 +2411,27 @@ impl TypeChecker {
                 return;
             }
 
+            if func.attrs.has("test") {
+                this.check_test_fn(id);
+            }
+
             let Some(body) = body else {
                 return;
             };
 
             let old_safety = std::mem::take(&mut this.safety);
-            let target = this.proj.scopes.get_mut(id).ret;
+            let output = this.async_output(id);
+            let item = this.generator_item(id);
+            if output.is_some() {
+                this.declare_poll_context(id);
+            }
+            let target = if item.is_some() {
+                TypeId::VOID
+            } else {
+                output.unwrap_or(this.proj.scopes.get(id).ret)
+            };
             let body = this.check_expr(body, Some(target));
+            this.check_escaping_lambda(&body, this.current);
             let body = if body.ty == TypeId::VOID {
                 Err(body)
             } else {
@@ -2066,10 +2454,83 @@ impl TypeChecker {
                     body
                 }
             });
+            if let Some(output) = output {
+                let body = this.proj.scopes.get_mut(id).body.take().unwrap();
+                let span = this.proj.scopes.get(id).name.span;
+                this.proj.scopes.get_mut(id).body =
+                    Some(this.make_poll(output, "Ready", [("0".into(), body)].into(), span));
+            } else if let Some(item) = item {
+                let body = this.proj.scopes.get_mut(id).body.take().unwrap();
+                let span = this.proj.scopes.get(id).name.span;
+                let none = CExpr::option_null(this.make_lang_type_by_name("option", [item], span));
+                this.proj.scopes.get_mut(id).body = Some(this.sequence(body, none, span));
+            }
             this.safety = old_safety;
         });
     }
 
+    fn check_impl_consts(
+        &mut self,
+        this: TypeId,
+        tr: &GenericTrait,
+        consts: Vec<VariableId>,
+        span: Span,
+    ) {
+        let tr_ut = self.proj.scopes.get(tr.id);
+        let mut required: Vec<_> = self.proj.scopes[tr_ut.body_scope]
+            .vns
+            .values()
+            .flat_map(|item| item.as_var().copied())
+            .filter(|&id| self.proj.scopes.get(id).is_const)
+            .collect();
+        required.sort_by_key(|&id| self.proj.scopes.get(id).name.span.pos);
+
+        let mut ty_args = tr.ty_args.clone();
+        ty_args.insert(*tr_ut.kind.as_trait().unwrap().0, this);
+        for id in consts {
+            let name = self.proj.scopes.get(id).name.clone();
+            let Some(pos) = required
+                .iter()
+                .position(|&id| self.proj.scopes.get(id).name.data == name.data)
+            else {
+                self.proj.diag.error(Error::new(
+                    format!(
+                        "no constant '{}' found in trait '{}'",
+                        name.data,
+                        self.proj.scopes.get(tr.id).name
+                    ),
+                    name.span,
+                ));
+                continue;
+            };
+
+            let wanted = required.remove(pos);
+            let wanted = resolve_type!(self, self.proj.scopes.get_mut(wanted).ty)
+                .with_templates(&mut self.proj.types, &ty_args);
+            let has = resolve_type!(self, self.proj.scopes.get_mut(id).ty);
+            if has != wanted && has != TypeId::UNKNOWN && wanted != TypeId::UNKNOWN {
+                self.proj.diag.error(Error::type_mismatch(
+                    wanted,
+                    has,
+                    &self.proj.scopes,
+                    &mut self.proj.types,
+                    name.span,
+                ))
+            }
+        }
+
+        for id in required {
+            self.proj.diag.error(Error::new(
+                format!(
+                    "missing constant '{}' in implementation of trait '{}'",
+                    self.proj.scopes.get(id).name.data,
+                    self.proj.scopes.get(tr.id).name
+                ),
+                span,
+            ))
+        }
+    }
+
     fn check_impl_blocks(&mut self, this_ty: TypeId, id: UserTypeId, impls: Vec<DImplBlock>) {
         let mut seen = HashSet::new();
         for (i, block) in impls.into_iter().enumerate() {
@@ -2159,6 +2620,7 @@ impl TypeChecker {
                 mfn,
                 [arg0, arg0val].into(),
                 self.current,
+                span,
             ),
         )
     }
@@ -2222,6 +2684,7 @@ impl TypeChecker {
                     mfn,
                     [(p0.label.clone(), arg0)].into(),
                     self.current,
+                    span,
                 ),
             )
         }
@@ -2233,405 +2696,16 @@ impl TypeChecker {
         let span = expr.span;
         match expr.data {
             PExprData::Binary { op, left, right } => {
-                let left_span = left.span;
-                let assignment = op.is_assignment();
-                match op {
-                    BinaryOp::Assign => {
-                        if let PExprData::Subscript { callee, mut args } = left.data {
-                            if args.is_empty() {
-                                return self.error(Error::new(
-                                    "subscript requires at least one argument",
-                                    span,
-                                ));
-                            }
-
-                            let callee = self.check_expr(*callee, None);
-                            let stripped = callee.ty.strip_references(&self.proj.types);
-                            if let &Type::Array(inner, _) = &self.proj.types[stripped] {
-                                let left = self.check_array_subscript(inner, callee, args);
-                                if op.is_assignment()
-                                    && !left.is_assignable(&self.proj.scopes, &self.proj.types)
-                                {
-                                    // TODO: report a better error here
-                                    self.error(Error::new(
-                                        "expression is not assignable",
-                                        left_span,
-                                    ))
-                                }
-
-                                let right = self.type_check(*right, left.ty);
-                                return CExpr::new(
-                                    TypeId::VOID,
-                                    CExprData::Binary(BinaryOp::Assign, left.into(), right.into()),
-                                );
-                            } else {
-                                args.push((None, *right));
-                                return self
-                                    .check_subscript(callee, stripped, args, target, true, span);
-                            }
-                        }
-                    }
-                    BinaryOp::NoneCoalesce | BinaryOp::NoneCoalesceAssign => {
-                        let Some(id) = self.proj.scopes.get_option_id() else {
-                            return self.error(Error::no_lang_item("option", span));
-                        };
-
-                        let target = if let Some(target) = target {
-                            let ut =
-                                GenericUserType::from_type_args(&self.proj.scopes, id, [target]);
-                            Some(self.proj.types.insert(Type::User(ut)))
-                        } else {
-                            None
-                        };
-                        let lhs_span = left.span;
-                        let lhs = self.check_expr(*left, target);
-                        let Some(target) = lhs.ty.as_option_inner(&self.proj) else {
-                            if lhs.ty != TypeId::UNKNOWN {
-                                self.proj.diag.error(Error::invalid_operator(
-                                    op,
-                                    &lhs.ty.name(&self.proj.scopes, &mut self.proj.types),
-                                    lhs_span,
-                                ));
-                            }
-                            return Default::default();
-                        };
-                        if assignment && !lhs.is_assignable(&self.proj.scopes, &self.proj.types) {
-                            // TODO: report a better error here
-                            self.error(Error::new("expression is not assignable", lhs_span))
-                        }
-
-                        let span = right.span;
-                        let rhs = self.check_expr_inner(*right, Some(target));
-                        let rhs = self.type_check_checked(rhs, target, span);
-                        return CExpr::new(
-                            if assignment { TypeId::VOID } else { target },
-                            CExprData::Binary(op, lhs.into(), rhs.into()),
-                        );
-                    }
-                    BinaryOp::LogicalAnd => {
-                        let was_listening = self.listening_expr == self.current_expr;
-                        let (left, lvars) = self.type_check_with_listen(*left);
-                        let (right, rvars) = self.enter(ScopeKind::None, |this| {
-                            this.define(&lvars);
-                            this.type_check_with_listen(*right)
-                        });
-                        if was_listening {
-                            self.listening_vars.extend(lvars);
-                            self.listening_vars.extend(rvars);
-                        }
-
-                        return CExpr::new(
-                            TypeId::BOOL,
-                            CExprData::Binary(op, left.into(), right.into()),
-                        );
-                    }
-                    _ => {}
-                }
-
-                let left = self.check_expr(*left, target);
-                if left.ty == TypeId::UNKNOWN {
-                    self.check_expr(*right, target);
-                    return Default::default();
-                }
-
-                if assignment && !left.is_assignable(&self.proj.scopes, &self.proj.types) {
-                    // TODO: report a better error here
-                    self.error(Error::new("expression is not assignable", left_span))
-                }
-
-                if op != BinaryOp::Assign && !left.ty.supports_binary(&self.proj.types, op) {
-                    return self.check_binary(left_span, left, *right, op, span);
-                }
-
-                match (&self.proj.types[left.ty], op) {
-                    (Type::RawPtr(_), BinaryOp::Sub) => {
-                        let span = right.span;
-                        let right = self.check_expr(*right, Some(TypeId::ISIZE));
-                        let right = self.try_coerce(right, TypeId::ISIZE);
-                        if right.ty == left.ty {
-                            CExpr::new(
-                                TypeId::ISIZE,
-                                CExprData::Binary(op, left.into(), right.into()),
-                            )
-                        } else if self.proj.types[right.ty].is_integral()
-                            || right.ty == TypeId::UNKNOWN
-                        {
-                            CExpr::new(left.ty, CExprData::Binary(op, left.into(), right.into()))
-                        } else {
-                            self.proj.diag.error(Error::type_mismatch_s(
-                                "{integer}",
-                                &right.ty.name(&self.proj.scopes, &mut self.proj.types),
-                                span,
-                            ));
-                            Default::default()
-                        }
-                    }
-                    (
-                        Type::RawPtr(_),
-                        BinaryOp::Add | BinaryOp::AddAssign | BinaryOp::SubAssign,
-                    ) => {
-                        let span = right.span;
-                        let right = self.check_expr(*right, Some(TypeId::USIZE));
-                        let right = self.try_coerce(right, TypeId::USIZE);
-                        if !self.proj.types[right.ty].is_integral() && right.ty != TypeId::UNKNOWN {
-                            self.proj.diag.error(Error::type_mismatch_s(
-                                "{integer}",
-                                &right.ty.name(&self.proj.scopes, &mut self.proj.types),
-                                span,
-                            ));
-                        }
-                        CExpr::new(
-                            if assignment { TypeId::VOID } else { left.ty },
-                            CExprData::Binary(op, left.into(), right.into()),
-                        )
-                    }
-                    (
-                        Type::Int(_)
-                        | Type::Uint(_)
-                        | Type::CInt(_)
-                        | Type::CUint(_)
-                        | Type::Isize
-                        | Type::Usize,
-                        BinaryOp::Shl | BinaryOp::Shr | BinaryOp::ShlAssign | BinaryOp::ShrAssign,
-                    ) => {
-                        let right = self.check_expr(*right, Some(TypeId::U32));
-                        let right = self.try_coerce(right, TypeId::U32);
-                        if !self.proj.types[right.ty]
-                            .as_integral(false)
-                            .is_some_and(|v| !v.signed)
-                            && right.ty != TypeId::UNKNOWN
-                        {
-                            self.proj.diag.error(Error::type_mismatch_s(
-                                "{unsigned}",
-                                &right.ty.name(&self.proj.scopes, &mut self.proj.types),
-                                span,
-                            ));
-                        }
-                        CExpr::new(
-                            if assignment { TypeId::VOID } else { left.ty },
-                            CExprData::Binary(op, left.into(), right.into()),
-                        )
-                    }
-                    _ => {
-                        let right = self.type_check(*right, left.ty);
-                        CExpr::new(
-                            match op {
-                                BinaryOp::NoneCoalesce => unreachable!(),
-                                BinaryOp::Cmp => self.make_lang_type_by_name("ordering", [], span),
-                                BinaryOp::Gt
-                                | BinaryOp::GtEqual
-                                | BinaryOp::Lt
-                                | BinaryOp::LtEqual
-                                | BinaryOp::Equal
-                                | BinaryOp::NotEqual
-                                | BinaryOp::LogicalOr
-                                | BinaryOp::LogicalAnd => TypeId::BOOL,
-                                op if op.is_assignment() => TypeId::VOID,
-                                _ => left.ty,
-                            },
-                            CExprData::Binary(op, left.into(), right.into()),
-                        )
-                    }
-                }
-            }
-            PExprData::Unary { op, expr } => {
-                let (out_ty, expr) = match op {
-                    UnaryOp::Deref => {
-                        let expr = if let Some(target) = target {
-                            let ty = self.proj.types.insert(Type::Ptr(target));
-                            self.check_expr(*expr, Some(ty))
-                        } else {
-                            self.check_expr(*expr, target)
-                        };
-
-                        match self.proj.types[expr.ty] {
-                            Type::Ptr(inner) | Type::MutPtr(inner) => (inner, expr),
-                            Type::RawPtr(inner) => {
-                                if self.safety != Safety::Unsafe {
-                                    self.proj.diag.error(Error::is_unsafe(span));
-                                }
-
-                                (inner, expr)
-                            }
-                            Type::Unknown => return Default::default(),
-                            _ => {
-                                bail!(
-                                    self,
-                                    Error::invalid_operator(
-                                        op,
-                                        &expr.ty.name(&self.proj.scopes, &mut self.proj.types),
-                                        span,
-                                    )
-                                )
-                            }
-                        }
-                    }
-                    UnaryOp::Addr => {
-                        let expr = self.check_expr(
-                            *expr,
-                            target.and_then(|id| id.as_pointee(&self.proj.types)),
-                        );
-                        match &expr.data {
-                            CExprData::Call(inner, _) => {
-                                // FIXME: don't test by name
-                                if matches!(&inner.data, CExprData::Fn(f, _)
-                                    if self.proj.scopes.get(f.id).name.data.starts_with("$sub"))
-                                {
-                                    self.proj.diag.warn(Error::subscript_addr(span));
-                                }
-                            }
-                            CExprData::Member { source, .. } => {
-                                if source.ty.is_packed_struct(&self.proj) {
-                                    self.proj.diag.warn(Error::bitfield_addr(span));
-                                }
-                            }
-                            CExprData::Fn(_, _) => {
-                                let Type::Fn(f) = &self.proj.types[expr.ty] else {
-                                    unreachable!()
-                                };
-                                let f = f.clone();
-                                let fptr = Type::FnPtr(
-                                    f.as_fn_ptr(&self.proj.scopes, &mut self.proj.types),
-                                );
-                                return CExpr::new(self.proj.types.insert(fptr), expr.data);
-                            }
-                            _ => {}
-                        }
-                        (self.proj.types.insert(Type::Ptr(expr.ty)), expr)
-                    }
-                    UnaryOp::AddrMut => {
-                        let expr = self.check_expr(
-                            *expr,
-                            target.and_then(|id| id.as_pointee(&self.proj.types)),
-                        );
-                        if !expr.can_addrmut(&self.proj.scopes, &self.proj.types) {
-                            self.error(Error::new(
-                                "cannot create mutable pointer to immutable memory location",
-                                span,
-                            ))
-                        }
-                        match &expr.data {
-                            CExprData::Call(inner, _) => {
-                                // FIXME: don't test by name
-                                if matches!(&inner.data, CExprData::Fn(f, _)
-                                    if self.proj.scopes.get(f.id).name.data.starts_with("$sub"))
-                                {
-                                    self.proj.diag.warn(Error::subscript_addr(span));
-                                }
-                            }
-                            CExprData::Member { source, .. } => {
-                                if source.ty.is_packed_struct(&self.proj) {
-                                    self.proj.diag.warn(Error::bitfield_addr(span));
-                                }
-                            }
-                            CExprData::Fn(_, _) => {
-                                self.proj.diag.warn(Error::new(
-                                    "&mut on function creates immutable function pointer (use &)",
-                                    span,
-                                ));
-
-                                let Type::Fn(f) = &self.proj.types[expr.ty] else {
-                                    unreachable!()
-                                };
-                                let f = f.clone();
-                                let fptr = Type::FnPtr(
-                                    f.as_fn_ptr(&self.proj.scopes, &mut self.proj.types),
-                                );
-                                return CExpr::new(self.proj.types.insert(fptr), expr.data);
-                            }
-                            _ => {}
-                        }
-                        (self.proj.types.insert(Type::MutPtr(expr.ty)), expr)
-                    }
-                    UnaryOp::AddrRaw => {
-                        let expr = self.check_expr(
-                            *expr,
-                            target.and_then(|id| id.as_pointee(&self.proj.types)),
-                        );
-                        match &expr.data {
-                            CExprData::Call(inner, _) => {
-                                // FIXME: don't test by name
-                                if matches!(&inner.data, CExprData::Fn(f, _)
-                                    if self.proj.scopes.get(f.id).name.data.starts_with("$sub"))
-                                {
-                                    self.proj.diag.warn(Error::subscript_addr(span));
-                                }
-                            }
-                            CExprData::Member { source, .. } => {
-                                if source.ty.is_packed_struct(&self.proj) {
-                                    self.proj.diag.warn(Error::bitfield_addr(span));
-                                }
-                            }
-                            CExprData::Fn(_, _) => self
-                                .error(Error::new("cannot create raw pointer to function", span)),
-                            _ => {}
-                        }
-                        (self.proj.types.insert(Type::RawPtr(expr.ty)), expr)
-                    }
-                    UnaryOp::Try => {
-                        let expr = self
-                            .check_expr(*expr, target.and_then(|t| t.as_option_inner(&self.proj)));
-                        if let Some(inner) = expr.ty.as_option_inner(&self.proj) {
-                            // TODO: lambdas
-                            if self
-                                .current_function()
-                                .and_then(|id| {
-                                    self.proj.scopes.get(id).ret.as_option_inner(&self.proj)
-                                })
-                                .is_none()
-                            {
-                                self.error(Error::new(
-                                    "operator '?' is only valid in functions that return Option",
-                                    span,
-                                ))
-                            }
-
-                            (inner, expr)
-                        } else if expr.ty == TypeId::UNKNOWN {
-                            return Default::default();
-                        } else {
-                            bail!(
-                                self,
-                                Error::invalid_operator(
-                                    op,
-                                    &expr.ty.name(&self.proj.scopes, &mut self.proj.types),
-                                    span,
-                                )
-                            );
-                        }
-                    }
-                    _ => {
-                        let span = expr.span;
-                        let expr = self.check_expr(*expr, target);
-                        if !expr
-                            .ty
-                            .supports_unary(&self.proj.scopes, &self.proj.types, op)
-                        {
-                            return self.check_unary(expr, op, span);
-                        }
-
-                        if matches!(
-                            op,
-                            UnaryOp::PostIncrement
-                                | UnaryOp::PostDecrement
-                                | UnaryOp::PreIncrement
-                                | UnaryOp::PreDecrement
-                        ) && !expr.is_assignable(&self.proj.scopes, &self.proj.types)
-                        {
-                            self.error(Error::new("expression is not assignable", span))
-                        }
-
-                        (expr.ty, expr)
-                    }
-                };
-
-                CExpr::new(out_ty, CExprData::Unary(op, expr.into()))
+                self.check_binary_expr(op, left, right, target, span)
             }
+            PExprData::Unary { op, expr } => self.check_unary_expr(op, expr, target, span),
             PExprData::Call { callee, args } => self.check_call(target, *callee, args, span),
             PExprData::Array(elements) => {
                 let mut checked = Vec::with_capacity(elements.len());
                 let mut elements = elements.into_iter();
-                let ty = if let Some(Type::Array(ty, _)) = target.map(|t| &self.proj.types[t]) {
+                let ty = if let Some(Type::Array(ty, _) | Type::GenericArray(ty, _)) =
+                    target.map(|t| &self.proj.types[t])
+                {
                     *ty
                 } else if let Some(expr) = elements.next() {
                     let expr = self.check_expr(expr, None);
@@ -2649,20 +2723,15 @@ impl TypeChecker {
                 )
             }
             PExprData::ArrayWithInit { init, count } => {
-                let init = if let Some(&Type::Array(ty, _)) = target.map(|t| &self.proj.types[t]) {
+                let init = if let Some(&Type::Array(ty, _) | &Type::GenericArray(ty, _)) =
+                    target.map(|t| &self.proj.types[t])
+                {
                     self.type_check(*init, ty)
                 } else {
                     self.check_expr(*init, target)
                 };
-                if let Some(res) = self.consteval_check(*count, TypeId::USIZE) {
-                    let count = res.val.try_into().unwrap();
-                    CExpr::new(
-                        self.proj.types.insert(Type::Array(init.ty, count)),
-                        CExprData::ArrayWithInit {
-                            init: init.into(),
-                            count,
-                        },
-                    )
+                if let Some(ty) = self.array_type(init.ty, *count) {
+                    CExpr::new(ty, CExprData::ArrayWithInit(init.into()))
                 } else {
                     Default::default()
                 }
@@ -2857,74 +2926,7 @@ impl TypeChecker {
                 self.make_lang_type_by_name("string", [], span),
                 CExprData::String(s),
             ),
-            PExprData::StringInterpolation(parts) => {
-                let Some(fmt_id) = self.proj.scopes.lang_traits.get("format").copied() else {
-                    return self.error(Error::no_lang_item("Format", span));
-                };
-                let formatter = self
-                    .proj
-                    .scopes
-                    .lang_types
-                    .get("string_formatter")
-                    .and_then(|&id| {
-                        self.proj.scopes[self.proj.scopes.get(id).body_scope]
-                            .find_in_vns("new")
-                            .and_then(|f| f.into_fn().ok())
-                            .map(|f| {
-                                CExpr::new(
-                                    self.proj.types.insert(Type::User(GenericUserType::new(
-                                        id,
-                                        Default::default(),
-                                    ))),
-                                    CExprData::call(
-                                        &mut self.proj.types,
-                                        GenericFn::new(f, Default::default()),
-                                        Default::default(),
-                                        self.current,
-                                    ),
-                                )
-                            })
-                    })
-                    .unwrap_or_else(|| self.error(Error::no_lang_item("String Formatter", span)));
-                let mut out_parts = Vec::with_capacity(parts.len());
-                for expr in parts {
-                    let span = expr.span;
-                    let expr = self.check_expr(expr, None);
-                    let ty = expr.ty.strip_references(&self.proj.types);
-                    if ty == TypeId::UNKNOWN {
-                        continue;
-                    }
-
-                    let Some(mfn) = self.get_member_fn_ex(
-                        ty,
-                        Some(&GenericTrait::from_type_args(&self.proj.scopes, fmt_id, [])),
-                        "fmt",
-                        self.current,
-                        |this, id| TypeArgs::in_order(&this.proj.scopes, id, [formatter.ty]),
-                    ) else {
-                        self.proj.diag.error(Error::doesnt_implement(
-                            &ty.name(&self.proj.scopes, &mut self.proj.types),
-                            "Format",
-                            span,
-                        ));
-                        continue;
-                    };
-
-                    let ptr_to_unk = self.proj.types.insert(Type::Ptr(TypeId::UNKNOWN));
-                    if !matches!(&expr.data, CExprData::String(s) if s.is_empty()) {
-                        out_parts.push((mfn, expr.auto_deref(&mut self.proj.types, ptr_to_unk)));
-                    }
-                }
-
-                CExpr::new(
-                    self.make_lang_type_by_name("string", [], span),
-                    CExprData::StringInterp {
-                        parts: out_parts,
-                        formatter: formatter.into(),
-                        scope: self.current,
-                    },
-                )
-            }
+            PExprData::StringInterpolation(parts) => self.check_string_interpolation(parts, span),
             PExprData::ByteString(s) => {
                 let arr = self.proj.types.insert(Type::Array(TypeId::U8, s.len()));
                 CExpr::new(
@@ -2956,138 +2958,7 @@ impl TypeChecker {
                     .unwrap_or(TypeId::F64),
                 CExprData::Float(value),
             ),
-            PExprData::Path(path) => match self.resolve_value_path(&path, target) {
-                ResolvedValue::Var(id) => {
-                    let var = self.proj.scopes.get(id);
-                    if !var.is_static {
-                        if self.current_function() != self.proj.scopes.function_of(var.scope) {
-                            self.proj.diag.error(Error::new(
-                                "cannot reference local variable of enclosing function",
-                                span,
-                            ));
-                        }
-                        if self
-                            .current_static
-                            .as_ref()
-                            .is_some_and(|v| !self.is_var_accessible(v.0, var.scope))
-                        {
-                            self.proj.diag.error(Error::new(
-                                "cannot reference local variable from outside of static initializer",
-                                span,
-                            ));
-                        }
-                    } else if let Some((cur, deps)) = &mut self.current_static {
-                        deps.push(id);
-                        let recursive = match self.proj.static_deps.get(&id) {
-                            Some(Dependencies::Resolved(v_deps)) => v_deps.contains(cur),
-                            Some(Dependencies::Resolving) => true,
-                            _ => false,
-                        };
-                        if recursive {
-                            self.proj.diag.error(Error::new(
-                                "static initializer depends directly or indirectly on itself",
-                                span,
-                            ));
-                        }
-                    }
-
-                    let ty = var.ty;
-                    self.proj.scopes.get_mut(id).unused = false;
-                    CExpr::new(ty, CExprData::Var(id))
-                }
-                ResolvedValue::Fn(mut func) => {
-                    let unknowns: HashSet<_> = func
-                        .ty_args
-                        .iter()
-                        .filter_map(|(&id, &ty)| (ty == TypeId::UNKNOWN).then_some(id))
-                        .collect();
-                    if let Some(target) = target {
-                        func.infer_type_args(
-                            &self.proj.types,
-                            self.proj.scopes.get(func.id).ret,
-                            target,
-                        );
-                    }
-                    self.check_bounds_filtered(&func, &unknowns, path.final_component_span());
-
-                    if let Some(id) = self.proj.scopes.get(func.id).constructor {
-                        if self
-                            .proj
-                            .scopes
-                            .get(id)
-                            .is_empty_variant(&self.proj.scopes.get(func.id).name.data)
-                        {
-                            return CExpr::new(
-                                self.proj
-                                    .types
-                                    .insert(Type::User(GenericUserType::new(id, func.ty_args))),
-                                CExprData::VariantInstance(
-                                    self.proj.scopes.get(func.id).name.data.clone(),
-                                    Default::default(),
-                                ),
-                            );
-                        }
-                    }
-
-                    CExpr::new(
-                        self.proj.types.insert(Type::Fn(func.clone())),
-                        CExprData::Fn(func, self.current),
-                    )
-                }
-                ResolvedValue::MemberFn(mut mfn) => {
-                    let unknowns: HashSet<_> = mfn
-                        .func
-                        .ty_args
-                        .iter()
-                        .filter_map(|(&id, &ty)| (ty == TypeId::UNKNOWN).then_some(id))
-                        .collect();
-                    if let Some(target) = target {
-                        mfn.func.infer_type_args(
-                            &self.proj.types,
-                            self.proj.scopes.get(mfn.func.id).ret,
-                            target,
-                        );
-                    }
-                    self.check_bounds_filtered(&mfn.func, &unknowns, path.final_component_span());
-
-                    if let Some(id) = self.proj.scopes.get(mfn.func.id).constructor {
-                        if self
-                            .proj
-                            .scopes
-                            .get(id)
-                            .is_empty_variant(&self.proj.scopes.get(mfn.func.id).name.data)
-                        {
-                            return CExpr::new(
-                                self.proj
-                                    .types
-                                    .insert(Type::User(GenericUserType::new(id, mfn.func.ty_args))),
-                                CExprData::VariantInstance(
-                                    self.proj.scopes.get(mfn.func.id).name.data.clone(),
-                                    Default::default(),
-                                ),
-                            );
-                        }
-                    }
-
-                    CExpr::new(
-                        self.proj.types.insert(Type::Fn(mfn.func.clone())),
-                        CExprData::MemFn(mfn, self.current),
-                    )
-                }
-                ResolvedValue::UnionConstructor(ut) => bail!(
-                    self,
-                    Error::expected_found(
-                        "expression",
-                        &format!(
-                            "type '{}'",
-                            ut.name(&self.proj.scopes, &mut self.proj.types)
-                        ),
-                        span,
-                    )
-                ),
-                ResolvedValue::NotFound(err) => self.error(err),
-                ResolvedValue::Error => Default::default(),
-            },
+            PExprData::Path(path) => self.check_path_expr(path, target, span),
             PExprData::Block(body, label) => {
                 let block = self.create_block(
                     body,
@@ -3096,268 +2967,73 @@ impl TypeChecker {
                         yields: false,
                         label,
                         branches: false,
+                        catches: false,
                     }),
                 );
-                let data = self.proj.scopes[block.scope].kind.as_block().unwrap();
-                let target = match (data.branches, data.yields) {
-                    (true, false) => Some(TypeId::NEVER),
-                    (_, true) => data.target,
-                    _ => None,
-                };
-                CExpr::new(target.unwrap_or(TypeId::VOID), CExprData::Block(block))
+                CExpr::new(self.block_type(&block), CExprData::Block(block))
             }
             PExprData::If {
                 cond,
                 if_branch,
                 else_branch,
-            } => {
-                let (cond, vars) = self.type_check_with_listen(*cond);
-                let target = if else_branch.is_none() {
-                    target.and_then(|t| t.as_option_inner(&self.proj))
-                } else {
-                    target
-                };
-
-                let if_span = if_branch.span;
-                let mut if_branch = self.enter(ScopeKind::None, |this| {
-                    this.define(&vars);
-                    this.check_expr_inner(*if_branch, target)
-                });
-                if let Some(target) = target {
-                    if_branch = self.type_check_checked(if_branch, target, if_span);
-                }
-
-                let mut out_type = if_branch.ty;
-                let else_branch =
-                    if let Some(expr) = else_branch {
-                        if out_type == TypeId::NEVER {
-                            let expr = self.check_expr_inner(*expr, None);
-                            out_type = expr.ty;
-                            if_branch = self.try_coerce(if_branch, expr.ty);
-                            Some(expr)
-                        } else {
-                            let span = expr.span;
-                            let source = self.check_expr_inner(*expr, target.or(Some(out_type)));
-                            if source
-                                .ty
-                                .as_option_inner(&self.proj)
-                                .is_some_and(|v| v == out_type)
-                            {
-                                if_branch = self.coerce(if_branch, source.ty).unwrap();
-                                out_type = source.ty;
-                                Some(source)
-                            } else {
-                                Some(self.type_check_checked(source, out_type, span))
-                            }
-                        }
-                    } else if if_branch.data.is_yielding_block(&self.proj.scopes) {
-                        if out_type == TypeId::NEVER
-                            || out_type == TypeId::VOID
-                            || out_type == TypeId::UNKNOWN
-                        {
-                            out_type = TypeId::VOID;
-                            Some(CExpr::new(TypeId::VOID, CExprData::Void))
-                        } else {
-                            out_type = self.make_lang_type_by_name("option", [out_type], span);
-                            if_branch = self.try_coerce(if_branch, out_type);
-                            Some(self.check_expr_inner(
-                                Located::new(span, PExprData::None),
-                                Some(out_type),
-                            ))
-                        }
-                    } else {
-                        None
-                    };
-
-                CExpr::new(
-                    out_type,
-                    CExprData::If {
-                        cond: cond.into(),
-                        if_branch: if_branch.into(),
-                        else_branch: else_branch.map(|e| e.into()),
-                    },
-                )
-            }
-            PExprData::Loop {
-                cond,
-                body,
-                do_while,
-                label,
-            } => {
-                let infinite = cond.is_none();
-                let target = self.loop_target(target, infinite);
-                let (cond, body) = if let Some(cond) = cond {
-                    let (cond, vars) = self.type_check_with_listen(*cond);
-                    let body = self.create_block_with_init(
-                        body,
-                        ScopeKind::Loop(LoopScopeKind {
-                            target,
-                            breaks: LoopBreak::None,
-                            infinite,
-                            label,
-                        }),
-                        |this| {
-                            if !do_while {
-                                this.define(&vars);
-                            }
-                        },
-                    );
-                    (Some(cond.into()), body)
-                } else {
-                    (
-                        None,
-                        self.create_block(
-                            body,
-                            ScopeKind::Loop(LoopScopeKind {
-                                target,
-                                breaks: LoopBreak::None,
-                                infinite,
-                                label,
-                            }),
-                        ),
-                    )
-                };
-                let (out_type, optional) =
-                    self.loop_out_type(&self.proj.scopes[body.scope].kind.clone(), span);
-                CExpr::new(
-                    out_type,
-                    CExprData::Loop {
-                        cond,
-                        body,
-                        do_while,
-                        optional,
-                    },
-                )
-            }
-            PExprData::For {
-                patt,
-                iter,
-                body,
-                label,
-            } => self.check_for_expr(target, patt, iter, body, label),
-            PExprData::Member {
-                source,
-                member: name,
-                generics,
-            } => {
-                if !generics.is_empty() {
-                    self.error(Error::new(
-                        "member variables cannot have type arguments",
-                        span,
-                    ))
-                }
-
-                let source = self.check_expr(*source, None);
-                let id = source.ty.strip_references(&self.proj.types);
-                self.check_dot_completions(span, id, true);
-                let ut_id = match &self.proj.types[id] {
-                    Type::User(data) => data.id,
-                    Type::Unknown => return Default::default(),
-                    _ => {
-                        bail!(
-                            self,
-                            Error::no_member(
-                                &id.name(&self.proj.scopes, &mut self.proj.types),
-                                &name.data,
-                                name.span,
-                            )
-                        );
-                    }
-                };
-                self.resolve_members(ut_id);
-                self.check_hover(
-                    name.span,
-                    LspItem::Property(Some(source.ty), ut_id, name.data.clone()),
-                );
-
-                let ut = self.proj.scopes.get(ut_id);
-                let Some(member) = ut.members.get(&name.data) else {
-                    bail!(
-                        self,
-                        Error::no_member(
-                            &source.ty.name(&self.proj.scopes, &mut self.proj.types),
-                            &name.data,
-                            name.span,
-                        )
-                    );
-                };
-
-                if ut.kind.is_unsafe_union() && self.safety != Safety::Unsafe {
-                    self.proj.diag.error(Error::is_unsafe(name.span));
-                }
-
-                let ty = member.ty.with_ut_templates(&mut self.proj.types, id);
-                if !member.public && !self.can_access_privates(ut.scope) {
-                    self.proj.diag.error(Error::private_member(
-                        &id.name(&self.proj.scopes, &mut self.proj.types),
-                        &name.data,
-                        name.span,
-                    ));
-                }
-                CExpr::new(
-                    ty,
-                    CExprData::Member {
-                        source: source.auto_deref(&mut self.proj.types, id).into(),
-                        member: name.data,
-                    },
-                )
-            }
-            PExprData::Subscript { callee, args } => {
-                if args.is_empty() {
-                    return self
-                        .error(Error::new("subscript requires at least one argument", span));
-                }
+            } => self.check_if_expr(cond, if_branch, else_branch, target, span),
+            PExprData::Loop {
+                cond,
+                body,
+                do_while,
+                label,
+            } => self.check_loop_expr(cond, body, do_while, label, target, span),
+            PExprData::For {
+                patt,
+                iter,
+                body,
+                label,
+            } => self.check_for_expr(target, patt, iter, body, label),
+            PExprData::Member {
+                source,
+                member,
+                generics,
+            } => self.check_member_expr(source, generics, member, span),
+            PExprData::Subscript { callee, args } => {
+                if args.is_empty() {
+                    return self
+                        .error(Error::new("subscript requires at least one argument", span));
+                }
 
                 let callee = self.check_expr(*callee, None);
                 let stripped = callee.ty.strip_references(&self.proj.types);
-                if let &Type::Array(target, _) = &self.proj.types[stripped] {
-                    self.check_array_subscript(target, callee, args)
+                if let &Type::Array(target, _) | &Type::GenericArray(target, _) =
+                    &self.proj.types[stripped]
+                {
+                    self.check_array_subscript(target, callee, args, span)
                 } else {
                     self.check_subscript(callee, stripped, args, target, false, span)
                 }
             }
             PExprData::Return(expr) => self.check_return(*expr, span),
+            PExprData::Await(expr) => self.check_await(*expr, span),
+            PExprData::Yield(expr) => self.check_generator_yield(*expr, span),
+            PExprData::Raise(expr) => {
+                let Some((scope, err_ty)) = self.raise_target("'raise'", None, span) else {
+                    self.check_expr(*expr, None);
+                    return Default::default();
+                };
+                let span = expr.span;
+                let err = self.check_expr(*expr, err_ty);
+                self.raise_to(scope, err, span)
+            }
+            PExprData::Try {
+                body,
+                catch,
+                handler,
+            } => self.check_try(target, body, catch, handler),
             PExprData::Tail(expr) => match &self.proj.scopes[self.current].kind {
-                ScopeKind::Function(_) | ScopeKind::Lambda(_, _) => self.check_return(*expr, span),
+                ScopeKind::Function(_) | ScopeKind::Lambda(_) => self.check_return(*expr, span),
                 ScopeKind::Loop { .. } => self.type_check(*expr, TypeId::VOID),
                 ScopeKind::Block(data) => self.check_yield(Some(expr), data.clone(), self.current),
                 _ => self.error(Error::new("yield outside of block", expr.span)),
             },
-            PExprData::Break(expr, label) => {
-                if let Some(label) = label {
-                    let label_data = Some(&label.data);
-                    for (id, scope) in self.proj.scopes.walk(self.current) {
-                        if scope.kind.is_defer() {
-                            break;
-                        }
-
-                        match &scope.kind {
-                            ScopeKind::Loop(data) if data.label.as_ref() == label_data => {
-                                return self.check_break(expr, data.clone(), id);
-                            }
-                            ScopeKind::Block(data) if data.label.as_ref() == label_data => {
-                                return self.check_yield(expr, data.clone(), id);
-                            }
-                            _ => {}
-                        }
-                    }
-
-                    if let Some(expr) = expr {
-                        self.check_expr(*expr, None);
-                    }
-                    return self
-                        .error(Error::new(format!("undefined label '{label}'"), label.span));
-                }
-
-                let Some((loop_data, id)) = self.current_loop(&None) else {
-                    if let Some(expr) = expr {
-                        self.check_expr(*expr, None);
-                    }
-                    return self.error(Error::new("break outside of loop", span));
-                };
-
-                self.check_break(expr, loop_data.clone(), id)
-            }
+            PExprData::Break(expr, label) => self.check_break_expr(expr, label, span),
             PExprData::Continue(label) => {
                 let Some((_, id)) = self.current_loop(&label) else {
                     if let Some(label) = label {
@@ -3385,196 +3061,20 @@ impl TypeChecker {
                 std::mem::swap(&mut this.current_expr, &mut prev);
                 CExpr::new(TypeId::BOOL, CExprData::Is(expr.into(), patt))
             }),
-            PExprData::Match { expr, body } => {
-                let scrutinee = self.check_expr(*expr, None);
-                let mut has_never = false;
-                let mut target = target;
-                let mut result: Vec<_> = body
-                    .into_iter()
-                    .map(|(patt, expr)| {
-                        let span = expr.span;
-                        let (patt, expr) = self.enter(ScopeKind::None, |this| {
-                            (
-                                this.check_full_pattern(scrutinee.ty, patt),
-                                this.check_expr(expr, target),
-                            )
-                        });
-
-                        if let Some(target) = target {
-                            (patt, self.type_check_checked(expr, target, span))
-                        } else {
-                            if expr.ty == TypeId::NEVER {
-                                has_never = true;
-                            } else {
-                                target = Some(expr.ty);
-                            }
-
-                            (patt, expr)
-                        }
-                    })
-                    .collect();
-                let target = target.unwrap_or(if has_never {
-                    TypeId::NEVER
-                } else {
-                    TypeId::VOID
-                });
-                if !matches!(target, TypeId::NEVER | TypeId::VOID) {
-                    for (_, e) in result.iter_mut() {
-                        *e = self.try_coerce(std::mem::take(e), target);
-                    }
-                }
-
-                self.check_match_coverage(scrutinee.ty, result.iter().map(|it| &it.0), span);
-                CExpr::new(
-                    target,
-                    CExprData::Match {
-                        expr: scrutinee.into(),
-                        body: result,
-                    },
-                )
-            }
-            PExprData::As { expr, ty, throwing } => {
-                let to_id = self.resolve_typehint(&ty);
-                let expr = self.check_expr(*expr, Some(to_id));
-                let expr = match self.coerce(expr, to_id) {
-                    Ok(expr) => return expr,
-                    Err(expr) => expr,
-                };
-
-                let mut from_id = expr.ty;
-                if let Type::User(ut) = &self.proj.types[from_id] {
-                    let id = ut.id;
-                    self.resolve_members(id);
-                    if let Some(tag) = self
-                        .proj
-                        .scopes
-                        .get(id)
-                        .kind
-                        .as_union()
-                        .filter(|u| u.enum_union)
-                        .map(|u| u.tag)
-                    {
-                        from_id = tag;
-                    }
-                }
-
-                match Cast::get(&self.proj.types[from_id], &self.proj.types[to_id]) {
-                    Cast::None => self.proj.diag.error(Error::new(
-                        format!(
-                            "cannot cast expression of type '{}' to '{}'",
-                            from_id.name(&self.proj.scopes, &mut self.proj.types),
-                            to_id.name(&self.proj.scopes, &mut self.proj.types),
-                        ),
-                        span,
-                    )),
-                    Cast::Unsafe if self.safety != Safety::Unsafe => {
-                        self.error(Error::is_unsafe(span))
-                    }
-                    Cast::Fallible if !throwing => self.proj.diag.error(Error::new(
-                        format!(
-                            "cast of expression of type '{}' to '{}' requires fallible cast",
-                            from_id.name(&self.proj.scopes, &mut self.proj.types),
-                            to_id.name(&self.proj.scopes, &mut self.proj.types),
-                        ),
-                        span,
-                    )),
-                    Cast::Infallible if throwing => self.proj.diag.warn(Error::new(
-                        format!(
-                            "cast from type '{}' to '{}' is infallible and may use an `as` cast",
-                            from_id.name(&self.proj.scopes, &mut self.proj.types),
-                            to_id.name(&self.proj.scopes, &mut self.proj.types),
-                        ),
-                        span,
-                    )),
-                    _ => {}
-                }
-                CExpr::new(to_id, CExprData::As(expr.into(), throwing))
-            }
+            PExprData::Match { expr, body } => self.check_match_expr(expr, body, target, span),
+            PExprData::As { expr, ty, throwing } => self.check_as_expr(expr, ty, throwing, span),
             PExprData::Error => CExpr::default(),
-            PExprData::Lambda {
-                params,
-                ret,
-                body,
-                moves: _,
-            } => {
-                let ty_is_generic = |this: &TypeChecker, ty: TypeId| {
-                    !this
-                        .proj
-                        .types
-                        .get(ty)
-                        .as_user()
-                        .is_some_and(|ut| this.proj.scopes.get(ut.id).kind.is_template())
-                };
-
-                let mut lparams = Vec::new();
-                let ret = ret.map(|ret| self.resolve_typehint(&ret)).or_else(|| {
-                    target
-                        .as_ref()
-                        .and_then(|&ty| self.proj.types[ty].as_fn_ptr())
-                        .and_then(|f| ty_is_generic(self, f.ret).then_some(f.ret))
-                });
-                // TODO: lambdas should have a unique type
-                let (id, body) = self.enter(ScopeKind::Lambda(ret, false), |this| {
-                    for (i, (name, hint)) in params.into_iter().enumerate() {
-                        let has_hint = hint.is_some();
-                        let ty = hint
-                            .map(|ty| this.resolve_typehint(&ty))
-                            .or_else(|| {
-                                target
-                                    .as_ref()
-                                    .and_then(|&ty| this.proj.types[ty].as_fn_ptr())
-                                    .and_then(|f| f.params.get(i))
-                                    .filter(|&&ty| ty_is_generic(this, ty))
-                                    .cloned()
-                            })
-                            .unwrap_or_else(|| {
-                                this.error(Error::new(
-                                    format!("cannot infer type of parameter '{}'", name.data),
-                                    name.span,
-                                ))
-                            });
-
-                        lparams.push(ty);
-                        this.insert::<VariableId>(
-                            Variable {
-                                public: false,
-                                name,
-                                ty,
-                                is_static: false,
-                                mutable: false,
-                                value: None,
-                                unused: true,
-                                has_hint,
-                            },
-                            false,
-                            false,
-                        );
-                    }
-
-                    let body = if let PExprData::Block(body, _) = body.data {
-                        this.check_block(body)
-                    } else {
-                        vec![CStmt::Expr(this.check_expr(
-                            PExpr::new(body.span, PExprData::Return(body)),
-                            None,
-                        ))]
-                    };
-
-                    (this.current, body)
-                });
-                let (target, yields) = self.proj.scopes[id].kind.as_lambda().unwrap();
-                let fnptr = Type::FnPtr(FnPtr {
-                    params: lparams,
-                    ret: yields.then(|| *target).flatten().unwrap_or(TypeId::VOID),
-                });
-                CExpr::new(self.proj.types.insert(fnptr), CExprData::Lambda(body))
+            PExprData::Lambda { .. } => {
+                let sig = target.and_then(|target| self.callable_signature(target));
+                self.check_lambda(expr, target, sig)
             }
             PExprData::Unsafe(expr) => {
                 // for unsafe specifically, span is only the keyword
                 if self.safety == Safety::Unsafe {
-                    self.proj
-                        .diag
-                        .warn(Error::new("unsafe expression in unsafe context", span))
+                    self.lint(
+                        Lint::RedundantUnsafe,
+                        Error::new("unsafe expression in unsafe context", span),
+                    )
                 }
 
                 // consider the body of the
@@ -3587,345 +3087,2622 @@ impl TypeChecker {
         }
     }
 
-    fn check_expr(&mut self, expr: PExpr, target: Option<TypeId>) -> CExpr {
-        let expr = self.check_expr_inner(expr, target);
-        if expr.ty == TypeId::NEVER
-            && !matches!(expr.data, CExprData::Yield(_, scope) if scope == self.current)
-        {
-            // TODO: lambdas
-            if let ScopeKind::Block(BlockScopeKind { branches, .. }) =
-                &mut self.proj.scopes[self.current].kind
-            {
-                *branches = true;
-            }
+    /// Returns the local variable `left` names if `op` is a plain assignment and the variable hasn't
+    /// been read yet.
+    fn assigned_local(&mut self, op: BinaryOp, left: &PExpr) -> Option<VariableId> {
+        let PExprData::Path(path) = &left.data else {
+            return None;
+        };
+        if op != BinaryOp::Assign {
+            return None;
         }
-        expr
-    }
 
-    fn type_check_with_listen(&mut self, expr: PExpr) -> (CExpr, Vec<VariableId>) {
-        self.listen_for_vars(self.current_expr + 1, |this| {
-            this.type_check(expr, TypeId::BOOL)
-        })
+        let name = path.as_identifier()?;
+        let Some(ValueItem::Var(id)) = self.find_in_vns(name).map(|item| item.id) else {
+            return None;
+        };
+        let var = self.proj.scopes.get(id);
+        ((var.unused || self.unread_vars.contains(&id)) && !var.is_static).then_some(id)
     }
 
-    fn listen_for_vars<T>(
+    fn check_binary_expr(
         &mut self,
-        expr: usize,
-        f: impl FnOnce(&mut Self) -> T,
-    ) -> (T, Vec<VariableId>) {
-        let prev = std::mem::take(&mut self.listening_vars);
-        let prev_insp = std::mem::replace(&mut self.listening_expr, expr);
-        let res = f(self);
-        self.listening_expr = prev_insp;
-        (res, std::mem::replace(&mut self.listening_vars, prev))
-    }
-
-    fn define(&mut self, vars: &[VariableId]) {
-        for &var in vars.iter() {
-            let name = self.proj.scopes.get(var).name.data.clone();
-            self.proj.scopes[self.current]
-                .vns
-                .insert(name, Vis::new(ValueItem::Var(var), false));
-        }
-    }
+        op: BinaryOp,
+        left: Box<PExpr>,
+        right: Box<PExpr>,
+        target: Option<TypeId>,
+        span: Span,
+    ) -> CExpr {
+        let left_span = left.span;
+        let assignment = op.is_assignment();
+        match op {
+            BinaryOp::Assign => {
+                if let PExprData::Subscript { callee, mut args } = left.data {
+                    if args.is_empty() {
+                        return self
+                            .error(Error::new("subscript requires at least one argument", span));
+                    }
 
-    fn current_loop(&self, label: &Option<Located<String>>) -> Option<(&LoopScopeKind, ScopeId)> {
-        let label = label.as_ref().map(|l| &l.data);
-        self.proj
-            .scopes
-            .walk(self.current)
-            .take_while(|(_, scope)| !scope.kind.is_defer())
-            .find_map(|(id, scope)| {
-                scope
-                    .kind
-                    .as_loop()
-                    .filter(|l| label.is_none() || l.label.as_ref() == label)
-                    .zip(Some(id))
-            })
-    }
+                    let callee = self.check_expr(*callee, None);
+                    let stripped = callee.ty.strip_references(&self.proj.types);
+                    if let &Type::Array(inner, _) | &Type::GenericArray(inner, _) =
+                        &self.proj.types[stripped]
+                    {
+                        let left = self.check_array_subscript(inner, callee, args, left_span);
+                        if op.is_assignment() {
+                            if !left.is_assignable(&self.proj.scopes, &self.proj.types) {
+                                // TODO: report a better error here
+                                self.error(Error::new("expression is not assignable", left_span))
+                            }
+                            self.mark_lambda_mutation(&left);
+                        }
 
-    fn check_array_subscript(
-        &mut self,
-        target: TypeId,
-        callee: CExpr,
-        args: Vec<(Option<String>, PExpr)>,
-    ) -> CExpr {
-        fn maybe_span(this: &mut TypeChecker, ty: TypeId, imm: bool) -> Option<UserTypeId> {
-            let id = *this.proj.scopes.lang_traits.get("range_bounds")?;
-            let bound = GenericTrait::from_type_args(&this.proj.scopes, id, [TypeId::USIZE]);
-            if this.implements_trait(ty, &bound) {
-                let span_ty = if imm {
-                    *this.proj.scopes.lang_types.get("span")?
+                        let right = self.type_check(*right, left.ty);
+                        return CExpr::new(
+                            TypeId::VOID,
+                            CExprData::Binary(BinaryOp::Assign, left.into(), right.into()),
+                        );
+                    } else {
+                        args.push((None, *right));
+                        return self.check_subscript(callee, stripped, args, target, true, span);
+                    }
+                }
+            }
+            BinaryOp::NoneCoalesce | BinaryOp::NoneCoalesceAssign => {
+                let Some(id) = self.proj.scopes.get_option_id() else {
+                    return self.error(Error::no_lang_item("option", span));
+                };
+
+                let target = if let Some(target) = target {
+                    let ut = GenericUserType::from_type_args(&self.proj.scopes, id, [target]);
+                    Some(self.proj.types.insert(Type::User(ut)))
                 } else {
-                    *this.proj.scopes.lang_types.get("span_mut")?
+                    None
                 };
-                Some(span_ty)
-            } else {
-                None
+                let lhs_span = left.span;
+                let lhs = self.check_expr(*left, target);
+                let Some(target) = lhs.ty.as_option_inner(&self.proj) else {
+                    if lhs.ty != TypeId::UNKNOWN {
+                        self.proj.diag.error(Error::invalid_operator(
+                            op,
+                            &lhs.ty.name(&self.proj.scopes, &mut self.proj.types),
+                            lhs_span,
+                        ));
+                    }
+                    return Default::default();
+                };
+                if assignment {
+                    if !lhs.is_assignable(&self.proj.scopes, &self.proj.types) {
+                        // TODO: report a better error here
+                        self.error(Error::new("expression is not assignable", lhs_span))
+                    }
+                    self.mark_lambda_mutation(&lhs);
+                }
+
+                let span = right.span;
+                let rhs = self.check_expr_inner(*right, Some(target));
+                let rhs = self.type_check_checked(rhs, target, span);
+                return CExpr::new(
+                    if assignment { TypeId::VOID } else { target },
+                    CExprData::Binary(op, lhs.into(), rhs.into()),
+                );
+            }
+            BinaryOp::LogicalAnd => {
+                let was_listening = self.listening_expr == self.current_expr;
+                let (left, lvars) = self.type_check_with_listen(*left);
+                let (right, rvars) = self.enter(ScopeKind::None, |this| {
+                    this.define(&lvars);
+                    this.type_check_with_listen(*right)
+                });
+                if was_listening {
+                    self.listening_vars.extend(lvars);
+                    self.listening_vars.extend(rvars);
+                }
+
+                return CExpr::new(
+                    TypeId::BOOL,
+                    CExprData::Binary(op, left.into(), right.into()),
+                );
             }
+            _ => {}
         }
 
-        let mut args = args.into_iter();
-        let (name, expr) = args.next().unwrap();
-        if let Some(name) = name {
-            self.error(Error::new(
-                format!("unknown parameter: '{name}'"),
-                expr.span,
-            ))
+        let unread = self.assigned_local(op, &left);
+        let left = self.check_expr(*left, target);
+        if let Some(id) = unread {
+            // a plain assignment to a local doesn't read it
+            self.unread_vars.insert(id);
+        }
+        if left.ty == TypeId::UNKNOWN {
+            self.check_expr(*right, target);
+            return Default::default();
         }
 
-        let arg_span = expr.span;
-        let arg = self.check_expr(expr, Some(TypeId::USIZE));
-        if let Some((_, arg)) = args.next() {
-            let last = args.last().map(|(_, arg)| arg.span).unwrap_or(arg.span);
-            self.error(Error::new(
-                "multidimensional array subscript is not supported",
-                arg.span.extended_to(last),
-            ))
+        if assignment {
+            if !left.is_assignable(&self.proj.scopes, &self.proj.types) {
+                // TODO: report a better error here
+                self.error(Error::new("expression is not assignable", left_span))
+            }
+            self.mark_lambda_mutation(&left);
         }
 
-        match self.coerce(arg, TypeId::USIZE) {
-            Ok(expr) => CExpr::new(
-                target,
-                CExprData::Subscript {
-                    callee: callee.into(),
-                    arg: expr.into(),
-                },
-            ),
-            Err(expr) if self.proj.types[expr.ty].is_integral() => CExpr::new(
-                target,
-                CExprData::Subscript {
-                    callee: callee.into(),
-                    arg: expr.into(),
-                },
-            ),
-            Err(expr) => {
-                let Some(id) = maybe_span(self, expr.ty, self.immutable_receiver(&callee)) else {
-                    bail!(
-                        self,
-                        Error::expected_found(
-                            "array index",
-                            &format!(
-                                "type '{}'",
-                                expr.ty.name(&self.proj.scopes, &mut self.proj.types)
-                            ),
-                            arg_span,
-                        )
-                    );
-                };
+        if op != BinaryOp::Assign && !left.ty.supports_binary(&self.proj.types, op) {
+            return self.check_binary(left_span, left, *right, op, span);
+        }
 
+        match (&self.proj.types[left.ty], op) {
+            (Type::RawPtr(_), BinaryOp::Sub) => {
+                let span = right.span;
+                let right = self.check_expr(*right, Some(TypeId::ISIZE));
+                let right = self.try_coerce(right, TypeId::ISIZE);
+                if right.ty == left.ty {
+                    CExpr::new(
+                        TypeId::ISIZE,
+                        CExprData::Binary(op, left.into(), right.into()),
+                    )
+                } else if self.proj.types[right.ty].is_integral() || right.ty == TypeId::UNKNOWN {
+                    CExpr::new(left.ty, CExprData::Binary(op, left.into(), right.into()))
+                } else {
+                    self.proj.diag.error(Error::type_mismatch_s(
+                        "{integer}",
+                        &right.ty.name(&self.proj.scopes, &mut self.proj.types),
+                        span,
+                    ));
+                    Default::default()
+                }
+            }
+            (Type::RawPtr(_), BinaryOp::Add | BinaryOp::AddAssign | BinaryOp::SubAssign) => {
+                let span = right.span;
+                let right = self.check_expr(*right, Some(TypeId::USIZE));
+                let right = self.try_coerce(right, TypeId::USIZE);
+                if !self.proj.types[right.ty].is_integral() && right.ty != TypeId::UNKNOWN {
+                    self.proj.diag.error(Error::type_mismatch_s(
+                        "{integer}",
+                        &right.ty.name(&self.proj.scopes, &mut self.proj.types),
+                        span,
+                    ));
+                }
                 CExpr::new(
-                    self.proj
-                        .types
-                        .insert(Type::User(GenericUserType::from_type_args(
-                            &self.proj.scopes,
-                            id,
-                            [target],
-                        ))),
-                    CExprData::SliceArray {
-                        callee: callee.into(),
-                        arg: expr.into(),
+                    if assignment { TypeId::VOID } else { left.ty },
+                    CExprData::Binary(op, left.into(), right.into()),
+                )
+            }
+            (
+                Type::Int(_)
+                | Type::Uint(_)
+                | Type::CInt(_)
+                | Type::CUint(_)
+                | Type::Isize
+                | Type::Usize,
+                BinaryOp::Shl | BinaryOp::Shr | BinaryOp::ShlAssign | BinaryOp::ShrAssign,
+            ) => {
+                let right = self.check_expr(*right, Some(TypeId::U32));
+                let right = self.try_coerce(right, TypeId::U32);
+                if self.proj.types[right.ty]
+                    .as_integral(false)
+                    .is_none_or(|v| v.signed)
+                    && right.ty != TypeId::UNKNOWN
+                {
+                    self.proj.diag.error(Error::type_mismatch_s(
+                        "{unsigned}",
+                        &right.ty.name(&self.proj.scopes, &mut self.proj.types),
+                        span,
+                    ));
+                }
+                CExpr::new(
+                    if assignment { TypeId::VOID } else { left.ty },
+                    CExprData::Binary(op, left.into(), right.into()),
+                )
+            }
+            _ => {
+                let right = self.type_check(*right, left.ty);
+                CExpr::new(
+                    match op {
+                        BinaryOp::NoneCoalesce => unreachable!(),
+                        BinaryOp::Cmp => self.make_lang_type_by_name("ordering", [], span),
+                        BinaryOp::Gt
+                        | BinaryOp::GtEqual
+                        | BinaryOp::Lt
+                        | BinaryOp::LtEqual
+                        | BinaryOp::Equal
+                        | BinaryOp::NotEqual
+                        | BinaryOp::LogicalOr
+                        | BinaryOp::LogicalAnd => TypeId::BOOL,
+                        op if op.is_assignment() => TypeId::VOID,
+                        _ => left.ty,
                     },
+                    CExprData::Binary(op, left.into(), right.into()),
                 )
             }
         }
     }
 
-    fn check_subscript(
+    fn check_unary_expr(
         &mut self,
-        callee: CExpr,
-        ty: TypeId,
-        args: Vec<(Option<String>, PExpr)>,
+        op: UnaryOp,
+        expr: Box<PExpr>,
         target: Option<TypeId>,
-        assign: bool,
         span: Span,
     ) -> CExpr {
-        let imm_receiver = self.immutable_receiver(&callee);
-        if let Some(ut) = self.proj.types[ty].as_user().cloned() {
-            let mut candidates: Vec<_> = self
-                .proj
-                .scopes
-                .get(ut.id)
-                .subscripts
-                .iter()
-                .cloned()
-                .filter(|&f| assign == self.proj.scopes.get(f).assign_subscript)
-                .filter(|&f| self.proj.scopes.get(f).params.len() == args.len() + 1)
-                .collect();
-            candidates.iter().for_each(|&f| self.resolve_proto(f));
-            candidates.sort_unstable_by(|&a, &b| {
-                let left = self
-                    .proj
-                    .scopes
-                    .get(a)
-                    .params
-                    .first()
-                    .is_some_and(|p| self.proj.types[p.ty].is_mut_ptr());
-                let right = self
-                    .proj
-                    .scopes
-                    .get(b)
-                    .params
-                    .first()
-                    .is_some_and(|p| self.proj.types[p.ty].is_mut_ptr());
-                right.cmp(&left)
-            });
-
-            for f in candidates {
-                if imm_receiver
-                    && self
-                        .proj
-                        .scopes
-                        .get(f)
-                        .params
-                        .first()
-                        .is_some_and(|p| self.proj.types[p.ty].is_mut_ptr())
-                {
-                    continue;
-                }
+        let (out_ty, expr) = match op {
+            UnaryOp::Deref => {
+                let expr = if let Some(target) = target {
+                    let ty = self.proj.types.insert(Type::Ptr(target));
+                    self.check_expr(*expr, Some(ty))
+                } else {
+                    self.check_expr(*expr, target)
+                };
 
-                let mut func = GenericFn::from_id(&self.proj.scopes, f);
-                func.ty_args.copy_args(&ut.ty_args);
+                match self.proj.types[expr.ty] {
+                    Type::Ptr(inner) | Type::MutPtr(inner) => (inner, expr),
+                    Type::RawPtr(inner) => {
+                        if self.safety != Safety::Unsafe {
+                            self.proj.diag.error(Error::is_unsafe(span));
+                        }
 
-                let args = args.clone();
-                let recv = callee
-                    .clone()
-                    .auto_deref(&mut self.proj.types, self.proj.scopes.get(f).params[0].ty);
-                let err_idx = self.proj.diag.capture_errors();
-                let (args, ret, failed) =
-                    self.check_fn_args(&mut func, Some(recv), args, target, span);
-                // TODO: if the arguments have non overload related errors, just stop overload
-                // resolution
-                if failed
-                    || args
-                        .iter()
-                        .any(|arg| matches!(arg.1.data, CExprData::Error))
-                {
-                    self.proj.diag.truncate_errors(err_idx);
-                    continue;
+                        (inner, expr)
+                    }
+                    Type::Unknown => return Default::default(),
+                    _ => {
+                        bail!(
+                            self,
+                            Error::invalid_operator(
+                                op,
+                                &expr.ty.name(&self.proj.scopes, &mut self.proj.types),
+                                span,
+                            )
+                        )
+                    }
                 }
-                // unsafe doesnt cause check_fn_args to fail, but we mute errors, so check again
-                // here
-                if self.proj.scopes.get(func.id).is_unsafe && self.safety != Safety::Unsafe {
-                    self.error(Error::is_unsafe(span))
+            }
+            UnaryOp::Addr => {
+                let expr =
+                    self.check_expr(*expr, target.and_then(|id| id.as_pointee(&self.proj.types)));
+                match &expr.data {
+                    CExprData::Call(inner, _, _) => {
+                        // FIXME: don't test by name
+                        if matches!(&inner.data, CExprData::Fn(f, _)
+                            if self.proj.scopes.get(f.id).name.data.starts_with("$sub"))
+                        {
+                            self.lint(Lint::SubscriptAddr, Error::subscript_addr(span));
+                        }
+                    }
+                    CExprData::Member { source, .. } if source.ty.is_packed_struct(&self.proj) => {
+                        self.lint(Lint::BitfieldAddr, Error::bitfield_addr(span));
+                    }
+                    CExprData::Fn(_, _) => {
+                        let Type::Fn(f) = &self.proj.types[expr.ty] else {
+                            unreachable!()
+                        };
+                        let f = f.clone();
+                        let fptr =
+                            Type::FnPtr(f.as_fn_ptr(&self.proj.scopes, &mut self.proj.types));
+                        return CExpr::new(self.proj.types.insert(fptr), expr.data);
+                    }
+                    _ => {}
                 }
-
-                if !assign {
-                    if let Type::Ptr(inner) | Type::MutPtr(inner) = &self.proj.types[ret] {
-                        return CExpr::new(
-                            *inner,
-                            CExprData::AutoDeref(
-                                CExpr::new(
-                                    ret,
-                                    CExprData::call(&mut self.proj.types, func, args, self.current),
-                                )
-                                .into(),
-                                1,
+                (self.proj.types.insert(Type::Ptr(expr.ty)), expr)
+            }
+            UnaryOp::AddrMut => {
+                let expr =
+                    self.check_expr(*expr, target.and_then(|id| id.as_pointee(&self.proj.types)));
+                if !expr.can_addrmut(&self.proj.scopes, &self.proj.types) {
+                    self.error(Error::new(
+                        "cannot create mutable pointer to immutable memory location",
+                        span,
+                    ))
+                }
+                self.mark_lambda_mutation(&expr);
+                match &expr.data {
+                    CExprData::Call(inner, _, _) => {
+                        // FIXME: don't test by name
+                        if matches!(&inner.data, CExprData::Fn(f, _)
+                            if self.proj.scopes.get(f.id).name.data.starts_with("$sub"))
+                        {
+                            self.lint(Lint::SubscriptAddr, Error::subscript_addr(span));
+                        }
+                    }
+                    CExprData::Member { source, .. } if source.ty.is_packed_struct(&self.proj) => {
+                        self.lint(Lint::BitfieldAddr, Error::bitfield_addr(span));
+                    }
+                    CExprData::Fn(_, _) => {
+                        self.lint(
+                            Lint::MutFnPtr,
+                            Error::new(
+                                "&mut on function creates immutable function pointer (use &)",
+                                span,
                             ),
                         );
+
+                        let Type::Fn(f) = &self.proj.types[expr.ty] else {
+                            unreachable!()
+                        };
+                        let f = f.clone();
+                        let fptr =
+                            Type::FnPtr(f.as_fn_ptr(&self.proj.scopes, &mut self.proj.types));
+                        return CExpr::new(self.proj.types.insert(fptr), expr.data);
                     }
+                    _ => {}
                 }
-
-                return CExpr::new(
-                    ret,
-                    CExprData::call(&mut self.proj.types, func, args, self.current),
-                );
+                (self.proj.types.insert(Type::MutPtr(expr.ty)), expr)
             }
-        }
-
-        let args: Vec<_> = args
-            .into_iter()
-            .map(|(_, expr)| self.check_expr(expr, None))
-            .collect();
-        if ty == TypeId::UNKNOWN || args.iter().any(|expr| expr.ty == TypeId::UNKNOWN) {
-            return Default::default();
-        }
-
-        bail!(
-            self,
-            Error::new(
-                format!(
-                    "type '{}' does not support subscript{} with arguments of type ({})",
-                    &callee.ty.name(&self.proj.scopes, &mut self.proj.types),
-                    if assign { " assign" } else { "" },
-                    args.into_iter()
-                        .map(|expr| expr.ty.name(&self.proj.scopes, &mut self.proj.types))
-                        .collect::<Vec<_>>()
-                        .join(", "),
+            UnaryOp::AddrRaw => {
+                let expr =
+                    self.check_expr(*expr, target.and_then(|id| id.as_pointee(&self.proj.types)));
+                match &expr.data {
+                    CExprData::Call(inner, _, _) => {
+                        // FIXME: don't test by name
+                        if matches!(&inner.data, CExprData::Fn(f, _)
+                            if self.proj.scopes.get(f.id).name.data.starts_with("$sub"))
+                        {
+                            self.lint(Lint::SubscriptAddr, Error::subscript_addr(span));
+                        }
+                    }
+                    CExprData::Member { source, .. } if source.ty.is_packed_struct(&self.proj) => {
+                        self.lint(Lint::BitfieldAddr, Error::bitfield_addr(span));
+                    }
+                    CExprData::Fn(_, _) => {
+                        self.error(Error::new("cannot create raw pointer to function", span))
+                    }
+                    _ => {}
+                }
+                (self.proj.types.insert(Type::RawPtr(expr.ty)), expr)
+            }
+            UnaryOp::Try => {
+                let expr =
+                    self.check_expr(*expr, target.and_then(|t| t.as_option_inner(&self.proj)));
+                if let Some((ok, err)) = expr.ty.as_result(&self.proj) {
+                    return self.check_result_try(expr, ok, err, span);
+                } else if let Some(inner) = expr.ty.as_option_inner(&self.proj) {
+                    return self.check_option_try(expr, inner, span);
+                } else if expr.ty == TypeId::UNKNOWN {
+                    return Default::default();
+                } else {
+                    bail!(
+                        self,
+                        Error::invalid_operator(
+                            op,
+                            &expr.ty.name(&self.proj.scopes, &mut self.proj.types),
+                            span,
+                        )
+                    );
+                }
+            }
+            _ => {
+                let span = expr.span;
+                let expr = self.check_expr(*expr, target);
+                if !expr
+                    .ty
+                    .supports_unary(&self.proj.scopes, &self.proj.types, op)
+                {
+                    return self.check_unary(expr, op, span);
+                }
+
+                if matches!(
+                    op,
+                    UnaryOp::PostIncrement
+                        | UnaryOp::PostDecrement
+                        | UnaryOp::PreIncrement
+                        | UnaryOp::PreDecrement
+                ) {
+                    if !expr.is_assignable(&self.proj.scopes, &self.proj.types) {
+                        self.error(Error::new("expression is not assignable", span))
+                    }
+                    self.mark_lambda_mutation(&expr);
+                }
+
+                (expr.ty, expr)
+            }
+        };
+
+        CExpr::new(out_ty, CExprData::Unary(op, expr.into()))
+    }
+
+    fn check_string_interpolation(
+        &mut self,
+        parts: Vec<(PExpr, Option<Located<FormatSpec>>)>,
+        span: Span,
+    ) -> CExpr {
+        let Some(fmt_id) = self.proj.scopes.lang_traits.get("format").copied() else {
+            return self.error(Error::no_lang_item("Format", span));
+        };
+        let formatter = self
+            .proj
+            .scopes
+            .lang_types
+            .get("string_formatter")
+            .and_then(|&id| {
+                self.proj.scopes[self.proj.scopes.get(id).body_scope]
+                    .find_in_vns("new")
+                    .and_then(|f| f.into_fn().ok())
+                    .map(|f| {
+                        CExpr::new(
+                            self.proj
+                                .types
+                                .insert(Type::User(GenericUserType::new(id, Default::default()))),
+                            CExprData::call(
+                                &mut self.proj.types,
+                                GenericFn::new(f, Default::default()),
+                                Default::default(),
+                                self.current,
+                                span,
+                            ),
+                        )
+                    })
+            })
+            .unwrap_or_else(|| self.error(Error::no_lang_item("String Formatter", span)));
+        let mut out_parts = Vec::with_capacity(parts.len());
+        for (expr, spec) in parts {
+            let span = expr.span;
+            let mut expr = self.check_expr(expr, None);
+            let mut ty = expr.ty.strip_references(&self.proj.types);
+            if ty == TypeId::UNKNOWN {
+                continue;
+            }
+
+            let fmt_tr = GenericTrait::from_type_args(&self.proj.scopes, fmt_id, []);
+            let fmt_args =
+                |this: &mut Self, id| TypeArgs::in_order(&this.proj.scopes, id, [formatter.ty]);
+            let Some(mut mfn) =
+                self.get_member_fn_ex(ty, Some(&fmt_tr), "fmt", self.current, fmt_args)
+            else {
+                self.proj.diag.error(Error::doesnt_implement(
+                    &ty.name(&self.proj.scopes, &mut self.proj.types),
+                    "Format",
+                    span,
+                ));
+                continue;
+            };
+
+            if let Some(spec) = spec {
+                expr = self.check_format_spec(expr, ty, spec);
+                ty = expr.ty;
+                let Some(f) =
+                    self.get_member_fn_ex(ty, Some(&fmt_tr), "fmt", self.current, fmt_args)
+                else {
+                    continue;
+                };
+                mfn = f;
+            }
+
+            let ptr_to_unk = self.proj.types.insert(Type::Ptr(TypeId::UNKNOWN));
+            if !matches!(&expr.data, CExprData::String(s) if s.is_empty()) {
+                out_parts.push((mfn, expr.auto_deref(&mut self.proj.types, ptr_to_unk)));
+            }
+        }
+
+        CExpr::new(
+            self.make_lang_type_by_name("string", [], span),
+            CExprData::StringInterp {
+                parts: out_parts,
+                formatter: formatter.into(),
+                scope: self.current,
+            },
+        )
+    }
+
+    fn check_path_expr(&mut self, path: Path, target: Option<TypeId>, span: Span) -> CExpr {
+        match self.resolve_value_path(&path, target) {
+            ResolvedValue::Var(id) if self.trait_of_const(id).is_some() => {
+                let tr = self.trait_of_const(id).unwrap();
+                let tr_ut = self.proj.scopes.get(tr);
+                if !self
+                    .proj
+                    .scopes
+                    .walk(self.current)
+                    .any(|(id, _)| id == tr_ut.body_scope)
+                {
+                    return self.error(Error::new(
+                        format!(
+                            "constant '{}' of trait '{}' can only be used through a type that \
+                             implements it",
+                            self.proj.scopes.get(id).name.data,
+                            tr_ut.name.data,
+                        ),
+                        span,
+                    ));
+                }
+
+                let this = *tr_ut.kind.as_trait().unwrap().0;
+                let this = GenericUserType::from_id(&self.proj.scopes, &mut self.proj.types, this);
+                let this = self.proj.types.insert(Type::User(this));
+                CExpr::new(self.proj.scopes.get(id).ty, CExprData::AssocConst(this, id))
+            }
+            ResolvedValue::AssocConst { ty, id, value_ty } => {
+                CExpr::new(value_ty, CExprData::AssocConst(ty, id))
+            }
+            ResolvedValue::ConstParam(id) => {
+                let ty = self.const_param_ty(id).unwrap();
+                CExpr::new(ty, CExprData::ConstParam(id))
+            }
+            ResolvedValue::Var(id) => {
+                let var = self.proj.scopes.get(id);
+                if !var.is_static {
+                    if self.current_function() != self.proj.scopes.function_of(var.scope) {
+                        self.proj.diag.error(Error::new(
+                            "cannot reference local variable of enclosing function",
+                            span,
+                        ));
+                    }
+                    if self
+                        .current_static
+                        .as_ref()
+                        .is_some_and(|v| !self.is_var_accessible(v.0, var.scope))
+                    {
+                        self.proj.diag.error(Error::new(
+                            "cannot reference local variable from outside of static initializer",
+                            span,
+                        ));
+                    }
+                } else if let Some((cur, deps)) = &mut self.current_static {
+                    deps.push(id);
+                    let recursive = match self.proj.static_deps.get(&id) {
+                        Some(Dependencies::Resolved(v_deps)) => v_deps.contains(cur),
+                        Some(Dependencies::Resolving) => true,
+                        _ => false,
+                    };
+                    if recursive {
+                        self.proj.diag.error(Error::new(
+                            "static initializer depends directly or indirectly on itself",
+                            span,
+                        ));
+                    }
+                }
+
+                let ty = var.ty;
+                if !var.is_static {
+                    self.capture_var(id);
+                }
+                self.proj.scopes.get_mut(id).unused = false;
+                self.unread_vars.remove(&id);
+                CExpr::new(ty, CExprData::Var(id))
+            }
+            ResolvedValue::Fn(mut func) => {
+                let unknowns: HashSet<_> = func
+                    .ty_args
+                    .iter()
+                    .filter_map(|(&id, &ty)| (ty == TypeId::UNKNOWN).then_some(id))
+                    .collect();
+                if let Some(target) = target {
+                    func.infer_type_args(
+                        &mut self.proj.types,
+                        self.proj.scopes.get(func.id).ret,
+                        target,
+                    );
+                }
+                self.check_bounds_filtered(&func, &unknowns, path.final_component_span());
+
+                if let Some(id) = self.proj.scopes.get(func.id).constructor {
+                    if self
+                        .proj
+                        .scopes
+                        .get(id)
+                        .is_empty_variant(&self.proj.scopes.get(func.id).name.data)
+                    {
+                        return CExpr::new(
+                            self.proj
+                                .types
+                                .insert(Type::User(GenericUserType::new(id, func.ty_args))),
+                            CExprData::VariantInstance(
+                                self.proj.scopes.get(func.id).name.data.clone(),
+                                Default::default(),
+                            ),
+                        );
+                    }
+                }
+
+                CExpr::new(
+                    self.proj.types.insert(Type::Fn(func.clone())),
+                    CExprData::Fn(func, self.current),
+                )
+            }
+            ResolvedValue::MemberFn(mut mfn) => {
+                let unknowns: HashSet<_> = mfn
+                    .func
+                    .ty_args
+                    .iter()
+                    .filter_map(|(&id, &ty)| (ty == TypeId::UNKNOWN).then_some(id))
+                    .collect();
+                if let Some(target) = target {
+                    mfn.func.infer_type_args(
+                        &mut self.proj.types,
+                        self.proj.scopes.get(mfn.func.id).ret,
+                        target,
+                    );
+                }
+                self.check_bounds_filtered(&mfn.func, &unknowns, path.final_component_span());
+
+                if let Some(id) = self.proj.scopes.get(mfn.func.id).constructor {
+                    if self
+                        .proj
+                        .scopes
+                        .get(id)
+                        .is_empty_variant(&self.proj.scopes.get(mfn.func.id).name.data)
+                    {
+                        return CExpr::new(
+                            self.proj
+                                .types
+                                .insert(Type::User(GenericUserType::new(id, mfn.func.ty_args))),
+                            CExprData::VariantInstance(
+                                self.proj.scopes.get(mfn.func.id).name.data.clone(),
+                                Default::default(),
+                            ),
+                        );
+                    }
+                }
+
+                CExpr::new(
+                    self.proj.types.insert(Type::Fn(mfn.func.clone())),
+                    CExprData::MemFn(mfn, self.current),
+                )
+            }
+            ResolvedValue::UnionConstructor(ut) => bail!(
+                self,
+                Error::expected_found(
+                    "expression",
+                    &format!(
+                        "type '{}'",
+                        ut.name(&self.proj.scopes, &mut self.proj.types)
+                    ),
+                    span,
+                )
+            ),
+            ResolvedValue::NotFound(err) => self.error(err),
+            ResolvedValue::Error => Default::default(),
+        }
+    }
+
+    fn check_if_expr(
+        &mut self,
+        cond: Box<PExpr>,
+        if_branch: Box<PExpr>,
+        else_branch: Option<Box<PExpr>>,
+        target: Option<TypeId>,
+        span: Span,
+    ) -> CExpr {
+        let (cond, vars) = self.type_check_with_listen(*cond);
+        let target = if else_branch.is_none() {
+            target.and_then(|t| t.as_option_inner(&self.proj))
+        } else {
+            target
+        };
+
+        let if_span = if_branch.span;
+        let mut if_branch = self.enter(ScopeKind::None, |this| {
+            this.define(&vars);
+            this.check_expr_inner(*if_branch, target)
+        });
+        if let Some(target) = target {
+            if_branch = self.type_check_checked(if_branch, target, if_span);
+        }
+
+        let mut out_type = if_branch.ty;
+        let else_branch = if let Some(expr) = else_branch {
+            if out_type == TypeId::NEVER {
+                let expr = self.check_expr_inner(*expr, None);
+                out_type = expr.ty;
+                if_branch = self.try_coerce(if_branch, expr.ty);
+                Some(expr)
+            } else {
+                let span = expr.span;
+                let source = self.check_expr_inner(*expr, target.or(Some(out_type)));
+                if source
+                    .ty
+                    .as_option_inner(&self.proj)
+                    .is_some_and(|v| v == out_type)
+                {
+                    if_branch = self.coerce(if_branch, source.ty).unwrap();
+                    out_type = source.ty;
+                    Some(source)
+                } else {
+                    Some(self.type_check_checked(source, out_type, span))
+                }
+            }
+        } else if if_branch.data.is_yielding_block(&self.proj.scopes) {
+            if out_type == TypeId::NEVER || out_type == TypeId::VOID || out_type == TypeId::UNKNOWN
+            {
+                out_type = TypeId::VOID;
+                Some(CExpr::new(TypeId::VOID, CExprData::Void))
+            } else {
+                out_type = self.make_lang_type_by_name("option", [out_type], span);
+                if_branch = self.try_coerce(if_branch, out_type);
+                Some(self.check_expr_inner(Located::new(span, PExprData::None), Some(out_type)))
+            }
+        } else {
+            // the condition may be false, so the if expression doesn't diverge
+            if out_type == TypeId::NEVER {
+                out_type = TypeId::VOID;
+            }
+            None
+        };
+
+        CExpr::new(
+            out_type,
+            CExprData::If {
+                cond: cond.into(),
+                if_branch: if_branch.into(),
+                else_branch: else_branch.map(|e| e.into()),
+            },
+        )
+    }
+
+    fn check_loop_expr(
+        &mut self,
+        cond: Option<Box<PExpr>>,
+        body: Vec<PStmt>,
+        do_while: bool,
+        label: Option<String>,
+        target: Option<TypeId>,
+        span: Span,
+    ) -> CExpr {
+        let infinite = cond.is_none();
+        let target = self.loop_target(target, infinite);
+        let (cond, body) = if let Some(cond) = cond {
+            let (cond, vars) = self.type_check_with_listen(*cond);
+            let body = self.create_block_with_init(
+                body,
+                ScopeKind::Loop(LoopScopeKind {
+                    target,
+                    breaks: LoopBreak::None,
+                    infinite,
+                    label,
+                }),
+                |this| {
+                    if !do_while {
+                        this.define(&vars);
+                    }
+                },
+            );
+            (Some(cond.into()), body)
+        } else {
+            (
+                None,
+                self.create_block(
+                    body,
+                    ScopeKind::Loop(LoopScopeKind {
+                        target,
+                        breaks: LoopBreak::None,
+                        infinite,
+                        label,
+                    }),
                 ),
+            )
+        };
+        let (out_type, optional) =
+            self.loop_out_type(&self.proj.scopes[body.scope].kind.clone(), span);
+        CExpr::new(
+            out_type,
+            CExprData::Loop {
+                cond,
+                body,
+                do_while,
+                optional,
+            },
+        )
+    }
+
+    fn check_member_expr(
+        &mut self,
+        source: Box<PExpr>,
+        generics: Vec<TypeHint>,
+        name: Located<String>,
+        span: Span,
+    ) -> CExpr {
+        if !generics.is_empty() {
+            self.error(Error::new(
+                "member variables cannot have type arguments",
                 span,
+            ))
+        }
+
+        let source = self.check_expr(*source, None);
+        let id = source.ty.strip_references(&self.proj.types);
+        self.check_dot_completions(span, id, true);
+        let ut_id = match &self.proj.types[id] {
+            Type::User(data) => data.id,
+            Type::Unknown => return Default::default(),
+            _ => {
+                let err = self.member_not_found(id, &name);
+                bail!(self, err);
+            }
+        };
+        self.resolve_members(ut_id);
+        self.check_hover(
+            name.span,
+            LspItem::Property(Some(source.ty), ut_id, name.data.clone()),
+        );
+
+        let ut = self.proj.scopes.get(ut_id);
+        let Some(member) = ut.members.get(&name.data) else {
+            let err = self.member_not_found(source.ty, &name);
+            bail!(self, err);
+        };
+
+        if ut.kind.is_unsafe_union() && self.safety != Safety::Unsafe {
+            self.proj.diag.error(Error::is_unsafe(name.span));
+        }
+
+        let ty = member.ty.with_ut_templates(&mut self.proj.types, id);
+        if !member.public && !self.can_access_privates(ut.scope) {
+            self.proj.diag.error(Error::private_member(
+                &id.name(&self.proj.scopes, &mut self.proj.types),
+                &name.data,
+                name.span,
+            ));
+        }
+        CExpr::new(
+            ty,
+            CExprData::Member {
+                source: source.auto_deref(&mut self.proj.types, id).into(),
+                member: name.data,
+            },
+        )
+    }
+
+    fn check_break_expr(
+        &mut self,
+        expr: Option<Box<PExpr>>,
+        label: Option<Located<String>>,
+        span: Span,
+    ) -> CExpr {
+        if let Some(label) = label {
+            let label_data = Some(&label.data);
+            for (id, scope) in self.proj.scopes.walk(self.current) {
+                if scope.kind.is_defer() {
+                    break;
+                }
+
+                match &scope.kind {
+                    ScopeKind::Loop(data) if data.label.as_ref() == label_data => {
+                        return self.check_break(expr, data.clone(), id);
+                    }
+                    ScopeKind::Block(data) if data.label.as_ref() == label_data => {
+                        return self.check_yield(expr, data.clone(), id);
+                    }
+                    _ => {}
+                }
+            }
+
+            if let Some(expr) = expr {
+                self.check_expr(*expr, None);
+            }
+            return self.error(Error::new(format!("undefined label '{label}'"), label.span));
+        }
+
+        let Some((loop_data, id)) = self.current_loop(&None) else {
+            if let Some(expr) = expr {
+                self.check_expr(*expr, None);
+            }
+            return self.error(Error::new("break outside of loop", span));
+        };
+
+        self.check_break(expr, loop_data.clone(), id)
+    }
+
+    fn check_match_expr(
+        &mut self,
+        expr: Box<PExpr>,
+        body: Vec<(Located<FullPattern>, PExpr)>,
+        target: Option<TypeId>,
+        span: Span,
+    ) -> CExpr {
+        let scrutinee = self.check_expr(*expr, None);
+        let mut has_never = false;
+        let mut target = target;
+        let mut result: Vec<_> = body
+            .into_iter()
+            .map(|(patt, expr)| {
+                let span = expr.span;
+                let (patt, expr) = self.enter(ScopeKind::None, |this| {
+                    (
+                        this.check_full_pattern(scrutinee.ty, patt),
+                        this.check_expr(expr, target),
+                    )
+                });
+
+                if let Some(target) = target {
+                    (patt, self.type_check_checked(expr, target, span))
+                } else {
+                    if expr.ty == TypeId::NEVER {
+                        has_never = true;
+                    } else {
+                        target = Some(expr.ty);
+                    }
+
+                    (patt, expr)
+                }
+            })
+            .collect();
+        let target = target.unwrap_or(if has_never {
+            TypeId::NEVER
+        } else {
+            TypeId::VOID
+        });
+        if !matches!(target, TypeId::NEVER | TypeId::VOID) {
+            for (_, e) in result.iter_mut() {
+                *e = self.try_coerce(std::mem::take(e), target);
+            }
+        }
+
+        self.check_match_coverage(scrutinee.ty, result.iter().map(|it| &it.0), span);
+        CExpr::new(
+            target,
+            CExprData::Match {
+                expr: scrutinee.into(),
+                body: result,
+            },
+        )
+    }
+
+    fn check_as_expr(
+        &mut self,
+        expr: Box<PExpr>,
+        ty: TypeHint,
+        throwing: bool,
+        span: Span,
+    ) -> CExpr {
+        let to_id = self.resolve_typehint(&ty);
+        let expr = self.check_expr(*expr, Some(to_id));
+        let expr = match self.coerce(expr, to_id) {
+            Ok(expr) => return expr,
+            Err(expr) => expr,
+        };
+
+        let mut from_id = expr.ty;
+        if let Type::User(ut) = &self.proj.types[from_id] {
+            let id = ut.id;
+            self.resolve_members(id);
+            if let Some(tag) = self
+                .proj
+                .scopes
+                .get(id)
+                .kind
+                .as_union()
+                .filter(|u| u.enum_union)
+                .map(|u| u.tag)
+            {
+                from_id = tag;
+            }
+        }
+
+        match Cast::get(&self.proj.types[from_id], &self.proj.types[to_id]) {
+            Cast::None => self.proj.diag.error(Error::new(
+                format!(
+                    "cannot cast expression of type '{}' to '{}'",
+                    from_id.name(&self.proj.scopes, &mut self.proj.types),
+                    to_id.name(&self.proj.scopes, &mut self.proj.types),
+                ),
+                span,
+            )),
+            Cast::Unsafe if self.safety != Safety::Unsafe => self.error(Error::is_unsafe(span)),
+            Cast::Fallible if !throwing => self.proj.diag.error(Error::new(
+                format!(
+                    "cast of expression of type '{}' to '{}' requires fallible cast",
+                    from_id.name(&self.proj.scopes, &mut self.proj.types),
+                    to_id.name(&self.proj.scopes, &mut self.proj.types),
+                ),
+                span,
+            )),
+            Cast::Infallible if throwing => {
+                let err = Error::new(
+                    format!(
+                        "cast from type '{}' to '{}' is infallible and may use an `as` cast",
+                        from_id.name(&self.proj.scopes, &mut self.proj.types),
+                        to_id.name(&self.proj.scopes, &mut self.proj.types),
+                    ),
+                    span,
+                );
+                self.lint(Lint::InfallibleCast, err)
+            }
+            _ => {}
+        }
+        CExpr::new(to_id, CExprData::As(expr.into(), throwing))
+    }
+
+    fn check_lambda(&mut self, expr: PExpr, target: Option<TypeId>, sig: Option<FnPtr>) -> CExpr {
+        let span = expr.span;
+        let PExprData::Lambda {
+            params,
+            ret,
+            body,
+            moves,
+        } = expr.data
+        else {
+            unreachable!("ICE: check_lambda called with non-lambda expression");
+        };
+
+        let ty_is_generic = |this: &TypeChecker, ty: TypeId| {
+            !this
+                .proj
+                .types
+                .get(ty)
+                .as_user()
+                .is_some_and(|ut| this.proj.scopes.get(ut.id).kind.is_template())
+        };
+
+        let mut lparams = Vec::new();
+        let mut param_vars = Vec::new();
+        let ret = ret.map(|ret| self.resolve_typehint(&ret)).or_else(|| {
+            sig.as_ref()
+                .and_then(|f| ty_is_generic(self, f.ret).then_some(f.ret))
+        });
+        let kind = ScopeKind::Lambda(LambdaScopeKind {
+            target: ret,
+            returns: false,
+            captures: Vec::new(),
+            moves,
+            mutates: false,
+        });
+        let body = self.enter(kind, |this| {
+            for (i, (name, hint)) in params.into_iter().enumerate() {
+                let has_hint = hint.is_some();
+                let ty = hint
+                    .map(|ty| this.resolve_typehint(&ty))
+                    .or_else(|| {
+                        sig.as_ref()
+                            .and_then(|f| f.params.get(i))
+                            .filter(|&&ty| ty_is_generic(this, ty))
+                            .cloned()
+                    })
+                    .unwrap_or_else(|| {
+                        this.error(Error::new(
+                            format!("cannot infer type of parameter '{}'", name.data),
+                            name.span,
+                        ))
+                    });
+
+                lparams.push(ty);
+                param_vars.push(this.insert::<VariableId>(
+                    Variable {
+                        public: false,
+                        name,
+                        ty,
+                        is_static: false,
+                        is_const: false,
+                        mutable: false,
+                        value: None,
+                        unused: true,
+                        has_hint,
+                    },
+                    false,
+                    false,
+                ));
+            }
+
+            let body = if let PExprData::Block(body, _) = body.data {
+                this.check_block(body)
+            } else {
+                let span = body.span;
+                vec![Located::new(
+                    span,
+                    CStmt::Expr(this.check_expr(PExpr::new(span, PExprData::Return(body)), None)),
+                )]
+            };
+
+            Block {
+                body,
+                scope: this.current,
+            }
+        });
+        let lambda = self.proj.scopes[body.scope].kind.as_lambda().unwrap();
+        let captures = lambda.captures.clone();
+        let mutates = lambda.mutates;
+        let fnptr = FnPtr {
+            params: lparams,
+            ret: lambda
+                .returns
+                .then_some(lambda.target)
+                .flatten()
+                .unwrap_or(TypeId::VOID),
+        };
+        let kind = target.map(|target| &self.proj.types[target]);
+        if kind.is_some_and(|kind| kind.is_fn_ptr()) {
+            if !captures.is_empty() {
+                self.error(Error::new(
+                    "lambda that captures its environment cannot be converted to a function pointer",
+                    span,
+                ))
+            }
+
+            return CExpr::new(
+                self.proj.types.insert(Type::FnPtr(fnptr)),
+                CExprData::Lambda {
+                    params: param_vars,
+                    captures,
+                    moves,
+                    body,
+                },
+            );
+        }
+
+        let is_dyn = kind.is_some_and(|kind| kind.is_dyn_fn());
+        let lambda = Lambda {
+            params: fnptr.params.clone(),
+            ret: fnptr.ret,
+            mutates,
+        };
+        let lambda = CExpr::new(
+            self.insert_lambda_type(lambda, &captures, moves, body.scope, span),
+            CExprData::Lambda {
+                params: param_vars,
+                captures,
+                moves,
+                body,
+            },
+        );
+        if is_dyn {
+            CExpr::new(
+                self.proj.types.insert(Type::DynFn(fnptr)),
+                CExprData::DynFnCoerce(lambda.into()),
+            )
+        } else {
+            lambda
+        }
+    }
+
+    fn check_expr(&mut self, expr: PExpr, target: Option<TypeId>) -> CExpr {
+        let expr = self.check_expr_inner(expr, target);
+        if expr.ty == TypeId::NEVER
+            && !matches!(expr.data, CExprData::Yield(_, scope) if scope == self.current)
+        {
+            // TODO: lambdas
+            if let ScopeKind::Block(BlockScopeKind { branches, .. }) =
+                &mut self.proj.scopes[self.current].kind
+            {
+                *branches = true;
+            }
+        }
+        expr
+    }
+
+    fn type_check_with_listen(&mut self, expr: PExpr) -> (CExpr, Vec<VariableId>) {
+        self.listen_for_vars(self.current_expr + 1, |this| {
+            this.type_check(expr, TypeId::BOOL)
+        })
+    }
+
+    fn listen_for_vars<T>(
+        &mut self,
+        expr: usize,
+        f: impl FnOnce(&mut Self) -> T,
+    ) -> (T, Vec<VariableId>) {
+        let prev = std::mem::take(&mut self.listening_vars);
+        let prev_insp = std::mem::replace(&mut self.listening_expr, expr);
+        let res = f(self);
+        self.listening_expr = prev_insp;
+        (res, std::mem::replace(&mut self.listening_vars, prev))
+    }
+
+    fn define(&mut self, vars: &[VariableId]) {
+        for &var in vars.iter() {
+            let name = self.proj.scopes.get(var).name.data.clone();
+            self.proj.scopes[self.current]
+                .vns
+                .insert(name, Vis::new(ValueItem::Var(var), false));
+        }
+    }
+
+    fn current_loop(&self, label: &Option<Located<String>>) -> Option<(&LoopScopeKind, ScopeId)> {
+        let label = label.as_ref().map(|l| &l.data);
+        self.proj
+            .scopes
+            .walk(self.current)
+            .take_while(|(_, scope)| !scope.kind.is_defer())
+            .find_map(|(id, scope)| {
+                scope
+                    .kind
+                    .as_loop()
+                    .filter(|l| label.is_none() || l.label.as_ref() == label)
+                    .zip(Some(id))
+            })
+    }
+
+    fn check_array_subscript(
+        &mut self,
+        target: TypeId,
+        callee: CExpr,
+        args: Vec<(Option<String>, PExpr)>,
+        span: Span,
+    ) -> CExpr {
+        fn maybe_span(this: &mut TypeChecker, ty: TypeId, imm: bool) -> Option<UserTypeId> {
+            let id = *this.proj.scopes.lang_traits.get("range_bounds")?;
+            let bound = GenericTrait::from_type_args(&this.proj.scopes, id, [TypeId::USIZE]);
+            if this.implements_trait(ty, &bound) {
+                let span_ty = if imm {
+                    *this.proj.scopes.lang_types.get("span")?
+                } else {
+                    *this.proj.scopes.lang_types.get("span_mut")?
+                };
+                Some(span_ty)
+            } else {
+                None
+            }
+        }
+
+        let mut args = args.into_iter();
+        let (name, expr) = args.next().unwrap();
+        if let Some(name) = name {
+            self.error(Error::new(
+                format!("unknown parameter: '{name}'"),
+                expr.span,
+            ))
+        }
+
+        let arg_span = expr.span;
+        let arg = self.check_expr(expr, Some(TypeId::USIZE));
+        if let Some((_, arg)) = args.next() {
+            let last = args.last().map(|(_, arg)| arg.span).unwrap_or(arg.span);
+            self.error(Error::new(
+                "multidimensional array subscript is not supported",
+                arg.span.extended_to(last),
+            ))
+        }
+
+        match self.coerce(arg, TypeId::USIZE) {
+            Ok(expr) => CExpr::new(
+                target,
+                CExprData::Subscript {
+                    callee: callee.into(),
+                    arg: expr.into(),
+                    span,
+                },
+            ),
+            Err(expr) if self.proj.types[expr.ty].is_integral() => CExpr::new(
+                target,
+                CExprData::Subscript {
+                    callee: callee.into(),
+                    arg: expr.into(),
+                    span,
+                },
+            ),
+            Err(expr) => {
+                let Some(id) = maybe_span(self, expr.ty, self.immutable_receiver(&callee)) else {
+                    bail!(
+                        self,
+                        Error::expected_found(
+                            "array index",
+                            &format!(
+                                "type '{}'",
+                                expr.ty.name(&self.proj.scopes, &mut self.proj.types)
+                            ),
+                            arg_span,
+                        )
+                    );
+                };
+
+                CExpr::new(
+                    self.proj
+                        .types
+                        .insert(Type::User(GenericUserType::from_type_args(
+                            &self.proj.scopes,
+                            id,
+                            [target],
+                        ))),
+                    CExprData::SliceArray {
+                        callee: callee.into(),
+                        arg: expr.into(),
+                    },
+                )
+            }
+        }
+    }
+
+    fn check_subscript(
+        &mut self,
+        callee: CExpr,
+        ty: TypeId,
+        args: Vec<(Option<String>, PExpr)>,
+        target: Option<TypeId>,
+        assign: bool,
+        span: Span,
+    ) -> CExpr {
+        let imm_receiver = self.immutable_receiver(&callee);
+        if let Some(ut) = self.proj.types[ty].as_user().cloned() {
+            let mut candidates: Vec<_> = self
+                .proj
+                .scopes
+                .get(ut.id)
+                .subscripts
+                .iter()
+                .cloned()
+                .filter(|&f| assign == self.proj.scopes.get(f).assign_subscript)
+                .filter(|&f| self.proj.scopes.get(f).params.len() == args.len() + 1)
+                .collect();
+            candidates.iter().for_each(|&f| self.resolve_proto(f));
+            candidates.sort_unstable_by(|&a, &b| {
+                let left = self
+                    .proj
+                    .scopes
+                    .get(a)
+                    .params
+                    .first()
+                    .is_some_and(|p| self.proj.types[p.ty].is_mut_ptr());
+                let right = self
+                    .proj
+                    .scopes
+                    .get(b)
+                    .params
+                    .first()
+                    .is_some_and(|p| self.proj.types[p.ty].is_mut_ptr());
+                right.cmp(&left)
+            });
+
+            for f in candidates {
+                if imm_receiver
+                    && self
+                        .proj
+                        .scopes
+                        .get(f)
+                        .params
+                        .first()
+                        .is_some_and(|p| self.proj.types[p.ty].is_mut_ptr())
+                {
+                    continue;
+                }
+
+                let mut func = GenericFn::from_id(&self.proj.scopes, f);
+                func.ty_args.copy_args(&ut.ty_args);
+
+                let args = args.clone();
+                let recv = callee
+                    .clone()
+                    .auto_deref(&mut self.proj.types, self.proj.scopes.get(f).params[0].ty);
+                let err_idx = self.proj.diag.capture_errors();
+                let (args, ret, failed) =
+                    self.check_fn_args(&mut func, Some(recv), args, target, span);
+                // TODO: if the arguments have non overload related errors, just stop overload
+                // resolution
+                if failed
+                    || args
+                        .iter()
+                        .any(|arg| matches!(arg.1.data, CExprData::Error))
+                {
+                    self.proj.diag.truncate_errors(err_idx);
+                    continue;
+                }
+                // unsafe doesnt cause check_fn_args to fail, but we mute errors, so check again
+                // here
+                if self.proj.scopes.get(func.id).is_unsafe && self.safety != Safety::Unsafe {
+                    self.error(Error::is_unsafe(span))
+                }
+
+                if !assign {
+                    if let Type::Ptr(inner) | Type::MutPtr(inner) = &self.proj.types[ret] {
+                        return CExpr::new(
+                            *inner,
+                            CExprData::AutoDeref(
+                                CExpr::new(
+                                    ret,
+                                    CExprData::call(
+                                        &mut self.proj.types,
+                                        func,
+                                        args,
+                                        self.current,
+                                        span,
+                                    ),
+                                )
+                                .into(),
+                                1,
+                            ),
+                        );
+                    }
+                }
+
+                return CExpr::new(
+                    ret,
+                    CExprData::call(&mut self.proj.types, func, args, self.current, span),
+                );
+            }
+        }
+
+        let args: Vec<_> = args
+            .into_iter()
+            .map(|(_, expr)| self.check_expr(expr, None))
+            .collect();
+        if ty == TypeId::UNKNOWN || args.iter().any(|expr| expr.ty == TypeId::UNKNOWN) {
+            return Default::default();
+        }
+
+        bail!(
+            self,
+            Error::new(
+                format!(
+                    "type '{}' does not support subscript{} with arguments of type ({})",
+                    &callee.ty.name(&self.proj.scopes, &mut self.proj.types),
+                    if assign { " assign" } else { "" },
+                    args.into_iter()
+                        .map(|expr| expr.ty.name(&self.proj.scopes, &mut self.proj.types))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                ),
+                span,
+            )
+        )
+    }
+
+    fn immutable_receiver(&self, callee: &CExpr) -> bool {
+        if !matches!(
+            self.proj.types[callee.ty],
+            Type::Ptr(_) | Type::MutPtr(_) | Type::DynPtr(_) | Type::DynMutPtr(_)
+        ) && !callee.can_addrmut(&self.proj.scopes, &self.proj.types)
+        {
+            return true;
+        }
+
+        let mut ty = &self.proj.types[callee.ty];
+        while let Type::MutPtr(inner) = ty {
+            ty = &self.proj.types[*inner];
+        }
+
+        matches!(ty, Type::Ptr(_) | Type::DynPtr(_))
+    }
+
+    fn is_var_accessible(&self, static_var: VariableId, scope: ScopeId) -> bool {
+        for (_, scope) in self.proj.scopes.walk(scope) {
+            match scope.kind {
+                ScopeKind::None | ScopeKind::Defer | ScopeKind::Block(_) | ScopeKind::Loop(_) => {
+                    continue
+                }
+                ScopeKind::Static(v) if v == static_var => return true,
+                _ => break,
+            }
+        }
+
+        false
+    }
+
+    fn callable_signature(&mut self, ty: TypeId) -> Option<FnPtr> {
+        if let Some(f) = self.proj.scopes.fn_signature(&mut self.proj.types, ty) {
+            return Some(f);
+        }
+
+        let bound = self.template_fn_bound(ty)?;
+        self.fn_bound_signature(&bound)
+    }
+
+    fn fn_bound_signature(&self, bound: &GenericTrait) -> Option<FnPtr> {
+        let mut ty_args = bound.ty_args.values();
+        let (&args, &ret) = (ty_args.next()?, ty_args.next()?);
+        let params = match &self.proj.types[args] {
+            Type::Void => Vec::new(),
+            Type::User(ut) if self.proj.scopes.get(ut.id).kind.is_tuple() => {
+                ut.ty_args.values().copied().collect()
+            }
+            _ => return None,
+        };
+        Some(FnPtr { params, ret })
+    }
+
+    fn infer_from_fn_bound<T>(
+        &mut self,
+        func: &mut WithTypeArgs<T>,
+        bound: &GenericTrait,
+        ty: TypeId,
+    ) {
+        let (Some(bound), Some(f)) = (
+            self.fn_bound_signature(bound),
+            self.proj.scopes.fn_signature(&mut self.proj.types, ty),
+        ) else {
+            return;
+        };
+
+        for (&param, &arg) in bound.params.iter().zip(f.params.iter()) {
+            func.infer_type_args(&mut self.proj.types, param, arg);
+        }
+        func.infer_type_args(&mut self.proj.types, bound.ret, f.ret);
+    }
+
+    fn template_fn_bound(&mut self, ty: TypeId) -> Option<GenericTrait> {
+        self.template_lang_bound(ty, &FN_TRAITS)
+    }
+
+    fn template_lang_bound(&mut self, ty: TypeId, names: &[&str]) -> Option<GenericTrait> {
+        let id = self.proj.types[ty].as_user()?.id;
+        if !self.proj.scopes.get(id).kind.is_template() {
+            return None;
+        }
+
+        self.resolve_impls(id);
+        let lang_traits = &self.proj.scopes.lang_traits;
+        self.proj
+            .scopes
+            .get(id)
+            .impls
+            .iter()
+            .flat_map(|imp| imp.as_checked())
+            .find(|tr| {
+                names
+                    .iter()
+                    .any(|name| lang_traits.get(*name) == Some(&tr.id))
+            })
+            .cloned()
+    }
+
+    /// Infers the output type of a `Future` bound from the argument `ty`, so that calls like
+    /// `block_on(fut)` do not require the output type to be specified.
+    fn infer_from_future_bound<T>(
+        &mut self,
+        func: &mut WithTypeArgs<T>,
+        bound: &GenericTrait,
+        ty: TypeId,
+    ) {
+        let Some(&param) = bound.ty_args.values().next() else {
+            return;
+        };
+
+        let output = if let Some(output) = self.proj.scopes.future_output(&mut self.proj.types, ty)
+        {
+            output
+        } else {
+            let Some(ut) = self.proj.types[ty].as_user().cloned() else {
+                return;
+            };
+            self.resolve_impls(ut.id);
+            let Some(&output) = self
+                .proj
+                .scopes
+                .get(ut.id)
+                .impls
+                .iter()
+                .flat_map(|imp| imp.as_checked())
+                .find(|tr| tr.id == bound.id)
+                .and_then(|tr| tr.ty_args.values().next())
+            else {
+                return;
+            };
+            output.with_templates(&mut self.proj.types, &ut.ty_args)
+        };
+        func.infer_type_args(&mut self.proj.types, param, output);
+    }
+
+    fn insert_lambda_type(
+        &mut self,
+        lambda: Lambda,
+        captures: &[VariableId],
+        moves: bool,
+        scope: ScopeId,
+        span: Span,
+    ) -> TypeId {
+        // the lambda type must be distinct for every instantiation of the enclosing function
+        let type_params = self.visible_type_params();
+        let members = captures
+            .iter()
+            .map(|&id| {
+                let var = self.proj.scopes.get(id);
+                let ty = match (moves, var.mutable) {
+                    (true, _) => var.ty,
+                    (false, true) => self.proj.types.insert(Type::MutPtr(var.ty)),
+                    (false, false) => self.proj.types.insert(Type::Ptr(var.ty)),
+                };
+                (
+                    var.name.data.clone(),
+                    CheckedMember::new(false, ty, var.name.span),
+                )
+            })
+            .collect();
+        let id = UserTypeId::insert_in(
+            &mut self.proj.scopes,
+            UserType {
+                public: false,
+                name: Located::new(span, format!("$lambda{}", scope.0)),
+                body_scope: scope,
+                kind: UserTypeKind::Lambda(lambda),
+                impls: Vec::new(),
+                impl_blocks: Vec::new(),
+                type_params,
+                attrs: Default::default(),
+                fns: Vec::new(),
+                members,
+                subscripts: Vec::new(),
+                members_resolved: true,
+                recursive: false,
+                implicit: false,
+                const_ty: None,
+            },
+            false,
+            self.current,
+        )
+        .id;
+        let ut = GenericUserType::from_id(&self.proj.scopes, &mut self.proj.types, id);
+        self.proj.types.insert(Type::User(ut))
+    }
+
+    fn insert_frame_type(
+        &mut self,
+        func: FunctionId,
+        output: TypeId,
+        kind: fn(FnFrame) -> UserTypeKind,
+    ) -> TypeId {
+        // like lambdas, the frame must be distinct for every instantiation of the function
+        let type_params = self.visible_type_params();
+        let span = self.proj.scopes.get(func).name.span;
+        let kind = kind(FnFrame {
+            func,
+            output,
+            context: None,
+        });
+        let name = if kind.is_async_fn() {
+            "$future"
+        } else {
+            "$generator"
+        };
+        let id = UserTypeId::insert_in(
+            &mut self.proj.scopes,
+            UserType {
+                public: false,
+                name: Located::new(span, name.into()),
+                body_scope: self.current,
+                kind,
+                impls: Vec::new(),
+                impl_blocks: Vec::new(),
+                type_params,
+                attrs: Default::default(),
+                fns: Vec::new(),
+                members: Default::default(),
+                subscripts: Vec::new(),
+                members_resolved: true,
+                recursive: false,
+                implicit: false,
+                const_ty: None,
+            },
+            false,
+            self.current,
+        )
+        .id;
+        let ut = GenericUserType::from_id(&self.proj.scopes, &mut self.proj.types, id);
+        self.proj.types.insert(Type::User(ut))
+    }
+
+    fn visible_type_params(&self) -> Vec<UserTypeId> {
+        let mut type_params = Vec::new();
+        for (_, scope) in self.proj.scopes.walk(self.current) {
+            match scope.kind {
+                ScopeKind::Function(id) => {
+                    if !type_params.is_empty() {
+                        break;
+                    }
+                    type_params.extend_from_slice(&self.proj.scopes.get(id).type_params);
+                }
+                ScopeKind::UserType(id) => {
+                    let ut = self.proj.scopes.get(id);
+                    type_params.extend_from_slice(&ut.type_params);
+                    if let &UserTypeKind::Trait(this, _) = &ut.kind {
+                        type_params.push(this);
+                    }
+                    break;
+                }
+                _ => {}
+            }
+        }
+        type_params
+    }
+
+    /// Declares the variable holding the `Context` passed to each poll of the async function `func`.
+    fn declare_poll_context(&mut self, func: FunctionId) {
+        let span = self.proj.scopes.get(func).name.span;
+        let context = self.make_lang_type_by_name("context", [], span);
+        let ty = self.proj.types.insert(Type::MutPtr(context));
+        let var = self.insert::<VariableId>(
+            Variable {
+                public: false,
+                name: Located::new(Span::default(), "$cx".into()),
+                ty,
+                is_static: false,
+                is_const: false,
+                mutable: false,
+                value: None,
+                unused: false,
+                has_hint: false,
+            },
+            false,
+            false,
+        );
+        let ret = self.proj.scopes.get(func).ret;
+        if let Some(ut) = self.proj.types[ret].as_user() {
+            if let Some(frame) = self.proj.scopes.get_mut(ut.id).kind.as_frame_mut() {
+                frame.context = Some(var);
+            }
+        }
+    }
+
+    /// Returns the variable declared by `declare_poll_context` for the async function `func`.
+    fn poll_context(&self, func: FunctionId) -> Option<VariableId> {
+        let ut = self.proj.types[self.proj.scopes.get(func).ret].as_user()?;
+        self.proj.scopes.get(ut.id).kind.as_async_fn()?.context
+    }
+
+    /// Returns the declared return type of `func` if it is an `async fn`.
+    fn async_output(&self, func: FunctionId) -> Option<TypeId> {
+        let f = self.proj.scopes.get(func);
+        if !f.is_async {
+            return None;
+        }
+
+        let ut = self.proj.types[f.ret].as_user()?;
+        Some(self.proj.scopes.get(ut.id).kind.as_async_fn()?.output)
+    }
+
+    /// Returns the declared return type of `func` if it is a generator.
+    fn generator_item(&self, func: FunctionId) -> Option<TypeId> {
+        let f = self.proj.scopes.get(func);
+        if !f.is_generator {
+            return None;
+        }
+
+        let ut = self.proj.types[f.ret].as_user()?;
+        Some(self.proj.scopes.get(ut.id).kind.as_generator()?.output)
+    }
+
+    /// Creates an expression that evaluates `first` for its side effects, then yields `then`.
+    fn sequence(&mut self, first: CExpr, then: CExpr, span: Span) -> CExpr {
+        let ty = then.ty;
+        let kind = ScopeKind::Block(BlockScopeKind {
+            target: Some(ty),
+            yields: true,
+            label: None,
+            branches: false,
+            catches: false,
+        });
+        self.enter(kind, |this| {
+            CExpr::new(
+                ty,
+                CExprData::Block(Block {
+                    body: vec![
+                        Located::new(span, CStmt::Expr(first)),
+                        Located::new(
+                            span,
+                            CStmt::Expr(CExpr::new(
+                                TypeId::NEVER,
+                                CExprData::Yield(Some(then.into()), this.current),
+                            )),
+                        ),
+                    ],
+                    scope: this.current,
+                }),
+            )
+        })
+    }
+
+    fn make_poll(
+        &mut self,
+        output: TypeId,
+        variant: &str,
+        args: IndexMap<String, CExpr>,
+        span: Span,
+    ) -> CExpr {
+        CExpr::new(
+            self.make_lang_type_by_name("poll", [output], span),
+            CExprData::VariantInstance(variant.into(), args),
+        )
+    }
+
+    /// Records that `expr` is about to be mutated, so that any lambda owning a copy of the
+    /// underlying variable requires a mutable receiver to be called.
+    fn mark_lambda_mutation(&mut self, mut expr: &CExpr) {
+        let id = loop {
+            match &expr.data {
+                &CExprData::Var(id) => break id,
+                CExprData::Member { source, .. } | CExprData::Subscript { callee: source, .. }
+                    if !matches!(
+                        self.proj.types[source.ty],
+                        Type::Ptr(_) | Type::MutPtr(_) | Type::RawPtr(_)
+                    ) =>
+                {
+                    expr = source
+                }
+                _ => return,
+            }
+        };
+
+        let scope = self.defining_scope(id);
+        let Some(lambda) = self
+            .proj
+            .scopes
+            .walk(self.current)
+            .take_while(|&(id, _)| id != scope)
+            .find(|(_, scope)| scope.kind.as_lambda().is_some_and(|lambda| lambda.moves))
+            .map(|(id, _)| id)
+        else {
+            return;
+        };
+        let lambda = self.proj.scopes[lambda].kind.as_lambda_mut().unwrap();
+        if lambda.captures.contains(&id) {
+            lambda.mutates = true;
+        }
+    }
+
+    /// Returns the innermost scope visible from the current one that defines `var`. This differs
+    /// from the scope `var` was declared in for bindings of an `is` pattern, which are declared
+    /// while checking the condition and then defined again in the scope of the branch.
+    fn defining_scope(&self, var: VariableId) -> ScopeId {
+        let name = &self.proj.scopes.get(var).name.data;
+        self.proj
+            .scopes
+            .walk(self.current)
+            .find(|(_, scope)| {
+                scope
+                    .vns
+                    .get(name)
+                    .is_some_and(|item| matches!(**item, ValueItem::Var(id) if id == var))
+            })
+            .map_or(self.proj.scopes.get(var).scope, |(id, _)| id)
+    }
+
+    fn capture_var(&mut self, var: VariableId) {
+        let scope = self.defining_scope(var);
+        let lambdas: Vec<_> = self
+            .proj
+            .scopes
+            .walk(self.current)
+            .take_while(|&(id, _)| id != scope)
+            .filter(|(_, scope)| scope.kind.is_lambda())
+            .map(|(id, _)| id)
+            .collect();
+        for id in lambdas {
+            let lambda = self.proj.scopes[id].kind.as_lambda_mut().unwrap();
+            if !lambda.captures.contains(&var) {
+                lambda.captures.push(var);
+            }
+        }
+    }
+
+    fn check_return(&mut self, expr: PExpr, span: Span) -> CExpr {
+        for (id, scope) in self.proj.scopes.walk(self.current) {
+            match &scope.kind {
+                ScopeKind::Lambda(lambda) => {
+                    let target = lambda.target;
+                    let span = expr.span;
+                    let mut expr = self.check_expr(expr, target);
+                    if let Some(target) = target {
+                        expr = self.type_check_checked(expr, target, span);
+                    }
+                    self.check_escaping_lambda(&expr, id);
+
+                    let lambda = self.proj.scopes[id].kind.as_lambda_mut().unwrap();
+                    lambda.target = Some(target.unwrap_or(expr.ty));
+                    lambda.returns = true;
+                    return CExpr::new(TypeId::NEVER, CExprData::Return(expr.into()));
+                }
+                &ScopeKind::Function(func) => {
+                    if let Some(item) = self.generator_item(func) {
+                        let expr = self.type_check(expr, TypeId::VOID);
+                        let none =
+                            CExpr::option_null(self.make_lang_type_by_name("option", [item], span));
+                        let none = if matches!(expr.data, CExprData::Void) {
+                            none
+                        } else {
+                            self.sequence(expr, none, span)
+                        };
+                        return CExpr::new(TypeId::NEVER, CExprData::Return(none.into()));
+                    }
+
+                    let Some(output) = self.async_output(func) else {
+                        let target = self.proj.scopes.get(func).ret;
+                        let expr = self.type_check(expr, target);
+                        self.check_escaping_lambda(&expr, id);
+                        return CExpr::new(TypeId::NEVER, CExprData::Return(expr.into()));
+                    };
+
+                    let expr = self.type_check(expr, output);
+                    self.check_escaping_lambda(&expr, id);
+                    let ready = self.make_poll(output, "Ready", [("0".into(), expr)].into(), span);
+                    return CExpr::new(TypeId::NEVER, CExprData::Return(ready.into()));
+                }
+                ScopeKind::Defer => {
+                    self.proj
+                        .diag
+                        .error(Error::new("cannot return in defer block", span));
+                    return self.check_expr(expr, None);
+                }
+                _ => {}
+            }
+        }
+
+        // this should never be possible, but report error instead of crashing for LSP reasons
+        self.error(Error::new(
+            "return expression outside of function",
+            expr.span,
+        ))
+    }
+
+    /// Reports an error if the value `expr` returned from `scope` is a lambda that captures
+    /// variables declared in `scope` by reference, since they no longer exist once it returns.
+    fn check_escaping_lambda(&mut self, expr: &CExpr, scope: ScopeId) {
+        match &expr.data {
+            CExprData::DynFnCoerce(expr) => return self.check_escaping_lambda(expr, scope),
+            CExprData::If {
+                if_branch,
+                else_branch,
+                ..
+            } => {
+                self.check_escaping_lambda(if_branch, scope);
+                if let Some(else_branch) = else_branch {
+                    self.check_escaping_lambda(else_branch, scope);
+                }
+                return;
+            }
+            CExprData::Match { body, .. } => {
+                for (_, expr) in body.iter() {
+                    self.check_escaping_lambda(expr, scope);
+                }
+                return;
+            }
+            CExprData::Block(block) => {
+                if let Some(Located {
+                    data:
+                        CStmt::Expr(CExpr {
+                            data: CExprData::Yield(Some(value), yield_scope),
+                            ..
+                        }),
+                    ..
+                }) = block.body.last()
+                {
+                    if *yield_scope == block.scope {
+                        self.check_escaping_lambda(value, scope);
+                    }
+                }
+                return;
+            }
+            _ => {}
+        }
+
+        let Some(ut) = self.proj.types[expr.ty].as_user() else {
+            return;
+        };
+        let ut = self.proj.scopes.get(ut.id);
+        let Some(lambda) = ut
+            .kind
+            .is_lambda()
+            .then(|| self.proj.scopes[ut.body_scope].kind.as_lambda())
+            .flatten()
+            .filter(|lambda| !lambda.moves)
+        else {
+            return;
+        };
+        let span = ut.name.span;
+        let Some(var) = lambda.captures.iter().find(|&&var| {
+            self.proj
+                .scopes
+                .walk(self.proj.scopes.get(var).scope)
+                .any(|(id, _)| id == scope)
+        }) else {
+            return;
+        };
+        let name = self.proj.scopes.get(*var).name.data.clone();
+        self.proj.diag.error(
+            Error::new(
+                format!("returned lambda captures local variable '{name}' by reference"),
+                span,
+            )
+            .with_note("use a 'move' lambda to capture it by value"),
+        );
+    }
+
+    fn check_generator_yield(&mut self, expr: PExpr, span: Span) -> CExpr {
+        let mut item = None;
+        for (_, scope) in self.proj.scopes.walk(self.current) {
+            match scope.kind {
+                ScopeKind::Function(id) => {
+                    item = self.generator_item(id);
+                    break;
+                }
+                ScopeKind::Lambda(_) => break,
+                ScopeKind::Defer => {
+                    self.proj
+                        .diag
+                        .error(Error::new("cannot yield in defer block", span));
+                    return self.check_expr(expr, None);
+                }
+                _ => {}
+            }
+        }
+
+        let Some(item) = item else {
+            self.check_expr(expr, None);
+            return self.error(Error::new(
+                "'yield' is only allowed inside generator functions",
+                span,
+            ));
+        };
+
+        let expr = self.type_check(expr, item);
+        let opt = self.make_lang_type_by_name("option", [item], span);
+        let expr = self.try_coerce(expr, opt);
+        CExpr::new(TypeId::VOID, CExprData::Suspend(expr.into()))
+    }
+
+    fn check_await(&mut self, expr: PExpr, span: Span) -> CExpr {
+        let mut output = None;
+        let mut context = None;
+        for (_, scope) in self.proj.scopes.walk(self.current) {
+            match scope.kind {
+                ScopeKind::Function(id) => {
+                    output = self.async_output(id);
+                    context = self.poll_context(id);
+                    break;
+                }
+                ScopeKind::Lambda(_) => break,
+                ScopeKind::Defer => {
+                    self.proj
+                        .diag
+                        .error(Error::new("cannot await in defer block", span));
+                    return self.check_expr(expr, None);
+                }
+                _ => {}
+            }
+        }
+
+        let fut = self.check_expr(expr, None);
+        let Some(output) = output else {
+            return self.error(Error::new(
+                "'.await' is only allowed inside async functions",
+                span,
+            ));
+        };
+        let Some(fut_tr_id) = self.proj.scopes.lang_traits.get("future").copied() else {
+            return self.error(Error::no_lang_item("Future", span));
+        };
+        if fut.ty == TypeId::UNKNOWN {
+            return Default::default();
+        }
+
+        let mfn = self.get_member_fn_legacy(fut.ty, fut_tr_id, "poll", self.current);
+        let name = fut.ty.name(&self.proj.scopes, &mut self.proj.types);
+        let Some(mut mfn) = mfn else {
+            return self.error(Error::doesnt_implement(&name, "Future", span));
+        };
+
+        let f = self.proj.scopes.get(mfn.func.id);
+        let [p0, p1] = &f.params[..] else {
+            panic!("ICE: Future::poll() should have 2 parameters");
+        };
+        let (p0, p1) = (p0.label.clone(), p1.label.clone());
+        let Some(context) = context else {
+            return Default::default();
+        };
+        let poll_ty = f
+            .ret
+            .with_templates(&mut self.proj.types, &mfn.func.ty_args);
+        let Some(value_ty) = self.proj.types[poll_ty]
+            .as_user()
+            .and_then(|ut| ut.first_type_arg())
+        else {
+            return self.error(Error::doesnt_implement(&name, "Future", span));
+        };
+        self.trait_hack(&mut mfn, value_ty);
+
+        // desugar `fut.await` into:
+        // {
+        //     mut $fut = fut;
+        //     loop {
+        //         if Future::poll(&mut $fut, $cx) is :Ready($ready) { break $ready; }
+        //         <suspend with Poll::Pending>
+        //     }
+        // }
+        let kind = ScopeKind::Block(BlockScopeKind {
+            target: None,
+            yields: true,
+            label: None,
+            branches: false,
+            catches: false,
+        });
+        self.enter(kind, |this| {
+            let fut_ty = fut.ty;
+            let fut_var = this.insert::<VariableId>(
+                Variable {
+                    public: false,
+                    name: Located::new(Span::default(), format!("$fut{}", this.current.0)),
+                    ty: fut_ty,
+                    is_static: false,
+                    is_const: false,
+                    mutable: true,
+                    value: None,
+                    unused: false,
+                    has_hint: false,
+                },
+                false,
+                false,
+            );
+
+            let kind = ScopeKind::Loop(LoopScopeKind {
+                target: Some(value_ty),
+                breaks: LoopBreak::WithValue,
+                infinite: true,
+                label: None,
+            });
+            let body = this.enter(kind, |this| {
+                let ready_var = this.insert::<VariableId>(
+                    Variable {
+                        public: false,
+                        name: Located::new(Span::default(), format!("$ready{}", this.current.0)),
+                        ty: value_ty,
+                        is_static: false,
+                        is_const: false,
+                        mutable: false,
+                        value: None,
+                        unused: false,
+                        has_hint: false,
+                    },
+                    false,
+                    false,
+                );
+
+                let arg0 = CExpr::new(
+                    this.proj.types.insert(Type::MutPtr(fut_ty)),
+                    CExprData::Unary(
+                        UnaryOp::AddrMut,
+                        CExpr::new(fut_ty, CExprData::Var(fut_var)).into(),
+                    ),
+                );
+                let arg1 = CExpr::new(this.proj.scopes.get(context).ty, CExprData::Var(context));
+                let poll_call = CExpr::new(
+                    poll_ty,
+                    CExprData::member_call(
+                        &mut this.proj.types,
+                        mfn,
+                        [(p0, arg0), (p1, arg1)].into(),
+                        this.current,
+                        span,
+                    ),
+                );
+                let cond = CExpr::new(
+                    TypeId::BOOL,
+                    CExprData::Is(
+                        poll_call.into(),
+                        CPattern::refutable(PatternData::Variant {
+                            pattern: Some(
+                                CPattern::irrefutable(PatternData::Destrucure {
+                                    patterns: vec![(
+                                        "0".into(),
+                                        value_ty,
+                                        CPattern::irrefutable(PatternData::Variable(ready_var)),
+                                    )],
+                                    borrows: false,
+                                })
+                                .into(),
+                            ),
+                            variant: "Ready".into(),
+                            inner: TypeId::UNKNOWN,
+                            borrows: false,
+                        }),
+                    ),
+                );
+                let ready = CExpr::new(value_ty, CExprData::Var(ready_var));
+                let brk = CExpr::new(
+                    TypeId::NEVER,
+                    CExprData::Break(Some(ready.into()), this.current),
+                );
+                let kind = ScopeKind::Block(BlockScopeKind {
+                    target: None,
+                    yields: false,
+                    label: None,
+                    branches: false,
+                    catches: false,
+                });
+                let if_branch = this.enter(kind, |this| {
+                    CExpr::new(
+                        TypeId::NEVER,
+                        CExprData::Block(Block {
+                            body: vec![Located::new(span, CStmt::Expr(brk))],
+                            scope: this.current,
+                        }),
+                    )
+                });
+                let pending = this.make_poll(output, "Pending", IndexMap::new(), span);
+                Block {
+                    body: vec![
+                        Located::new(
+                            span,
+                            CStmt::Expr(CExpr::new(
+                                TypeId::VOID,
+                                CExprData::If {
+                                    cond: cond.into(),
+                                    if_branch: if_branch.into(),
+                                    else_branch: None,
+                                },
+                            )),
+                        ),
+                        Located::new(
+                            span,
+                            CStmt::Expr(CExpr::new(
+                                TypeId::VOID,
+                                CExprData::Suspend(pending.into()),
+                            )),
+                        ),
+                    ],
+                    scope: this.current,
+                }
+            });
+            let poll_loop = CExpr::new(
+                value_ty,
+                CExprData::Loop {
+                    cond: None,
+                    body,
+                    do_while: false,
+                    optional: false,
+                },
+            );
+
+            CExpr::new(
+                value_ty,
+                CExprData::Block(Block {
+                    body: vec![
+                        Located::new(
+                            span,
+                            CStmt::Let(
+                                CPattern {
+                                    irrefutable: true,
+                                    data: PatternData::Variable(fut_var),
+                                },
+                                Some(fut),
+                            ),
+                        ),
+                        Located::new(
+                            span,
+                            CStmt::Expr(CExpr::new(
+                                value_ty,
+                                CExprData::Yield(Some(poll_loop.into()), this.current),
+                            )),
+                        ),
+                    ],
+                    scope: this.current,
+                }),
             )
-        )
+        })
     }
 
-    fn immutable_receiver(&self, callee: &CExpr) -> bool {
-        if !matches!(
-            self.proj.types[callee.ty],
-            Type::Ptr(_) | Type::MutPtr(_) | Type::DynPtr(_) | Type::DynMutPtr(_)
-        ) && !callee.can_addrmut(&self.proj.scopes, &self.proj.types)
-        {
-            return true;
+    /// Finds the scope that handles an error raised from the current scope, which is either the
+    /// innermost `try` block or the enclosing function if it returns `Result`, along with the
+    /// error type it expects.
+    ///
+    /// If `option` is the type of an `Option` operand, the target is instead the innermost
+    /// function or lambda, which must return `Option`. `try` blocks don't catch a `None`.
+    fn raise_target(
+        &mut self,
+        what: &str,
+        option: Option<TypeId>,
+        span: Span,
+    ) -> Option<(ScopeId, Option<TypeId>)> {
+        for (id, scope) in self.proj.scopes.walk(self.current) {
+            let ret = match &scope.kind {
+                ScopeKind::Block(data) if data.catches && option.is_none() => {
+                    return Some((id, data.target))
+                }
+                ScopeKind::Lambda(lambda) => lambda.target,
+                &ScopeKind::Function(func) => Some(
+                    self.async_output(func)
+                        .unwrap_or(self.proj.scopes.get(func).ret),
+                ),
+                ScopeKind::Defer => {
+                    self.proj.diag.error(Error::new(
+                        format!("cannot use {what} in defer block"),
+                        span,
+                    ));
+                    return None;
+                }
+                _ => continue,
+            };
+
+            match (option, ret) {
+                (None, Some(ret)) => {
+                    if let Some((_, err)) = ret.as_result(&self.proj) {
+                        return Some((id, Some(err)));
+                    }
+                }
+                (Some(_), Some(ret)) if ret.as_option_inner(&self.proj).is_some() => {
+                    return Some((id, None));
+                }
+                (Some(operand), Some(ret)) => {
+                    let operand = operand.name(&self.proj.scopes, &mut self.proj.types);
+                    let ret = ret.name(&self.proj.scopes, &mut self.proj.types);
+                    self.proj.diag.error(Error::new(
+                        format!(
+                            "{what} on type '{operand}' requires the function to return Option, \
+                             but it returns '{ret}'"
+                        ),
+                        span,
+                    ));
+                    return None;
+                }
+                _ => {}
+            }
+            break;
         }
 
-        let mut ty = &self.proj.types[callee.ty];
-        while let Type::MutPtr(inner) = ty {
-            ty = &self.proj.types[*inner];
+        if option.is_some() {
+            self.proj.diag.error(Error::new(
+                format!("{what} is only valid in functions that return Option"),
+                span,
+            ));
+        } else {
+            self.proj.diag.error(Error::new(
+                format!("{what} is only valid in functions that return Result or in 'try' blocks"),
+                span,
+            ));
         }
+        None
+    }
 
-        matches!(ty, Type::Ptr(_) | Type::DynPtr(_))
+    /// Transfers `err` to the handler found by `raise_target`, either by yielding it from a `try`
+    /// block or by returning `Result::Err` from the function.
+    fn raise_to(&mut self, scope: ScopeId, err: CExpr, span: Span) -> CExpr {
+        let (ret, func) = match &self.proj.scopes[scope].kind {
+            ScopeKind::Block(data) => {
+                let mut data = data.clone();
+                let err = if let Some(target) = data.target {
+                    self.type_check_checked(err, target, span)
+                } else {
+                    data.target = Some(err.ty);
+                    err
+                };
+                data.yields = true;
+                self.proj.scopes[scope].kind = ScopeKind::Block(data);
+                return CExpr::new(TypeId::NEVER, CExprData::Yield(Some(err.into()), scope));
+            }
+            ScopeKind::Lambda(lambda) => (lambda.target.unwrap(), None),
+            &ScopeKind::Function(func) => match self.async_output(func) {
+                Some(output) => (output, Some(output)),
+                None => (self.proj.scopes.get(func).ret, None),
+            },
+            _ => unreachable!(),
+        };
+
+        let (_, err_ty) = ret.as_result(&self.proj).unwrap();
+        let err = self.type_check_checked(err, err_ty, span);
+        let value = CExpr::new(
+            ret,
+            CExprData::VariantInstance("Err".into(), [("0".into(), err)].into()),
+        );
+        self.return_from(scope, value, func, span)
     }
 
-    fn is_var_accessible(&self, static_var: VariableId, scope: ScopeId) -> bool {
-        for (_, scope) in self.proj.scopes.walk(scope) {
-            match scope.kind {
-                ScopeKind::None | ScopeKind::Defer | ScopeKind::Block(_) | ScopeKind::Loop(_) => {
-                    continue
-                }
-                ScopeKind::Static(v) if v == static_var => return true,
-                _ => break,
-            }
+    /// Returns `null` from the function or lambda found by `raise_target` for an `Option` operand.
+    fn return_none(&mut self, scope: ScopeId, span: Span) -> CExpr {
+        let (ret, func) = match &self.proj.scopes[scope].kind {
+            ScopeKind::Lambda(lambda) => (lambda.target.unwrap(), None),
+            &ScopeKind::Function(func) => match self.async_output(func) {
+                Some(output) => (output, Some(output)),
+                None => (self.proj.scopes.get(func).ret, None),
+            },
+            _ => unreachable!(),
+        };
+        self.return_from(scope, CExpr::option_null(ret), func, span)
+    }
+
+    /// Returns `value` from the function or lambda `scope`, wrapping it in `Poll::Ready` when
+    /// returning from an async function with the given `output` type.
+    fn return_from(
+        &mut self,
+        scope: ScopeId,
+        mut value: CExpr,
+        output: Option<TypeId>,
+        span: Span,
+    ) -> CExpr {
+        if let Some(output) = output {
+            value = self.make_poll(output, "Ready", [("0".into(), value)].into(), span);
+        } else if let Some(lambda) = self.proj.scopes[scope].kind.as_lambda_mut() {
+            lambda.returns = true;
         }
+        CExpr::new(TypeId::NEVER, CExprData::Return(value.into()))
+    }
 
-        false
+    fn check_option_try(&mut self, expr: CExpr, inner: TypeId, span: Span) -> CExpr {
+        let Some((scope, _)) = self.raise_target("operator '?'", Some(expr.ty), span) else {
+            return Default::default();
+        };
+
+        // desugar `expr?` into:
+        // match expr {
+        //     :Some($some) => $some,
+        //     :None => return null,
+        // }
+        let (some_patt, some_val) = self.enter(ScopeKind::None, |this| {
+            let (patt, var) = this.variant_binding("Some", inner);
+            (patt, CExpr::new(inner, CExprData::Var(var)))
+        });
+        let none_patt = CPattern::refutable(PatternData::Variant {
+            pattern: None,
+            variant: "None".into(),
+            inner: TypeId::UNKNOWN,
+            borrows: false,
+        });
+        let ret = self.return_none(scope, span);
+        let ret = self.try_coerce(ret, inner);
+        CExpr::new(
+            inner,
+            CExprData::Match {
+                expr: expr.into(),
+                body: vec![(some_patt, some_val), (none_patt, ret)],
+            },
+        )
     }
 
-    fn check_return(&mut self, expr: PExpr, span: Span) -> CExpr {
-        for (id, scope) in self.proj.scopes.walk(self.current) {
-            match &scope.kind {
-                &ScopeKind::Lambda(target, _) => {
-                    let span = expr.span;
-                    let mut expr = self.check_expr(expr, target);
-                    self.proj.scopes[id].kind = if let Some(target) = target {
-                        expr = self.type_check_checked(expr, target, span);
-                        ScopeKind::Lambda(Some(target), true)
-                    } else {
-                        ScopeKind::Lambda(Some(expr.ty), true)
-                    };
+    fn check_result_try(&mut self, expr: CExpr, ok: TypeId, err: TypeId, span: Span) -> CExpr {
+        let Some((scope, _)) = self.raise_target("operator '?'", None, span) else {
+            return Default::default();
+        };
 
-                    return CExpr::new(TypeId::NEVER, CExprData::Return(expr.into()));
-                }
-                &ScopeKind::Function(id) => {
-                    let target = self.proj.scopes.get(id).ret;
-                    return CExpr::new(
+        // desugar `expr?` into:
+        // match expr {
+        //     :Ok($ok) => $ok,
+        //     :Err($err) => <raise $err>,
+        // }
+        let (ok_patt, ok_val) = self.enter(ScopeKind::None, |this| {
+            let (patt, var) = this.variant_binding("Ok", ok);
+            (patt, CExpr::new(ok, CExprData::Var(var)))
+        });
+        let (err_patt, raise) = self.enter(ScopeKind::None, |this| {
+            let (patt, var) = this.variant_binding("Err", err);
+            let raise = this.raise_to(scope, CExpr::new(err, CExprData::Var(var)), span);
+            (patt, this.try_coerce(raise, ok))
+        });
+        CExpr::new(
+            ok,
+            CExprData::Match {
+                expr: expr.into(),
+                body: vec![(ok_patt, ok_val), (err_patt, raise)],
+            },
+        )
+    }
+
+    /// Creates the pattern `:<variant>($var)`, binding the payload to a new hidden variable.
+    fn variant_binding(&mut self, variant: &str, ty: TypeId) -> (CPattern, VariableId) {
+        let var = self.insert::<VariableId>(
+            Variable {
+                public: false,
+                name: Located::new(
+                    Span::default(),
+                    format!("${}{}", variant.to_lowercase(), self.current.0),
+                ),
+                ty,
+                is_static: false,
+                is_const: false,
+                mutable: false,
+                value: None,
+                unused: false,
+                has_hint: false,
+            },
+            false,
+            false,
+        );
+        let patt = CPattern::refutable(PatternData::Variant {
+            pattern: Some(
+                CPattern::irrefutable(PatternData::Destrucure {
+                    patterns: vec![(
+                        "0".into(),
+                        ty,
+                        CPattern::irrefutable(PatternData::Variable(var)),
+                    )],
+                    borrows: false,
+                })
+                .into(),
+            ),
+            variant: variant.into(),
+            inner: TypeId::UNKNOWN,
+            borrows: false,
+        });
+        (patt, var)
+    }
+
+    fn check_try(
+        &mut self,
+        target: Option<TypeId>,
+        body: Vec<PStmt>,
+        catch: Located<Pattern>,
+        handler: Vec<PStmt>,
+    ) -> CExpr {
+        // desugar `try { body } catch patt { handler }` into:
+        // {
+        //     let patt = <catching block> {
+        //         <yield body from the outer block>
+        //     };
+        //     <yield handler from the outer block>
+        // }
+        // where errors raised in the body are yielded from the catching block.
+        let kind = ScopeKind::Block(BlockScopeKind {
+            target,
+            yields: false,
+            label: None,
+            branches: false,
+            catches: false,
+        });
+        let span = catch.span;
+        self.enter(kind, |this| {
+            let outer = this.current;
+            let finish = |value: CExpr| {
+                let stmt = if value.ty == TypeId::NEVER {
+                    CStmt::Expr(value)
+                } else {
+                    CStmt::Expr(CExpr::new(
                         TypeId::NEVER,
-                        CExprData::Return(self.type_check(expr, target).into()),
-                    );
-                }
-                ScopeKind::Defer => {
-                    self.proj
-                        .diag
-                        .error(Error::new("cannot return in defer block", span));
-                    return self.check_expr(expr, None);
+                        CExprData::Yield(Some(value.into()), outer),
+                    ))
+                };
+                Located::new(span, stmt)
+            };
+
+            let kind = ScopeKind::Block(BlockScopeKind {
+                target: None,
+                yields: false,
+                label: None,
+                branches: false,
+                catches: true,
+            });
+            let (try_block, mut out_ty) = this.enter(kind, |this| {
+                let body = this.create_block(
+                    body,
+                    ScopeKind::Block(BlockScopeKind {
+                        target,
+                        yields: false,
+                        label: None,
+                        branches: false,
+                        catches: false,
+                    }),
+                );
+                let mut body = CExpr::new(this.block_type(&body), CExprData::Block(body));
+                if let Some(target) = target {
+                    body = this.try_coerce(body, target);
                 }
-                _ => {}
+
+                let data = this.proj.scopes[this.current].kind.as_block().unwrap();
+                let err_ty = data.target.filter(|_| data.yields).unwrap_or(TypeId::VOID);
+                let out_ty = target.or(Some(body.ty).filter(|&ty| ty != TypeId::NEVER));
+                let try_block = CExpr::new(
+                    err_ty,
+                    CExprData::Block(Block {
+                        body: vec![finish(body)],
+                        scope: this.current,
+                    }),
+                );
+                (try_block, out_ty)
+            });
+
+            let patt_span = catch.span;
+            let patt = this.check_pattern(PatternParams {
+                binding: true,
+                scrutinee: try_block.ty,
+                mutable: false,
+                pattern: catch,
+                typ: PatternType::Regular,
+                has_hint: false,
+            });
+            if !patt.irrefutable {
+                this.error(Error::must_be_irrefutable("catch patterns", patt_span))
+            }
+
+            let handler = this.create_block(
+                handler,
+                ScopeKind::Block(BlockScopeKind {
+                    target: out_ty,
+                    yields: false,
+                    label: None,
+                    branches: false,
+                    catches: false,
+                }),
+            );
+            let mut handler = CExpr::new(this.block_type(&handler), CExprData::Block(handler));
+            if let Some(out_ty) = out_ty {
+                handler = this.type_check_checked(handler, out_ty, patt_span);
+            } else {
+                out_ty = Some(handler.ty);
             }
-        }
 
-        // this should never be possible, but report error instead of crashing for LSP reasons
-        self.error(Error::new(
-            "return expression outside of function",
-            expr.span,
-        ))
+            let out_ty = out_ty.unwrap();
+            if let ScopeKind::Block(data) = &mut this.proj.scopes[outer].kind {
+                data.target = Some(out_ty);
+                data.yields = out_ty != TypeId::NEVER;
+            }
+            CExpr::new(
+                out_ty,
+                CExprData::Block(Block {
+                    body: vec![
+                        Located::new(span, CStmt::Let(patt, Some(try_block))),
+                        finish(handler),
+                    ],
+                    scope: outer,
+                }),
+            )
+        })
     }
 
     fn check_break(
@@ -4004,6 +5781,7 @@ impl TypeChecker {
             yields: true,
             label: None,
             branches: false,
+            catches: false,
         });
         self.enter(kind, |this| {
             let Some(mut mfn) =
@@ -4054,6 +5832,7 @@ impl TypeChecker {
                     name: Located::new(Span::default(), format!("$iter{}", this.current.0)),
                     ty: iter.ty,
                     is_static: false,
+                    is_const: false,
                     mutable: true,
                     value: None,
                     unused: false,
@@ -4084,6 +5863,7 @@ impl TypeChecker {
                         mfn,
                         [(p0, arg0)].into(),
                         this.current,
+                        span,
                     ),
                 )
             };
@@ -4120,17 +5900,23 @@ impl TypeChecker {
                 out,
                 CExprData::Block(Block {
                     body: vec![
-                        CStmt::Let(
-                            CPattern {
-                                irrefutable: true,
-                                data: PatternData::Variable(iter_var),
-                            },
-                            Some(iter),
+                        Located::new(
+                            span,
+                            CStmt::Let(
+                                CPattern {
+                                    irrefutable: true,
+                                    data: PatternData::Variable(iter_var),
+                                },
+                                Some(iter),
+                            ),
+                        ),
+                        Located::new(
+                            span,
+                            CStmt::Expr(CExpr::new(
+                                out,
+                                CExprData::Yield(Some(while_loop.into()), this.current),
+                            )),
                         ),
-                        CStmt::Expr(CExpr::new(
-                            out,
-                            CExprData::Yield(Some(while_loop.into()), this.current),
-                        )),
                     ],
                     scope: this.current,
                 }),
@@ -4216,6 +6002,106 @@ impl TypeChecker {
         )
     }
 
+    fn check_method_call(
+        &mut self,
+        recv: CExpr,
+        member: Located<String>,
+        generics: &[TypeHint],
+        args: Vec<(Option<String>, PExpr)>,
+        target: Option<TypeId>,
+        span: Span,
+    ) -> CExpr {
+        let id = recv.ty.strip_references(&self.proj.types);
+        if id == TypeId::UNKNOWN {
+            return Default::default();
+        }
+
+        // most of the time, the dot span will be inside a non-call member expression.
+        // however, if you start editing a function call, it is possible for the span
+        // to end up here
+        self.check_dot_completions(member.span, id, true);
+        let Some(mut mfn) = self.get_member_fn(id, &member.data, generics, span, self.current)
+        else {
+            let err = self.method_not_found(id, &member, span, true);
+            bail!(self, err);
+        };
+        self.check_hover(member.span, LspItem::Fn(mfn.func.id, None));
+        if mfn.typ.is_dynamic() && !self.proj.scopes.get(mfn.func.id).type_params.is_empty() {
+            self.error(Error::new(
+                "cannot call generic functions through a dynamic trait pointer",
+                span,
+            ))
+        }
+
+        let f = self.proj.scopes.get(mfn.func.id);
+        if !mfn.public && !self.can_access_privates(mfn.owner) {
+            self.proj.diag.error(Error::new(
+                format!(
+                    "cannot access private method '{}' of type '{}'",
+                    self.proj.scopes.get(mfn.func.id).name.data,
+                    id.name(&self.proj.scopes, &mut self.proj.types)
+                ),
+                span,
+            ));
+        }
+
+        let Some(this_param) = f.params.first().filter(|p| p.label == THIS_PARAM) else {
+            return self.error(Error::new(
+                format!("associated function '{member}' cannot be used as a method"),
+                span,
+            ));
+        };
+
+        let this_param_ty = this_param.ty;
+        if self.proj.types[this_param_ty].is_mut_ptr() {
+            if !matches!(
+                self.proj.types[recv.ty],
+                Type::Ptr(_) | Type::MutPtr(_) | Type::DynPtr(_) | Type::DynMutPtr(_)
+            ) && !recv.can_addrmut(&self.proj.scopes, &self.proj.types)
+            {
+                self.error(Error::new(
+                    format!("cannot call method '{member}' with immutable receiver"),
+                    span,
+                ))
+            }
+
+            let mut ty = &self.proj.types[recv.ty];
+            while let Type::MutPtr(inner) = ty {
+                ty = &self.proj.types[*inner];
+            }
+
+            if matches!(ty, Type::Ptr(_) | Type::DynPtr(_)) {
+                self.error(Error::new(
+                    format!("cannot call method '{member}' through an immutable pointer"),
+                    span,
+                ))
+            }
+
+            if matches!(&recv.data, CExprData::Member { source, member: _ } if source.ty.is_packed_struct(&self.proj))
+            {
+                self.lint(
+                    Lint::BitfieldReceiver,
+                    Error::new(
+                        "call to mutating method with bitfield receiver operates on a copy",
+                        span,
+                    ),
+                )
+            }
+            self.mark_lambda_mutation(&recv);
+        }
+
+        let recv = recv.auto_deref(&mut self.proj.types, this_param_ty);
+        let (args, ret, _) = self.check_fn_args(&mut mfn.func, Some(recv), args, target, span);
+        if mfn.typ.is_dynamic() {
+            CExpr::new(ret, CExprData::CallDyn(mfn.func, args))
+        } else {
+            CExpr::new(
+                ret,
+                CExprData::member_call(&mut self.proj.types, mfn, args, self.current, span),
+            )
+        }
+    }
+
     fn check_call(
         &mut self,
         target: Option<TypeId>,
@@ -4230,100 +6116,7 @@ impl TypeChecker {
                 generics,
             } => {
                 let recv = self.check_expr(*source, None);
-                let id = recv.ty.strip_references(&self.proj.types);
-                if id == TypeId::UNKNOWN {
-                    return Default::default();
-                }
-
-                // most of the time, the dot span will be inside a non-call member expression.
-                // however, if you start editing a function call, it is possible for the span
-                // to end up here
-                self.check_dot_completions(member.span, id, true);
-                let Some(mut mfn) =
-                    self.get_member_fn(id, &member.data, &generics, span, self.current)
-                else {
-                    bail!(
-                        self,
-                        Error::no_method(
-                            &id.name(&self.proj.scopes, &mut self.proj.types),
-                            &member.data,
-                            span,
-                        )
-                    );
-                };
-                self.check_hover(member.span, LspItem::Fn(mfn.func.id, None));
-                if mfn.typ.is_dynamic() && !self.proj.scopes.get(mfn.func.id).type_params.is_empty()
-                {
-                    self.error(Error::new(
-                        "cannot call generic functions through a dynamic trait pointer",
-                        span,
-                    ))
-                }
-
-                let f = self.proj.scopes.get(mfn.func.id);
-                if !mfn.public && !self.can_access_privates(mfn.owner) {
-                    self.proj.diag.error(Error::new(
-                        format!(
-                            "cannot access private method '{}' of type '{}'",
-                            self.proj.scopes.get(mfn.func.id).name.data,
-                            id.name(&self.proj.scopes, &mut self.proj.types)
-                        ),
-                        span,
-                    ));
-                }
-
-                let Some(this_param) = f.params.first().filter(|p| p.label == THIS_PARAM) else {
-                    return self.error(Error::new(
-                        format!("associated function '{member}' cannot be used as a method"),
-                        span,
-                    ));
-                };
-
-                let this_param_ty = this_param.ty;
-                if self.proj.types[this_param_ty].is_mut_ptr() {
-                    if !matches!(
-                        self.proj.types[recv.ty],
-                        Type::Ptr(_) | Type::MutPtr(_) | Type::DynPtr(_) | Type::DynMutPtr(_)
-                    ) && !recv.can_addrmut(&self.proj.scopes, &self.proj.types)
-                    {
-                        self.error(Error::new(
-                            format!("cannot call method '{member}' with immutable receiver"),
-                            span,
-                        ))
-                    }
-
-                    let mut ty = &self.proj.types[recv.ty];
-                    while let Type::MutPtr(inner) = ty {
-                        ty = &self.proj.types[*inner];
-                    }
-
-                    if matches!(ty, Type::Ptr(_) | Type::DynPtr(_)) {
-                        self.error(Error::new(
-                            format!("cannot call method '{member}' through an immutable pointer"),
-                            span,
-                        ))
-                    }
-
-                    if matches!(&recv.data, CExprData::Member { source, member: _ } if source.ty.is_packed_struct(&self.proj))
-                    {
-                        self.proj.diag.warn(Error::new(
-                            "call to mutating method with bitfield receiver operates on a copy",
-                            span,
-                        ))
-                    }
-                }
-
-                let recv = recv.auto_deref(&mut self.proj.types, this_param_ty);
-                let (args, ret, _) =
-                    self.check_fn_args(&mut mfn.func, Some(recv), args, target, span);
-                if mfn.typ.is_dynamic() {
-                    return CExpr::new(ret, CExprData::CallDyn(mfn.func, args));
-                } else {
-                    return CExpr::new(
-                        ret,
-                        CExprData::member_call(&mut self.proj.types, mfn, args, self.current),
-                    );
-                }
+                return self.check_method_call(recv, member, &generics, args, target, span);
             }
             PExprData::Path(ref path) => match self.resolve_value_path(path, target) {
                 ResolvedValue::UnionConstructor(ut) => {
@@ -4358,7 +6151,13 @@ impl TypeChecker {
                     } else {
                         return CExpr::new(
                             ret,
-                            CExprData::member_call(&mut self.proj.types, mfn, args, self.current),
+                            CExprData::member_call(
+                                &mut self.proj.types,
+                                mfn,
+                                args,
+                                self.current,
+                                span,
+                            ),
                         );
                     }
                 }
@@ -4372,47 +6171,102 @@ impl TypeChecker {
         let span = callee.span;
         let callee = self.check_expr(callee, None);
         match &self.proj.types[callee.ty] {
-            Type::Unknown => Default::default(),
-            Type::Fn(func) => self.check_known_fn_call(func.clone(), args, target, span),
-            Type::FnPtr(f) => {
-                let f = f.clone();
-                let mut result = vec![];
-                for (i, (name, arg)) in args.into_iter().enumerate() {
-                    if let Some(&param) = f.params.get(i) {
-                        if name.is_some() {
-                            self.proj.diag.error(Error::new(
-                                "keyword parameters are not allowed here",
-                                arg.span,
-                            ));
-                        }
+            Type::Unknown => return Default::default(),
+            Type::Fn(func) => return self.check_known_fn_call(func.clone(), args, target, span),
+            _ => {}
+        }
 
-                        result.push(self.type_check(arg, param));
-                    } else {
-                        self.proj
-                            .diag
-                            .error(Error::new("too many positional arguments", span));
-                        break;
-                    }
+        if let Some(f) = self
+            .proj
+            .scopes
+            .fn_signature(&mut self.proj.types, callee.ty)
+        {
+            if self.proj.types[callee.ty]
+                .as_user()
+                .and_then(|ut| self.proj.scopes.get(ut.id).kind.as_lambda())
+                .is_some_and(|lambda| lambda.mutates)
+            {
+                if !callee.can_addrmut(&self.proj.scopes, &self.proj.types) {
+                    self.error(Error::new(
+                        "cannot call mutating lambda with immutable receiver",
+                        span,
+                    ))
                 }
+                self.mark_lambda_mutation(&callee);
+            }
+
+            let mut result = vec![];
+            for (i, (name, arg)) in args.into_iter().enumerate() {
+                if let Some(&param) = f.params.get(i) {
+                    if name.is_some() {
+                        self.proj.diag.error(Error::new(
+                            "keyword parameters are not allowed here",
+                            arg.span,
+                        ));
+                    }
 
-                if result.len() < f.params.len() {
-                    self.error(Error::new("too few positional arguments", span))
+                    result.push(self.type_check(arg, param));
+                } else {
+                    self.proj
+                        .diag
+                        .error(Error::new("too many positional arguments", span));
+                    break;
                 }
+            }
 
-                CExpr::new(f.ret, CExprData::CallFnPtr(callee.into(), result))
+            if result.len() < f.params.len() {
+                self.error(Error::new("too few positional arguments", span))
             }
-            _ => bail!(
-                self,
-                Error::expected_found(
-                    "callable item",
-                    &format!(
-                        "'{}'",
-                        &callee.ty.name(&self.proj.scopes, &mut self.proj.types)
-                    ),
-                    span,
-                )
-            ),
+
+            return CExpr::new(f.ret, CExprData::CallFnPtr(callee.into(), result));
+        }
+
+        if let Some(bound) = self.template_fn_bound(callee.ty) {
+            // f(a, b) is sugar for f.call((a, b)) when f is a type parameter
+            let tr = self.proj.scopes.get(bound.id);
+            let Some(method) = tr
+                .fns
+                .first()
+                .map(|f| self.proj.scopes.get(f.id).name.data.clone())
+            else {
+                return Default::default();
+            };
+            let mut elements = vec![];
+            for (name, arg) in args {
+                if name.is_some() {
+                    self.proj.diag.error(Error::new(
+                        "keyword parameters are not allowed here",
+                        arg.span,
+                    ));
+                }
+                elements.push(arg);
+            }
+            let args = if elements.is_empty() {
+                PExpr::new(span, PExprData::Void)
+            } else {
+                PExpr::new(span, PExprData::Tuple(elements))
+            };
+            return self.check_method_call(
+                callee,
+                Located::new(Span::default(), method),
+                &[],
+                vec![(None, args)],
+                target,
+                span,
+            );
         }
+
+        bail!(
+            self,
+            Error::expected_found(
+                "callable item",
+                &format!(
+                    "'{}'",
+                    &callee.ty.name(&self.proj.scopes, &mut self.proj.types)
+                ),
+                span,
+            )
+        )
     }
 
     fn check_known_fn_call(
@@ -4441,7 +6295,7 @@ impl TypeChecker {
         let (args, ret, _) = self.check_fn_args(&mut func, None, args, target, span);
         CExpr::new(
             ret,
-            CExprData::call(&mut self.proj.types, func, args, self.current),
+            CExprData::call(&mut self.proj.types, func, args, self.current, span),
         )
     }
 
@@ -4453,9 +6307,26 @@ impl TypeChecker {
     ) -> (CExpr, bool) {
         let mut target = ty.with_templates(&mut self.proj.types, &func.ty_args);
         let span = expr.span;
-        let expr = self.check_expr(expr, Some(target));
+        let bound = self.template_fn_bound(target);
+        let future_bound = self.template_lang_bound(target, &["future"]);
+        let expr = match (&expr.data, &bound) {
+            (PExprData::Lambda { .. }, Some(bound)) => {
+                // the bound may refer to type parameters that have already been inferred
+                let mut bound = bound.clone();
+                bound.fill_templates(&mut self.proj.types, &func.ty_args);
+                let sig = self.fn_bound_signature(&bound);
+                self.check_lambda(expr, Some(target), sig)
+            }
+            _ => self.check_expr(expr, Some(target)),
+        };
         if !func.ty_args.is_empty() {
-            func.infer_type_args(&self.proj.types, ty, expr.ty);
+            func.infer_type_args(&mut self.proj.types, ty, expr.ty);
+            if let Some(bound) = bound {
+                self.infer_from_fn_bound(func, &bound, expr.ty);
+            }
+            if let Some(bound) = future_bound {
+                self.infer_from_future_bound(func, &bound, expr.ty);
+            }
             target = target.with_templates(&mut self.proj.types, &func.ty_args);
         }
 
@@ -4483,6 +6354,9 @@ impl TypeChecker {
         span: Span,
     ) -> (IndexMap<String, CExpr>, TypeId, bool) {
         self.resolve_proto(func.id);
+        for &id in self.proj.scopes.get(func.id).type_params.iter() {
+            func.ty_args.entry(id).or_insert(TypeId::UNKNOWN);
+        }
 
         let unknowns: HashSet<_> = func
             .ty_args
@@ -4490,7 +6364,11 @@ impl TypeChecker {
             .filter_map(|(&id, &ty)| (ty == TypeId::UNKNOWN).then_some(id))
             .collect();
         if let Some(target) = target {
-            func.infer_type_args(&self.proj.types, self.proj.scopes.get(func.id).ret, target);
+            func.infer_type_args(
+                &mut self.proj.types,
+                self.proj.scopes.get(func.id).ret,
+                target,
+            );
         }
 
         let mut result = IndexMap::with_capacity(args.len());
@@ -4601,6 +6479,7 @@ impl TypeChecker {
             ))
         }
 
+        self.infer_from_trait_bounds(func);
         let f = self.check_bounds_filtered(func, &unknowns, span);
         failed = failed || f;
         if self.proj.scopes.get(func.id).is_unsafe && self.safety != Safety::Unsafe {
@@ -4628,6 +6507,11 @@ impl TypeChecker {
         for (&id, &ty) in func.ty_args.iter().filter(|(id, _)| unknowns.contains(id)) {
             if ty == TypeId::UNKNOWN {
                 failed = true;
+                if self.proj.scopes.get(id).implicit {
+                    // the bound that determines it will be reported as unsatisfied
+                    continue;
+                }
+
                 self.error(Error::new(
                     format!(
                         "cannot infer type for type parameter '{}'",
@@ -4672,18 +6556,45 @@ impl TypeChecker {
         failed
     }
 
-    fn check_block(&mut self, body: Vec<PStmt>) -> Vec<CStmt> {
+    fn check_block(&mut self, body: Vec<PStmt>) -> Vec<Located<CStmt>> {
         // TODO: do this in forward decl pass
         let declared: Vec<_> = body
             .into_iter()
-            .map(|stmt| self.declare_stmt(&mut vec![], stmt))
+            .map(|stmt| (stmt.span(), self.declare_stmt(&mut vec![], stmt)))
             .collect();
+        let mut diverges = false;
         declared
             .into_iter()
-            .map(|stmt| self.check_stmt(stmt))
+            .map(|(span, stmt)| {
+                let stmt = self.check_stmt(stmt);
+                if matches!(stmt, CStmt::None) {
+                    return Located::new(span, stmt);
+                }
+
+                if diverges {
+                    // only report the first statement, the rest are unreachable for the same reason
+                    self.lint(
+                        Lint::UnreachableCode,
+                        Error::new("unreachable statement", span),
+                    );
+                    diverges = false;
+                } else if let CStmt::Expr(expr) | CStmt::Let(_, Some(expr)) = &stmt {
+                    diverges = expr.ty == TypeId::NEVER;
+                }
+                Located::new(span, stmt)
+            })
             .collect()
     }
 
+    fn block_type(&self, block: &Block) -> TypeId {
+        let data = self.proj.scopes[block.scope].kind.as_block().unwrap();
+        match (data.branches, data.yields) {
+            (true, false) => TypeId::NEVER,
+            (_, true) => data.target.unwrap_or(TypeId::VOID),
+            _ => TypeId::VOID,
+        }
+    }
+
     fn create_block(&mut self, body: Vec<PStmt>, kind: ScopeKind) -> Block {
         self.create_block_with_init(body, kind, |_| {})
     }
@@ -4737,6 +6648,130 @@ impl TypeChecker {
         }
     }
 
+    /// Validates a format specifier for a value of type `ty` and wraps `expr` in a `Formatted`
+    /// carrying the equivalent `FormatOptions`.
+    fn check_format_spec(&mut self, expr: CExpr, ty: TypeId, spec: Located<FormatSpec>) -> CExpr {
+        let (span, spec) = (spec.span, spec.data);
+        let (numeric, integral) = (
+            self.proj.types[ty].is_numeric(),
+            self.proj.types[ty].is_integral(),
+        );
+        for (used, ok, what) in [
+            (spec.sign, numeric, "sign"),
+            (spec.zero, numeric, "zero padding"),
+            (spec.alternate, integral, "alternate form"),
+            (spec.precision.is_some(), numeric && !integral, "precision"),
+            (spec.ty.is_some(), integral, "radix"),
+        ] {
+            if used && !ok {
+                let name = ty.name(&self.proj.scopes, &mut self.proj.types);
+                self.proj.diag.error(Error::new(
+                    format!("format option '{what}' is not supported for type '{name}'"),
+                    span,
+                ));
+            }
+        }
+
+        let Some(opts_id) = self.proj.scopes.lang_types.get("format_options").copied() else {
+            return self.error(Error::no_lang_item("FormatOptions", span));
+        };
+        self.resolve_members(opts_id);
+        let member_ty = |this: &Self, name: &str| {
+            this.proj
+                .scopes
+                .get(opts_id)
+                .members
+                .get(name)
+                .map_or(TypeId::UNKNOWN, |m| m.ty)
+        };
+        let int = |ty, value: u64| CExpr::new(ty, CExprData::Int(ComptimeInt::from(value)));
+        let bool = |value: bool| CExpr::new(TypeId::BOOL, CExprData::Int(ComptimeInt::from(value)));
+        // without an explicit alignment, the Format implementation decides
+        let align_ty = member_ty(self, "align");
+        let align = spec.align.map_or(CExpr::option_null(align_ty), |align| {
+            let variant = match align {
+                FormatAlign::Left => "Left",
+                FormatAlign::Right => "Right",
+                FormatAlign::Center => "Center",
+            };
+            CExpr::new(
+                align_ty,
+                CExprData::VariantInstance(
+                    "Some".into(),
+                    [(
+                        "0".into(),
+                        CExpr::new(
+                            align_ty
+                                .as_option_inner(&self.proj)
+                                .unwrap_or(TypeId::UNKNOWN),
+                            CExprData::VariantInstance(variant.into(), Default::default()),
+                        ),
+                    )]
+                    .into(),
+                ),
+            )
+        });
+        let precision_ty = member_ty(self, "precision");
+        let precision = spec
+            .precision
+            .map_or(CExpr::option_null(precision_ty), |p| {
+                CExpr::new(
+                    precision_ty,
+                    CExprData::VariantInstance(
+                        "Some".into(),
+                        [("0".into(), int(TypeId::USIZE, p as u64))].into(),
+                    ),
+                )
+            });
+        let radix = match spec.ty {
+            Some('x' | 'X') => 16,
+            Some('o') => 8,
+            Some('b') => 2,
+            _ => 10,
+        };
+        let options = CExpr::new(
+            self.proj.types.insert(Type::User(GenericUserType::new(
+                opts_id,
+                Default::default(),
+            ))),
+            CExprData::Instance(
+                [
+                    (
+                        "fill".into(),
+                        int(TypeId::CHAR, spec.fill.unwrap_or(' ') as u64),
+                    ),
+                    ("align".into(), align),
+                    (
+                        "width".into(),
+                        int(TypeId::USIZE, spec.width.unwrap_or(0) as u64),
+                    ),
+                    ("precision".into(), precision),
+                    ("sign".into(), bool(spec.sign)),
+                    ("alternate".into(), bool(spec.alternate)),
+                    ("zero".into(), bool(spec.zero)),
+                    ("radix".into(), int(member_ty(self, "radix"), radix)),
+                    ("upper".into(), bool(spec.ty == Some('X'))),
+                ]
+                .into(),
+            ),
+        );
+
+        let ptr_to_unk = self.proj.types.insert(Type::Ptr(TypeId::UNKNOWN));
+        CExpr::new(
+            self.make_lang_type_by_name("formatted", [ty], span),
+            CExprData::Instance(
+                [
+                    (
+                        "value".into(),
+                        expr.auto_deref(&mut self.proj.types, ptr_to_unk),
+                    ),
+                    ("options".into(), options),
+                ]
+                .into(),
+            ),
+        )
+    }
+
     fn make_lang_type(
         &mut self,
         id: UserTypeId,
@@ -4779,6 +6814,22 @@ impl TypeChecker {
         match self.resolve_type_path(path) {
             ResolvedType::UserType(ut) => {
                 if self.proj.scopes.get(ut.id).kind.is_trait() {
+                    for (&id, &ty) in ut.ty_args.iter() {
+                        if ty == TypeId::UNKNOWN && self.proj.scopes.get(id).implicit {
+                            bail!(
+                                self,
+                                Error::new(
+                                    format!(
+                                        "the value of associated type '{}' in trait '{}' must be \
+                                         specified",
+                                        self.proj.scopes.get(id).name.data,
+                                        self.proj.scopes.get(ut.id).name.data,
+                                    ),
+                                    path.final_component_span(),
+                                )
+                            )
+                        }
+                    }
                     Some(ut)
                 } else {
                     bail!(
@@ -4883,12 +6934,8 @@ impl TypeChecker {
             }
             TypeHint::Array(ty, count) => {
                 let id = self.resolve_typehint(ty);
-                let Some(n) = self.consteval_check((**count).clone(), TypeId::USIZE) else {
-                    return self.proj.types.insert(Type::Array(id, 0));
-                };
-                self.proj
-                    .types
-                    .insert(Type::Array(id, n.val.try_into().unwrap()))
+                self.array_type(id, (**count).clone())
+                    .unwrap_or_else(|| self.proj.types.insert(Type::Array(id, 0)))
             }
             TypeHint::Option(ty) => self.resolve_lang_type("option", std::slice::from_ref(ty)),
             TypeHint::Vec(ty) => self.resolve_lang_type("vec", std::slice::from_ref(ty)),
@@ -4922,12 +6969,61 @@ impl TypeChecker {
                     params: params.iter().map(|p| self.resolve_typehint(p)).collect(),
                     ret: self.resolve_typehint(ret),
                 };
-                self.proj.types.insert(Type::FnPtr(fnptr))
+                self.proj.types.insert(Type::FnPtr(fnptr))
+            }
+            TypeHint::DynFn { params, ret } => {
+                let fnptr = FnPtr {
+                    params: params.iter().map(|p| self.resolve_typehint(p)).collect(),
+                    ret: self.resolve_typehint(ret),
+                };
+                self.proj.types.insert(Type::DynFn(fnptr))
+            }
+            TypeHint::AssocType(name, _) => self.error(Error::new(
+                "associated type bindings are only allowed in the type arguments of a trait",
+                name.span,
+            )),
+            TypeHint::Const(expr) => {
+                self.error(Error::new("expected a type, found a constant", expr.span))
             }
             TypeHint::Error => TypeId::UNKNOWN,
         }
     }
 
+    /// Returns the type named by the alias `id` with `ty_args` substituted for its type parameters.
+    fn resolve_alias(&mut self, id: UserTypeId, ty_args: &TypeArgs, span: Span) -> TypeId {
+        let ty = *self.proj.scopes.get(id).kind.as_alias().unwrap();
+        if matches!(self.proj.types[ty], Type::Unresolved(_)) {
+            if let Some(&current) = self.resolving_aliases.last() {
+                if self.resolving_aliases.contains(&id) {
+                    let name = format!("type alias '{}'", self.proj.scopes.get(id).name.data);
+                    let other = if current == id {
+                        "itself".into()
+                    } else {
+                        format!("type alias '{}'", self.proj.scopes.get(current).name.data)
+                    };
+                    bail!(self, Error::cyclic(&name, &other, span));
+                }
+            }
+
+            self.resolving_aliases.push(id);
+            resolve_type!(
+                self,
+                *self.proj.scopes.get_mut(id).kind.as_alias_mut().unwrap()
+            );
+            self.resolving_aliases.pop();
+        }
+
+        let ty = *self.proj.scopes.get(id).kind.as_alias().unwrap();
+        ty.with_templates(&mut self.proj.types, ty_args)
+    }
+
+    fn resolved_from_type(&self, ty: TypeId) -> ResolvedType {
+        match &self.proj.types[ty] {
+            Type::User(ut) => ResolvedType::UserType(ut.clone()),
+            _ => ResolvedType::Builtin(ty),
+        }
+    }
+
     fn resolve_members(&mut self, id: UserTypeId) {
         if self.proj.scopes.get(id).members_resolved {
             return;
@@ -5121,7 +7217,7 @@ impl TypeChecker {
     }
 
     fn check_member_dep(&mut self, mut this: TypeId, ut: TypeId, deps: &mut Vec<TypeId>) -> bool {
-        while let &Type::Array(inner, _) = &self.proj.types[this] {
+        while let &Type::Array(inner, _) | &Type::GenericArray(inner, _) = &self.proj.types[this] {
             this = inner;
         }
         if ut == this {
@@ -5137,7 +7233,7 @@ impl TypeChecker {
                 return true;
             }
             if let Some(Dependencies::Resolved(member_deps)) = self.proj.deps.get(&this) {
-                if member_deps.iter().any(|&v| v == ut) {
+                if member_deps.contains(&ut) {
                     return true;
                 }
 
@@ -5156,6 +7252,80 @@ impl TypeChecker {
         for i in 0..self.proj.scopes.get(id).impls.len() {
             resolve_impl!(self, self.proj.scopes.get_mut(id).impls[i]);
         }
+
+        if self.proj.scopes.get(id).kind.is_template() {
+            self.const_param_ty(id);
+            self.declare_projections(id);
+        }
+    }
+
+    /// Gives every associated type left unspecified by the bounds of the type parameter `id` a
+    /// hidden type parameter of its own, so it can be named as `T::Item` in the generic code.
+    fn declare_projections(&mut self, id: UserTypeId) {
+        let scope = self.proj.scopes.get(id).body_scope;
+        let owner = self.proj.scopes[scope].kind.clone();
+        match owner {
+            ScopeKind::Function(_) => {}
+            ScopeKind::UserType(ut) if !self.proj.scopes.get(ut).kind.is_trait() => {}
+            _ => return,
+        }
+
+        for i in 0..self.proj.scopes.get(id).impls.len() {
+            let Some(bound) = self.proj.scopes.get(id).impls[i].as_checked().cloned() else {
+                continue;
+            };
+
+            for param in self.proj.scopes.get(bound.id).type_params.clone() {
+                if !self.proj.scopes.get(param).implicit
+                    || bound.ty_args.get(&param) != Some(&TypeId::UNKNOWN)
+                {
+                    continue;
+                }
+
+                self.resolve_impls(param);
+                let impls = self
+                    .proj
+                    .scopes
+                    .get(param)
+                    .impls
+                    .iter()
+                    .flat_map(|tr| tr.as_checked().cloned())
+                    .map(|mut tr| {
+                        tr.fill_templates(&mut self.proj.types, &bound.ty_args);
+                        TraitImpl::Checked(tr)
+                    })
+                    .collect();
+                let name = self.proj.scopes.get(id).name.clone();
+                let mut ut = UserType::template(
+                    Located::new(
+                        name.span,
+                        format!("{}::{}", name.data, self.proj.scopes.get(param).name.data),
+                    ),
+                    scope,
+                    impls,
+                );
+                ut.implicit = true;
+                let projection = UserTypeId::insert_in(&mut self.proj.scopes, ut, false, scope).id;
+                let ty = self.proj.types.insert(Type::User(GenericUserType::new(
+                    projection,
+                    Default::default(),
+                )));
+                if let Some(TraitImpl::Checked(bound)) =
+                    self.proj.scopes.get_mut(id).impls.get_mut(i)
+                {
+                    bound.ty_args.insert(param, ty);
+                }
+                match owner {
+                    ScopeKind::Function(f) => {
+                        self.proj.scopes.get_mut(f).type_params.push(projection)
+                    }
+                    ScopeKind::UserType(ut) => {
+                        self.proj.scopes.get_mut(ut).type_params.push(projection)
+                    }
+                    _ => unreachable!(),
+                }
+            }
+        }
     }
 
     fn resolve_impls_recursive(&mut self, id: UserTypeId) {
@@ -5187,7 +7357,21 @@ impl TypeChecker {
                 }
             }
 
-            resolve_type!(self, self.proj.scopes.get_mut(id).ret);
+            let ret = resolve_type!(self, self.proj.scopes.get_mut(id).ret);
+            if let Some(ut) = self.proj.types[ret].as_user().map(|ut| ut.id) {
+                if self.proj.scopes.get(ut).kind.as_frame().is_some() {
+                    resolve_type!(
+                        self,
+                        self.proj
+                            .scopes
+                            .get_mut(ut)
+                            .kind
+                            .as_frame_mut()
+                            .unwrap()
+                            .output
+                    );
+                }
+            }
 
             for i in 0..self.proj.scopes.get(id).type_params.len() {
                 self.resolve_impls(self.proj.scopes.get(id).type_params[i]);
@@ -5218,6 +7402,10 @@ impl TypeChecker {
 
             // ty_args: [X = int]
             for (arg, val) in tr.ty_args.iter_mut() {
+                if self.proj.scopes.get(*arg).implicit {
+                    continue;
+                }
+
                 let Type::User(ut) = &self.proj.types[*val] else {
                     return None;
                 };
@@ -5266,7 +7454,7 @@ impl TypeChecker {
                 .filter(|tr| tr.id == bound.id)
             {
                 tr.fill_templates(&mut self.proj.types, &ut.ty_args);
-                if &tr == bound {
+                if self.trait_matches(&tr, bound) {
                     return true;
                 }
             }
@@ -5279,7 +7467,7 @@ impl TypeChecker {
             || self
                 .proj
                 .scopes
-                .has_builtin_impl(&self.proj.types, ty, bound)
+                .has_builtin_impl(&mut self.proj.types, ty, bound)
         {
             return true;
         }
@@ -5299,6 +7487,112 @@ impl TypeChecker {
         false
     }
 
+    /// Returns whether the implementation `tr` satisfies `bound`. Associated types the bound
+    /// leaves unspecified match anything.
+    fn trait_matches(&self, tr: &GenericTrait, bound: &GenericTrait) -> bool {
+        tr.id == bound.id
+            && bound.ty_args.iter().all(|(id, &ty)| {
+                (ty == TypeId::UNKNOWN && self.proj.scopes.get(*id).implicit)
+                    || tr.ty_args.get(id) == Some(&ty)
+            })
+    }
+
+    /// Returns the non-blanket trait implementations of `ty`, including supertraits and those
+    /// provided by extensions in scope.
+    fn implemented_traits(&mut self, ty: TypeId) -> Vec<GenericTrait> {
+        let mut sources: Vec<_> = self.proj.types[ty].as_user().cloned().into_iter().collect();
+        sources.extend(self.extensions_in_scope_for(ty, self.current));
+
+        let mut result = Vec::new();
+        for ut in sources {
+            if self.proj.scopes.get(ut.id).kind.is_template() {
+                self.resolve_impls(ut.id);
+            }
+            self.resolve_impls_recursive(ut.id);
+            for i in 0..self.proj.scopes.get(ut.id).impls.len() {
+                let Some(tr) = self.proj.scopes.get(ut.id).impls[i].as_checked().cloned() else {
+                    continue;
+                };
+                if self
+                    .proj
+                    .scopes
+                    .get(ut.id)
+                    .impl_blocks
+                    .get(i)
+                    .is_some_and(|block| !block.type_params.is_empty())
+                {
+                    continue;
+                }
+
+                for mut tr in self
+                    .proj
+                    .scopes
+                    .get_trait_impls_ex(&mut self.proj.types, tr)
+                {
+                    tr.fill_templates(&mut self.proj.types, &ut.ty_args);
+                    result.push(tr);
+                }
+            }
+        }
+        result
+    }
+
+    /// Returns the type bound to the associated type `name` by one of the traits `ty` implements.
+    fn resolve_assoc_type(&mut self, ty: TypeId, name: &str) -> Option<TypeId> {
+        self.implemented_traits(ty).into_iter().find_map(|tr| {
+            self.proj
+                .scopes
+                .get(tr.id)
+                .type_params
+                .iter()
+                .find(|&&id| {
+                    let param = self.proj.scopes.get(id);
+                    param.implicit && param.name.data == name
+                })
+                .map(|id| tr.ty_args.get(id).copied().unwrap_or_default())
+        })
+    }
+
+    /// Infers the unknown type arguments of `item` that are determined by the associated types of
+    /// the trait bounds of its known type arguments.
+    fn infer_from_trait_bounds<T>(&mut self, item: &mut WithTypeArgs<T>) {
+        for (id, ty) in item.ty_args.0.clone() {
+            if ty == TypeId::UNKNOWN {
+                continue;
+            }
+
+            for bound in self.proj.scopes.get(id).impls.clone() {
+                let Ok(bound) = bound.into_checked() else {
+                    continue;
+                };
+                if !bound
+                    .ty_args
+                    .keys()
+                    .any(|&id| self.proj.scopes.get(id).implicit)
+                {
+                    continue;
+                }
+
+                let Some(imp) = self
+                    .implemented_traits(ty)
+                    .into_iter()
+                    .find(|tr| tr.id == bound.id)
+                else {
+                    continue;
+                };
+                for (param, &arg) in bound.ty_args.iter() {
+                    if let Some(&target) = imp
+                        .ty_args
+                        .get(param)
+                        .filter(|_| self.proj.scopes.get(*param).implicit)
+                    {
+                        item.infer_type_args(&mut self.proj.types, arg, target);
+                    }
+                }
+            }
+        }
+    }
+
     fn extensions_in_scope_for(&mut self, ty: TypeId, scope: ScopeId) -> Vec<GenericExtension> {
         fn implements_trait(
             this: &mut TypeChecker,
@@ -5311,7 +7605,7 @@ impl TypeChecker {
             if this
                 .proj
                 .scopes
-                .has_builtin_impl(&this.proj.types, ty, bound)
+                .has_builtin_impl(&mut this.proj.types, ty, bound)
             {
                 return true;
             }
@@ -5364,7 +7658,7 @@ impl TypeChecker {
             this.resolve_impls(ext);
 
             let mut ext = GenericExtension::from_id_unknown(&this.proj.scopes, ext);
-            ext.infer_type_args(&this.proj.types, ext_ty_id, ty);
+            ext.infer_type_args(&mut this.proj.types, ext_ty_id, ty);
             if ext_ty_id.with_templates(&mut this.proj.types, &ext.ty_args) != ty {
                 return None;
             }
@@ -5436,7 +7730,7 @@ impl TypeChecker {
             .get(id)
             .fns
             .iter()
-            .find(|&&id| (scopes.get(*id).name.data == method))
+            .find(|&&id| scopes.get(*id).name.data == method)
             .copied()
     }
 
@@ -5479,7 +7773,7 @@ impl TypeChecker {
             let imp_ty_args = if let Some(wanted_tr) = wanted_tr {
                 let (impl_i, impl_ut, imp) = impl_block.as_mut()?;
                 let ty_args = this.is_impl_usable(*impl_ut, *impl_i, imp, wanted_tr)?;
-                if imp != wanted_tr {
+                if !this.trait_matches(imp, wanted_tr) {
                     return None;
                 }
 
@@ -5529,7 +7823,7 @@ impl TypeChecker {
                     .scopes
                     .get_trait_impls_ex(&mut this.proj.types, tr)
                 {
-                    if wanted_tr.is_some_and(|id| id != &imp) {
+                    if wanted_tr.is_some_and(|wanted| !this.trait_matches(&imp, wanted)) {
                         continue;
                     }
 
@@ -5568,6 +7862,66 @@ impl TypeChecker {
             None
         }
 
+        if let Some(tr) = self.proj.scopes.frame_trait(&mut self.proj.types, inst) {
+            let tr_id = tr.id;
+            if wanted_tr.is_none_or(|wanted| wanted == &tr) {
+                if let Some(f) = Self::search(&self.proj.scopes, tr_id, method) {
+                    let mut func = GenericFn::new(f.id, finish(self, f.id));
+                    func.ty_args.copy_args(&tr.ty_args);
+                    func.ty_args.insert(
+                        *self.proj.scopes.get(tr_id).kind.as_trait().unwrap().0,
+                        inst,
+                    );
+                    return Some(MemberFn {
+                        func,
+                        owner: self.proj.scopes.get(tr_id).scope,
+                        typ: MemberFnType::Trait(tr),
+                        public: f.public,
+                        inst,
+                    });
+                }
+            }
+        }
+
+        if let Some(f) = self.proj.scopes.fn_signature(&mut self.proj.types, inst) {
+            let args = if f.params.is_empty() {
+                TypeId::VOID
+            } else {
+                self.proj.scopes.get_tuple(f.params, &mut self.proj.types)
+            };
+            for name in FN_TRAITS {
+                let Some(&tr_id) = self.proj.scopes.lang_traits.get(name) else {
+                    continue;
+                };
+                let tr = GenericTrait::from_type_args(&self.proj.scopes, tr_id, [args, f.ret]);
+                if wanted_tr.is_some_and(|wanted| wanted != &tr)
+                    || !self
+                        .proj
+                        .scopes
+                        .has_builtin_impl(&mut self.proj.types, inst, &tr)
+                {
+                    continue;
+                }
+
+                let Some(f) = Self::search(&self.proj.scopes, tr_id, method) else {
+                    continue;
+                };
+                let mut func = GenericFn::new(f.id, finish(self, f.id));
+                func.ty_args.copy_args(&tr.ty_args);
+                func.ty_args.insert(
+                    *self.proj.scopes.get(tr_id).kind.as_trait().unwrap().0,
+                    inst,
+                );
+                return Some(MemberFn {
+                    func,
+                    owner: self.proj.scopes.get(tr_id).scope,
+                    typ: MemberFnType::Trait(tr),
+                    public: f.public,
+                    inst,
+                });
+            }
+        }
+
         // TODO: trait implement overload ie.
         // impl Eq<f32> { ... } impl Eq<i32> { ... }
         let ut = if let Type::User(ut) = &self.proj.types[inst] {
@@ -5636,7 +7990,10 @@ impl TypeChecker {
         self.get_member_fn_ex(ty, None, method, scope, |this, id| {
             this.resolve_type_args(id, generics, false, span)
         })
-        .inspect(|memfn| self.resolve_proto(memfn.func.id))
+        .inspect(|memfn| {
+            self.resolve_proto(memfn.func.id);
+            self.mark_fn_used(memfn.func.id);
+        })
     }
 
     fn get_member_fn_legacy(
@@ -5736,6 +8093,17 @@ impl TypeChecker {
             None
         }
 
+        if let Some(tr) = self
+            .proj
+            .scopes
+            .frame_trait(&mut self.proj.types, inst)
+            .filter(|tr| tr.id == wanted_tr)
+        {
+            return self.get_member_fn_ex(inst, Some(&tr), method, scope, |this, id| {
+                GenericFn::from_id_unknown(&this.proj.scopes, id).ty_args
+            });
+        }
+
         let ut = if let Type::User(ut) = &self.proj.types[inst] {
             let ut = ut.clone();
             if let Some(f) = Self::search(&self.proj.scopes, ut.id, method) {
@@ -5881,6 +8249,7 @@ impl TypeChecker {
         }
     }
 
+    #[allow(clippy::result_large_err)]
     fn coerce(&mut self, mut expr: CExpr, target: TypeId) -> Result<CExpr, CExpr> {
         fn may_ptr_coerce(types: &Types, lhs: &Type, rhs: &Type) -> bool {
             match (lhs, rhs) {
@@ -5934,6 +8303,19 @@ impl TypeChecker {
                     Err(expr)
                 }
             }
+            (Type::User(_), Type::DynFn(rhs)) => {
+                let rhs = rhs.clone();
+                if self
+                    .proj
+                    .scopes
+                    .fn_signature(&mut self.proj.types, expr.ty)
+                    .is_some_and(|f| f == rhs)
+                {
+                    Ok(CExpr::new(target, CExprData::DynFnCoerce(expr.into())))
+                } else {
+                    Err(expr)
+                }
+            }
             (lhs, rhs) if may_ptr_coerce(&self.proj.types, lhs, rhs) => {
                 expr.ty = target;
                 Ok(expr)
@@ -5997,6 +8379,7 @@ impl TypeChecker {
                     name,
                     ty,
                     is_static: false,
+                    is_const: false,
                     mutable,
                     value: None,
                     unused: typ != PatternType::BodylessFn,
@@ -6090,7 +8473,7 @@ impl TypeChecker {
         {
             let patt_applies = |patt: &PatternData, name: &str| {
                 patt.as_variant().is_some_and(|(sub, variant, _, _)| {
-                    name == variant && sub.as_ref().map_or(true, |sub| sub.irrefutable)
+                    name == variant && sub.as_ref().is_none_or(|sub| sub.irrefutable)
                 })
             };
 
@@ -6103,7 +8486,7 @@ impl TypeChecker {
                         || patt
                             .data
                             .as_or()
-                            .is_some_and(|p| p.iter().any(|sub| patt_applies(&sub.data, name)))
+                            .is_some_and(|(p, _)| p.iter().any(|sub| patt_applies(&sub.data, name)))
                     {
                         continue 'outer;
                     }
@@ -6113,10 +8496,10 @@ impl TypeChecker {
             }
 
             if !missing.is_empty() {
-                return self.error(Error::match_statement(
+                self.error(Error::match_statement(
                     &format!("(missing variant(s) {})", missing.join(", ")),
                     span,
-                ));
+                ))
             }
         } else if !patterns.any(|patt| patt.irrefutable) {
             // covers struct/array/void
@@ -6161,13 +8544,20 @@ impl TypeChecker {
                     span,
                 )))
             }
-            ResolvedValue::Var(id) => {
+            ResolvedValue::Var(id) | ResolvedValue::AssocConst { id, .. } => {
                 return Err(Some(Error::expected_found(
                     &scrutinee.name(&self.proj.scopes, &mut self.proj.types),
                     &format!("variable '{}'", self.proj.scopes.get(id).name),
                     span,
                 )))
             }
+            ResolvedValue::ConstParam(id) => {
+                return Err(Some(Error::expected_found(
+                    &scrutinee.name(&self.proj.scopes, &mut self.proj.types),
+                    &format!("constant '{}'", self.proj.scopes.get(id).name),
+                    span,
+                )))
+            }
             ResolvedValue::NotFound(err) => return Err(Some(err)),
             ResolvedValue::Error => return Err(None),
         };
@@ -6455,11 +8845,8 @@ impl TypeChecker {
         } in destructures
         {
             let Some(member) = self.proj.scopes.get(ut_id).members.get(&name.data) else {
-                self.proj.diag.error(Error::no_member(
-                    &scrutinee.name(&self.proj.scopes, &mut self.proj.types),
-                    &name.data,
-                    name.span,
-                ));
+                let err = self.member_not_found(scrutinee, &name);
+                self.proj.diag.error(err);
                 continue;
             };
 
@@ -6623,9 +9010,9 @@ impl TypeChecker {
         subpatterns: Vec<Located<Pattern>>,
         typ: PatternType,
     ) -> CPattern {
-        let mut prev_vars = HashMap::new();
+        let mut first_vars: Vec<VariableId> = vec![];
+        let mut alt_vars = vec![];
         let mut patterns = vec![];
-        let nsubpatterns = subpatterns.len();
         for (i, pattern) in subpatterns.into_iter().enumerate() {
             let patt_span = pattern.span;
             let (res, vars) = self.listen_for_vars(self.current_expr, |this| {
@@ -6634,34 +9021,36 @@ impl TypeChecker {
                     scrutinee,
                     mutable,
                     pattern,
-                    typ: if i + 1 != nsubpatterns {
-                        PatternType::BodylessFn
-                    } else {
-                        typ
-                    },
+                    typ: PatternType::BodylessFn,
                     has_hint: false,
                 })
             });
             patterns.push(res);
-            if self.current_expr == self.listening_expr {
-                self.listening_vars.extend_from_slice(&vars);
+            // the variables of each alternative are only visible to the body through the
+            // variables created for the whole pattern below
+            for &id in vars.iter() {
+                let name = self.proj.scopes.get(id).name.data.clone();
+                self.proj.scopes[self.current].vns.remove(&name);
             }
 
             if i == 0 {
-                prev_vars = vars
-                    .into_iter()
-                    .map(|v| (self.proj.scopes.get(v).name.data.clone(), v))
-                    .collect();
+                alt_vars.push(vars.iter().copied().map(Some).collect());
+                first_vars = vars;
                 continue;
             }
 
-            let mut prev_vars = prev_vars.clone();
+            let mut prev_vars: HashMap<_, _> = first_vars
+                .iter()
+                .enumerate()
+                .map(|(pos, &v)| (self.proj.scopes.get(v).name.data.clone(), pos))
+                .collect();
+            let mut this_vars = vec![None; first_vars.len()];
             for &id in vars.iter() {
                 let var = self.proj.scopes.get(id);
-                if let Some(old_ty) = prev_vars
-                    .remove(&var.name.data)
-                    .map(|v| self.proj.scopes.get(v).ty)
-                {
+                if let Some(pos) = prev_vars.remove(&var.name.data) {
+                    this_vars[pos] = Some(id);
+                    let old = self.proj.scopes.get(first_vars[pos]);
+                    let (old_ty, old_mutable) = (old.ty, old.mutable);
                     if var.ty != old_ty {
                         let ty_name = var.ty.name(&self.proj.scopes, &mut self.proj.types);
                         let old_ty_name = old_ty.name(&self.proj.scopes, &mut self.proj.types);
@@ -6672,6 +9061,15 @@ impl TypeChecker {
                             ),
                             var.name.span,
                         ))
+                    } else if var.mutable != old_mutable {
+                        self.error(Error::new(
+                            format!(
+                                "variable '{}' must be bound {}mutably in all cases",
+                                var.name.data,
+                                if old_mutable { "" } else { "im" },
+                            ),
+                            var.name.span,
+                        ))
                     }
                 } else {
                     self.error(Error::new(
@@ -6687,10 +9085,25 @@ impl TypeChecker {
                     patt_span,
                 ))
             }
+
+            alt_vars.push(this_vars);
+        }
+
+        let mut bindings = vec![];
+        for (i, &id) in first_vars.iter().enumerate() {
+            let var = self.proj.scopes.get(id);
+            let (name, ty, mutable) = (var.name.clone(), var.ty, var.mutable);
+            let Some(alts) = alt_vars.iter().map(|vars| vars[i]).collect() else {
+                continue;
+            };
+
+            if let Some(id) = self.insert_pattern_var(typ, name, ty, mutable, false) {
+                bindings.push((id, alts));
+            }
         }
 
         // TODO: the pattern can be irrefutable if it is exhaustive
-        CPattern::refutable(PatternData::Or(patterns))
+        CPattern::refutable(PatternData::Or { patterns, bindings })
     }
 
     fn check_pattern(
@@ -6969,13 +9382,14 @@ impl TypeChecker {
 
 /// Path resolution routines
 impl TypeChecker {
-    fn find_in_tns(&self, name: &str) -> Option<Vis<TypeItem>> {
+    fn find_in_tns(&mut self, name: &str) -> Option<Vis<TypeItem>> {
         if let Some(item) = self.proj.scopes.autouse_tns.get(name).copied() {
             return Some(item);
         }
 
         for (id, scope) in self.proj.scopes.walk(self.current) {
             if let Some(item) = self.proj.scopes[id].find_in_tns(name) {
+                self.proj.scopes[id].unused_imports.remove(name);
                 return Some(item);
             }
 
@@ -6987,7 +9401,7 @@ impl TypeChecker {
         None
     }
 
-    fn find_in_vns(&self, name: &str) -> Option<Vis<ValueItem>> {
+    fn find_in_vns(&mut self, name: &str) -> Option<Vis<ValueItem>> {
         if let Some(item) = self.proj.scopes.autouse_vns.get(name).copied() {
             return Some(item);
         }
@@ -6998,6 +9412,7 @@ impl TypeChecker {
                     continue;
                 }
 
+                self.proj.scopes[id].unused_imports.remove(name);
                 return Some(item);
             }
 
@@ -7017,7 +9432,178 @@ impl TypeChecker {
         ) {
             Some(module)
         } else {
-            self.error(Error::new("cannot use super here", span))
+            self.error(Error::new("cannot use super here", span))
+        }
+    }
+
+    /// Returns the names that can be referred to without qualification from the current scope.
+    fn visible_names(&self, types: bool) -> Vec<&str> {
+        let mut names = vec![];
+        for (_, scope) in self.proj.scopes.walk(self.current) {
+            if types {
+                names.extend(scope.tns.keys().map(|name| name.as_str()));
+            } else {
+                let in_type = matches!(scope.kind, ScopeKind::UserType(_));
+                names.extend(
+                    scope
+                        .vns
+                        .iter()
+                        .filter(|(_, item)| !(in_type && item.is_fn()))
+                        .map(|(name, _)| name.as_str()),
+                );
+            }
+
+            if matches!(scope.kind, ScopeKind::Module(_)) {
+                break;
+            }
+        }
+
+        if types {
+            names.extend(
+                self.proj
+                    .scopes
+                    .autouse_tns
+                    .keys()
+                    .map(|name| name.as_str()),
+            );
+        } else {
+            names.extend(
+                self.proj
+                    .scopes
+                    .autouse_vns
+                    .keys()
+                    .map(|name| name.as_str()),
+            );
+        }
+        names
+    }
+
+    /// Finds an accessible item called `name` declared in some other module, returning the path
+    /// that can be used to import it.
+    fn importable_path(&self, name: &str, types: bool) -> Option<String> {
+        let scopes = &self.proj.scopes;
+        let mut paths: Vec<_> = scopes
+            .scopes()
+            .filter(|(_, scope)| scope.kind.is_module())
+            .filter(|&(id, scope)| {
+                let declared = if types {
+                    scope
+                        .find_in_tns(name)
+                        .filter(|item| match item.id {
+                            TypeItem::Type(ut) => scopes.get(ut).scope == id,
+                            TypeItem::Module(module) => scopes[module].parent == Some(id),
+                        })
+                        .map(|item| item.public)
+                } else {
+                    scope
+                        .find_in_vns(name)
+                        .filter(|item| match item.id {
+                            ValueItem::Fn(f) => scopes.get(f).scope == id,
+                            ValueItem::StructConstructor(ut, _)
+                            | ValueItem::UnionConstructor(ut) => scopes.get(ut).scope == id,
+                            ValueItem::Var(var) => scopes.get(var).scope == id,
+                        })
+                        .map(|item| item.public)
+                };
+                declared.is_some_and(|public| public || self.can_access_privates(id))
+            })
+            .map(|(id, _)| {
+                let mut path: Vec<_> = scopes
+                    .walk(id)
+                    .filter_map(|(_, scope)| scope.kind.as_module())
+                    .map(|name| name.data.as_str())
+                    .collect();
+                path.reverse();
+                path.push(name);
+                path.join("::")
+            })
+            .collect();
+        paths.sort_by_key(|path| (path.len(), path.clone()));
+        paths.into_iter().next()
+    }
+
+    /// Creates the error for a failed lookup of `name` in `scope`. Lookups that fell back to the
+    /// root scope started from the current scope, so they also get suggestions for names that are
+    /// visible from it and for items that could be imported.
+    fn symbol_not_found(&self, name: &Located<String>, scope: ScopeId, types: bool) -> Error {
+        let mut err = Error::no_symbol(&name.data, name.span);
+        let cap = self.can_access_privates(scope);
+        let mut candidates: Vec<_> = if types {
+            self.proj.scopes[scope]
+                .tns
+                .iter()
+                .filter(|(_, item)| item.public || cap)
+                .map(|(name, _)| name.as_str())
+                .collect()
+        } else {
+            self.proj.scopes[scope]
+                .vns
+                .iter()
+                .filter(|(_, item)| item.public || cap)
+                .map(|(name, _)| name.as_str())
+                .collect()
+        };
+        if scope == ScopeId::ROOT {
+            candidates.extend(self.visible_names(types));
+        }
+        if let Some(similar) = closest_match(&name.data, candidates) {
+            err = err.with_note(format!("did you mean '{similar}'?"));
+        }
+        if scope == ScopeId::ROOT {
+            if let Some(path) = self.importable_path(&name.data, types) {
+                err = err.with_note(format!(
+                    "an item with this name exists in another module: add `use {path};`"
+                ));
+            }
+        }
+        err
+    }
+
+    fn member_not_found(&mut self, ty: TypeId, name: &Located<String>) -> Error {
+        let items = self.member_items(ty, true);
+        let err = Error::no_member(
+            &ty.name(&self.proj.scopes, &mut self.proj.types),
+            &name.data,
+            name.span,
+        );
+        if name.data.starts_with(|ch: char| ch.is_ascii_digit()) {
+            return err;
+        }
+
+        let candidates = items.iter().filter_map(|item| match item {
+            LspItem::Property(_, _, name) => Some(name.as_str()),
+            _ => None,
+        });
+        match closest_match(&name.data, candidates) {
+            Some(similar) => err.with_note(format!("did you mean '{similar}'?")),
+            None => err,
+        }
+    }
+
+    fn method_not_found(
+        &mut self,
+        ty: TypeId,
+        name: &Located<String>,
+        span: Span,
+        method: bool,
+    ) -> Error {
+        let items = self.member_items(ty, method);
+        let err = if method {
+            Error::no_method(
+                &ty.name(&self.proj.scopes, &mut self.proj.types),
+                &name.data,
+                span,
+            )
+        } else {
+            Error::no_symbol(&name.data, span)
+        };
+        let candidates = items.iter().filter_map(|item| match item {
+            &LspItem::Fn(id, _) => Some(self.proj.scopes.get(id).name.data.as_str()),
+            _ => None,
+        });
+        match closest_match(&name.data, candidates) {
+            Some(similar) => err.with_note(format!("did you mean '{similar}'?")),
+            None => err,
         }
     }
 
@@ -7058,6 +9644,7 @@ impl TypeChecker {
                 self.resolve_use_in(ScopeId::ROOT, *public, components, tail)
             }
             PathOrigin::Infer => unreachable!("Infer origin in use path"),
+            PathOrigin::This(_) => unreachable!("This origin in use path"),
         }
     }
 
@@ -7083,7 +9670,7 @@ impl TypeChecker {
                     }
                     return Ok(());
                 }
-                _ => return Err(Error::no_symbol(&comp.data, comp.span)),
+                _ => return Err(self.symbol_not_found(comp, scope, true)),
             }
         }
 
@@ -7096,12 +9683,12 @@ impl TypeChecker {
                     self.error(Error::private(&tail.data, tail.span))
                 }
 
-                if self.proj.scopes[self.current]
+                if let Some(prev) = self.proj.scopes[self.current]
                     .tns
                     .insert(tail.data.clone(), Vis::new(*item, public))
-                    .is_some()
                 {
-                    self.error(Error::redefinition(&tail.data, tail.span))
+                    let prev = InsertedItem::from(prev.id).span(&self.proj.scopes);
+                    self.error(Error::redefinition(&tail.data, tail.span, prev))
                 }
                 found = true;
             }
@@ -7121,13 +9708,16 @@ impl TypeChecker {
                     skip = true;
                 }
 
-                if !skip
-                    && self.proj.scopes[self.current]
-                        .vns
-                        .insert(tail.data.clone(), Vis::new(*item, public))
-                        .is_some()
+                if let Some(prev) = (!skip)
+                    .then(|| {
+                        self.proj.scopes[self.current]
+                            .vns
+                            .insert(tail.data.clone(), Vis::new(*item, public))
+                    })
+                    .flatten()
                 {
-                    self.error(Error::redefinition(&tail.data, tail.span))
+                    let prev = InsertedItem::from(prev.id).span(&self.proj.scopes);
+                    self.error(Error::redefinition(&tail.data, tail.span, prev))
                 }
                 found = true;
             }
@@ -7138,6 +9728,23 @@ impl TypeChecker {
                     tail.span,
                 ));
             }
+
+            // traits and extensions are used implicitly by method calls, which aren't tracked, and
+            // autouse items shadow anything imported with the same name
+            let implicit = self.proj.scopes.autouse_tns.contains_key(&tail.data)
+                || self.proj.scopes.autouse_vns.contains_key(&tail.data)
+                || self.proj.scopes[scope]
+                    .find_in_tns(&tail.data)
+                    .and_then(|item| item.as_type().copied())
+                    .is_some_and(|id| {
+                        let kind = &self.proj.scopes.get(id).kind;
+                        kind.is_trait() || kind.is_extension()
+                    });
+            if !public && !implicit {
+                self.proj.scopes[self.current]
+                    .unused_imports
+                    .insert(tail.data.clone(), tail.span);
+            }
         } else {
             let cap = self.can_access_privates(scope);
             if let Some((scope, current)) = self.proj.scopes.borrow_twice(scope, self.current) {
@@ -7192,13 +9799,18 @@ impl TypeChecker {
             };
             self.check_hover(tail.span, (*id).into());
             self.proj.tokens.push(SpanSemanticToken::Variant(tail.span));
+            if !public {
+                self.proj.scopes[self.current]
+                    .unused_imports
+                    .insert(tail.data.clone(), tail.span);
+            }
 
-            if self.proj.scopes[self.current]
+            if let Some(prev) = self.proj.scopes[self.current]
                 .vns
                 .insert(tail.data.clone(), Vis::new((*id).into(), public))
-                .is_some()
             {
-                self.error(Error::redefinition(&tail.data, tail.span))
+                let prev = InsertedItem::from(prev.id).span(&self.proj.scopes);
+                self.error(Error::redefinition(&tail.data, tail.span, prev))
             }
         } else {
             for id in constructors.copied().collect::<Vec<_>>() {
@@ -7237,6 +9849,11 @@ impl TypeChecker {
                     Some(TypeItem::Type(id)) => {
                         self.check_hover(name.span, id.into());
                         let ty_args = self.resolve_type_args(id, ty_args, true, name.span);
+                        if self.proj.scopes.get(id).kind.is_alias() {
+                            let ty = self.resolve_alias(id, &ty_args, name.span);
+                            return self.resolve_type_path_from_type(ty, rest, span);
+                        }
+
                         if rest.is_empty() {
                             if self.proj.scopes.get(id).kind.is_extension() {
                                 return self.error(Error::expected_found(
@@ -7249,12 +9866,11 @@ impl TypeChecker {
                             return ResolvedType::UserType(GenericUserType::new(id, ty_args));
                         }
 
-                        self.resolve_type_path_in(
-                            rest,
-                            ty_args,
-                            self.proj.scopes.get(id).body_scope,
-                            span,
-                        )
+                        let ty = self
+                            .proj
+                            .types
+                            .insert(Type::User(GenericUserType::new(id, ty_args)));
+                        self.resolve_type_path_from_type(ty, rest, span)
                     }
                     Some(TypeItem::Module(id)) if !rest.is_empty() => {
                         self.check_hover(name.span, id.into());
@@ -7275,10 +9891,66 @@ impl TypeChecker {
                     ),
                 }
             }
+            PathOrigin::This(this_span) => {
+                let ty = self.resolve_typehint(&TypeHint::This(this_span));
+                self.resolve_assoc_type_path(ty, &path.components)
+            }
             PathOrigin::Infer => unreachable!("Infer path in type path"),
         }
     }
 
+    /// Resolves the remaining components of a type path starting at `ty`, preferring items
+    /// declared in the body of `ty` over its associated types.
+    fn resolve_type_path_from_type(
+        &mut self,
+        ty: TypeId,
+        rest: &[PathComponent],
+        span: Span,
+    ) -> ResolvedType {
+        let Some((name, _)) = rest.first() else {
+            return self.resolved_from_type(ty);
+        };
+
+        match &self.proj.types[ty] {
+            Type::User(ut) if !self.proj.scopes.get(ut.id).kind.is_template() => {
+                let ut = ut.clone();
+                let scope = self.proj.scopes.get(ut.id).body_scope;
+                if self.proj.scopes[scope].find_in_tns(&name.data).is_some()
+                    || self.resolve_assoc_type(ty, &name.data).is_none()
+                {
+                    return self.resolve_type_path_in(rest, ut.ty_args, scope, span);
+                }
+                self.resolve_assoc_type_path(ty, rest)
+            }
+            _ => self.resolve_assoc_type_path(ty, rest),
+        }
+    }
+
+    fn resolve_assoc_type_path(&mut self, mut ty: TypeId, rest: &[PathComponent]) -> ResolvedType {
+        for (name, args) in rest {
+            if ty == TypeId::UNKNOWN {
+                return ResolvedType::Error;
+            }
+
+            let Some(assoc) = self.resolve_assoc_type(ty, &name.data) else {
+                let ty = ty.name(&self.proj.scopes, &mut self.proj.types);
+                return self.error(Error::new(
+                    format!("no associated type '{}' found for type '{ty}'", name.data),
+                    name.span,
+                ));
+            };
+            if !args.is_empty() {
+                return self.error(Error::new(
+                    "associated types cannot be parameterized with type arguments",
+                    name.span,
+                ));
+            }
+            ty = assoc;
+        }
+
+        self.resolved_from_type(ty)
+    }
+
     fn resolve_type_path_in(
         &mut self,
         data: &[PathComponent],
@@ -7293,7 +9965,7 @@ impl TypeChecker {
             }
 
             let Some(item) = self.proj.scopes[scope].find_in_tns(&name.data) else {
-                return self.error(Error::no_symbol(&name.data, name.span));
+                return self.error(self.symbol_not_found(name, scope, true));
             };
 
             if !item.public && !self.can_access_privates(scope) {
@@ -7307,6 +9979,11 @@ impl TypeChecker {
                     scope = ty.body_scope;
 
                     let args = self.resolve_type_args(id, args, true, name.span);
+                    if self.proj.scopes.get(id).kind.is_alias() {
+                        let ty = self.resolve_alias(id, &args, name.span);
+                        return self.resolve_type_path_from_type(ty, &data[i + 1..], total_span);
+                    }
+
                     if done {
                         if self.proj.scopes.get(id).kind.is_extension() {
                             return self.error(Error::expected_found(
@@ -7319,6 +9996,17 @@ impl TypeChecker {
                         return ResolvedType::UserType(GenericUserType::new(id, args));
                     }
 
+                    let (next, _) = &data[i + 1];
+                    if self.proj.scopes[scope].find_in_tns(&next.data).is_none() {
+                        let ty = self
+                            .proj
+                            .types
+                            .insert(Type::User(GenericUserType::new(id, args.clone())));
+                        if self.resolve_assoc_type(ty, &next.data).is_some() {
+                            return self.resolve_assoc_type_path(ty, &data[i + 1..]);
+                        }
+                    }
+
                     ty_args.copy_args(&args);
                 }
                 TypeItem::Module(id) => {
@@ -7371,6 +10059,7 @@ impl TypeChecker {
                     match self.find_in_vns(&name.data).map(|f| f.id) {
                         Some(ValueItem::Fn(id)) => {
                             self.resolve_proto(id);
+                            self.mark_fn_used(id);
                             self.check_hover(name.span, id.into());
                             let mut ty_args = self.resolve_type_args(id, ty_args, false, name.span);
                             if let Some(id) = self.proj.scopes.get(id).constructor {
@@ -7414,12 +10103,23 @@ impl TypeChecker {
 
                             ResolvedValue::Var(id)
                         }
-                        None => self.resolve_value_path_in(
-                            &path.components,
-                            Default::default(),
-                            ScopeId::ROOT,
-                            span,
-                        ),
+                        None => {
+                            if let Some(id) = self
+                                .find_in_tns(&name.data)
+                                .and_then(|item| item.as_type().copied())
+                                .filter(|&id| self.proj.scopes.get(id).const_ty.is_some())
+                            {
+                                self.check_hover(name.span, id.into());
+                                return ResolvedValue::ConstParam(id);
+                            }
+
+                            self.resolve_value_path_in(
+                                &path.components,
+                                Default::default(),
+                                ScopeId::ROOT,
+                                span,
+                            )
+                        }
                     }
                 } else {
                     match self.find_in_tns(&name.data).map(|t| t.id) {
@@ -7428,6 +10128,9 @@ impl TypeChecker {
                             let mut ty_args = self.resolve_type_args(id, ty_args, false, name.span);
                             if let UserTypeKind::Trait(this, _) = self.proj.scopes.get(id).kind {
                                 ty_args.insert(this, TypeId::UNKNOWN);
+                            } else if self.proj.scopes.get(id).kind.is_alias() {
+                                let ty = self.resolve_alias(id, &ty_args, name.span);
+                                return self.resolve_value_path_from_type(ty, rest, span);
                             } else {
                                 let ty = Type::User(GenericUserType::new(id, ty_args));
                                 let id = self.proj.types.insert(ty);
@@ -7461,6 +10164,13 @@ impl TypeChecker {
                     }
                 }
             }
+            PathOrigin::This(this_span) => {
+                let ty = self.resolve_typehint(&TypeHint::This(this_span));
+                if ty == TypeId::UNKNOWN {
+                    return ResolvedValue::Error;
+                }
+                self.resolve_value_path_from_type(ty, &path.components, span)
+            }
             PathOrigin::Infer => {
                 let Some(scope) = target
                     .and_then(|t| self.proj.types[t.strip_references(&self.proj.types)].as_user())
@@ -7477,7 +10187,10 @@ impl TypeChecker {
                     ResolvedValue::NotFound(_) | ResolvedValue::Error => return res,
                     ResolvedValue::Fn(func) => func,
                     ResolvedValue::MemberFn(mfn) => &mut mfn.func,
-                    ResolvedValue::UnionConstructor(_) | ResolvedValue::Var(_) => {
+                    ResolvedValue::UnionConstructor(_)
+                    | ResolvedValue::Var(_)
+                    | ResolvedValue::AssocConst { .. }
+                    | ResolvedValue::ConstParam(_) => {
                         self.proj
                             .diag
                             .error(Error::new("infer path must be to union variant", span));
@@ -7524,7 +10237,7 @@ impl TypeChecker {
         };
         for (i, (name, args)) in rest.iter().enumerate() {
             let Some(item) = self.proj.scopes[scope].find_in_tns(&name.data) else {
-                return ResolvedValue::NotFound(Error::no_symbol(&name.data, name.span));
+                return ResolvedValue::NotFound(self.symbol_not_found(name, scope, true));
             };
 
             if !item.public && !self.can_access_privates(scope) {
@@ -7540,6 +10253,9 @@ impl TypeChecker {
                     scope = ty.body_scope;
                     if let UserTypeKind::Trait(this, _) = ty.kind {
                         ty_args.insert(this, TypeId::UNKNOWN);
+                    } else if ty.kind.is_alias() {
+                        let ty = self.resolve_alias(id, &ty_args, name.span);
+                        return self.resolve_value_path_from_type(ty, &data[i + 1..], total_span);
                     } else {
                         let ty = Type::User(GenericUserType::new(id, ty_args));
                         let id = self.proj.types.insert(ty);
@@ -7562,7 +10278,7 @@ impl TypeChecker {
 
         self.check_module_completions(total_span, false, scope);
         let Some(item) = self.proj.scopes[scope].find_in_vns(&last_name.data) else {
-            return ResolvedValue::NotFound(Error::no_symbol(&last_name.data, last_name.span));
+            return ResolvedValue::NotFound(self.symbol_not_found(last_name, scope, false));
         };
 
         if !item.public && !self.can_access_privates(scope) {
@@ -7573,6 +10289,7 @@ impl TypeChecker {
         match *item {
             ValueItem::Fn(id) => {
                 self.resolve_proto(id);
+                self.mark_fn_used(id);
                 self.check_hover(span, id.into());
                 if let Some(id) = self.proj.scopes.get(id).constructor {
                     if self.proj.scopes.get(id).kind.is_union() {
@@ -7618,9 +10335,20 @@ impl TypeChecker {
     ) -> ResolvedValue {
         let ((name, args), rest) = rest.split_first().unwrap();
         self.check_dot_completions(total_span, ty, false);
+        if rest.is_empty() {
+            if let Some(res) = self.resolve_assoc_const(ty, &name.data, name.span) {
+                if !args.is_empty() {
+                    self.error(Error::new(
+                        "variables cannot be parameterized with type arguments",
+                        name.span,
+                    ))
+                }
+                return res;
+            }
+        }
 
         let Some(mfn) = self.get_member_fn(ty, &name.data, args, name.span, self.current) else {
-            return ResolvedValue::NotFound(Error::no_symbol(&name.data, name.span));
+            return ResolvedValue::NotFound(self.method_not_found(ty, name, name.span, false));
         };
 
         self.check_hover(name.span, mfn.func.id.into());
@@ -7647,6 +10375,84 @@ impl TypeChecker {
         ResolvedValue::MemberFn(mfn)
     }
 
+    /// Finds the constant `name` associated with `ty`, either declared by the type itself or required
+    /// by one of the traits it is known to implement.
+    fn resolve_assoc_const(&mut self, ty: TypeId, name: &str, span: Span) -> Option<ResolvedValue> {
+        if let Some(id) = self.find_assoc_const(ty, name, self.current) {
+            self.check_hover(span, id.into());
+            resolve_type!(self, self.proj.scopes.get_mut(id).ty);
+            let var = self.proj.scopes.get(id);
+            if !var.public && !self.can_access_privates(var.scope) {
+                self.error(Error::private(name, span))
+            }
+            self.proj.scopes.get_mut(id).unused = false;
+            return Some(ResolvedValue::Var(id));
+        }
+
+        for tr in self.implemented_traits(ty) {
+            let scope = self.proj.scopes.get(tr.id).body_scope;
+            let Some(id) = self.proj.scopes[scope]
+                .vns
+                .get(name)
+                .and_then(|item| item.as_var().copied())
+            else {
+                continue;
+            };
+
+            self.check_hover(span, id.into());
+            let mut ty_args = tr.ty_args.clone();
+            ty_args.insert(*self.proj.scopes.get(tr.id).kind.as_trait().unwrap().0, ty);
+            let value_ty = resolve_type!(self, self.proj.scopes.get_mut(id).ty)
+                .with_templates(&mut self.proj.types, &ty_args);
+            return Some(ResolvedValue::AssocConst { ty, id, value_ty });
+        }
+
+        None
+    }
+
+    /// Returns the constant named `name` declared in the body of `ty` or one of its trait
+    /// implementations.
+    pub(crate) fn find_assoc_const(
+        &mut self,
+        ty: TypeId,
+        name: &str,
+        scope: ScopeId,
+    ) -> Option<VariableId> {
+        let mut sources: Vec<_> = self.proj.types[ty]
+            .as_user()
+            .map(|ut| ut.id)
+            .filter(|&id| !self.proj.scopes.get(id).kind.is_template())
+            .into_iter()
+            .collect();
+        sources.extend(
+            self.extensions_in_scope_for(ty, scope)
+                .into_iter()
+                .map(|ext| ext.id),
+        );
+        sources.into_iter().find_map(|id| {
+            let scope = self.proj.scopes.get(id).body_scope;
+            self.proj.scopes[scope]
+                .vns
+                .get(name)
+                .and_then(|item| item.as_var().copied())
+                .filter(|&id| self.proj.scopes.get(id).is_const)
+        })
+    }
+
+    /// Returns the trait declaring the constant `id` if it is a trait's constant.
+    fn trait_of_const(&self, id: VariableId) -> Option<UserTypeId> {
+        let var = self.proj.scopes.get(id);
+        if !var.is_const {
+            return None;
+        }
+
+        self.proj.scopes[var.scope]
+            .kind
+            .as_user_type()
+            .copied()
+            .filter(|&id| self.proj.scopes.get(id).kind.is_trait())
+    }
+
     fn resolve_type_args<T: ItemId>(
         &mut self,
         id: T,
@@ -7657,33 +10463,67 @@ impl TypeChecker {
     where
         T::Value: HasTypeParams,
     {
+        for i in 0..self.proj.scopes.get(id).get_type_params().len() {
+            self.resolve_impls(self.proj.scopes.get(id).get_type_params()[i]);
+        }
+
         let params = self.proj.scopes.get(id).get_type_params().to_vec();
-        if (typehint || !args.is_empty()) && args.len() != params.len() {
+        let (explicit, implicit): (Vec<UserTypeId>, Vec<_>) = params
+            .iter()
+            .partition(|&&param| !self.proj.scopes.get(param).implicit);
+        let (assoc, positional): (Vec<_>, Vec<_>) = args
+            .iter()
+            .partition(|arg| matches!(arg, TypeHint::AssocType(_, _)));
+        if (typehint || !positional.is_empty()) && positional.len() != explicit.len() {
             self.error(Error::new(
                 format!(
                     "expected {} type argument(s), received {}",
-                    params.len(),
-                    args.len()
+                    explicit.len(),
+                    positional.len()
                 ),
                 span,
             ))
         }
 
-        let ty_args = TypeArgs(
-            params
+        let mut ty_args = TypeArgs(params.iter().map(|&id| (id, TypeId::UNKNOWN)).collect());
+        for (&param, ty) in explicit.iter().zip(positional) {
+            let ty = if self.proj.scopes.get(param).const_ty.is_some() {
+                self.resolve_const_arg(param, ty, span)
+            } else {
+                self.resolve_typehint(ty)
+            };
+            ty_args.insert(param, ty);
+        }
+        for arg in assoc {
+            let TypeHint::AssocType(name, ty) = arg else {
+                unreachable!()
+            };
+            let ty = self.resolve_typehint(ty);
+            if let Some(&param) = implicit
                 .iter()
-                .cloned()
-                .zip(
-                    args.iter()
-                        .map(|ty| self.resolve_typehint(ty))
-                        .take(params.len())
-                        .chain(
-                            std::iter::repeat(TypeId::UNKNOWN)
-                                .take(params.len().checked_sub(args.len()).unwrap_or_default()),
-                        ),
-                )
-                .collect(),
-        );
+                .find(|&&param| self.proj.scopes.get(param).name.data == name.data)
+            {
+                ty_args.insert(param, ty);
+            } else if implicit.is_empty() {
+                self.error(Error::new(
+                    "associated type bindings are only allowed in the type arguments of a trait",
+                    name.span,
+                ))
+            } else {
+                self.error(Error::new(
+                    format!(
+                        "no associated type '{}' found in '{}'",
+                        name.data,
+                        id.name(&self.proj.scopes).data
+                    ),
+                    name.span,
+                ))
+            }
+        }
+
+        let mut item = WithTypeArgs::new((), ty_args);
+        self.infer_from_trait_bounds(&mut item);
+        let ty_args = item.ty_args;
         for (&id, &ty) in ty_args.iter() {
             self.check_bounds(&ty_args, ty, self.proj.scopes.get(id).impls.clone(), span);
         }
@@ -7715,11 +10555,88 @@ impl TypeChecker {
     }
 }
 
+/// Const generic parameter related functions
+impl TypeChecker {
+    /// Returns the type of the value of `id` if it is a const generic parameter.
+    fn const_param_ty(&mut self, id: UserTypeId) -> Option<TypeId> {
+        let mut ty = self.proj.scopes.get(id).const_ty?;
+        if matches!(self.proj.types[ty], Type::Unresolved(_)) {
+            resolve_type!(self, ty);
+            if ty != TypeId::UNKNOWN && ty.as_integral(&self.proj.types, false).is_none() {
+                self.proj.diag.error(Error::new(
+                    format!(
+                        "const generic parameter '{}' must have an integer type",
+                        self.proj.scopes.get(id).name.data
+                    ),
+                    self.proj.scopes.get(id).name.span,
+                ));
+                ty = TypeId::UNKNOWN;
+            }
+            self.proj.scopes.get_mut(id).const_ty = Some(ty);
+        }
+        Some(ty)
+    }
+
+    /// Resolves the argument passed for the const generic parameter `param`. Either a literal, a
+    /// constant, or another const generic parameter is accepted.
+    fn resolve_const_arg(&mut self, param: UserTypeId, arg: &TypeHint, span: Span) -> TypeId {
+        let ty = self.const_param_ty(param).unwrap();
+        let expr = match arg {
+            TypeHint::Const(expr) => (**expr).clone(),
+            TypeHint::Regular(path) => PExpr::new(path.span(), PExprData::Path(path.clone())),
+            _ => return self.error(Error::new("expected a constant, found a type", span)),
+        };
+
+        let span = expr.span;
+        let expr = self.type_check(expr, ty);
+        if expr.ty != ty {
+            return TypeId::UNKNOWN;
+        }
+
+        if let CExprData::ConstParam(id) = expr.data {
+            self.proj
+                .types
+                .insert(Type::User(GenericUserType::new(id, Default::default())))
+        } else if let Some(value) = self.consteval(&expr, span) {
+            self.proj.types.insert(Type::Const(value.val))
+        } else {
+            TypeId::UNKNOWN
+        }
+    }
+
+    /// Returns the type of an array of `ty` with `count` elements. The length must be known at
+    /// compile time or be a const generic parameter.
+    fn array_type(&mut self, ty: TypeId, count: PExpr) -> Option<TypeId> {
+        let span = count.span;
+        let count = self.type_check(count, TypeId::USIZE);
+        if count.ty != TypeId::USIZE {
+            return None;
+        }
+
+        if let CExprData::ConstParam(id) = count.data {
+            Some(self.proj.types.insert(Type::GenericArray(ty, id)))
+        } else {
+            let n = self.consteval(&count, span)?;
+            Some(
+                self.proj
+                    .types
+                    .insert(Type::Array(ty, n.val.try_into().unwrap())),
+            )
+        }
+    }
+}
+
 struct ConstValue {
     ty: TypeId,
     val: ComptimeInt,
 }
 
+impl ConstValue {
+    fn is_true(&self) -> bool {
+        self.val != ComptimeInt::new(0)
+    }
+}
+
 /// CTFE related functions
 impl TypeChecker {
     fn consteval_check(&mut self, expr: PExpr, target: TypeId) -> Option<ConstValue> {
@@ -7731,90 +10648,274 @@ impl TypeChecker {
     }
 
     fn consteval(&mut self, expr: &CExpr, span: Span) -> Option<ConstValue> {
-        match &expr.data {
-            CExprData::Int(val) => Some(ConstValue {
-                ty: expr.ty,
-                val: val.clone(),
-            }),
+        let val = match &expr.data {
+            CExprData::Int(val) => val.clone(),
+            CExprData::Binary(op @ (BinaryOp::LogicalAnd | BinaryOp::LogicalOr), lhs, rhs) => {
+                // as at runtime, the right hand side is only evaluated if it decides the result
+                let lhs = self.consteval(lhs, span)?;
+                if lhs.is_true() == (*op == BinaryOp::LogicalOr) {
+                    lhs.val
+                } else {
+                    self.consteval(rhs, span)?.val
+                }
+            }
             CExprData::Binary(op, lhs, rhs) => {
-                let mut lhs = self.consteval(lhs, span)?;
+                let lhs = self.consteval(lhs, span)?;
                 let rhs = self.consteval(rhs, span)?;
-                let int = lhs.ty.as_integral(&self.proj.types, false).unwrap();
-                lhs.val = match op {
-                    BinaryOp::Add => lhs.val + &rhs.val,
-                    BinaryOp::Sub => lhs.val - &rhs.val,
-                    BinaryOp::Mul => lhs.val * &rhs.val,
-                    BinaryOp::Div => lhs.val / &rhs.val,
-                    BinaryOp::Rem => lhs.val % &rhs.val,
-                    BinaryOp::BitAnd => lhs.val & &rhs.val,
-                    BinaryOp::Xor => lhs.val ^ &rhs.val,
-                    BinaryOp::BitOr => lhs.val | &rhs.val,
-                    BinaryOp::Shl => {
-                        if let Some(rhs) = rhs.val.into_word().filter(|w| (0..int.bits).contains(w))
+                match Self::consteval_binary(&self.proj.types, *op, lhs.val, rhs.val, lhs.ty, span)
+                {
+                    Ok(val) => val,
+                    Err(err) => return self.error(err),
+                }
+            }
+            CExprData::Unary(op, inner) => {
+                let inner = self.consteval(inner, span)?;
+                match Self::consteval_unary(&self.proj.types, *op, inner.val, expr.ty, span) {
+                    Ok(val) => val,
+                    Err(err) => return self.error(err),
+                }
+            }
+            CExprData::As(inner, throwing) => {
+                let value = self.consteval(inner, span)?;
+                match Self::consteval_cast(
+                    &self.proj.types,
+                    value.val,
+                    value.ty,
+                    expr.ty,
+                    *throwing,
+                    span,
+                ) {
+                    Ok(val) => val,
+                    Err(err) => return self.error(err),
+                }
+            }
+            CExprData::If {
+                cond,
+                if_branch,
+                else_branch,
+            } => {
+                if self.consteval(cond, span)?.is_true() {
+                    self.consteval(if_branch, span)?.val
+                } else if let Some(else_branch) = else_branch {
+                    self.consteval(else_branch, span)?.val
+                } else {
+                    return self.error(Error::no_consteval(span));
+                }
+            }
+            CExprData::Block(block) => match &block.body[..] {
+                [Located {
+                    data:
+                        CStmt::Expr(CExpr {
+                            data: CExprData::Yield(Some(value), scope),
+                            ..
+                        }),
+                    ..
+                }] if *scope == block.scope => self.consteval(value, span)?.val,
+                _ => return self.error(Error::no_consteval(span)),
+            },
+            CExprData::Call(callee, args, _) => match &callee.data {
+                CExprData::MemFn(mfn, _) if self.is_str_len(mfn.func.id) => {
+                    let Some(s) = args
+                        .values()
+                        .next()
+                        .and_then(|this| self.consteval_str(this))
+                    else {
+                        return self.error(Error::no_consteval(span));
+                    };
+                    ComptimeInt::from(s.len())
+                }
+                CExprData::Fn(func, _) if self.proj.scopes.intrinsics.contains_key(&func.id) => {
+                    match &self.proj.scopes.get(func.id).name.data[..] {
+                        "size_of" | "align_of"
+                            if func
+                                .first_type_arg()
+                                .unwrap()
+                                .contains_frame(&self.proj.scopes, &mut self.proj.types) =>
                         {
-                            lhs.val << rhs
-                        } else {
-                            self.proj.diag.error(Error::no_consteval(span)); // TODO: span of the expression that caused it
-                            ComptimeInt::from(0)
+                            return self.error(Error::no_consteval(span));
                         }
-                    }
-                    BinaryOp::Shr => {
-                        if let Some(rhs) = rhs.val.into_word().filter(|w| (0..int.bits).contains(w))
-                        {
-                            lhs.val >> rhs
-                        } else {
-                            self.proj.diag.error(Error::no_consteval(span)); // TODO: span of the expression that caused it
-                            ComptimeInt::from(0)
+                        "size_of" => {
+                            let ty = func.first_type_arg().unwrap();
+                            // TODO: make sure the ty has had resolve_members()
+                            // and resolve_dependencies() called on it and doesn't have any template args
+                            let (sz, _) =
+                                ty.size_and_align(&self.proj.scopes, &mut self.proj.types);
+                            ComptimeInt::from(sz)
+                        }
+                        "align_of" => {
+                            let ty = func.first_type_arg().unwrap();
+                            // TODO: make sure the ty has had resolve_members()
+                            // and resolve_dependencies() called on it and doesn't have any template args
+                            let (_, align) =
+                                ty.size_and_align(&self.proj.scopes, &mut self.proj.types);
+                            ComptimeInt::from(align)
                         }
+                        _ => return self.error(Error::no_consteval(span)),
                     }
-                    _ => return self.error(Error::no_consteval(span)),
-                };
-
-                if !lhs.val.fits_into(int.bits, int.signed) {
-                    lhs.val = ComptimeInt::from(0);
-                    self.error(Error::consteval_overflow(span))
                 }
+                _ => self.interpret_int(expr, span)?,
+            },
+            &CExprData::Var(id) if self.proj.scopes.get(id).is_const => {
+                self.resolve_const(id);
+                match self.proj.scopes.get(id).value.clone() {
+                    Some(value) => self.consteval(&value, span)?.val,
+                    None => return self.error(Error::no_consteval(span)),
+                }
+            }
+            CExprData::Error => return None,
+            _ => self.interpret_int(expr, span)?,
+        };
 
-                Some(lhs)
+        if let Some(int) = expr.ty.as_integral(&self.proj.types, false) {
+            if !val.fits_into(int.bits, int.signed) {
+                self.proj.diag.error(Error::consteval_overflow(span));
+                return Some(ConstValue {
+                    ty: expr.ty,
+                    val: ComptimeInt::from(0),
+                });
             }
-            CExprData::Call(callee, _) => {
-                let CExprData::Fn(func, _) = &callee.data else {
-                    return self.error(Error::no_consteval(span));
-                };
+        }
 
-                if !self.proj.scopes.intrinsics.contains_key(&func.id) {
-                    return self.error(Error::no_consteval(span));
+        Some(ConstValue { ty: expr.ty, val })
+    }
+
+    /// Applies the arithmetic or comparison operator `op` to two integers. `lhs_ty` is the type of
+    /// the left hand side, which bounds the amount of a shift.
+    fn consteval_binary(
+        types: &Types,
+        op: BinaryOp,
+        lhs: ComptimeInt,
+        rhs: ComptimeInt,
+        lhs_ty: TypeId,
+        span: Span,
+    ) -> Result<ComptimeInt, Error> {
+        Ok(match op {
+            BinaryOp::Add => lhs + &rhs,
+            BinaryOp::Sub => lhs - &rhs,
+            BinaryOp::Mul => lhs * &rhs,
+            BinaryOp::Div | BinaryOp::Rem if rhs == ComptimeInt::new(0) => {
+                return Err(Error::consteval_div_zero(span));
+            }
+            BinaryOp::Div => lhs / &rhs,
+            BinaryOp::Rem => lhs % &rhs,
+            BinaryOp::BitAnd => lhs & &rhs,
+            BinaryOp::Xor => lhs ^ &rhs,
+            BinaryOp::BitOr => lhs | &rhs,
+            BinaryOp::Shl | BinaryOp::Shr => {
+                // TODO: span of the expression that caused it
+                let Some(rhs) = lhs_ty
+                    .as_integral(types, false)
+                    .and_then(|int| rhs.into_word().filter(|w| (0..int.bits).contains(w)))
+                else {
+                    return Err(Error::no_consteval(span));
+                };
+                if op == BinaryOp::Shl {
+                    lhs << rhs
+                } else {
+                    lhs >> rhs
                 }
+            }
+            BinaryOp::Gt => ComptimeInt::from(lhs > rhs),
+            BinaryOp::GtEqual => ComptimeInt::from(lhs >= rhs),
+            BinaryOp::Lt => ComptimeInt::from(lhs < rhs),
+            BinaryOp::LtEqual => ComptimeInt::from(lhs <= rhs),
+            BinaryOp::Equal => ComptimeInt::from(lhs == rhs),
+            BinaryOp::NotEqual => ComptimeInt::from(lhs != rhs),
+            _ => return Err(Error::no_consteval(span)),
+        })
+    }
 
-                match &self.proj.scopes.get(func.id).name.data[..] {
-                    "size_of" => {
-                        let ty = func.first_type_arg().unwrap();
-                        // TODO: make sure the ty has had resolve_members()
-                        // and resolve_dependencies() called on it and doesn't have any template args
-                        let (sz, _) = ty.size_and_align(&self.proj.scopes, &mut self.proj.types);
-                        Some(ConstValue {
-                            ty: TypeId::USIZE,
-                            val: ComptimeInt::from(sz),
-                        })
-                    }
-                    "align_of" => {
-                        let ty = func.first_type_arg().unwrap();
-                        // TODO: make sure the ty has had resolve_members()
-                        // and resolve_dependencies() called on it and doesn't have any template args
-                        let (_, align) = ty.size_and_align(&self.proj.scopes, &mut self.proj.types);
-                        Some(ConstValue {
-                            ty: TypeId::USIZE,
-                            val: ComptimeInt::from(align),
-                        })
-                    }
-                    _ => self.error(Error::no_consteval(span)),
+    fn consteval_unary(
+        types: &Types,
+        op: UnaryOp,
+        val: ComptimeInt,
+        ty: TypeId,
+        span: Span,
+    ) -> Result<ComptimeInt, Error> {
+        Ok(match op {
+            UnaryOp::Plus => val,
+            UnaryOp::Neg => -val,
+            UnaryOp::Not if ty == TypeId::BOOL => ComptimeInt::from(val == ComptimeInt::new(0)),
+            UnaryOp::Not => {
+                let Some(int) = ty.as_integral(types, false) else {
+                    return Err(Error::no_consteval(span));
+                };
+                if int.signed {
+                    -val - 1
+                } else {
+                    int.max() - &val
                 }
             }
-            CExprData::Error => None,
-            _ => self.error(Error::no_consteval(span)),
+            _ => return Err(Error::no_consteval(span)),
+        })
+    }
+
+    /// Casts `val` between integer, `bool` and `char` types.
+    fn consteval_cast(
+        types: &Types,
+        val: ComptimeInt,
+        from: TypeId,
+        to: TypeId,
+        throwing: bool,
+        span: Span,
+    ) -> Result<ComptimeInt, Error> {
+        let (Some(int), Some(_)) = (to.as_integral(types, true), from.as_integral(types, true))
+        else {
+            return Err(Error::no_consteval(span));
+        };
+
+        if to == TypeId::BOOL {
+            Ok(ComptimeInt::from(val != ComptimeInt::new(0)))
+        } else if throwing && !val.fits_into(int.bits, int.signed) {
+            Err(Error::consteval_overflow(span))
+        } else {
+            Ok(Self::consteval_wrap(val, int))
+        }
+    }
+
+    /// Truncates `val` to the width of `int`, as a cast between integer types does at runtime.
+    fn consteval_wrap(val: ComptimeInt, int: Integer) -> ComptimeInt {
+        if val.fits_into(int.bits, int.signed) {
+            return val;
+        }
+
+        let modulus = ComptimeInt::from(1) << int.bits;
+        let mut val = val % &modulus;
+        if val.is_negative() {
+            val = val + &modulus;
+        }
+        if int.signed && val > int.max() {
+            val = val - &modulus;
+        }
+        val
+    }
+
+    /// Evaluates `expr` to the string it refers to, if it is a string literal or a constant holding
+    /// one.
+    fn consteval_str(&mut self, expr: &CExpr) -> Option<String> {
+        match &expr.data {
+            CExprData::String(s) => Some(s.clone()),
+            CExprData::Unary(UnaryOp::Addr, inner) | CExprData::AutoDeref(inner, _) => {
+                self.consteval_str(inner)
+            }
+            &CExprData::Var(id) if self.proj.scopes.get(id).is_const => {
+                self.resolve_const(id);
+                let value = self.proj.scopes.get(id).value.clone()?;
+                self.consteval_str(&value)
+            }
+            _ => None,
         }
     }
 
+    fn is_str_len(&self, id: FunctionId) -> bool {
+        let f = self.proj.scopes.get(id);
+        f.name.data == "len"
+            && self.proj.scopes[f.scope]
+                .kind
+                .as_user_type()
+                .is_some_and(|&ut| self.proj.scopes.lang_types.get("string") == Some(&ut))
+    }
+
     fn trait_hack(&self, mfn: &mut MemberFn, ty: TypeId) {
         // FIXME: get rid of this function
         if let MemberFnType::Trait(tr) = &mut mfn.typ {
@@ -7868,6 +10969,7 @@ static INTRINSICS: LazyLock<HashSet<&str>> = LazyLock::new(|| {
         "size_of",
         "align_of",
         "panic",
+        "caller_location",
         "binary_op",
         "unary_op",
         "numeric_cast",
@@ -7879,5 +10981,8 @@ static INTRINSICS: LazyLock<HashSet<&str>> = LazyLock::new(|| {
         "type_name",
         "read_volatile",
         "write_volatile",
+        "bounds_checks",
+        "static_assert",
+        "compile_error",
     ])
 });
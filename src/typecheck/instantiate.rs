@@ -0,0 +1,301 @@
+//! Evaluates `static_assert` and `compile_error` once all function bodies are checked. Generic
+//! functions are walked once for every set of type arguments they are instantiated with, starting
+//! from the non-generic functions, so the assertions see the concrete types. Branches of an `if`
+//! whose condition is known for an instantiation are skipped, along with the calls they make.
+
+use std::collections::HashSet;
+
+use super::{MemberFn, MemberFnType, TypeChecker};
+use crate::{
+    ast::checked::{Block, Expr as CExpr, ExprData as CExprData, Stmt as CStmt},
+    error::Error,
+    sym::{FunctionId, ScopeId, ScopeKind},
+    typeid::{GenericFn, TypeArgs},
+};
+
+struct Guard<'a> {
+    cond: &'a CExpr,
+    branch: bool,
+    /// The value of the condition for this instantiation, once it has been evaluated
+    known: Option<Option<bool>>,
+}
+
+struct Instantiator<'a, 'b> {
+    tc: &'b mut TypeChecker,
+    func: &'b GenericFn,
+    guards: Vec<Guard<'a>>,
+    calls: Vec<GenericFn>,
+}
+
+impl TypeChecker {
+    pub(super) fn check_instantiations(&mut self) {
+        let mut queue: Vec<_> = self
+            .proj
+            .scopes
+            .functions()
+            .filter(|(_, f)| f.body.is_some() && !self.in_generic_scope(f.body_scope))
+            .map(|(id, _)| GenericFn::from_id(&self.proj.scopes, id))
+            .collect();
+        let mut visited: HashSet<_> = queue.iter().cloned().collect();
+        while let Some(func) = queue.pop() {
+            let Some(body) = self.proj.scopes.get(func.id).body.clone() else {
+                continue;
+            };
+
+            let mut inst = Instantiator {
+                tc: self,
+                func: &func,
+                guards: Vec::new(),
+                calls: Vec::new(),
+            };
+            inst.expr(&body);
+            for call in inst.calls {
+                if visited.insert(call.clone()) {
+                    queue.push(call);
+                }
+            }
+        }
+    }
+
+    /// Returns true if `scope` is inside a function or type that has type parameters, or a trait.
+    pub(super) fn in_generic_scope(&self, scope: ScopeId) -> bool {
+        self.proj
+            .scopes
+            .walk(scope)
+            .any(|(_, scope)| match scope.kind {
+                ScopeKind::Function(id) => !self.proj.scopes.get(id).type_params.is_empty(),
+                ScopeKind::UserType(id) => {
+                    let ut = self.proj.scopes.get(id);
+                    !ut.type_params.is_empty() || ut.kind.is_trait()
+                }
+                _ => false,
+            })
+    }
+}
+
+impl<'a> Instantiator<'a, '_> {
+    fn block(&mut self, block: &'a Block) {
+        for stmt in block.body.iter() {
+            match &stmt.data {
+                CStmt::Expr(expr) | CStmt::Defer(expr) | CStmt::Let(_, Some(expr)) => {
+                    self.expr(expr)
+                }
+                CStmt::Guard { cond, body } => {
+                    self.expr(cond);
+                    self.guarded(cond, false, body);
+                }
+                CStmt::Let(_, None) | CStmt::None => {}
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &'a CExpr) {
+        match &expr.data {
+            CExprData::If {
+                cond,
+                if_branch,
+                else_branch,
+            } => {
+                self.expr(cond);
+                self.guarded(cond, true, if_branch);
+                if let Some(else_branch) = else_branch {
+                    self.guarded(cond, false, else_branch);
+                }
+            }
+            CExprData::Call(callee, args, span) => {
+                if let CExprData::Fn(func, _) = &callee.data {
+                    match self.tc.proj.scopes.intrinsic_name(func.id) {
+                        Some("static_assert") => {
+                            if self.reachable() {
+                                let err =
+                                    self.tc.check_static_assert(args, &self.func.ty_args, *span);
+                                self.report(err);
+                            }
+                            return;
+                        }
+                        Some("compile_error") => {
+                            if self.reachable() {
+                                let err =
+                                    self.tc.check_compile_error(args, &self.func.ty_args, *span);
+                                self.report(err);
+                            }
+                            return;
+                        }
+                        _ => {}
+                    }
+                }
+
+                self.expr(callee);
+                args.values().for_each(|arg| self.expr(arg));
+            }
+            CExprData::Fn(func, _) => {
+                let mut func = func.clone();
+                func.fill_templates(&mut self.tc.proj.types, &self.func.ty_args);
+                self.call(func);
+            }
+            CExprData::MemFn(mfn, scope) => self.member_call(mfn, *scope),
+            CExprData::AffixOperator {
+                callee, mfn, scope, ..
+            } => {
+                self.expr(callee);
+                self.member_call(mfn, *scope);
+            }
+            CExprData::StringInterp {
+                formatter,
+                parts,
+                scope,
+            } => {
+                self.expr(formatter);
+                for (mfn, expr) in parts {
+                    self.expr(expr);
+                    self.member_call(mfn, *scope);
+                }
+            }
+            CExprData::Binary(_, lhs, rhs) => {
+                self.expr(lhs);
+                self.expr(rhs);
+            }
+            CExprData::VecWithInit { init, count } => {
+                self.expr(init);
+                self.expr(count);
+            }
+            CExprData::Subscript { callee, arg, .. } | CExprData::SliceArray { callee, arg } => {
+                self.expr(callee);
+                self.expr(arg);
+            }
+            CExprData::Unary(_, expr)
+            | CExprData::AutoDeref(expr, _)
+            | CExprData::DynCoerce(expr, _)
+            | CExprData::DynFnCoerce(expr)
+            | CExprData::SpanMutCoerce(expr)
+            | CExprData::ArrayWithInit(expr)
+            | CExprData::Member { source: expr, .. }
+            | CExprData::As(expr, _)
+            | CExprData::Is(expr, _)
+            | CExprData::Return(expr)
+            | CExprData::Suspend(expr)
+            | CExprData::NeverCoerce(expr)
+            | CExprData::Yield(Some(expr), _)
+            | CExprData::Break(Some(expr), _) => self.expr(expr),
+            CExprData::CallDyn(_, args)
+            | CExprData::VariantInstance(_, args)
+            | CExprData::Instance(args) => args.values().for_each(|arg| self.expr(arg)),
+            CExprData::CallFnPtr(callee, args) => {
+                self.expr(callee);
+                args.iter().for_each(|arg| self.expr(arg));
+            }
+            CExprData::Array(exprs) | CExprData::Vec(exprs) | CExprData::Set(exprs, _) => {
+                exprs.iter().for_each(|expr| self.expr(expr))
+            }
+            CExprData::Map(exprs, _) => {
+                for (key, value) in exprs {
+                    self.expr(key);
+                    self.expr(value);
+                }
+            }
+            CExprData::Block(body) | CExprData::Lambda { body, .. } => self.block(body),
+            CExprData::Loop { cond, body, .. } => {
+                if let Some(cond) = cond {
+                    self.expr(cond);
+                }
+                self.block(body);
+            }
+            CExprData::Match { expr, body } => {
+                self.expr(expr);
+                body.iter().for_each(|(_, expr)| self.expr(expr));
+            }
+            CExprData::Int(_)
+            | CExprData::Float(_)
+            | CExprData::String(_)
+            | CExprData::ByteString(_)
+            | CExprData::Void
+            | CExprData::Var(_)
+            | CExprData::AssocConst(_, _)
+            | CExprData::ConstParam(_)
+            | CExprData::Yield(None, _)
+            | CExprData::Break(None, _)
+            | CExprData::Continue(_)
+            | CExprData::Error => {}
+        }
+    }
+
+    fn guarded(&mut self, cond: &'a CExpr, branch: bool, expr: &'a CExpr) {
+        self.guards.push(Guard {
+            cond,
+            branch,
+            known: None,
+        });
+        self.expr(expr);
+        self.guards.pop();
+    }
+
+    /// Returns false if one of the enclosing branches is never taken in this instantiation.
+    fn reachable(&mut self) -> bool {
+        for i in 0..self.guards.len() {
+            let guard = &self.guards[i];
+            let known = match guard.known {
+                Some(known) => known,
+                None => {
+                    let known = self.tc.try_eval_bool(guard.cond, &self.func.ty_args);
+                    self.guards[i].known = Some(known);
+                    known
+                }
+            };
+            if known.is_some_and(|value| value != self.guards[i].branch) {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn call(&mut self, func: GenericFn) {
+        if !func.ty_args.is_empty() && self.reachable() {
+            self.calls.push(func);
+        }
+    }
+
+    fn member_call(&mut self, mfn: &MemberFn, scope: ScopeId) {
+        let types = &mut self.tc.proj.types;
+        let mut func = mfn.func.clone();
+        func.fill_templates(types, &self.func.ty_args);
+        if let MemberFnType::Trait(tr) = &mfn.typ {
+            let inst = mfn.inst.with_templates(types, &self.func.ty_args);
+            let mut tr = tr.clone();
+            tr.fill_templates(types, &self.func.ty_args);
+            let name = self.tc.proj.scopes.get(mfn.func.id).name.data.clone();
+            let ty_args = func.ty_args.clone();
+            let Some(imp) =
+                self.tc
+                    .get_member_fn_ex(inst, Some(&tr), &name, scope, |tc, id: FunctionId| {
+                        TypeArgs::in_order(tc.scopes(), id, ty_args.0.into_iter().map(|kv| kv.1))
+                    })
+            else {
+                return;
+            };
+            func = imp.func;
+        }
+
+        self.call(func);
+    }
+
+    fn report(&mut self, err: Option<Error>) {
+        let Some(mut err) = err else {
+            return;
+        };
+        if !self.func.ty_args.is_empty() {
+            let args: Vec<_> = self
+                .func
+                .ty_args
+                .values()
+                .map(|ty| ty.name(&self.tc.proj.scopes, &mut self.tc.proj.types))
+                .collect();
+            let func = format!(
+                "{}<{}>",
+                self.tc.proj.scopes.get(self.func.id).name.data,
+                args.join(", ")
+            );
+            err = err.with_note(format!("required by the instantiation '{func}'"));
+        }
+        self.tc.proj.diag.error(err);
+    }
+}
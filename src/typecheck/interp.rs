@@ -0,0 +1,1193 @@
+//! A tree-walking interpreter over the checked AST. It runs `const fn`s during type checking, so
+//! their results can be used in array lengths and in the initializers of constants and statics.
+
+use std::collections::HashMap;
+
+use indexmap::IndexMap;
+
+use super::{MemberFnType, TypeChecker};
+use crate::{
+    ast::{
+        checked::{
+            ArrayPattern, Block, Expr as CExpr, ExprData as CExprData, Pattern as CPattern,
+            PatternData, Stmt as CStmt,
+        },
+        parsed::RangePattern,
+        BinaryOp, UnaryOp,
+    },
+    comptime_int::ComptimeInt,
+    error::Error,
+    lexer::Span,
+    sym::{ParamPattern, ScopeId, UserTypeId, VariableId},
+    typeid::{GenericFn, Type, TypeArgs, TypeId},
+};
+
+/// The number of expressions a single evaluation may run before it is assumed to never finish.
+const STEP_LIMIT: usize = 1_000_000;
+/// The number of calls a single evaluation may have in progress at once.
+const CALL_LIMIT: usize = 64;
+
+#[derive(Debug, Clone)]
+pub(super) enum Value {
+    /// Integers, `bool`s and `char`s
+    Int(ComptimeInt),
+    Str(String),
+    Array(Vec<Value>),
+    Struct(IndexMap<String, Value>),
+    Variant(String, IndexMap<String, Value>),
+    Ptr(Place),
+    Void,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Place {
+    root: Root,
+    path: Vec<Step>,
+}
+
+impl Place {
+    fn join(&self, step: Step) -> Place {
+        let mut place = self.clone();
+        place.path.push(step);
+        place
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Root {
+    /// A local variable of the call frame at the given depth
+    Var(usize, VariableId),
+    /// A temporary whose address was taken in the call frame at the given depth
+    Temp(usize, usize),
+    Const(VariableId),
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+    Member(String),
+    Index(ComptimeInt),
+}
+
+enum Stop {
+    Break(ScopeId, Value),
+    Continue(ScopeId),
+    Yield(ScopeId, Value),
+    Return(Value),
+    /// Evaluation failed. The error is `None` if it was already reported during type checking.
+    Error(Option<Error>),
+}
+
+impl From<Error> for Stop {
+    fn from(err: Error) -> Self {
+        Stop::Error(Some(err))
+    }
+}
+
+type Eval<T = Value> = Result<T, Stop>;
+
+#[derive(Default)]
+struct Frame {
+    vars: HashMap<VariableId, Value>,
+    temps: Vec<Value>,
+    ty_args: TypeArgs,
+}
+
+struct Interpreter<'a> {
+    tc: &'a mut TypeChecker,
+    frames: Vec<Frame>,
+    consts: HashMap<VariableId, Value>,
+    steps: usize,
+    /// The span of the innermost call being evaluated, where errors are reported
+    span: Span,
+}
+
+impl TypeChecker {
+    /// Runs `expr` in the interpreter, reporting an error if it can't be evaluated.
+    pub(super) fn interpret(&mut self, expr: &CExpr, span: Span) -> Option<Value> {
+        self.interpret_with(expr, TypeArgs::default(), span)
+    }
+
+    fn interpret_with(&mut self, expr: &CExpr, ty_args: TypeArgs, span: Span) -> Option<Value> {
+        match self.run(expr, ty_args, span) {
+            Ok(value) => Some(value),
+            Err(Some(err)) => self.error(err),
+            Err(None) => None,
+        }
+    }
+
+    fn run(&mut self, expr: &CExpr, ty_args: TypeArgs, span: Span) -> Result<Value, Option<Error>> {
+        let mut interp = Interpreter {
+            tc: self,
+            frames: vec![Frame {
+                ty_args,
+                ..Default::default()
+            }],
+            consts: HashMap::new(),
+            steps: 0,
+            span,
+        };
+        match interp.eval(expr) {
+            Ok(value) => Ok(value),
+            Err(Stop::Error(err)) => Err(err),
+            Err(_) => Err(None),
+        }
+    }
+
+    /// Evaluates `cond` if its value is known at compile time, without reporting any errors.
+    pub(super) fn try_eval_bool(&mut self, cond: &CExpr, ty_args: &TypeArgs) -> Option<bool> {
+        match self.run(cond, ty_args.clone(), Span::default()) {
+            Ok(Value::Int(val)) => Some(val != ComptimeInt::new(0)),
+            _ => None,
+        }
+    }
+
+    /// Evaluates the arguments of a call to `static_assert`, returning the error to report if
+    /// the condition is false.
+    pub(super) fn check_static_assert(
+        &mut self,
+        args: &IndexMap<String, CExpr>,
+        ty_args: &TypeArgs,
+        span: Span,
+    ) -> Option<Error> {
+        let (cond, msg) = (args.get("cond")?, args.get("msg")?);
+        match self.interpret_with(cond, ty_args.clone(), span)? {
+            Value::Int(val) if val == ComptimeInt::new(0) => {}
+            _ => return None,
+        }
+
+        match self.interpret_with(msg, ty_args.clone(), span)? {
+            Value::Str(msg) if !msg.is_empty() => Some(Error::static_assert(Some(&msg), span)),
+            _ => Some(Error::static_assert(None, span)),
+        }
+    }
+
+    /// Evaluates the arguments of a call to `compile_error`, returning the error it raises.
+    pub(super) fn check_compile_error(
+        &mut self,
+        args: &IndexMap<String, CExpr>,
+        ty_args: &TypeArgs,
+        span: Span,
+    ) -> Option<Error> {
+        match self.interpret_with(args.get("msg")?, ty_args.clone(), span)? {
+            Value::Str(msg) => Some(Error::new(msg, span)),
+            _ => self.error(Error::no_consteval(span)),
+        }
+    }
+
+    pub(super) fn interpret_int(&mut self, expr: &CExpr, span: Span) -> Option<ComptimeInt> {
+        match self.interpret(expr, span)? {
+            Value::Int(val) => Some(val),
+            _ => self.error(Error::no_consteval(span)),
+        }
+    }
+
+    /// Turns the result of the interpreter back into an expression of type `ty`, so it can be
+    /// emitted as the value of a constant or static.
+    pub(super) fn materialize(&mut self, value: Value, ty: TypeId, span: Span) -> Option<CExpr> {
+        let data = match value {
+            Value::Int(val) => CExprData::Int(val),
+            Value::Str(s) => CExprData::String(s),
+            Value::Void => CExprData::Void,
+            Value::Array(values) => {
+                let &Type::Array(inner, _) = &self.proj.types[ty] else {
+                    return self.error(Error::no_consteval(span));
+                };
+                CExprData::Array(
+                    values
+                        .into_iter()
+                        .map(|value| self.materialize(value, inner, span))
+                        .collect::<Option<_>>()?,
+                )
+            }
+            Value::Struct(members) => {
+                CExprData::Instance(self.materialize_members(members, ty, None, span)?)
+            }
+            Value::Variant(name, members) => {
+                let members = self.materialize_members(members, ty, Some(&name), span)?;
+                CExprData::VariantInstance(name, members)
+            }
+            Value::Ptr(_) => {
+                return self.error(
+                    Error::no_consteval(span)
+                        .with_note("the result of constant evaluation cannot contain pointers"),
+                )
+            }
+        };
+        Some(CExpr::new(ty, data))
+    }
+
+    fn materialize_members(
+        &mut self,
+        members: IndexMap<String, Value>,
+        ty: TypeId,
+        variant: Option<&str>,
+        span: Span,
+    ) -> Option<IndexMap<String, CExpr>> {
+        let mut result = IndexMap::new();
+        for (name, value) in members {
+            let Some(member_ty) = self.member_type(ty, variant, &name) else {
+                return self.error(Error::no_consteval(span));
+            };
+            result.insert(name, self.materialize(value, member_ty, span)?);
+        }
+        Some(result)
+    }
+
+    /// Finds the type of the member `name` of the struct or union `ty`, looking in the payload of
+    /// `variant` if it isn't a shared member.
+    fn member_type(&mut self, ty: TypeId, variant: Option<&str>, name: &str) -> Option<TypeId> {
+        let ut = self.proj.scopes.get(self.proj.types[ty].as_user()?.id);
+        if let Some(member) = ut.members.get(name) {
+            return Some(member.ty.with_ut_templates(&mut self.proj.types, ty));
+        }
+
+        let payload = ut.kind.as_union()?.variants.get(variant?)?.ty?;
+        let payload = payload.with_ut_templates(&mut self.proj.types, ty);
+        let member = self
+            .proj
+            .scopes
+            .get(self.proj.types[payload].as_user()?.id)
+            .members
+            .get(name)?
+            .ty;
+        Some(member.with_ut_templates(&mut self.proj.types, payload))
+    }
+}
+
+impl Interpreter<'_> {
+    fn eval(&mut self, expr: &CExpr) -> Eval {
+        self.steps += 1;
+        if self.steps > STEP_LIMIT {
+            return Err(self.step_limit());
+        }
+
+        match &expr.data {
+            CExprData::Int(val) => Ok(Value::Int(val.clone())),
+            CExprData::String(s) => Ok(Value::Str(s.clone())),
+            CExprData::Void => Ok(Value::Void),
+            CExprData::Binary(op, lhs, rhs) => self.binary(*op, lhs, rhs, expr.ty),
+            CExprData::Unary(op, inner) => self.unary(*op, inner, expr.ty),
+            CExprData::Var(_)
+            | CExprData::AssocConst(_, _)
+            | CExprData::AutoDeref(_, _)
+            | CExprData::Member { .. }
+            | CExprData::Subscript { .. } => self.eval_place(expr),
+            CExprData::ConstParam(id) => self.eval_const_param(*id),
+            CExprData::Call(callee, args, span) => {
+                let prev = std::mem::replace(&mut self.span, *span);
+                let value = self.call(callee, args);
+                self.span = prev;
+                value
+            }
+            CExprData::Instance(members) => self.eval_members(members).map(Value::Struct),
+            CExprData::VariantInstance(name, members) => self
+                .eval_members(members)
+                .map(|members| Value::Variant(name.clone(), members)),
+            CExprData::Array(elements) => elements
+                .iter()
+                .map(|expr| self.eval(expr))
+                .collect::<Eval<_>>()
+                .map(Value::Array),
+            CExprData::ArrayWithInit(init) => self.eval_array_with_init(init, expr.ty),
+            CExprData::As(inner, throwing) => self.eval_cast(inner, expr.ty, *throwing),
+            CExprData::Block(block) => self.block(block),
+            CExprData::If {
+                cond,
+                if_branch,
+                else_branch,
+            } => self.eval_if(cond, if_branch, else_branch.as_deref()),
+            CExprData::Loop {
+                cond,
+                body,
+                do_while,
+                optional,
+            } => self.eval_loop(cond.as_deref(), body, *do_while, *optional),
+            CExprData::Match { expr, body } => self.eval_match(expr, body),
+            CExprData::Is(expr, patt) => self.eval_is(expr, patt),
+            CExprData::Return(expr) => Err(self.eval(expr).map_or_else(|stop| stop, Stop::Return)),
+            CExprData::Yield(expr, scope) => Err(self
+                .eval_opt(expr)
+                .map_or_else(|stop| stop, |value| Stop::Yield(*scope, value))),
+            CExprData::Break(expr, scope) => Err(self
+                .eval_opt(expr)
+                .map_or_else(|stop| stop, |value| Stop::Break(*scope, value))),
+            CExprData::Continue(scope) => Err(Stop::Continue(*scope)),
+            CExprData::NeverCoerce(expr) => self.eval(expr),
+            CExprData::Error => Err(Stop::Error(None)),
+            data => self.unsupported(Self::unsupported_expr(data)),
+        }
+    }
+
+    fn step_limit(&self) -> Stop {
+        Error::consteval_limit(&format!("limit of {STEP_LIMIT} steps"), self.span).into()
+    }
+
+    fn unsupported_expr(data: &CExprData) -> &'static str {
+        match data {
+            CExprData::Float(_) => "floating point numbers",
+            CExprData::ByteString(_) => "byte strings",
+            CExprData::StringInterp { .. } => "string interpolation",
+            CExprData::Vec(_)
+            | CExprData::VecWithInit { .. }
+            | CExprData::Set(_, _)
+            | CExprData::Map(_, _) => "heap allocated collections",
+            CExprData::SliceArray { .. } | CExprData::SpanMutCoerce(_) => "spans",
+            CExprData::DynCoerce(_, _) | CExprData::DynFnCoerce(_) | CExprData::CallDyn(_, _) => {
+                "trait objects"
+            }
+            CExprData::Fn(_, _) | CExprData::MemFn(_, _) | CExprData::CallFnPtr(_, _) => {
+                "function pointers"
+            }
+            CExprData::Lambda { .. } => "lambdas",
+            CExprData::AffixOperator { .. } => "overloaded operators",
+            CExprData::Suspend(_) => "generators",
+            _ => "this expression",
+        }
+    }
+
+    fn eval_const_param(&mut self, id: UserTypeId) -> Eval {
+        let ty = self.frame().ty_args.get(&id).copied();
+        match ty.map(|ty| &self.tc.proj.types[ty]) {
+            Some(Type::Const(val)) => Ok(Value::Int(val.clone())),
+            _ => Err(Error::no_consteval(self.span).into()),
+        }
+    }
+
+    fn eval_if(&mut self, cond: &CExpr, if_branch: &CExpr, else_branch: Option<&CExpr>) -> Eval {
+        if self.eval_bool(cond)? {
+            self.eval(if_branch)
+        } else if let Some(else_branch) = else_branch {
+            self.eval(else_branch)
+        } else {
+            Ok(Value::Void)
+        }
+    }
+
+    fn eval_is(&mut self, expr: &CExpr, patt: &CPattern) -> Eval {
+        let value = self.eval(expr)?;
+        Ok(Value::Int(ComptimeInt::from(self.bind(patt, value)?)))
+    }
+
+    fn eval_place(&mut self, expr: &CExpr) -> Eval {
+        if let Some(place) = self.place_of(expr)? {
+            return self.load(&place);
+        }
+
+        match &expr.data {
+            CExprData::Member { source, member } => {
+                let value = self.eval(source)?;
+                self.project(value, &Step::Member(member.clone()))
+            }
+            CExprData::Subscript { callee, arg, .. } => {
+                let value = self.eval(callee)?;
+                let index = self.eval_int(arg)?;
+                self.project(value, &Step::Index(index))
+            }
+            _ => self.unsupported("this expression"),
+        }
+    }
+
+    fn eval_array_with_init(&mut self, init: &CExpr, ty: TypeId) -> Eval {
+        let ty = self.ty(ty);
+        let &Type::Array(_, len) = &self.tc.proj.types[ty] else {
+            return self.unsupported("arrays of unknown length");
+        };
+        let value = self.eval(init)?;
+        Ok(Value::Array(vec![value; len]))
+    }
+
+    fn eval_cast(&mut self, inner: &CExpr, ty: TypeId, throwing: bool) -> Eval {
+        let from = self.ty(inner.ty);
+        let to = self.ty(ty);
+        let val = self.eval_int(inner)?;
+        let types = &self.tc.proj.types;
+        Ok(Value::Int(TypeChecker::consteval_cast(
+            types, val, from, to, throwing, self.span,
+        )?))
+    }
+
+    fn eval_loop(
+        &mut self,
+        cond: Option<&CExpr>,
+        body: &Block,
+        do_while: bool,
+        optional: bool,
+    ) -> Eval {
+        loop {
+            if let Some(cond) = cond.filter(|_| !do_while) {
+                if !self.eval_bool(cond)? {
+                    break;
+                }
+            }
+
+            match self.block(body) {
+                Ok(_) => {}
+                Err(Stop::Continue(scope)) if scope == body.scope => {}
+                Err(Stop::Break(scope, value)) if scope == body.scope => {
+                    return Ok(if optional {
+                        Value::Variant("Some".into(), [("0".into(), value)].into())
+                    } else {
+                        value
+                    });
+                }
+                Err(stop) => return Err(stop),
+            }
+
+            if let Some(cond) = cond.filter(|_| do_while) {
+                if !self.eval_bool(cond)? {
+                    break;
+                }
+            }
+        }
+
+        Ok(if optional {
+            Value::Variant("None".into(), IndexMap::new())
+        } else {
+            Value::Void
+        })
+    }
+
+    fn eval_match(&mut self, expr: &CExpr, body: &[(CPattern, CExpr)]) -> Eval {
+        let value = self.eval(expr)?;
+        for (patt, arm) in body {
+            if self.bind(patt, value.clone())? {
+                return self.eval(arm);
+            }
+        }
+        self.unsupported("this match expression")
+    }
+
+    fn eval_opt(&mut self, expr: &Option<Box<CExpr>>) -> Eval {
+        match expr {
+            Some(expr) => self.eval(expr),
+            None => Ok(Value::Void),
+        }
+    }
+
+    fn eval_int(&mut self, expr: &CExpr) -> Eval<ComptimeInt> {
+        match self.eval(expr)? {
+            Value::Int(val) => Ok(val),
+            _ => self.unsupported("this operation"),
+        }
+    }
+
+    fn eval_bool(&mut self, expr: &CExpr) -> Eval<bool> {
+        Ok(self.eval_int(expr)? != ComptimeInt::new(0))
+    }
+
+    fn eval_members(&mut self, members: &IndexMap<String, CExpr>) -> Eval<IndexMap<String, Value>> {
+        members
+            .iter()
+            .map(|(name, expr)| Ok((name.clone(), self.eval(expr)?)))
+            .collect()
+    }
+
+    fn block(&mut self, block: &Block) -> Eval {
+        for stmt in block.body.iter() {
+            match self.stmt(&stmt.data) {
+                Ok(()) => {}
+                Err(Stop::Yield(scope, value)) if scope == block.scope => return Ok(value),
+                Err(stop) => return Err(stop),
+            }
+        }
+        Ok(Value::Void)
+    }
+
+    fn stmt(&mut self, stmt: &CStmt) -> Eval<()> {
+        match stmt {
+            CStmt::Expr(expr) => {
+                self.eval(expr)?;
+            }
+            CStmt::Let(patt, Some(value)) => {
+                let value = self.eval(value)?;
+                self.bind(patt, value)?;
+            }
+            CStmt::Let(_, None) | CStmt::None => {}
+            CStmt::Guard { cond, body } => {
+                if !self.eval_bool(cond)? {
+                    self.eval(body)?;
+                }
+            }
+            CStmt::Defer(_) => return self.unsupported("defer statements"),
+        }
+        Ok(())
+    }
+
+    fn binary(&mut self, op: BinaryOp, lhs: &CExpr, rhs: &CExpr, ty: TypeId) -> Eval {
+        match op {
+            BinaryOp::LogicalAnd | BinaryOp::LogicalOr => {
+                let lhs = self.eval_bool(lhs)?;
+                let value = if lhs == (op == BinaryOp::LogicalOr) {
+                    lhs
+                } else {
+                    self.eval_bool(rhs)?
+                };
+                Ok(Value::Int(ComptimeInt::from(value)))
+            }
+            BinaryOp::NoneCoalesce => match self.eval(lhs)? {
+                Value::Variant(name, mut members) if name == "Some" => {
+                    Ok(members.swap_remove("0").unwrap_or(Value::Void))
+                }
+                _ => self.eval(rhs),
+            },
+            BinaryOp::Assign => {
+                let place = self.expect_place(lhs)?;
+                let value = self.eval(rhs)?;
+                self.store(&place, value)?;
+                Ok(Value::Void)
+            }
+            BinaryOp::NoneCoalesceAssign => self.unsupported("this operator"),
+            op if op.is_assignment() => {
+                let place = self.expect_place(lhs)?;
+                let Value::Int(current) = self.load(&place)? else {
+                    return self.unsupported("this operator");
+                };
+                let rhs = self.eval_int(rhs)?;
+                let op = match op {
+                    BinaryOp::AddAssign => BinaryOp::Add,
+                    BinaryOp::SubAssign => BinaryOp::Sub,
+                    BinaryOp::MulAssign => BinaryOp::Mul,
+                    BinaryOp::DivAssign => BinaryOp::Div,
+                    BinaryOp::RemAssign => BinaryOp::Rem,
+                    BinaryOp::BitAndAssign => BinaryOp::BitAnd,
+                    BinaryOp::BitOrAssign => BinaryOp::BitOr,
+                    BinaryOp::XorAssign => BinaryOp::Xor,
+                    BinaryOp::ShlAssign => BinaryOp::Shl,
+                    _ => BinaryOp::Shr,
+                };
+                let value = self.arith(op, current, rhs, lhs.ty, lhs.ty)?;
+                self.store(&place, value)?;
+                Ok(Value::Void)
+            }
+            _ => match (self.eval(lhs)?, self.eval(rhs)?) {
+                (Value::Int(l), Value::Int(r)) => self.arith(op, l, r, lhs.ty, ty),
+                (Value::Str(l), Value::Str(r)) if op == BinaryOp::Equal => {
+                    Ok(Value::Int(ComptimeInt::from(l == r)))
+                }
+                (Value::Str(l), Value::Str(r)) if op == BinaryOp::NotEqual => {
+                    Ok(Value::Int(ComptimeInt::from(l != r)))
+                }
+                _ => self.unsupported("this operator"),
+            },
+        }
+    }
+
+    fn arith(
+        &mut self,
+        op: BinaryOp,
+        lhs: ComptimeInt,
+        rhs: ComptimeInt,
+        lhs_ty: TypeId,
+        ty: TypeId,
+    ) -> Eval {
+        let lhs_ty = self.ty(lhs_ty);
+        let val =
+            TypeChecker::consteval_binary(&self.tc.proj.types, op, lhs, rhs, lhs_ty, self.span)?;
+        self.check_overflow(val, ty)
+    }
+
+    fn check_overflow(&mut self, val: ComptimeInt, ty: TypeId) -> Eval {
+        let ty = self.ty(ty);
+        match ty.as_integral(&self.tc.proj.types, false) {
+            Some(int) if !val.fits_into(int.bits, int.signed) => {
+                Err(Error::consteval_overflow(self.span).into())
+            }
+            _ => Ok(Value::Int(val)),
+        }
+    }
+
+    fn unary(&mut self, op: UnaryOp, inner: &CExpr, ty: TypeId) -> Eval {
+        match op {
+            UnaryOp::Plus | UnaryOp::Neg | UnaryOp::Not => {
+                let val = self.eval_int(inner)?;
+                let ty = self.ty(ty);
+                let val =
+                    TypeChecker::consteval_unary(&self.tc.proj.types, op, val, ty, self.span)?;
+                self.check_overflow(val, ty)
+            }
+            UnaryOp::Deref => {
+                let place = self.expect_ptr(inner)?;
+                self.load(&place)
+            }
+            UnaryOp::Addr | UnaryOp::AddrMut | UnaryOp::AddrRaw => {
+                let place = match self.place_of(inner)? {
+                    Some(place) => place,
+                    None => {
+                        let value = self.eval(inner)?;
+                        self.temp(value)
+                    }
+                };
+                Ok(Value::Ptr(place))
+            }
+            UnaryOp::PostIncrement
+            | UnaryOp::PostDecrement
+            | UnaryOp::PreIncrement
+            | UnaryOp::PreDecrement => {
+                let place = self.expect_place(inner)?;
+                let Value::Int(old) = self.load(&place)? else {
+                    return self.unsupported("this operator");
+                };
+                let new = if matches!(op, UnaryOp::PostIncrement | UnaryOp::PreIncrement) {
+                    old.clone() + 1
+                } else {
+                    old.clone() - 1
+                };
+                let new = self.check_overflow(new, inner.ty)?;
+                self.store(&place, new.clone())?;
+                if matches!(op, UnaryOp::PostIncrement | UnaryOp::PostDecrement) {
+                    Ok(Value::Int(old))
+                } else {
+                    Ok(new)
+                }
+            }
+            UnaryOp::Unwrap | UnaryOp::Try => match self.eval(inner)? {
+                Value::Variant(name, mut members) if name == "Some" || name == "Ok" => {
+                    Ok(members.swap_remove("0").unwrap_or(Value::Void))
+                }
+                value @ Value::Variant(_, _) if op == UnaryOp::Try => Err(Stop::Return(value)),
+                Value::Variant(_, _) => Err(Error::consteval_panic(
+                    "attempted to unwrap a null value",
+                    self.span,
+                )
+                .into()),
+                _ => self.unsupported("this operator"),
+            },
+        }
+    }
+
+    fn call(&mut self, callee: &CExpr, args: &IndexMap<String, CExpr>) -> Eval {
+        let mut func = match &callee.data {
+            CExprData::Fn(func, _) => func.clone(),
+            CExprData::MemFn(mfn, _) if matches!(mfn.typ, MemberFnType::Normal) => mfn.func.clone(),
+            CExprData::MemFn(_, _) => return self.unsupported("calls to trait functions"),
+            _ => return self.unsupported("function pointers"),
+        };
+        func.fill_templates(
+            &mut self.tc.proj.types,
+            &self.frames.last().unwrap().ty_args,
+        );
+
+        if let Some(name) = self.tc.proj.scopes.intrinsics.get(&func.id) {
+            return self.intrinsic(&name.clone(), &func, args);
+        }
+
+        if let Some(value) = self.builtin(&func, args)? {
+            return Ok(value);
+        }
+
+        let f = self.tc.proj.scopes.get(func.id);
+        if let Some(ut) = f.constructor {
+            return self.construct(&func, ut, args);
+        }
+
+        if !f.is_const || self.frames.len() > CALL_LIMIT {
+            return Err(self.cannot_call(&func));
+        }
+
+        self.tc.resolve_const_fn(func.id);
+        let f = self.tc.proj.scopes.get(func.id);
+        let Some(body) = f.body.clone() else {
+            return Err(self.cannot_call(&func));
+        };
+        let params: Vec<_> = f
+            .params
+            .iter()
+            .map(|param| (param.label.clone(), param.patt.clone()))
+            .collect();
+
+        let mut values = self.eval_members(args)?;
+        self.frames.push(Frame {
+            ty_args: func.ty_args,
+            ..Default::default()
+        });
+        for (label, patt) in params {
+            let (ParamPattern::Checked(patt), Some(value)) = (patt, values.swap_remove(&label))
+            else {
+                return Err(Stop::Error(None));
+            };
+            self.bind(&patt, value)?;
+        }
+
+        let value = match self.eval(&body) {
+            Ok(value) | Err(Stop::Return(value)) => value,
+            Err(stop) => return Err(stop),
+        };
+        self.frames.pop();
+        Ok(value)
+    }
+
+    fn construct(
+        &mut self,
+        func: &GenericFn,
+        ut: UserTypeId,
+        args: &IndexMap<String, CExpr>,
+    ) -> Eval {
+        let name = self.tc.proj.scopes.get(func.id).name.data.clone();
+        let members = self.eval_members(args)?;
+        Ok(if self.tc.proj.scopes.get(ut).kind.is_union() {
+            Value::Variant(name, members)
+        } else {
+            Value::Struct(members)
+        })
+    }
+
+    fn cannot_call(&self, func: &GenericFn) -> Stop {
+        let f = self.tc.proj.scopes.get(func.id);
+        if !f.is_const {
+            Error::no_consteval(self.span)
+                .with_note(format!("'{}' is not a const function", f.name.data))
+                .into()
+        } else if self.frames.len() > CALL_LIMIT {
+            Error::consteval_limit(&format!("limit of {CALL_LIMIT} nested calls"), self.span).into()
+        } else {
+            Error::no_consteval(self.span)
+                .with_note(format!(
+                    "'{}' cannot be evaluated before its body has been checked",
+                    f.name.data
+                ))
+                .into()
+        }
+    }
+
+    fn intrinsic(&mut self, name: &str, func: &GenericFn, args: &IndexMap<String, CExpr>) -> Eval {
+        match name {
+            "size_of" | "align_of" => {
+                let ty = func.first_type_arg().unwrap();
+                if ty.contains_frame(&self.tc.proj.scopes, &mut self.tc.proj.types) {
+                    return Err(Error::no_consteval(self.span)
+                        .with_note("the layout of async function and generator frames is not known until code generation")
+                        .into());
+                }
+                let (size, align) =
+                    ty.size_and_align(&self.tc.proj.scopes, &mut self.tc.proj.types);
+                Ok(Value::Int(ComptimeInt::from(if name == "size_of" {
+                    size
+                } else {
+                    align
+                })))
+            }
+            "panic" => {
+                let message = match args.values().next().map(|arg| self.eval(arg)).transpose()? {
+                    Some(Value::Str(message)) => message,
+                    _ => "explicit panic".into(),
+                };
+                Err(Error::consteval_panic(&message, self.span).into())
+            }
+            "unreachable_unchecked" => {
+                Err(Error::consteval_panic("entered unreachable code", self.span).into())
+            }
+            _ => Err(Error::no_consteval(self.span)
+                .with_note(format!("the intrinsic '{name}' is not supported"))
+                .into()),
+        }
+    }
+
+    /// Evaluates calls to library functions the interpreter implements itself: the length of a
+    /// string, and stepping the range of a `for` loop.
+    fn builtin(&mut self, func: &GenericFn, args: &IndexMap<String, CExpr>) -> Eval<Option<Value>> {
+        if self.tc.is_str_len(func.id) {
+            let Some(this) = args.values().next() else {
+                return Ok(None);
+            };
+            let mut value = self.eval(this)?;
+            while let Value::Ptr(place) = value {
+                value = self.load(&place)?;
+            }
+            let Value::Str(s) = value else {
+                return self.unsupported("this string");
+            };
+            return Ok(Some(Value::Int(ComptimeInt::from(s.len()))));
+        }
+
+        if self.tc.proj.scopes.get(func.id).name.data != "next" {
+            return Ok(None);
+        }
+        let Some(this) = args.values().next() else {
+            return Ok(None);
+        };
+        let this_ty = self.ty(this.ty);
+        let Some(ut) = self.tc.proj.types[this_ty.strip_references(&self.tc.proj.types)]
+            .as_user()
+            .cloned()
+        else {
+            return Ok(None);
+        };
+        let lang = &self.tc.proj.scopes.lang_types;
+        let kind = ["range", "range_inclusive", "range_from"]
+            .into_iter()
+            .find(|&name| lang.get(name) == Some(&ut.id));
+        let (Some(kind), Some(item)) = (kind, ut.first_type_arg()) else {
+            return Ok(None);
+        };
+
+        let place = self.expect_ptr(this)?;
+        let start_place = place.join(Step::Member("start".into()));
+        let Value::Int(start) = self.load(&start_place)? else {
+            return self.unsupported("this range");
+        };
+        let end = match kind {
+            "range_from" => None,
+            _ => match self.load(&place.join(Step::Member("end".into())))? {
+                Value::Int(end) => Some(end),
+                _ => return self.unsupported("this range"),
+            },
+        };
+
+        let int = item.as_integral(&self.tc.proj.types, true);
+        let next = start.clone() + 1;
+        let has_next = match (kind, &end) {
+            ("range", Some(end)) => start < *end,
+            ("range_inclusive", Some(end)) => start <= *end,
+            _ => int.is_some_and(|int| next.fits_into(int.bits, int.signed)),
+        };
+        if !has_next {
+            return Ok(Some(Value::Variant("None".into(), IndexMap::new())));
+        }
+
+        if kind == "range_inclusive" && end.as_ref() == Some(&start) {
+            // avoid overflow at the upper end
+            self.store(
+                &place.join(Step::Member("end".into())),
+                Value::Int(start.clone() - 1),
+            )?;
+        } else {
+            self.store(&start_place, Value::Int(next))?;
+        }
+        Ok(Some(Value::Variant(
+            "Some".into(),
+            [("0".into(), Value::Int(start))].into(),
+        )))
+    }
+
+    /// Matches `value` against `patt`, binding its variables in the current frame.
+    fn bind(&mut self, patt: &CPattern, value: Value) -> Eval<bool> {
+        match &patt.data {
+            &PatternData::Variable(id) => {
+                self.frame_mut().vars.insert(id, value);
+                Ok(true)
+            }
+            PatternData::Void => Ok(true),
+            PatternData::Error => Err(Stop::Error(None)),
+            PatternData::Int(val) => match self.scrutinee(value)?.0 {
+                Value::Int(value) => Ok(value == *val),
+                _ => self.unsupported("this pattern"),
+            },
+            PatternData::IntRange(RangePattern {
+                inclusive,
+                start,
+                end,
+            }) => match self.scrutinee(value)?.0 {
+                Value::Int(value) => Ok(start.as_ref().is_none_or(|start| value >= *start)
+                    && if *inclusive {
+                        value <= *end
+                    } else {
+                        value < *end
+                    }),
+                _ => self.unsupported("this pattern"),
+            },
+            PatternData::String(s) => match self.scrutinee(value)?.0 {
+                Value::Str(value) => Ok(value == *s),
+                _ => self.unsupported("this pattern"),
+            },
+            PatternData::Variant {
+                pattern,
+                variant,
+                borrows,
+                ..
+            } => {
+                let (value, place) = self.scrutinee(value)?;
+                let Value::Variant(name, members) = value else {
+                    return self.unsupported("this pattern");
+                };
+                if name != *variant {
+                    return Ok(false);
+                }
+
+                let Some(pattern) = pattern else {
+                    return Ok(true);
+                };
+                let inner = if *borrows {
+                    let place = place.unwrap_or_else(|| self.temp(Value::Struct(members)));
+                    Value::Ptr(place)
+                } else {
+                    Value::Struct(members)
+                };
+                self.bind(pattern, inner)
+            }
+            PatternData::Destrucure { patterns, borrows } => {
+                let (value, place) = self.scrutinee(value)?;
+                let (Value::Struct(mut members) | Value::Variant(_, mut members)) = value else {
+                    return self.unsupported("this pattern");
+                };
+                let place = place.filter(|_| *borrows);
+                for (name, _, patt) in patterns {
+                    let inner = if *borrows {
+                        let place = match &place {
+                            Some(place) => place.join(Step::Member(name.clone())),
+                            None => {
+                                let value = members.swap_remove(name).unwrap_or(Value::Void);
+                                self.temp(value)
+                            }
+                        };
+                        Value::Ptr(place)
+                    } else {
+                        members.swap_remove(name).unwrap_or(Value::Void)
+                    };
+                    if !self.bind(patt, inner)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            PatternData::Array {
+                patterns:
+                    ArrayPattern {
+                        patterns,
+                        rest,
+                        arr_len,
+                        ..
+                    },
+                borrows,
+            } => {
+                if rest.is_some_and(|rest| rest.id.is_some()) {
+                    return self.unsupported("rest patterns with a binding");
+                }
+
+                let (value, place) = self.scrutinee(value)?;
+                let Value::Array(mut items) = value else {
+                    return self.unsupported("this pattern");
+                };
+                let place = place.filter(|_| *borrows);
+                for (i, patt) in patterns.iter().enumerate() {
+                    let i = match rest {
+                        Some(rest) if i >= rest.pos => arr_len - (patterns.len() - i),
+                        _ => i,
+                    };
+                    let inner = if *borrows {
+                        let place = match &place {
+                            Some(place) => place.join(Step::Index(ComptimeInt::from(i))),
+                            None => {
+                                let value = std::mem::replace(&mut items[i], Value::Void);
+                                self.temp(value)
+                            }
+                        };
+                        Value::Ptr(place)
+                    } else {
+                        std::mem::replace(&mut items[i], Value::Void)
+                    };
+                    if !self.bind(patt, inner)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            PatternData::Or { patterns, bindings } => {
+                for (i, patt) in patterns.iter().enumerate() {
+                    if self.bind(patt, value.clone())? {
+                        for (outer, inner) in bindings {
+                            if let Some(value) = self.frame().vars.get(&inner[i]).cloned() {
+                                self.frame_mut().vars.insert(*outer, value);
+                            }
+                        }
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            PatternData::Span { .. } => self.unsupported("span patterns"),
+        }
+    }
+
+    /// Reads through `value` if it is a pointer, returning the value it points to and its place.
+    fn scrutinee(&mut self, value: Value) -> Eval<(Value, Option<Place>)> {
+        match value {
+            Value::Ptr(place) => Ok((self.load(&place)?, Some(place))),
+            value => Ok((value, None)),
+        }
+    }
+
+    /// Finds the location `expr` refers to, if it is a variable or part of one.
+    fn place_of(&mut self, expr: &CExpr) -> Eval<Option<Place>> {
+        let root = match &expr.data {
+            &CExprData::Var(id) => {
+                let var = self.tc.proj.scopes.get(id);
+                if var.is_const {
+                    self.load_const(id)?;
+                    Root::Const(id)
+                } else if var.is_static {
+                    return self.unsupported("statics");
+                } else {
+                    Root::Var(self.frames.len() - 1, id)
+                }
+            }
+            CExprData::AssocConst(ty, id) => {
+                let ty = self.ty(*ty);
+                let name = self.tc.proj.scopes.get(*id).name.data.clone();
+                let Some(id) = self.tc.find_assoc_const(ty, &name, self.tc.current) else {
+                    return Err(Error::no_consteval(self.span).into());
+                };
+                self.load_const(id)?;
+                Root::Const(id)
+            }
+            CExprData::Member { source, member } => {
+                return Ok(self
+                    .place_of(source)?
+                    .map(|place| place.join(Step::Member(member.clone()))));
+            }
+            CExprData::Subscript { callee, arg, .. } => {
+                // subscripting a pointer to an array indexes the array it points to
+                let mut ty = self.ty(callee.ty);
+                let mut place = match self.place_of(callee)? {
+                    Some(place) => place,
+                    None => match ty.as_pointee(&self.tc.proj.types) {
+                        Some(inner) => {
+                            ty = inner;
+                            self.expect_ptr(callee)?
+                        }
+                        None => return Ok(None),
+                    },
+                };
+                while let Some(inner) = ty.as_pointee(&self.tc.proj.types) {
+                    let Value::Ptr(next) = self.load(&place)? else {
+                        return self.unsupported("this pointer");
+                    };
+                    place = next;
+                    ty = inner;
+                }
+                let index = self.eval_int(arg)?;
+                return Ok(Some(place.join(Step::Index(index))));
+            }
+            CExprData::Unary(UnaryOp::Deref, inner) => return self.expect_ptr(inner).map(Some),
+            CExprData::AutoDeref(inner, count) => {
+                let mut place = self.expect_ptr(inner)?;
+                for _ in 1..*count {
+                    match self.load(&place)? {
+                        Value::Ptr(next) => place = next,
+                        _ => return self.unsupported("this pointer"),
+                    }
+                }
+                return Ok(Some(place));
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(Place { root, path: vec![] }))
+    }
+
+    fn expect_place(&mut self, expr: &CExpr) -> Eval<Place> {
+        match self.place_of(expr)? {
+            Some(place) => Ok(place),
+            None => self.unsupported("this assignment"),
+        }
+    }
+
+    fn expect_ptr(&mut self, expr: &CExpr) -> Eval<Place> {
+        match self.eval(expr)? {
+            Value::Ptr(place) => Ok(place),
+            _ => self.unsupported("this pointer"),
+        }
+    }
+
+    fn load_const(&mut self, id: VariableId) -> Eval<()> {
+        if self.consts.contains_key(&id) {
+            return Ok(());
+        }
+
+        self.tc.resolve_const(id);
+        let Some(value) = self.tc.proj.scopes.get(id).value.clone() else {
+            return Err(Stop::Error(None));
+        };
+        self.frames.push(Frame::default());
+        let value = self.eval(&value)?;
+        self.frames.pop();
+        self.consts.insert(id, value);
+        Ok(())
+    }
+
+    fn temp(&mut self, value: Value) -> Place {
+        let depth = self.frames.len() - 1;
+        let temps = &mut self.frame_mut().temps;
+        temps.push(value);
+        Place {
+            root: Root::Temp(depth, temps.len() - 1),
+            path: vec![],
+        }
+    }
+
+    fn load(&mut self, place: &Place) -> Eval {
+        Ok(self.slot(place)?.clone())
+    }
+
+    fn store(&mut self, place: &Place, value: Value) -> Eval<()> {
+        if let (Root::Var(depth, id), []) = (place.root, &place.path[..]) {
+            if let Some(frame) = self.frames.get_mut(depth) {
+                frame.vars.insert(id, value);
+                return Ok(());
+            }
+        }
+
+        *self.slot(place)? = value;
+        Ok(())
+    }
+
+    fn slot(&mut self, place: &Place) -> Eval<&mut Value> {
+        let span = self.span;
+        let root = match place.root {
+            Root::Var(depth, id) => self.frames.get_mut(depth).and_then(|f| f.vars.get_mut(&id)),
+            Root::Temp(depth, i) => self.frames.get_mut(depth).and_then(|f| f.temps.get_mut(i)),
+            Root::Const(id) => self.consts.get_mut(&id),
+        };
+        let Some(mut value) = root else {
+            return Err(Error::no_consteval(span)
+                .with_note("a pointer outlived the value it points to")
+                .into());
+        };
+        for step in place.path.iter() {
+            value = Self::step(value, step, span)?;
+        }
+        Ok(value)
+    }
+
+    fn project(&mut self, mut value: Value, step: &Step) -> Eval {
+        Ok(std::mem::replace(
+            Self::step(&mut value, step, self.span)?,
+            Value::Void,
+        ))
+    }
+
+    fn step<'v>(value: &'v mut Value, step: &Step, span: Span) -> Eval<&'v mut Value> {
+        match (value, step) {
+            (Value::Struct(members) | Value::Variant(_, members), Step::Member(name)) => members
+                .get_mut(name)
+                .ok_or_else(|| Error::no_consteval(span).into()),
+            (Value::Array(items), Step::Index(index)) => {
+                let len = items.len();
+                index
+                    .clone()
+                    .try_into()
+                    .ok()
+                    .and_then(|i: usize| items.get_mut(i))
+                    .ok_or_else(|| Error::consteval_bounds(index, len, span).into())
+            }
+            _ => Err(Error::no_consteval(span).into()),
+        }
+    }
+
+    fn ty(&mut self, ty: TypeId) -> TypeId {
+        let frame = self.frames.last().unwrap();
+        ty.with_templates(&mut self.tc.proj.types, &frame.ty_args)
+    }
+
+    fn frame(&self) -> &Frame {
+        self.frames.last().unwrap()
+    }
+
+    fn frame_mut(&mut self) -> &mut Frame {
+        self.frames.last_mut().unwrap()
+    }
+
+    fn unsupported<T>(&self, what: &str) -> Eval<T> {
+        Err(Error::no_consteval(self.span)
+            .with_note(format!("{what} cannot be evaluated at compile time"))
+            .into())
+    }
+}
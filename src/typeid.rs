@@ -106,9 +106,26 @@ impl<T> WithTypeArgs<T> {
         self.ty_args.values().next().cloned()
     }
 
-    pub fn infer_type_args(&mut self, types: &Types, mut src: TypeId, mut target: TypeId) {
+    pub fn infer_type_args(&mut self, types: &mut Types, mut src: TypeId, mut target: TypeId) {
         loop {
             match (&types[src], &types[target]) {
+                (&Type::GenericArray(gi, len), &Type::Array(ti, n)) => {
+                    if self.ty_args.get(&len) == Some(&TypeId::UNKNOWN) {
+                        let n = types.insert(Type::Const(ComptimeInt::from(n)));
+                        self.ty_args.insert(len, n);
+                    }
+                    src = gi;
+                    target = ti;
+                }
+                (&Type::GenericArray(gi, len), &Type::GenericArray(ti, n)) => {
+                    if self.ty_args.get(&len) == Some(&TypeId::UNKNOWN) {
+                        let n =
+                            types.insert(Type::User(GenericUserType::new(n, Default::default())));
+                        self.ty_args.insert(len, n);
+                    }
+                    src = gi;
+                    target = ti;
+                }
                 (
                     Type::Ptr(gi) | Type::MutPtr(gi) | Type::RawPtr(gi),
                     Type::Ptr(ti) | Type::MutPtr(ti) | Type::RawPtr(ti),
@@ -120,7 +137,9 @@ impl<T> WithTypeArgs<T> {
                     src = *gi;
                     target = *ti;
                 }
-                (Type::FnPtr(src), Type::FnPtr(target)) => {
+                (Type::FnPtr(src), Type::FnPtr(target))
+                | (Type::DynFn(src), Type::DynFn(target)) => {
+                    let (src, target) = (src.clone(), target.clone());
                     for (&src, &target) in src.params.iter().zip(target.params.iter()) {
                         self.infer_type_args(types, src, target);
                     }
@@ -138,8 +157,13 @@ impl<T> WithTypeArgs<T> {
                         entry.insert(target);
                     } else if let Type::User(target) = target_ty {
                         if src.id == target.id {
-                            for (&src, &target) in src.ty_args.values().zip(target.ty_args.values())
-                            {
+                            let pairs: Vec<_> = src
+                                .ty_args
+                                .values()
+                                .copied()
+                                .zip(target.ty_args.values().copied())
+                                .collect();
+                            for (src, target) in pairs {
                                 self.infer_type_args(types, src, target);
                             }
                         }
@@ -213,6 +237,33 @@ impl GenericUserType {
                 }
                 format!("{result})")
             }
+            crate::sym::UserTypeKind::Lambda(lambda) => {
+                let lambda = lambda.clone();
+                let mut result = "lambda fn(".to_string();
+                for (i, param) in lambda.params.into_iter().enumerate() {
+                    if i > 0 {
+                        result.push_str(", ");
+                    }
+                    let param = param.with_templates(types, &self.ty_args);
+                    result.push_str(&param.name(scopes, types));
+                }
+                let ret = lambda.ret.with_templates(types, &self.ty_args);
+                format!("{result}) => {}", ret.name(scopes, types))
+            }
+            crate::sym::UserTypeKind::AsyncFn(f) | crate::sym::UserTypeKind::Generator(f) => {
+                let kind = if scopes.get(self.id).kind.is_async_fn() {
+                    "async fn"
+                } else {
+                    "generator fn"
+                };
+                let f = f.clone();
+                let output = f.output.with_templates(types, &self.ty_args);
+                format!(
+                    "{kind} {} => {}",
+                    scopes.get(f.func).name.data,
+                    output.name(scopes, types)
+                )
+            }
             _ => {
                 let is_lang_type =
                     |name: &str| scopes.lang_types.get(name).is_some_and(|&id| id == self.id);
@@ -234,13 +285,27 @@ impl GenericUserType {
                     );
                 }
 
+                // associated types are only shown when bound in a trait, hidden type parameters
+                // standing in for them are never shown
+                let is_trait = scopes.get(self.id).kind.is_trait();
+                let args: Vec<_> = self
+                    .ty_args
+                    .iter()
+                    .filter(|(&id, &ty)| {
+                        !scopes.get(id).implicit || (is_trait && ty != TypeId::UNKNOWN)
+                    })
+                    .map(|(&id, &ty)| (id, ty))
+                    .collect();
                 let mut result = scopes.get(self.id).name.data.clone();
-                if !self.ty_args.is_empty() {
+                if !args.is_empty() {
                     result.push('<');
-                    for (i, concrete) in self.ty_args.values().enumerate() {
+                    for (i, (id, concrete)) in args.into_iter().enumerate() {
                         if i > 0 {
                             result.push_str(", ");
                         }
+                        if scopes.get(id).implicit {
+                            result.push_str(&format!("{} = ", scopes.get(id).name.data));
+                        }
                         result.push_str(&concrete.name(scopes, types));
                     }
                     result.push('>');
@@ -262,6 +327,16 @@ impl GenericUserType {
             .and_then(|_| self.first_type_arg())
     }
 
+    /// Returns the success and error types of a `Result<T, E>`.
+    pub fn as_result(&self, scopes: &Scopes) -> Option<(TypeId, TypeId)> {
+        if scopes.lang_types.get("result") != Some(&self.id) {
+            return None;
+        }
+
+        let mut args = self.ty_args.values();
+        Some((*args.next()?, *args.next()?))
+    }
+
     pub fn can_omit_tag(&self, scopes: &Scopes, types: &Types) -> Option<TypeId> {
         self.as_option_inner(scopes).filter(|&inner| {
             matches!(
@@ -372,6 +447,7 @@ pub enum Type {
     Char,
     Fn(GenericFn),
     FnPtr(FnPtr),
+    DynFn(FnPtr),
     User(GenericUserType),
     Ptr(TypeId),
     MutPtr(TypeId),
@@ -379,9 +455,21 @@ pub enum Type {
     DynPtr(GenericTrait),
     DynMutPtr(GenericTrait),
     Array(TypeId, usize),
+    /// An array whose length is the const generic parameter `.1`
+    GenericArray(TypeId, UserTypeId),
+    /// The value passed for a const generic parameter
+    Const(ComptimeInt),
 }
 
 impl Type {
+    pub fn as_callable(&self) -> Option<&FnPtr> {
+        if let Type::FnPtr(f) | Type::DynFn(f) = self {
+            Some(f)
+        } else {
+            None
+        }
+    }
+
     pub fn as_dyn_pointee(&self) -> Option<&GenericTrait> {
         if let Type::DynMutPtr(tr) | Type::DynPtr(tr) = self {
             Some(tr)
@@ -473,6 +561,10 @@ impl Type {
     pub fn as_option_inner(&self, scopes: &Scopes) -> Option<TypeId> {
         self.as_user().and_then(|s| s.as_option_inner(scopes))
     }
+
+    pub fn as_result(&self, scopes: &Scopes) -> Option<(TypeId, TypeId)> {
+        self.as_user().and_then(|s| s.as_result(scopes))
+    }
 }
 
 pub struct Types {
@@ -590,9 +682,14 @@ impl TypeId {
             &Type::RawPtr(id) => format!("*raw {}", id.name(scopes, types)),
             Type::DynPtr(id) => format!("*dyn {}", id.clone().name(scopes, types)),
             Type::DynMutPtr(id) => format!("*dyn mut {}", id.clone().name(scopes, types)),
-            Type::FnPtr(f) => {
+            Type::FnPtr(f) | Type::DynFn(f) => {
+                let mut result = if types[self].is_dyn_fn() {
+                    "dyn fn("
+                } else {
+                    "fn("
+                }
+                .to_string();
                 let f = f.clone();
-                let mut result = "fn(".to_string();
                 for (i, &param) in f.params.iter().enumerate() {
                     if i > 0 {
                         result.push_str(", ");
@@ -622,6 +719,10 @@ impl TypeId {
             }
             Type::User(ty) => ty.clone().name(scopes, types),
             &Type::Array(ty, count) => format!("[{}; {}]", ty.name(scopes, types), count),
+            &Type::GenericArray(ty, len) => {
+                format!("[{}; {}]", ty.name(scopes, types), scopes.get(len).name)
+            }
+            Type::Const(value) => value.to_string(),
             Type::Isize => "int".into(),
             Type::Usize => "uint".into(),
             id @ (Type::CInt(ty) | Type::CUint(ty)) => {
@@ -638,6 +739,10 @@ impl TypeId {
         proj.types[self].as_option_inner(&proj.scopes)
     }
 
+    pub fn as_result(self, proj: &Project) -> Option<(TypeId, TypeId)> {
+        proj.types[self].as_result(&proj.scopes)
+    }
+
     pub fn strip_references(self, types: &Types) -> TypeId {
         let mut id = self;
         while let Type::Ptr(inner) | Type::MutPtr(inner) = &types[id] {
@@ -718,7 +823,7 @@ impl TypeId {
                 this.is_integral() || this.is_raw_ptr()
             }
             Not => this.is_integral() || this.is_bool(),
-            Try => this.as_option_inner(scopes).is_some(),
+            Try => this.as_option_inner(scopes).is_some() || this.as_result(scopes).is_some(),
             Plus => this.is_numeric(),
             Deref => this.is_any_ptr(),
             Addr | AddrMut | AddrRaw => true,
@@ -748,6 +853,7 @@ impl TypeId {
             Type::Bool => std::mem::size_of::<bool>(),
             Type::Char => std::mem::size_of::<char>(),
             Type::FnPtr(_) => std::mem::size_of::<fn()>(),
+            Type::DynFn(_) => std::mem::size_of::<*const ()>() + std::mem::size_of::<fn()>(),
             Type::User(ut) if !scopes.get(ut.id).recursive => {
                 let ut = ut.clone();
                 struct SizeAndAlign {
@@ -815,6 +921,32 @@ impl TypeId {
         (sz, sz.clamp(1, MAX_ALIGN))
     }
 
+    /// Returns true if this type holds the frame of an async function or generator by value. The
+    /// members of a frame are only known once its poll function has been generated, so
+    /// `size_and_align` can't compute the layout of such types.
+    pub fn contains_frame(self, scopes: &Scopes, types: &mut Types) -> bool {
+        match &types[self] {
+            &Type::Array(inner, _) => inner.contains_frame(scopes, types),
+            Type::User(ut) if !scopes.get(ut.id).recursive => {
+                let ut = ut.clone();
+                let data = scopes.get(ut.id);
+                if data.kind.as_frame().is_some() {
+                    return true;
+                }
+
+                let mut members: Vec<_> = data.members.values().map(|m| m.ty).collect();
+                if let Some(union) = data.kind.as_union() {
+                    members.extend(union.variants.values().flat_map(|v| v.ty));
+                }
+                members.into_iter().any(|ty| {
+                    ty.with_templates(types, &ut.ty_args)
+                        .contains_frame(scopes, types)
+                })
+            }
+            _ => false,
+        }
+    }
+
     pub fn bit_size(self, proj: &Project) -> BitSizeResult {
         if let Some(int) = self.as_integral(&proj.types, true) {
             BitSizeResult::Size(int.bits)
@@ -872,6 +1004,15 @@ impl TypeId {
                 let ty = Type::Array(ty.with_templates(types, map), len);
                 types.insert(ty)
             }
+            &Type::GenericArray(ty, len) => {
+                let ty = ty.with_templates(types, map);
+                let ty = match map.get(&len).map(|&len| &types[len]) {
+                    Some(Type::Const(n)) => Type::Array(ty, n.clone().into_word().unwrap_or(0)),
+                    Some(Type::User(ut)) => Type::GenericArray(ty, ut.id),
+                    _ => Type::GenericArray(ty, len),
+                };
+                types.insert(ty)
+            }
             Type::Ptr(t) => {
                 let ty = Type::Ptr(t.with_templates(types, map));
                 types.insert(ty)
@@ -900,17 +1041,22 @@ impl TypeId {
                 tr.fill_templates(types, map);
                 types.insert(Type::Fn(tr))
             }
-            Type::FnPtr(f) => {
+            Type::FnPtr(f) | Type::DynFn(f) => {
+                let is_dyn = types[self].is_dyn_fn();
                 let f = f.clone();
-                let fnptr = Type::FnPtr(FnPtr {
+                let f = FnPtr {
                     params: f
                         .params
                         .iter()
                         .map(|p| p.with_templates(types, map))
                         .collect(),
                     ret: f.ret.with_templates(types, map),
-                });
-                types.insert(fnptr)
+                };
+                types.insert(if is_dyn {
+                    Type::DynFn(f)
+                } else {
+                    Type::FnPtr(f)
+                })
             }
             Type::DynPtr(tr) => {
                 let mut tr = tr.clone();